@@ -0,0 +1,52 @@
+/*
+ * C interface to the chip8 core, see ffi/src/lib.rs. Link with -lchip8_ffi.
+ *
+ * Safety: every machine pointer must come from chip8_new() and not be used after chip8_free();
+ * NULL is allowed and ignored. chip8_load_rom() reads `size` bytes from `data`. A machine must
+ * not be used from two threads at the same time.
+ */
+#ifndef CHIP8_H
+#define CHIP8_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct Chip8 Chip8;
+
+/* chip8_step() results */
+#define CHIP8_RUNNING           0
+#define CHIP8_WAITING_FOR_KEY   1   /* stopped at an FX0A until a key is pressed */
+#define CHIP8_FAULT             2   /* stopped by a fault, ie an access past the end of memory */
+#define CHIP8_INVALID_PC        3   /* the program counter left memory */
+
+/* A fresh machine with the font loaded */
+Chip8 *chip8_new(void);
+void chip8_free(Chip8 *chip8);
+
+/* Copies a ROM to 0x200. Returns 0, or -1 if it doesn't fit */
+int32_t chip8_load_rom(Chip8 *chip8, const uint8_t *data, size_t size);
+
+/* Runs one cycle, the timers count down every 10th (600 cycles a second for 60Hz timers) */
+int32_t chip8_step(Chip8 *chip8);
+
+/* chip8_screen_width() x chip8_screen_height() bytes, 0 or 1 per pixel, row by row. Only valid
+ * until the machine is stepped or freed */
+const uint8_t *chip8_get_framebuffer(const Chip8 *chip8);
+size_t chip8_screen_width(void);
+size_t chip8_screen_height(void);
+
+/* Presses (pressed != 0) or releases key 0-F */
+void chip8_set_key(Chip8 *chip8, uint8_t key, int32_t pressed);
+
+/* 1 while the buzzer should sound */
+int32_t chip8_sound_active(const Chip8 *chip8);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,117 @@
+/************
+C interface
+
+The core as a C library (libchip8_ffi.so, chip8_ffi.dll or libchip8_ffi.dylib, built with
+cargo build -p chip8-ffi), for C and C++ frontends and anything else that can call C. The
+declarations are in chip8.h next to this crate:
+
+    Chip8 *chip8 = chip8_new();
+    chip8_load_rom(chip8, rom, rom_size);
+    every frame: chip8_set_key() for the keys that changed, chip8_step() ten times,
+                 draw chip8_get_framebuffer(), beep while chip8_sound_active()
+    chip8_free(chip8);
+
+The machine is an opaque pointer that only these functions touch. Every function takes a NULL
+machine and does nothing (or returns 0 / NULL), the safety rules for the other pointers are in
+chip8.h. One machine must not be used from two threads at the same time.
+************/
+#![allow(clippy::missing_safety_doc)]
+
+extern crate chip8;
+
+use std::slice;
+
+use chip8::display::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use chip8::Chip8;
+
+//chip8_step() results
+pub const CHIP8_RUNNING: i32 = 0;
+pub const CHIP8_WAITING_FOR_KEY: i32 = 1;  //Stopped at an FX0A until a key is pressed
+pub const CHIP8_FAULT: i32 = 2;            //Stopped by a fault, ie an access past the end of memory (see the core's error.rs)
+pub const CHIP8_INVALID_PC: i32 = 3;       //The program counter left memory
+
+//A fresh machine with the font loaded and tracing off, free it with chip8_free()
+#[no_mangle]
+pub extern "C" fn chip8_new() -> *mut Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.initialize();
+    chip8.trace = false;
+    Box::into_raw(Box::new(chip8))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+//Copies a ROM into memory at 0x200. Returns 0, or -1 if it doesn't fit (nothing is loaded then)
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Chip8, data: *const u8, size: usize) -> i32 {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return -1,
+    };
+    if data.is_null() || size > 4096 - chip8.load_addr() as usize {
+        return -1;
+    }
+    chip8.load_rom_bytes(slice::from_raw_parts(data, size));
+    0
+}
+
+//Runs one cycle (the timers count down every 10th), returns one of the CHIP8_ states
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step(chip8: *mut Chip8) -> i32 {
+    let chip8 = match chip8.as_mut() {
+        Some(chip8) => chip8,
+        None => return CHIP8_INVALID_PC,
+    };
+    if chip8.pc() as usize + 1 >= 4096 {
+        return CHIP8_INVALID_PC;
+    }
+    match chip8.step() {
+        Err(ref err) if err.is_fatal() => CHIP8_FAULT,
+        _ if chip8.is_halted() => CHIP8_WAITING_FOR_KEY,
+        _ => CHIP8_RUNNING,
+    }
+}
+
+//The 64 x 32 screen, one byte per pixel (0 or 1) row by row. Points into the machine, so it's only
+//good until the next call that changes it
+#[no_mangle]
+pub unsafe extern "C" fn chip8_get_framebuffer(chip8: *const Chip8) -> *const u8 {
+    match chip8.as_ref() {
+        Some(chip8) => chip8.screen().as_ptr(),
+        None => std::ptr::null(),
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_screen_width() -> usize {
+    SCREEN_WIDTH
+}
+
+#[no_mangle]
+pub extern "C" fn chip8_screen_height() -> usize {
+    SCREEN_HEIGHT
+}
+
+//Presses (pressed != 0) or releases key 0-F, other keys are ignored
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_key(chip8: *mut Chip8, key: u8, pressed: i32) {
+    if let Some(chip8) = chip8.as_mut() {
+        if key < 16 {
+            chip8.press_key(key, pressed != 0);
+        }
+    }
+}
+
+//1 while the buzzer should sound
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound_active(chip8: *const Chip8) -> i32 {
+    match chip8.as_ref() {
+        Some(chip8) => chip8.sound_active() as i32,
+        None => 0,
+    }
+}
@@ -0,0 +1,246 @@
+/************
+Agent interface
+
+For bots and learning agents playing a ROM: every 60Hz frame the agent gets an Observation (the
+screen and a snapshot of the registers) and answers with an Action, the keys to hold during the
+next frame. run_episode plays one episode without a window as fast as the machine runs, until the
+frame limit or until the program faults or leaves memory:
+
+    let mut chip8 = Chip8::new();
+    chip8.initialize();
+    chip8.load_rom("pong.ch8")?;
+    let episode = run_episode(&mut chip8, &mut my_agent, 60 * 60);
+
+Rewards are up to the agent, they differ for every game: a Pong agent reads the scores from the
+registers the ROM keeps them in. The machine is left as the episode ended, so the final memory can
+be looked at as well.
+
+run_episode_with takes AgentSettings for the usual RL benchmark conventions:
+
+    frame_skip   - the agent acts every Nth frame and its action is held for all N, observations
+                   come with the events of all of them (1, acting every frame, by default)
+    sticky_keys  - the chance each frame of holding the last frame's keys again instead of the
+                   agent's, to keep agents from learning one exact key timing (0 by default)
+    scale        - observations shrunk by 2, 4, 8.. in both directions, a pixel lit if any of the
+                   pixels it covers is (1, the full 64 x 32 screen, by default)
+************/
+use std::mem;
+
+use rand::rngs::SmallRng;
+use rand::{Rng, SeedableRng};
+
+use framebuffer::{ColorDepth, Framebuffer};
+use frames::FrameEvent;
+use display::SCREEN_HEIGHT;
+use Chip8;
+
+//What the agent sees at the end of a frame
+pub struct Observation {
+    pub frame: u64,             //Counting from 1
+    pub screen: Vec<u8>,        //Palette indices, as in Chip8::framebuffer()
+    pub width: usize,           //Of the screen, smaller than the machine's when scaled down
+    pub height: usize,
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub waiting_for_key: bool,  //The program is stopped at an FX0A
+    pub events: Vec<FrameEvent>,
+}
+
+impl Observation {
+    pub fn new(chip8: &Chip8, frame: u64, events: Vec<FrameEvent>) -> Observation {
+        Observation::scaled(chip8, frame, events, 1)
+    }
+
+    //With the screen shrunk by `scale`, which has to divide 32
+    pub fn scaled(chip8: &Chip8, frame: u64, events: Vec<FrameEvent>, scale: usize) -> Observation {
+        assert!(scale > 0 && SCREEN_HEIGHT.is_multiple_of(scale), "scale has to divide the screen size");
+        let screen_width = chip8.screen_width();
+        let (width, height) = (screen_width / scale, chip8.screen_height() / scale);
+        let screen = if scale == 1 {
+            chip8.screen().to_vec()
+        } else {
+            let mut screen = vec![0; width * height];
+            for (index, pixel) in chip8.screen().iter().enumerate() {
+                let (x, y) = (index % screen_width / scale, index / screen_width / scale);
+                screen[x + y * width] |= *pixel;
+            }
+            screen
+        };
+
+        let mut v = [0; 16];
+        for (reg, value) in v.iter_mut().enumerate() {
+            *value = chip8.register(reg);
+        }
+        Observation {
+            frame,
+            screen,
+            width,
+            height,
+            v,
+            i: chip8.index(),
+            pc: chip8.pc(),
+            delay_timer: chip8.delay_timer(),
+            sound_timer: chip8.sound_timer(),
+            waiting_for_key: chip8.is_halted(),
+            events,
+        }
+    }
+
+    pub fn framebuffer(&self) -> Framebuffer<'_> {
+        Framebuffer::new(&self.screen, self.width, self.height, ColorDepth::OnePlane)
+    }
+}
+
+//The keys held down during the next frame, everything else is released
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Action {
+    pub keys: [bool; 16],
+}
+
+impl Action {
+    pub fn none() -> Action {
+        Action::default()
+    }
+
+    pub fn press(key: u8) -> Action {
+        let mut action = Action::none();
+        action.keys[key as usize & 0xF] = true;
+        action
+    }
+
+    //As a bit mask, bit K for key K, the way the batch runner takes keys
+    pub fn key_mask(&self) -> u16 {
+        self.keys.iter().enumerate().fold(0, |mask, (key, held)| mask | (*held as u16) << key)
+    }
+}
+
+pub trait Agent {
+    //Called once before the first frame of an episode
+    fn reset(&mut self) {}
+
+    fn act(&mut self, observation: &Observation) -> Action;
+}
+
+//Never presses anything, a baseline
+pub struct IdleAgent;
+
+impl Agent for IdleAgent {
+    fn act(&mut self, _observation: &Observation) -> Action {
+        Action::none()
+    }
+}
+
+//Holds a random key (or none) for every frame, from a seed so episodes can be replayed
+pub struct RandomAgent {
+    rng: SmallRng,
+    keys: Vec<u8>,
+}
+
+fn seeded_rng(seed: u64) -> SmallRng {
+    let mut bytes = [0; 16];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (seed >> ((i % 8) * 8)) as u8;
+    }
+    bytes[15] |= 1;
+    SmallRng::from_seed(bytes)
+}
+
+impl RandomAgent {
+    //Picks from the given keys only, ie the two paddle keys of Pong
+    pub fn new(seed: u64, keys: &[u8]) -> RandomAgent {
+        RandomAgent { rng: seeded_rng(seed), keys: keys.to_vec() }
+    }
+}
+
+impl Agent for RandomAgent {
+    fn act(&mut self, _observation: &Observation) -> Action {
+        match self.rng.gen_range(0, self.keys.len() + 1) {
+            0 => Action::none(),
+            pick => Action::press(self.keys[pick - 1]),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct AgentSettings {
+    pub frame_skip: u64,
+    pub sticky_keys: f64,   //0 to 1
+    pub scale: usize,
+    pub seed: u64,          //For the sticky keys
+}
+
+impl Default for AgentSettings {
+    fn default() -> AgentSettings {
+        AgentSettings { frame_skip: 1, sticky_keys: 0.0, scale: 1, seed: 0 }
+    }
+}
+
+//Turns the agent's keys into the keys actually held, repeating the last ones now and then
+pub struct StickyKeys {
+    rng: SmallRng,
+    chance: f64,
+    last: [bool; 16],
+}
+
+impl StickyKeys {
+    pub fn new(seed: u64, chance: f64) -> StickyKeys {
+        StickyKeys { rng: seeded_rng(seed), chance, last: [false; 16] }
+    }
+
+    //Called once per frame with the agent's keys
+    pub fn apply(&mut self, keys: [bool; 16]) -> [bool; 16] {
+        if self.chance <= 0.0 || !self.rng.gen_bool(self.chance.min(1.0)) {
+            self.last = keys;
+        }
+        self.last
+    }
+}
+
+//How an episode went
+pub struct Episode {
+    pub frames: u64,
+    pub end: Option<FrameEvent>,    //The fault or invalid PC that ended it, None if the frame limit did
+}
+
+pub fn run_episode(chip8: &mut Chip8, agent: &mut dyn Agent, max_frames: u64) -> Episode {
+    run_episode_with(chip8, agent, max_frames, &AgentSettings::default())
+}
+
+//max_frames counts the machine's frames, not the agent's steps
+pub fn run_episode_with(chip8: &mut Chip8, agent: &mut dyn Agent, max_frames: u64, settings: &AgentSettings) -> Episode {
+    agent.reset();
+    let skip = settings.frame_skip.max(1);
+    let mut sticky = StickyKeys::new(settings.seed, settings.sticky_keys);
+    let mut held = [false; 16];
+    let mut action = Action::none();
+    let mut events = Vec::new();
+    let mut observation = Observation::scaled(chip8, 0, Vec::new(), settings.scale);
+
+    for frame in 1..=max_frames {
+        if (frame - 1).is_multiple_of(skip) {
+            action = agent.act(&observation);
+        }
+        let keys = sticky.apply(action.keys);
+        for (key, (pressed, was)) in keys.iter().zip(held.iter()).enumerate() {
+            if pressed != was {
+                chip8.press_key(key as u8, *pressed);
+            }
+        }
+        held = keys;
+
+        if let Some(next) = chip8.frames().next() {
+            events.extend(next.events);
+        }
+        let end = events.iter().find(|event| matches!(event, FrameEvent::Fault(_) | FrameEvent::InvalidPc(_) | FrameEvent::Exited)).cloned();
+        if end.is_some() {
+            return Episode { frames: frame, end };
+        }
+        if frame.is_multiple_of(skip) {
+            observation = Observation::scaled(chip8, frame, mem::take(&mut events), settings.scale);
+        }
+    }
+    Episode { frames: max_frames, end: None }
+}
@@ -0,0 +1,78 @@
+/************
+Static ROM analysis
+
+Finds out which parts of a ROM are used without running it. Starting from 0x200, every path
+through the code is followed (jumps, calls, both sides of every skip), which gives the
+addresses of all reachable instructions. Along the way the addresses the code points I at
+(ANNN) are collected, those are the ROM's data: sprites, tables, BCD buffers.
+
+The analysis is conservative in one direction only: BNNN jumps (computed from V0) and
+self-modifying code can't be followed, so some code may be missed, but nothing reported as
+used is actually unused.
+************/
+use std::collections::BTreeSet;
+
+const ROM_START: usize = 0x200;
+
+pub struct Analysis {
+    pub code: BTreeSet<u16>,    //Addresses of reachable instructions
+    pub data: BTreeSet<u16>,    //Addresses loaded into I by reachable ANNN instructions
+    pub computed_jumps: bool,   //Whether reachable code has BNNN jumps that couldn't be followed
+}
+
+impl Analysis {
+    //One past the highest ROM address the analysis found a use for. Data only counts for its first
+    //byte, how long a sprite or table is can't be known statically
+    pub fn used_end(&self) -> usize {
+        let code_end = self.code.iter().next_back().map(|addr| *addr as usize + 2);
+        let data_end = self.data.iter().next_back().map(|addr| *addr as usize + 1);
+        code_end.max(data_end).unwrap_or(ROM_START)
+    }
+}
+
+pub fn analyze(rom: &[u8]) -> Analysis {
+    let end = ROM_START + rom.len();
+    let mut analysis = Analysis {
+        code: BTreeSet::new(),
+        data: BTreeSet::new(),
+        computed_jumps: false,
+    };
+
+    let mut pending = vec![ROM_START];
+    while let Some(addr) = pending.pop() {
+        //Outside the ROM, or already followed
+        if addr < ROM_START || addr + 1 >= end || !analysis.code.insert(addr as u16) {
+            continue;
+        }
+
+        let offset = addr - ROM_START;
+        let opcode = (rom[offset] as u16) << 8 | rom[offset + 1] as u16;
+        let nnn = (opcode & 0x0FFF) as usize;
+
+        match opcode & 0xF000 {
+            0x0000 if opcode == 0x00EE => {},
+            0x1000 => pending.push(nnn),
+            0x2000 => {
+                pending.push(nnn);
+                pending.push(addr + 2);
+            },
+            //Skips go on to either of the next two instructions
+            0x3000 | 0x4000 | 0x5000 | 0x9000 => {
+                pending.push(addr + 2);
+                pending.push(addr + 4);
+            },
+            0xE000 if opcode & 0xFF == 0x9E || opcode & 0xFF == 0xA1 => {
+                pending.push(addr + 2);
+                pending.push(addr + 4);
+            },
+            0xA000 => {
+                analysis.data.insert(nnn as u16);
+                pending.push(addr + 2);
+            },
+            0xB000 => analysis.computed_jumps = true,
+            _ => pending.push(addr + 2),
+        }
+    }
+
+    analysis
+}
@@ -0,0 +1,164 @@
+/************
+Per-ROM annotations
+
+Names for memory ranges, ie "sprite_table: 0x300-0x37F", kept in <rom>.annotations next to the
+ROM so they're there again the next time the ROM is debugged. The file has one annotation per
+line, # starts a comment:
+    region sprite_table 0x300-0x37F
+
+Wherever the debugger takes an address or an expression, a region name can be used instead of a
+number and stands for the region's first address. NAME+N is N bytes into the region, ie
+"poke sprite_table+2=0xFF" or "assert m(score) == 3".
+************/
+use std::collections::BTreeMap;
+use std::fs;
+
+use options::parse_number;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Region {
+    pub start: u16,
+    pub end: u16,   //Inclusive
+}
+
+pub struct Annotations {
+    path: String,
+    regions: BTreeMap<String, Region>,
+}
+
+pub fn annotations_path(rom: &str) -> String {
+    format!("{}.annotations", rom)
+}
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() || first == '_' => chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ => false,
+    }
+}
+
+//Parses "START-END", both inclusive
+fn parse_range(text: &str) -> Result<Region, String> {
+    let mut parts = text.splitn(2, '-');
+    let start = parse_number(parts.next().unwrap_or(""))?;
+    let end = match parts.next() {
+        Some(end) => parse_number(end)?,
+        None => start,
+    };
+    if end < start || end as usize >= 4096 {
+        return Err(format!("Invalid memory range {}", text));
+    }
+    Ok(Region { start, end })
+}
+
+impl Annotations {
+    //Loads the ROM's annotations, if it has any yet
+    pub fn load(rom: &str) -> Result<Annotations, String> {
+        let path = annotations_path(rom);
+        let mut annotations = Annotations {
+            path: path.clone(),
+            regions: BTreeMap::new(),
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(_) => return Ok(annotations),
+        };
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            match words.as_slice() {
+                [] => {},
+                ["region", name, range] if is_name(name) => {
+                    let region = parse_range(range).map_err(|err| format!("{}:{}: {}", path, number + 1, err))?;
+                    annotations.regions.insert(name.to_string(), region);
+                },
+                _ => return Err(format!("{}:{}: Invalid annotation {}", path, number + 1, line)),
+            }
+        }
+        Ok(annotations)
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let mut text = String::new();
+        for (name, region) in &self.regions {
+            text.push_str(&format!("region {} {:#05X}-{:#05X}\n", name, region.start, region.end));
+        }
+        fs::write(&self.path, text).map_err(|err| format!("Could not write {}: {}", self.path, err))
+    }
+
+    //Names a range, given as "NAME: START-END" or "NAME START-END", and saves the annotations
+    pub fn add_region(&mut self, text: &str) -> Result<(String, Region), String> {
+        let text = text.replacen(':', " ", 1);
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (name, range) = match words.as_slice() {
+            [name, range] => (*name, *range),
+            _ => return Err(String::from("Usage: region NAME: START-END")),
+        };
+        //Names that mean something in expressions already can't be taken
+        if !is_name(name) || ["v", "m", "i", "pc", "sp", "dt", "st"].contains(&name) {
+            return Err(format!("Invalid region name {}", name));
+        }
+
+        let region = parse_range(range)?;
+        self.regions.insert(name.to_string(), region);
+        self.save()?;
+        Ok((name.to_string(), region))
+    }
+
+    pub fn remove_region(&mut self, name: &str) -> Result<(), String> {
+        if self.regions.remove(name).is_none() {
+            return Err(format!("No region named {}", name));
+        }
+        self.save()
+    }
+
+    pub fn regions(&self) -> &BTreeMap<String, Region> {
+        &self.regions
+    }
+
+    //Names of the regions overlapping start..=end
+    pub fn regions_in(&self, start: u16, end: u16) -> Vec<&str> {
+        self.regions.iter()
+            .filter(|(_, region)| region.start <= end && region.end >= start)
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    //Replaces the region names (and NAME+N offsets) in an address or expression with their
+    //addresses, so the result can be parsed like any other
+    pub fn resolve(&self, text: &str) -> Result<String, String> {
+        let mut resolved = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            resolved.push_str(&rest[..start]);
+            let word_len = rest[start..].find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len() - start);
+            let word = &rest[start..start + word_len];
+            rest = &rest[start + word_len..];
+
+            //Hex digits after 0x look like words too, but a number's letters never start a word
+            let in_number = resolved.ends_with(|c: char| c.is_ascii_digit());
+            let region = match self.regions.get(word) {
+                Some(region) if !in_number => region,
+                _ => {
+                    resolved.push_str(word);
+                    continue;
+                }
+            };
+
+            let mut addr = region.start as u32;
+            if let Some(offset) = rest.strip_prefix('+') {
+                let offset_len = offset.find(|c: char| !c.is_ascii_alphanumeric()).unwrap_or(offset.len());
+                addr += parse_number(&offset[..offset_len])? as u32;
+                rest = &offset[offset_len..];
+            }
+            if addr >= 4096 {
+                return Err(format!("{} is past the end of memory", word));
+            }
+            resolved.push_str(&format!("{:#05X}", addr));
+        }
+        resolved.push_str(rest);
+        Ok(resolved)
+    }
+}
@@ -0,0 +1,83 @@
+/************
+Audio output
+
+The CHIP8 only has a buzzer that sounds while the sound timer is above zero, so a backend just
+needs to know when the tone starts and stops.
+
+Backends ("devices") are picked by name with --audio-device:
+    bell - rings the terminal bell when the buzzer starts (default)
+    null - no sound at all
+
+If the requested device does not exist or cannot be used (ie stdout is not a terminal on a
+headless server or CI container), we warn and fall back to the null backend instead of failing.
+************/
+use std::io::{self, IsTerminal, Write};
+
+use frontend::AudioSink;
+
+pub const DEFAULT_DEVICE: &str = "bell";
+
+//The buzzer goes through the core's AudioSink (see frontend.rs), backends add a name to it
+pub trait AudioBackend: AudioSink {
+    fn name(&self) -> &str;
+}
+
+//Plays nothing. Used when no real output is available
+pub struct NullAudio;
+
+impl AudioBackend for NullAudio {
+    fn name(&self) -> &str {
+        "null"
+    }
+}
+
+impl AudioSink for NullAudio {
+    fn set_beep(&mut self, _on: bool) {}
+}
+
+//Rings the terminal bell each time the buzzer starts
+pub struct BellAudio {
+    playing: bool,
+}
+
+impl AudioBackend for BellAudio {
+    fn name(&self) -> &str {
+        "bell"
+    }
+}
+
+impl AudioSink for BellAudio {
+    fn set_beep(&mut self, on: bool) {
+        if on && !self.playing {
+            print!("\x07");
+            io::stdout().flush().unwrap_or(());
+        }
+        self.playing = on;
+    }
+}
+
+//Names of all devices that can be passed to --audio-device
+pub fn device_names() -> Vec<&'static str> {
+    vec!["bell", "null"]
+}
+
+//Why the named device can't be used right now, if it can't
+pub fn check_device(name: &str) -> Result<(), String> {
+    match name {
+        "bell" if !io::stdout().is_terminal() => Err(String::from("no terminal available for the bell audio device")),
+        "bell" | "null" => Ok(()),
+        _ => Err(format!("unknown audio device {} (available: {})", name, device_names().join(", "))),
+    }
+}
+
+//Opens the named device, falling back to the null backend with a warning if it can't be used
+pub fn open_device(name: &str) -> Box<dyn AudioBackend> {
+    if let Err(err) = check_device(name) {
+        println!("Warning: {}, audio disabled", err);
+        return Box::new(NullAudio);
+    }
+    match name {
+        "bell" => Box::new(BellAudio { playing: false }),
+        _ => Box::new(NullAudio),
+    }
+}
@@ -0,0 +1,137 @@
+/************
+Batch runner
+
+Steps many independent machines one 60Hz frame per call, for training agents (see agent.rs) on a
+lot of episodes at once. Inputs and outputs are flat arrays instead of one object per machine:
+
+    keys        one u16 per machine, bit K set to hold key K during the frame
+    screens()   every machine's 64 x 32 screen back to back (64 * 32 bytes each, 0 or 1 per pixel)
+    registers() every machine's V0-VF back to back (16 bytes each)
+    done()      whether each machine has stopped (a memory fault or the PC left memory)
+
+A stopped machine is skipped by step() and keeps its last screen until it's given a new one with
+reset(). Nothing gets allocated per frame and tracing is turned off, so the cost per machine is
+running its cycles and copying its screen.
+************/
+use display::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use Chip8;
+
+const SCREEN_SIZE: usize = SCREEN_WIDTH * SCREEN_HEIGHT;
+
+pub struct BatchRunner {
+    machines: Vec<Chip8>,
+    held: Vec<u16>,
+    done: Vec<bool>,
+    frames: Vec<u64>,
+    screens: Vec<u8>,
+    registers: Vec<u8>,
+}
+
+//Runs the rest of the current frame, returns false if the machine stopped during it
+fn run_frame(chip8: &mut Chip8) -> bool {
+    loop {
+        if chip8.pc() as usize + 1 >= 4096 {
+            return false;
+        }
+        if chip8.step().is_err_and(|err| err.is_fatal()) {
+            return false;
+        }
+        if chip8.cycles().is_multiple_of(chip8.cycles_per_tick()) {
+            return true;
+        }
+    }
+}
+
+impl BatchRunner {
+    pub fn new(machines: Vec<Chip8>) -> BatchRunner {
+        let count = machines.len();
+        let mut runner = BatchRunner {
+            machines: Vec::with_capacity(count),
+            held: vec![0; count],
+            done: vec![false; count],
+            frames: vec![0; count],
+            screens: vec![0; count * SCREEN_SIZE],
+            registers: vec![0; count * 16],
+        };
+        for (index, chip8) in machines.into_iter().enumerate() {
+            runner.machines.push(chip8);
+            runner.reset_machine(index);
+        }
+        runner
+    }
+
+    pub fn len(&self) -> usize {
+        self.machines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.machines.is_empty()
+    }
+
+    //Runs one frame on every machine that hasn't stopped, with the keys held as given
+    pub fn step(&mut self, keys: &[u16]) {
+        assert_eq!(keys.len(), self.machines.len(), "one key mask per machine");
+        for (index, chip8) in self.machines.iter_mut().enumerate() {
+            if self.done[index] {
+                continue;
+            }
+            let changed = keys[index] ^ self.held[index];
+            if changed != 0 {
+                for key in (0..16).filter(|key| changed & (1 << key) != 0) {
+                    chip8.press_key(key, keys[index] & (1 << key) != 0);
+                }
+                self.held[index] = keys[index];
+            }
+
+            self.done[index] = !run_frame(chip8);
+            self.frames[index] += 1;
+            self.screens[index * SCREEN_SIZE..(index + 1) * SCREEN_SIZE].copy_from_slice(chip8.screen());
+            for reg in 0..16 {
+                self.registers[index * 16 + reg] = chip8.register(reg);
+            }
+        }
+    }
+
+    //Swaps in a new machine, ie a fresh one for the next episode
+    pub fn reset(&mut self, index: usize, chip8: Chip8) {
+        self.machines[index] = chip8;
+        self.reset_machine(index);
+    }
+
+    fn reset_machine(&mut self, index: usize) {
+        let chip8 = &mut self.machines[index];
+        chip8.trace = false;
+        self.held[index] = 0;
+        self.done[index] = false;
+        self.frames[index] = 0;
+        self.screens[index * SCREEN_SIZE..(index + 1) * SCREEN_SIZE].copy_from_slice(chip8.screen());
+        for reg in 0..16 {
+            self.registers[index * 16 + reg] = chip8.register(reg);
+        }
+    }
+
+    pub fn screens(&self) -> &[u8] {
+        &self.screens
+    }
+
+    pub fn screen(&self, index: usize) -> &[u8] {
+        &self.screens[index * SCREEN_SIZE..(index + 1) * SCREEN_SIZE]
+    }
+
+    pub fn registers(&self) -> &[u8] {
+        &self.registers
+    }
+
+    pub fn done(&self) -> &[bool] {
+        &self.done
+    }
+
+    //Frames each machine has run since it was (re)started
+    pub fn frames(&self) -> &[u64] {
+        &self.frames
+    }
+
+    pub fn machine(&self, index: usize) -> &Chip8 {
+        &self.machines[index]
+    }
+}
@@ -0,0 +1,180 @@
+/************
+Benchmark ROMs
+
+Usage: chip8 bench-rom <alu|draw|bcd> <outfile>
+       chip8 bench [--cycles N] [--jit]
+
+Generates small synthetic ROMs that each hammer one part of the core, so its speed can be measured
+in isolation and compared against other emulators running the very same ROM:
+    alu  - an endless loop of register arithmetic (8XY_ and 7XNN opcodes)
+    draw - an endless storm of sprite draws covering the whole screen (DXYN)
+    bcd  - an endless loop of BCD conversions into memory (FX33)
+
+"chip8 bench" runs every ROM headless for N cycles and prints how fast the core got through them,
+then for another 10 seconds taking a rewind snapshot every frame (see snapshot.rs), and prints
+what a snapshot costs per frame in time and compressed size. --jit runs the ROMs on the block
+translation backend (see jit.rs) instead, and also prints how many blocks it translated.
+************/
+use std::fs::File;
+use std::io::Write;
+use std::process;
+use std::time::Instant;
+
+use exitcode;
+use headless::{run_cycles, run_cycles_jit};
+use jit::Jit;
+use options::take_value;
+use romtag::RomTag;
+use snapshot::Rewind;
+use {Chip8, CYCLES_PER_TIMER_TICK, TIMER_HZ};
+
+pub const KINDS: [&str; 3] = ["alu", "draw", "bcd"];
+
+const DEFAULT_CYCLES: u64 = 5_000_000;
+const SNAPSHOT_FRAMES: usize = (TIMER_HZ * 10.0) as usize;
+
+//Turns a list of opcodes into ROM bytes, big endian like the interpreter reads them
+pub fn assemble(opcodes: &[u16]) -> Vec<u8> {
+    opcodes.iter().flat_map(|opcode| opcode.to_be_bytes().to_vec()).collect()
+}
+
+//Mixes V0 and V1 with every ALU operation, forever
+fn alu_loop() -> Vec<u8> {
+    assemble(&[
+        0x6001,     //0x200: V0 = 1
+        0x6103,     //0x202: V1 = 3
+        0x8014,     //0x204: V0 += V1
+        0x8102,     //0x206: V1 &= V0
+        0x8013,     //0x208: V0 ^= V1
+        0x8106,     //0x20A: V1 >>= 1
+        0x810E,     //0x20C: V1 <<= 1
+        0x7105,     //0x20E: V1 += 5
+        0x8015,     //0x210: V0 -= V1
+        0x8011,     //0x212: V0 |= V1
+        0x1204,     //0x214: jump to 0x204
+    ])
+}
+
+//Tiles the screen with the "0" font sprite, row by row, then starts over
+fn draw_storm() -> Vec<u8> {
+    assemble(&[
+        0x00E0,     //0x200: clear the screen
+        0xA050,     //0x202: I = font sprite "0"
+        0x6000,     //0x204: V0 = 0 (x)
+        0x6100,     //0x206: V1 = 0 (y)
+        0xD015,     //0x208: draw 5 rows at V0, V1
+        0x7008,     //0x20A: V0 += 8
+        0x3040,     //0x20C: skip if V0 == 64
+        0x1208,     //0x20E: jump to 0x208
+        0x6000,     //0x210: V0 = 0
+        0x7106,     //0x212: V1 += 6
+        0x311E,     //0x214: skip if V1 == 30
+        0x1208,     //0x216: jump to 0x208
+        0x1200,     //0x218: jump to 0x200
+    ])
+}
+
+//Converts a counting V0 to BCD at 0x300, forever
+fn bcd_loop() -> Vec<u8> {
+    assemble(&[
+        0xA300,     //0x200: I = 0x300
+        0x6000,     //0x202: V0 = 0
+        0xF033,     //0x204: BCD of V0 to I, I+1, I+2
+        0x7001,     //0x206: V0 += 1
+        0x1204,     //0x208: jump to 0x204
+    ])
+}
+
+pub fn generate(kind: &str) -> Result<Vec<u8>, String> {
+    match kind {
+        "alu" => Ok(alu_loop()),
+        "draw" => Ok(draw_storm()),
+        "bcd" => Ok(bcd_loop()),
+        _ => Err(format!("Unknown benchmark ROM {} (expected one of {})", kind, KINDS.join(", "))),
+    }
+}
+
+//Entry point for "chip8 bench-rom ...", with the arguments following "bench-rom"
+pub fn run_generate(args: &[String]) {
+    if args.len() != 2 {
+        println!("Usage: chip8 bench-rom <{}> <outfile>", KINDS.join("|"));
+        process::exit(exitcode::FAILURE);
+    }
+
+    let tag = RomTag {
+        title: Some(format!("{} benchmark", args[0])),
+        author: None,
+        profile: Some(String::from("chip8")),
+    };
+    let written = generate(&args[0]).and_then(|mut rom| {
+        tag.append_to(&mut rom)?;
+        File::create(&args[1])
+            .and_then(|mut file| file.write_all(&rom))
+            .map_err(|err| format!("Could not write {}: {}", args[1], err))
+    });
+    if let Err(err) = written {
+        println!("{}. Aborting", err);
+        process::exit(exitcode::FAILURE);
+    }
+}
+
+//Entry point for "chip8 bench ...", with the arguments following "bench"
+pub fn run_bench(args: &[String]) {
+    let mut args = args.to_vec();
+    let jit = match args.iter().position(|arg| arg == "--jit") {
+        Some(index) => {
+            args.remove(index);
+            true
+        },
+        None => false,
+    };
+    let cycles = match take_value(&mut args, "--cycles") {
+        Ok(None) => DEFAULT_CYCLES,
+        Ok(Some(cycles)) => match cycles.parse::<u64>() {
+            Ok(cycles) => cycles,
+            Err(_) => {
+                println!("Invalid cycle count {}. Aborting", cycles);
+                process::exit(exitcode::FAILURE);
+            }
+        },
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::FAILURE);
+        }
+    };
+
+    for kind in KINDS.iter() {
+        let mut chip8 = Chip8::new();
+        chip8.initialize();
+        chip8.load_rom_bytes(&generate(kind).unwrap());
+        chip8.trace = false;
+
+        let mut backend = if jit { Some(Jit::new()) } else { None };
+        let start = Instant::now();
+        let stop = match backend {
+            Some(ref mut jit) => run_cycles_jit(&mut chip8, jit, cycles),
+            None => run_cycles(&mut chip8, cycles),
+        };
+        let seconds = start.elapsed().as_secs_f64();
+
+        println!("{:5} {} cycles in {:.3}s, {:.2}M cycles/s ({})",
+                 kind, chip8.cycles(), seconds, chip8.cycles() as f64 / seconds / 1_000_000.0, stop);
+        if let Some(ref jit) = backend {
+            let stats = jit.stats();
+            println!("      jit: {} blocks, {} cycles translated, {} interpreted, {} invalidations",
+                     stats.blocks, stats.translated_cycles, stats.interpreted_cycles, stats.invalidations);
+        }
+
+        let mut rewind = Rewind::new(SNAPSHOT_FRAMES);
+        let mut snapshot_seconds = 0.0;
+        for _ in 0..SNAPSHOT_FRAMES {
+            run_cycles(&mut chip8, CYCLES_PER_TIMER_TICK);
+            let start = Instant::now();
+            rewind.push(chip8.save_state());
+            snapshot_seconds += start.elapsed().as_secs_f64();
+        }
+        println!("      snapshots: {:.1}us and {} bytes per frame ({} uncompressed)",
+                 snapshot_seconds / SNAPSHOT_FRAMES as f64 * 1_000_000.0,
+                 rewind.bytes() / rewind.len().max(1), chip8.save_state().len());
+    }
+}
@@ -0,0 +1,83 @@
+/************
+Frame budget diagnostics
+
+Finds out when the machine can't emulate CYCLES_PER_SECOND cycles per second, instead of letting
+the game silently run slow. The time spent emulating and rendering is added up over every
+second of wall clock time; the machine is behind when that took most of the second, or when the
+main loop had to give up on catching up (after falling more than a quarter second behind).
+
+While behind, a red mark is drawn in the top right corner of the window, and a warning with the
+numbers is printed (at most every WARN_INTERVAL seconds). Fast-forwarding doesn't count, it runs
+as fast as it can on purpose.
+************/
+use std::time::Instant;
+
+use piston_window::*;
+
+//Share of the wall clock time emulation and rendering may take before the machine counts as behind
+const BUSY_LIMIT: f64 = 0.9;
+const WARN_INTERVAL: f64 = 5.0;
+
+pub struct FrameBudget {
+    window_start: Instant,
+    emulation: f64,    //Seconds spent emulating in the current window
+    render: f64,       //Seconds spent rendering in the current window
+    dropped: f64,      //Emulated seconds given up on in the current window
+    behind: bool,
+    last_warning: Option<Instant>,
+}
+
+impl FrameBudget {
+    pub fn new() -> FrameBudget {
+        FrameBudget {
+            window_start: Instant::now(),
+            emulation: 0.0,
+            render: 0.0,
+            dropped: 0.0,
+            behind: false,
+            last_warning: None,
+        }
+    }
+
+    pub fn add_emulation(&mut self, seconds: f64, dropped: f64) {
+        self.emulation += seconds;
+        self.dropped += dropped;
+    }
+
+    pub fn add_render(&mut self, seconds: f64) {
+        self.render += seconds;
+    }
+
+    //Should be called every update. Closes the measuring window once a second has passed
+    pub fn update(&mut self, turbo: bool) {
+        let elapsed = self.window_start.elapsed().as_secs_f64();
+        if elapsed < 1.0 {
+            return;
+        }
+
+        let busy = (self.emulation + self.render) / elapsed;
+        self.behind = !turbo && (busy > BUSY_LIMIT || self.dropped > 0.0);
+
+        let warn_due = self.last_warning.is_none_or(|last| last.elapsed().as_secs_f64() >= WARN_INTERVAL);
+        if self.behind && warn_due {
+            println!("Warning: emulation can't keep up, {:.0}% of the time was spent emulating ({:.0} ms/s) and rendering ({:.0} ms/s), {:.0} ms of emulated time dropped",
+                     busy * 100.0, self.emulation / elapsed * 1000.0, self.render / elapsed * 1000.0, self.dropped * 1000.0);
+            self.last_warning = Some(Instant::now());
+        }
+
+        self.window_start = Instant::now();
+        self.emulation = 0.0;
+        self.render = 0.0;
+        self.dropped = 0.0;
+    }
+
+    pub fn draw(&self, window: &mut PistonWindow, event: &Event) {
+        if !self.behind {
+            return;
+        }
+        window.draw_2d(event, |c, g| {
+            let width = c.get_view_size()[0];
+            rectangle([1.0, 0.0, 0.0, 0.9], [width - 14.0, 6.0, 8.0, 8.0], c.transform, g);
+        });
+    }
+}
@@ -0,0 +1,165 @@
+/************
+Builder
+
+Sets up a machine in one expression instead of new(), initialize(), then a setter for every
+option and load_rom() in the right order:
+
+    let chip8 = Chip8::builder()
+        .clock_hz(700)
+        .seed(1234)
+        .stack_mode(StackMode::Grow)
+        .rom_bytes(&data)
+        .build()?;
+
+Anything not given keeps the defaults of Chip8::new(), and the font is always loaded. The
+settings are applied in the order that works whatever order they were given in: the font and
+load addresses first, then the ROM, so build() is where a bad address or a ROM too big for memory
+is reported, as a Chip8Error (see error.rs). Frontends that need more (extra segments, pokes,
+another machine's state) carry on with the Chip8 it returns.
+************/
+use error::Chip8Error;
+use extension::OpcodeExtension;
+use memory::AddressMode;
+use mmio::MmioBus;
+use protect::Protection;
+use quirks::Quirks;
+use sprite::{ClipCollision, DrawCheck};
+use stack::StackMode;
+use variant::Variant;
+use {Chip8, FONT_ADDR, PROGRAM_START};
+
+enum Rom<'a> {
+    Bytes(&'a [u8]),
+    File(String),
+}
+
+pub struct Chip8Builder<'a> {
+    chip8: Chip8,   //Holds the settings that can be applied straight away
+    load_addr: u16,
+    font_addr: u16,
+    rom: Option<Rom<'a>>,
+}
+
+impl Chip8 {
+    pub fn builder<'a>() -> Chip8Builder<'a> {
+        let mut chip8 = Chip8::new();
+        chip8.initialize();
+        Chip8Builder { chip8, load_addr: PROGRAM_START, font_addr: FONT_ADDR, rom: None }
+    }
+}
+
+impl<'a> Chip8Builder<'a> {
+    pub fn clock_hz(mut self, hz: u32) -> Chip8Builder<'a> {
+        self.chip8.set_clock_hz(hz);
+        self
+    }
+
+    pub fn seed(mut self, seed: u64) -> Chip8Builder<'a> {
+        self.chip8.set_seed(seed);
+        self
+    }
+
+    //Print every executed opcode (on by default, like Chip8::new())
+    pub fn trace(mut self, trace: bool) -> Chip8Builder<'a> {
+        self.chip8.trace = trace;
+        self
+    }
+
+    //Count warnings without printing them (see diagnostics.rs)
+    pub fn quiet(mut self, quiet: bool) -> Chip8Builder<'a> {
+        self.chip8.set_quiet(quiet);
+        self
+    }
+
+    pub fn load_addr(mut self, addr: u16) -> Chip8Builder<'a> {
+        self.load_addr = addr;
+        self
+    }
+
+    pub fn font_addr(mut self, addr: u16) -> Chip8Builder<'a> {
+        self.font_addr = addr;
+        self
+    }
+
+    //The ROM to load at the load address, from memory or from a file. The last one given wins
+    pub fn rom_bytes(mut self, bytes: &'a [u8]) -> Chip8Builder<'a> {
+        self.rom = Some(Rom::Bytes(bytes));
+        self
+    }
+
+    pub fn rom(mut self, path: &str) -> Chip8Builder<'a> {
+        self.rom = Some(Rom::File(path.to_string()));
+        self
+    }
+
+    pub fn stack_mode(mut self, mode: StackMode) -> Chip8Builder<'a> {
+        self.chip8.set_stack_mode(mode);
+        self
+    }
+
+    pub fn draw_check(mut self, check: DrawCheck, sprite_limit: usize) -> Chip8Builder<'a> {
+        self.chip8.set_draw_check(check, sprite_limit);
+        self
+    }
+
+    pub fn clip_collision(mut self, mode: ClipCollision) -> Chip8Builder<'a> {
+        self.chip8.set_clip_collision(mode);
+        self
+    }
+
+    //Which flavour of CHIP-8 to run (see variant.rs)
+    pub fn variant(mut self, variant: Variant) -> Chip8Builder<'a> {
+        self.chip8.set_variant(variant);
+        self
+    }
+
+    pub fn quirks(mut self, quirks: Quirks) -> Chip8Builder<'a> {
+        self.chip8.set_quirks(quirks);
+        self
+    }
+
+    pub fn address_mode(mut self, mode: AddressMode) -> Chip8Builder<'a> {
+        self.chip8.set_address_mode(mode);
+        self
+    }
+
+    pub fn protection(mut self, protection: Protection) -> Chip8Builder<'a> {
+        self.chip8.set_protection(protection);
+        self
+    }
+
+    pub fn mmio(mut self, mmio: MmioBus) -> Chip8Builder<'a> {
+        self.chip8.set_mmio(Some(mmio));
+        self
+    }
+
+    //Can be given more than once, extensions are offered opcodes in the order they were added
+    pub fn extension(mut self, extension: Box<dyn OpcodeExtension>) -> Chip8Builder<'a> {
+        self.chip8.add_extension(extension);
+        self
+    }
+
+    //A handler for opcodes the core doesn't define, see Chip8::register_opcode
+    pub fn opcode(mut self, mask: u16, pattern: u16, handler: impl FnMut(u16, &mut Chip8) + Send + Sync + 'static) -> Chip8Builder<'a> {
+        self.chip8.register_opcode(mask, pattern, handler);
+        self
+    }
+
+    pub fn build(self) -> Result<Chip8, Chip8Error> {
+        let mut chip8 = self.chip8;
+        chip8.set_font_addr(self.font_addr)?;
+        chip8.set_load_addr(self.load_addr)?;
+        match self.rom {
+            Some(Rom::Bytes(bytes)) => {
+                let room = 4096 - self.load_addr as usize;
+                if bytes.len() > room {
+                    return Err(Chip8Error::RomTooLarge { size: bytes.len(), room, addr: self.load_addr });
+                }
+                chip8.load_rom_bytes(bytes);
+            },
+            Some(Rom::File(path)) => chip8.load_rom(&path)?,
+            None => {},
+        }
+        Ok(chip8)
+    }
+}
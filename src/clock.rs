@@ -0,0 +1,91 @@
+/************
+Clocks and pacing
+
+The machine itself only counts cycles: the timers tick every CYCLES_PER_TIMER_TICK cycles, so a
+run is the same however fast it's emulated. What differs between frontends is how much time
+passes between two looks at the clock, and so how many cycles are due:
+
+RealClock     - wall clock time, for the window, which has to keep up with 60Hz for real
+VirtualClock  - every tick is a fixed amount of time, passing instantly. For headless and test runs,
+                which run as fast as the machine allows
+
+A Pacer turns the time a Clock reports into the number of cycles to run, the one code path both
+go through. It runs CYCLES_PER_SECOND cycles per second unless set to the machine's clock rate.
+The window's emulation thread (see emulation.rs) follows a RealClock with one.
+************/
+use std::time::Instant;
+
+use CYCLES_PER_SECOND;
+
+pub trait Clock {
+    //Seconds that passed since the last tick (or since the clock was made)
+    fn tick(&mut self) -> f64;
+}
+
+pub struct RealClock {
+    last: Instant,
+}
+
+impl RealClock {
+    pub fn new() -> RealClock {
+        RealClock { last: Instant::now() }
+    }
+}
+
+impl Clock for RealClock {
+    fn tick(&mut self) -> f64 {
+        let now = Instant::now();
+        let seconds = now.duration_since(self.last).as_secs_f64();
+        self.last = now;
+        seconds
+    }
+}
+
+pub struct VirtualClock {
+    step: f64,
+}
+
+impl VirtualClock {
+    pub fn new(step: f64) -> VirtualClock {
+        VirtualClock { step }
+    }
+
+    //A clock whose every tick is worth exactly the given number of cycles
+    pub fn cycles(cycles: u64) -> VirtualClock {
+        VirtualClock::new(cycles as f64 / CYCLES_PER_SECOND)
+    }
+}
+
+impl Clock for VirtualClock {
+    fn tick(&mut self) -> f64 {
+        self.step
+    }
+}
+
+pub struct Pacer {
+    pending: f64,   //Time that has passed but hasn't been emulated yet
+    max_lag: f64,   //Most time (at normal speed) ever caught up on at once
+    rate: f64,      //Cycles per second at normal speed
+}
+
+impl Pacer {
+    pub fn new(max_lag: f64) -> Pacer {
+        Pacer { pending: 0.0, max_lag, rate: CYCLES_PER_SECOND }
+    }
+
+    pub fn set_rate(&mut self, hz: f64) {
+        self.rate = hz;
+    }
+
+    //Takes the time passed since the last tick of the clock, sped up by `speed`, and returns how many
+    //cycles are due and how much time was dropped for being more than max_lag behind
+    pub fn advance(&mut self, clock: &mut dyn Clock, speed: f64) -> (u64, f64) {
+        let wanted = self.pending + clock.tick() * speed;
+        self.pending = wanted.min(self.max_lag * speed);
+        let dropped = wanted - self.pending;
+
+        let due = (self.pending * self.rate + 1e-9).floor();
+        self.pending = (self.pending - due / self.rate).max(0.0);
+        (due as u64, dropped)
+    }
+}
@@ -0,0 +1,162 @@
+/************
+Command palette
+
+Ctrl+P opens a searchable list of everything the window can do, so the hotkeys don't have to be
+remembered. Typing narrows the list down with fuzzy matching: the letters typed have to appear in
+the command's name in order, but not next to each other, so "sst" finds "Save state to slot 1".
+Matches at the start of words and runs of letters in a row rank first.
+
+There's no font to draw names with (the font sprites are only hex digits), so the window title
+shows the query and the selected command, and the overlay shows a bar per match with the selected
+one lit. The game is paused while the palette is open.
+
+Keys while the palette is open:
+    Up/Down     Pick a match
+    Return      Run it
+    Backspace   Delete the last letter typed
+    Ctrl+P      Close the palette
+
+Commands that have a hotkey run by pressing it (see main.rs), so they do exactly what the hotkey does.
+************/
+use piston_window::*;
+
+use panels::PanelKind;
+
+//How many matches the overlay shows
+const SHOWN: usize = 10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Hotkey(Key),
+    Speed(f64),
+    Panel(PanelKind),
+}
+
+pub struct Command {
+    pub name: &'static str,
+    pub action: Action,
+}
+
+pub const COMMANDS: &[Command] = &[
+    Command { name: "Save state to slot 1 (F1)", action: Action::Hotkey(Key::F1) },
+    Command { name: "Save state to slot 2 (F2)", action: Action::Hotkey(Key::F2) },
+    Command { name: "Save state to slot 3 (F3)", action: Action::Hotkey(Key::F3) },
+    Command { name: "Save state to slot 4 (F4)", action: Action::Hotkey(Key::F4) },
+    Command { name: "Load state menu (F9)", action: Action::Hotkey(Key::F9) },
+    Command { name: "Undo state load (F10)", action: Action::Hotkey(Key::F10) },
+    Command { name: "Save session recording (F8)", action: Action::Hotkey(Key::F8) },
+    Command { name: "Switch to next profile, colors and quirks (F5)", action: Action::Hotkey(Key::F5) },
+    Command { name: "Toggle deflicker (F6)", action: Action::Hotkey(Key::F6) },
+    Command { name: "Rotate display (F7)", action: Action::Hotkey(Key::F7) },
+    Command { name: "Toggle memory viewer (F11)", action: Action::Hotkey(Key::F11) },
+    Command { name: "Open ROM library (F12)", action: Action::Hotkey(Key::F12) },
+    Command { name: "Set speed to half", action: Action::Speed(0.5) },
+    Command { name: "Set speed to normal", action: Action::Speed(1.0) },
+    Command { name: "Set speed to double", action: Action::Speed(2.0) },
+    Command { name: "Set speed to quadruple", action: Action::Speed(4.0) },
+    Command { name: "Toggle debugger disassembly panel", action: Action::Panel(PanelKind::Disassembly) },
+    Command { name: "Toggle debugger registers panel", action: Action::Panel(PanelKind::Registers) },
+    Command { name: "Toggle debugger memory panel", action: Action::Panel(PanelKind::Hex) },
+];
+
+//How well the query matches the name, None if it doesn't. Every letter of the query has to be
+//found in the name, in order and ignoring case. Letters at the start of a word and letters right
+//after the previous match score more, skipped letters cost a little
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i32> {
+    let name: Vec<char> = name.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut position = 0;
+    let mut last_match: Option<usize> = None;
+
+    for wanted in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = position + name[position..].iter().position(|&c| c == wanted)?;
+        score += 1;
+        if found == 0 || !name[found - 1].is_alphanumeric() {
+            score += 8;
+        }
+        match last_match {
+            Some(last) if found == last + 1 => score += 5,
+            Some(last) => score -= (found - last - 1).min(5) as i32,
+            None => score -= found.min(5) as i32,
+        }
+        last_match = Some(found);
+        position = found + 1;
+    }
+    Some(score)
+}
+
+pub struct CommandPalette {
+    query: String,
+    matches: Vec<usize>,    //Indexes into COMMANDS, best first
+    selected: usize,
+}
+
+impl CommandPalette {
+    pub fn open() -> CommandPalette {
+        println!("Command palette: type to search, Up/Down to pick, Return to run, Ctrl+P to close");
+        let mut palette = CommandPalette { query: String::new(), matches: Vec::new(), selected: 0 };
+        palette.search();
+        palette
+    }
+
+    fn search(&mut self) {
+        let mut scored: Vec<(i32, usize)> = COMMANDS.iter().enumerate()
+            .filter_map(|(index, command)| fuzzy_score(&self.query, command.name).map(|score| (score, index)))
+            .collect();
+        //Stable, so equally good matches stay in the list's order
+        scored.sort_by_key(|&(score, _)| -score);
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = 0;
+    }
+
+    //Adds typed text to the query
+    pub fn text(&mut self, text: &str) {
+        let before = self.query.len();
+        self.query.extend(text.chars().filter(|c| !c.is_control()));
+        if self.query.len() != before {
+            self.search();
+        }
+    }
+
+    //Handles a key pressed while the palette is open. Returns the action to run once one is picked
+    pub fn key(&mut self, key: Key) -> Option<Action> {
+        match key {
+            Key::Up if self.selected > 0 => self.selected -= 1,
+            Key::Down if self.selected + 1 < self.matches.len() => self.selected += 1,
+            Key::Backspace => {
+                self.query.pop();
+                self.search();
+            },
+            Key::Return => return self.matches.get(self.selected).map(|&index| COMMANDS[index].action),
+            _ => {},
+        }
+        None
+    }
+
+    //What goes in the window title
+    pub fn describe(&self) -> String {
+        match self.matches.get(self.selected) {
+            Some(&index) => format!("> {}: {} ({}/{})", self.query, COMMANDS[index].name, self.selected + 1, self.matches.len()),
+            None => format!("> {}: no matching command", self.query),
+        }
+    }
+
+    pub fn draw(&self, window: &mut PistonWindow, event: &Event) {
+        window.draw_2d(event, |c, g| {
+            let view = c.get_view_size();
+            //Dim the game behind the palette
+            rectangle([0.0, 0.0, 0.0, 0.8], [0.0, 0.0, view[0], view[1]], c.transform, g);
+
+            let row = (view[1] / (SHOWN + 2) as f64).floor();
+            let left = (view[0] / 8.0).floor();
+            let width = view[0] - left * 2.0;
+            //Scroll along with the selection
+            let first = (self.selected + 1).saturating_sub(SHOWN);
+            for (shown, index) in (first..self.matches.len()).take(SHOWN).enumerate() {
+                let color = if index == self.selected { [1.0, 0.8, 0.0, 1.0] } else { [0.4, 0.4, 0.4, 1.0] };
+                let top = row * (shown + 1) as f64;
+                rectangle(color, [left, top + 2.0, width, row - 4.0], c.transform, g);
+            }
+        });
+    }
+}
@@ -0,0 +1,131 @@
+/************
+Control prompts
+
+When the ROM database (see metadata.rs) knows a ROM's controls, they are printed when the ROM
+starts, and the CHIP-8 keypad is shown over the game for a few seconds with the keys the game
+uses lit up. The keypad is drawn in the layout of the 1234/qwer/asdf/zxcv keys it's mapped to,
+so the lit keys show where to put your fingers. The keypad tester (see keytest.rs) draws the same
+keypad.
+
+With --auto-map, controls with a well known action are also bound to the keys people expect:
+left/right/up/down to the arrow keys and shoot/fire/jump/action/start to space. The original
+keys keep working.
+************/
+use piston_window::*;
+
+use input::{button_name, Keymap};
+use metadata::RomInfo;
+use FONT;
+
+//How long the keypad stays over the game
+const SHOW_SECONDS: f64 = 6.0;
+
+//The CHIP-8 keypad, as laid out on the host keyboard
+const KEYPAD: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+
+fn action_button(action: &str) -> Option<Key> {
+    match action.to_lowercase().as_str() {
+        "left" => Some(Key::Left),
+        "right" => Some(Key::Right),
+        "up" => Some(Key::Up),
+        "down" => Some(Key::Down),
+        "shoot" | "fire" | "jump" | "action" | "start" => Some(Key::Space),
+        _ => None,
+    }
+}
+
+//Binds the ROM's well known actions onto the arrow keys and space. The first control wins if
+//several share an action
+pub fn auto_map(info: &RomInfo, keymap: &mut Keymap) {
+    let mut mapped = Vec::new();
+    for control in &info.controls {
+        if let Some(key) = action_button(&control.action) {
+            if !mapped.contains(&key) {
+                keymap.bind(Button::Keyboard(key), control.key);
+                mapped.push(key);
+            }
+        }
+    }
+}
+
+//"Controls for Pong: 1 = up, 4 = down", with the host keys for each CHIP-8 key
+pub fn describe(info: &RomInfo, keymap: &Keymap) -> String {
+    let controls: Vec<String> = info.controls.iter().map(|control| {
+        let buttons: Vec<String> = keymap.buttons_for(control.key).into_iter().map(button_name).collect();
+        let buttons = if buttons.is_empty() { format!("key {:X} (unbound)", control.key) } else { buttons.join("/") };
+        format!("{} = {}", buttons, control.action)
+    }).collect();
+
+    format!("Controls for {}: {}", info.title.as_deref().unwrap_or("this ROM"), controls.join(", "))
+}
+
+pub struct ControlsOverlay {
+    used: [bool; 16],
+    remaining: f64,    //Seconds left on screen
+}
+
+impl ControlsOverlay {
+    pub fn new(info: &RomInfo) -> ControlsOverlay {
+        let mut used = [false; 16];
+        for control in &info.controls {
+            used[control.key as usize] = true;
+        }
+        ControlsOverlay { used, remaining: SHOW_SECONDS }
+    }
+
+    //Counts down the time on screen. Returns false once the overlay should go away
+    pub fn update(&mut self, dt: f64) -> bool {
+        self.remaining -= dt;
+        self.remaining > 0.0
+    }
+
+    pub fn draw(&self, window: &mut PistonWindow, event: &Event) {
+        window.draw_2d(event, |c, g| {
+            let view = c.get_view_size();
+            let cell = (view[0].min(view[1]) / 6.0).floor();
+            let left = ((view[0] - cell * 4.0) / 2.0).floor();
+            let top = ((view[1] - cell * 4.0) / 2.0).floor();
+            draw_keypad(&self.used, [left, top], cell, c, g);
+        });
+    }
+}
+
+//Draws a hex digit from the font sprites (4 x 5 pixels) with its top left corner at `pos`
+pub fn draw_digit(digit: u8, pos: [f64; 2], pixel: f64, color: [f32; 4], c: Context, g: &mut G2d) {
+    for line in 0..5 {
+        let bits = FONT[digit as usize * 5 + line];
+        for bit in 0..4 {
+            if bits & (0x80 >> bit) != 0 {
+                let square = [pos[0] + bit as f64 * pixel, pos[1] + line as f64 * pixel, pixel, pixel];
+                rectangle(color, square, c.transform, g);
+            }
+        }
+    }
+}
+
+//Draws the 4 x 4 keypad with its top left corner at `pos`, the keys in `lit` highlighted
+pub fn draw_keypad(lit: &[bool; 16], pos: [f64; 2], cell: f64, c: Context, g: &mut G2d) {
+    let pixel = (cell / 8.0).floor().max(1.0);
+    rectangle([0.0, 0.0, 0.0, 0.8], [pos[0] - 4.0, pos[1] - 4.0, cell * 4.0 + 8.0, cell * 4.0 + 8.0], c.transform, g);
+
+    for (row, keys) in KEYPAD.iter().enumerate() {
+        for (column, key) in keys.iter().enumerate() {
+            let x = pos[0] + column as f64 * cell;
+            let y = pos[1] + row as f64 * cell;
+            let (background, digit) = if lit[*key as usize] {
+                ([1.0, 0.8, 0.0, 1.0], [0.0, 0.0, 0.0, 1.0])
+            } else {
+                ([0.2, 0.2, 0.2, 1.0], [0.5, 0.5, 0.5, 1.0])
+            };
+            rectangle(background, [x + 2.0, y + 2.0, cell - 4.0, cell - 4.0], c.transform, g);
+
+            let glyph = [x + ((cell - pixel * 4.0) / 2.0).floor(), y + ((cell - pixel * 5.0) / 2.0).floor()];
+            draw_digit(*key, glyph, pixel, digit, c, g);
+        }
+    }
+}
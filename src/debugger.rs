@@ -0,0 +1,994 @@
+/************
+Debugger
+
+Usage: chip8 debug <romfile> [--script FILE] [--share ADDR] [options]
+
+Runs the ROM without a window and reads debugger commands, either typed in interactively or
+from a script file (one command per line, # starts a comment). Scripts are executed in order
+and stop at the first failed command, which makes them usable as reproducible bug recipes
+and assert-style ROM tests: a failed assertion ends the process with exit code 1 and the
+script line, the assertion and the values it compared. --share lets others attach to an
+interactive session over the network (see share.rs).
+
+Commands:
+    break ADDR           Set a breakpoint              (b)
+    break START-END      Set a region breakpoint: stop as soon as the program jumps, calls or returns into
+                         the range from outside it, ie break 0x000-0x1FF to catch wild jumps into the
+                         interpreter area. The stop names the instruction that went there
+    tbreak ADDR          Set a temporary breakpoint, removed once it's hit
+    until ADDR           Run until ADDR is reached once (a temporary breakpoint plus run)  (u)
+    delete ADDR          Remove a breakpoint (or START-END, a region breakpoint)  (d)
+    breakpoints          List breakpoints
+    run [CYCLES]         Run until a breakpoint or for the given number of cycles (r, continue, c)
+    step [N]             Execute N instructions (default 1)  (s)
+    frame [N]            Run until N more frames have ended, on the timer tick that ends them (default 1,
+                         at least 1)
+    next                 Like step, but runs a subroutine call (2NNN) until it returns  (n)
+    finish               Run until the current subroutine returns  (f)
+    regs                 Print registers
+    stack                Print the call stack, newest first, with the instruction each call returns to
+    sprite               Print where the last sprite was drawn and whether it collided
+    dump ADDR [LEN]      Print LEN bytes of memory starting at ADDR (default 16)
+    history [N]          Print the last N executed instructions (default 20)
+    poke ADDR=EXPR       Write a byte into memory (same expressions as --poke)
+    key KEY up|down      Release or press a keypad key (0-F)
+    assert EXPR OP EXPR  Fail unless the comparison holds, OP is one of == != < <= > >=. Besides the poke
+                         expressions, pixel(X,Y) is a screen pixel (0 or 1) and frame_hash the hash of the
+                         screen, collision whether the last sprite collided, ie assert v[0] == 3,
+                         assert pixel(10,5) == 1, assert frame_hash == 0x1F2E...
+    trace on|off         Print every executed opcode
+    trace ADDR "FORMAT"  Set a tracepoint: print FORMAT each time ADDR executes, without stopping.
+                         {EXPR} in the format is replaced by the value of a poke expression,
+                         ie trace 0x2F0 "lives={v(4)} x={v[0]}" (v[N] works as well as v(N))
+    untrace ADDR         Remove a tracepoint
+    tracepoints          List tracepoints
+    watch ADDR           Set a watchpoint: stop as soon as the byte at ADDR changes
+    unwatch ADDR         Remove a watchpoint
+    watchpoints          List watchpoints
+    cheat ADDR=EXPR      Keep writing a value into memory after every instruction, ie infinite lives
+    uncheat ADDR         Remove a cheat
+    cheats               List cheats
+    search HEX...        Find a byte sequence anywhere in memory, ?? matches any byte, ie search A2 ?? 60
+    search op PATTERN    Find an opcode pattern, X Y N K are wildcard digits, ie search op DXYN or search op 6XK0
+    export FILE          Save breakpoints, watchpoints, tracepoints, cheats, regions and hooks as JSON
+    import FILE          Add everything from an exported file, ie a setup someone shared for the ROM
+    region NAME: START-END  Name a memory range, saved in <rom>.annotations (see annotations.rs).
+                         Addresses and expressions can use the name, ie dump sprite_table
+    unregion NAME        Forget a named range
+    regions              List named ranges
+    smc off|log|break    What to do about self-modifying code: ignore, print (default) or stop on it
+    save FILE            Write a save state of the machine to FILE (same format as the window's slots)
+    load FILE            Restore the machine from a save state
+    hook ADDR COMMAND    Run a debugger command each time ADDR is about to execute, for practice tools
+                         in scripts, ie hook 0x2A4 save level.state (level start), hook 0x3B0 load
+                         level.state (death). Commands that run the machine can't be hooks
+    unhook ADDR          Remove a hook
+    hooks                List hooks
+    quit                 Leave the debugger          (q)
+
+Exported setups look like this (addresses are strings, so region names work in them too):
+    {"version":1,"breakpoints":["0x2F0"],"temporary_breakpoints":[],"region_breakpoints":["0x000-0x1FF"],
+     "watchpoints":["0x3A0"],
+     "tracepoints":[{"addr":"0x2F0","format":"lives={v(4)}"}],"cheats":["0x3A0=0x09"],
+     "regions":[{"name":"score","start":"0x3A0","end":"0x3A2"}],
+     "hooks":[{"addr":"0x3B0","command":"load level.state"}]}
+************/
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::process;
+
+use annotations::{Annotations, Region};
+use disasm::disassemble;
+use exitcode;
+use json::Json;
+use options::{parse_number, take_value, Options};
+use poke::{self, Expr, Poke};
+use protect::{CodeWrite, MemoryFault};
+use share::{Share, Source};
+use {create_chip8, Chip8};
+
+//Version of the exported setup format
+const SETUP_VERSION: i64 = 1;
+
+//Upper limit of cycles for a "run" without a cycle count, so a script can never hang forever
+const DEFAULT_RUN_LIMIT: u64 = 10_000_000;
+
+//Commands that run the machine, which a hook (running in the middle of a run) can't
+const RUNNING_COMMANDS: &[&str] = &["run", "r", "continue", "c", "step", "s", "frame", "next", "n", "finish", "f", "until", "u"];
+
+//Commands only the debugger's own terminal can run in a shared session: they end it, or read and write
+//files on this machine (hooks can save and load too)
+const LOCAL_COMMANDS: &[&str] = &["quit", "q", "save", "load", "export", "import", "hook"];
+
+//Most cycles a command from a shared session's client can run, so nobody can keep the session busy
+const REMOTE_RUN_LIMIT: u64 = 100_000;
+
+//Why a run stopped
+enum Stop {
+    Breakpoint(u16),
+    RegionBreakpoint(Region, u16, u16),   //The region, the instruction that went into it and where it went
+    Watchpoint(u16, u8, u8),    //Address, old and new value
+    Halted,         //Waiting for a key press (FX0A), which can never come without a key command
+    InvalidPc(u16),
+    Fault(MemoryFault),
+    CodeWrite(CodeWrite),
+    Finished,       //Ran the requested number of cycles
+}
+
+//What the command loop should do after a command
+enum Flow {
+    Continue,
+    Quit,
+}
+
+//What to do when the program writes over code it has already executed
+#[derive(Clone, Copy, PartialEq)]
+enum SmcMode {
+    Off,
+    Log,
+    Break,
+}
+
+//A piece of a tracepoint's format: text printed as is, or an expression printed as its value
+enum FormatPart {
+    Text(String),
+    Value(Expr),
+}
+
+struct Tracepoint {
+    format: String,
+    parts: Vec<FormatPart>,
+}
+
+impl Tracepoint {
+    fn parse(format: &str, annotations: &Annotations) -> Result<Tracepoint, String> {
+        let mut parts = Vec::new();
+        let mut rest = format;
+        while let Some(open) = rest.find('{') {
+            let close = match rest[open..].find('}') {
+                Some(close) => open + close,
+                None => return Err(format!("Missing }} in {}", format)),
+            };
+            if open > 0 {
+                parts.push(FormatPart::Text(rest[..open].to_string()));
+            }
+            parts.push(FormatPart::Value(Expr::parse(&annotations.resolve(&rest[open + 1..close])?)?));
+            rest = &rest[close + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(FormatPart::Text(rest.to_string()));
+        }
+        Ok(Tracepoint { format: format.to_string(), parts })
+    }
+
+    fn print(&self, chip8: &Chip8) {
+        let mut line = format!("[{:#06X}] ", chip8.pc());
+        for part in &self.parts {
+            match *part {
+                FormatPart::Text(ref text) => line.push_str(text),
+                FormatPart::Value(ref expr) => line.push_str(&expr.eval(chip8).to_string()),
+            }
+        }
+        println!("{}", line);
+    }
+}
+
+pub struct Debugger {
+    chip8: Chip8,
+    breakpoints: BTreeSet<u16>,
+    temporary_breakpoints: BTreeSet<u16>,
+    region_breakpoints: Vec<Region>,
+    tracepoints: BTreeMap<u16, Tracepoint>,
+    watchpoints: BTreeSet<u16>,
+    cheats: BTreeMap<u16, (String, Poke)>,  //As typed, and parsed
+    hooks: BTreeMap<u16, String>,
+    annotations: Annotations,
+    smc_mode: SmcMode,
+    run_limit: u64,     //Most cycles a command may run, lowered for the clients of a shared session
+}
+
+impl Debugger {
+    pub fn new(mut chip8: Chip8, annotations: Annotations) -> Debugger {
+        //Printing every opcode would drown out the debugger output
+        chip8.trace = false;
+
+        Debugger {
+            chip8,
+            breakpoints: BTreeSet::new(),
+            temporary_breakpoints: BTreeSet::new(),
+            region_breakpoints: Vec::new(),
+            tracepoints: BTreeMap::new(),
+            watchpoints: BTreeSet::new(),
+            cheats: BTreeMap::new(),
+            hooks: BTreeMap::new(),
+            annotations,
+            smc_mode: SmcMode::Log,
+            run_limit: u64::MAX,
+        }
+    }
+
+    //Executes a single instruction, unless the program counter has left memory
+    fn step(&mut self) -> Option<Stop> {
+        if let Some(hook) = self.hooks.get(&self.chip8.pc()).cloned() {
+            //A hook that fails doesn't stop the run, the program carries on as if it weren't there
+            if let Err(err) = self.execute(&hook) {
+                println!("Hook at {:#06X} failed: {}", self.chip8.pc(), err);
+            }
+        }
+        let pc = self.chip8.pc();
+        if pc as usize + 1 >= 4096 {
+            return Some(Stop::InvalidPc(pc));
+        }
+        if self.chip8.is_halted() {
+            return Some(Stop::Halted);
+        }
+        if let Some(tracepoint) = self.tracepoints.get(&pc) {
+            tracepoint.print(&self.chip8);
+        }
+        let watched: Vec<(u16, u8)> = self.watchpoints.iter().map(|addr| (*addr, self.chip8.read_byte(*addr))).collect();
+        if let (Err(_), Some(fault)) = (self.chip8.step(), self.chip8.fault()) {
+            return Some(Stop::Fault(fault));
+        }
+        //Only entering a region counts, running on inside it doesn't
+        let to = self.chip8.pc();
+        let inside = |region: &Region, addr: u16| region.start <= addr && addr <= region.end;
+        if let Some(region) = self.region_breakpoints.iter().find(|region| inside(region, to) && !inside(region, pc)) {
+            return Some(Stop::RegionBreakpoint(*region, pc, to));
+        }
+        for (_, poke) in self.cheats.values() {
+            poke.apply(&mut self.chip8);
+        }
+        for (addr, old) in watched {
+            let new = self.chip8.read_byte(addr);
+            if new != old {
+                return Some(Stop::Watchpoint(addr, old, new));
+            }
+        }
+
+        if let Some(write) = self.chip8.code_write() {
+            match self.smc_mode {
+                SmcMode::Off => {},
+                SmcMode::Log => println!("{}", write),
+                SmcMode::Break => return Some(Stop::CodeWrite(write)),
+            }
+        }
+        None
+    }
+
+    //Runs up to `cycles` instructions, stopping early at breakpoints
+    //The instruction at the current PC is always executed, so we can continue from a breakpoint
+    fn run(&mut self, cycles: u64) -> Stop {
+        self.run_until(cycles, |_| false)
+    }
+
+    //Like run, but also stops as soon as `done` holds after an instruction
+    fn run_until<F: Fn(&Chip8) -> bool>(&mut self, cycles: u64, done: F) -> Stop {
+        for cycle in 0..cycles.min(self.run_limit) {
+            let pc = self.chip8.pc();
+            if cycle > 0 && self.breakpoints.contains(&pc) {
+                return Stop::Breakpoint(pc);
+            }
+            if cycle > 0 && self.temporary_breakpoints.remove(&pc) {
+                return Stop::Breakpoint(pc);
+            }
+            if let Some(stop) = self.step() {
+                return stop;
+            }
+            if done(&self.chip8) {
+                break;
+            }
+        }
+        Stop::Finished
+    }
+
+    //Steps over the instruction at the PC, calls included: a 2NNN runs until the subroutine has
+    //returned to the instruction after it, with the stack back where it was
+    fn next(&mut self) -> Stop {
+        let pc = self.chip8.pc();
+        let opcode = self.chip8.opcode_at(pc);
+        if opcode & 0xF000 != 0x2000 {
+            return self.run(1);
+        }
+        let sp = self.chip8.sp();
+        self.run_until(DEFAULT_RUN_LIMIT, |chip8| chip8.sp() == sp && chip8.pc() == pc + 2)
+    }
+
+    //Runs until the subroutine the PC is in returns (its 00EE pops the stack below where it is now)
+    fn finish(&mut self) -> Result<Stop, String> {
+        let sp = self.chip8.sp();
+        if sp == 0 {
+            return Err(String::from("Not inside a subroutine"));
+        }
+        Ok(self.run_until(DEFAULT_RUN_LIMIT, |chip8| chip8.sp() < sp))
+    }
+
+    fn report(&self, stop: Stop) {
+        match stop {
+            Stop::Breakpoint(addr) => println!("Breakpoint hit at {:#06X}", addr),
+            Stop::RegionBreakpoint(region, from, to) => println!("Region breakpoint {:#06X}-{:#06X} hit at {:#06X}, reached from {:#06X} ({})",
+                                                                region.start, region.end, to, from, disassemble(self.chip8.opcode_at(from), self.chip8.variant())),
+            Stop::Watchpoint(addr, old, new) => println!("Watchpoint {:#06X} changed from {:#04X} to {:#04X} at {:#06X}",
+                                                         addr, old, new, self.chip8.pc()),
+            Stop::Halted => println!("Waiting for a key press at {:#06X}", self.chip8.pc()),
+            Stop::InvalidPc(addr) => println!("Program counter left memory at {:#06X}", addr),
+            Stop::Fault(fault) => println!("{}", fault),
+            Stop::CodeWrite(write) => println!("{}", write),
+            Stop::Finished => println!("Stopped at {:#06X}", self.chip8.pc()),
+        }
+    }
+
+    //An address argument, a number or a region name
+    fn address(&self, text: &str) -> Result<u16, String> {
+        parse_number(&self.annotations.resolve(text)?)
+    }
+
+    //A START-END argument, None if it's a single address
+    fn range(&self, text: &str) -> Result<Option<Region>, String> {
+        let (start, end) = match text.find('-') {
+            Some(dash) => (self.address(&text[..dash])?, self.address(&text[dash + 1..])?),
+            None => return Ok(None),
+        };
+        if end < start || end as usize >= 4096 {
+            return Err(format!("Invalid memory range {}", text));
+        }
+        Ok(Some(Region { start, end }))
+    }
+
+    fn add_region_breakpoint(&mut self, region: Region) {
+        if !self.region_breakpoints.contains(&region) {
+            self.region_breakpoints.push(region);
+        }
+    }
+
+    //Parses and executes a single debugger command
+    fn execute(&mut self, line: &str) -> Result<Flow, String> {
+        let line = match line.find('#') {
+            Some(comment) => &line[..comment],
+            None => line,
+        };
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return Ok(Flow::Continue),
+        };
+        let args: Vec<&str> = words.collect();
+
+        match command {
+            "break" | "b" => {
+                if let Some(region) = self.range(argument(&args, 0)?)? {
+                    self.add_region_breakpoint(region);
+                    println!("Region breakpoint set at {:#06X}-{:#06X}", region.start, region.end);
+                    return Ok(Flow::Continue);
+                }
+                let addr = self.address(argument(&args, 0)?)?;
+                self.breakpoints.insert(addr);
+                println!("Breakpoint set at {:#06X}", addr);
+            },
+            "delete" | "d" => {
+                if let Some(region) = self.range(argument(&args, 0)?)? {
+                    let count = self.region_breakpoints.len();
+                    self.region_breakpoints.retain(|other| *other != region);
+                    if self.region_breakpoints.len() == count {
+                        return Err(format!("No region breakpoint at {:#06X}-{:#06X}", region.start, region.end));
+                    }
+                    return Ok(Flow::Continue);
+                }
+                let addr = self.address(argument(&args, 0)?)?;
+                if !self.breakpoints.remove(&addr) && !self.temporary_breakpoints.remove(&addr) {
+                    return Err(format!("No breakpoint at {:#06X}", addr));
+                }
+            },
+            "tbreak" => {
+                let addr = self.address(argument(&args, 0)?)?;
+                self.temporary_breakpoints.insert(addr);
+                println!("Temporary breakpoint set at {:#06X}", addr);
+            },
+            "until" | "u" => {
+                let addr = self.address(argument(&args, 0)?)?;
+                self.temporary_breakpoints.insert(addr);
+                let stop = self.run(DEFAULT_RUN_LIMIT);
+                //Stopping somewhere else first shouldn't leave the breakpoint behind
+                self.temporary_breakpoints.remove(&addr);
+                self.report(stop);
+            },
+            "breakpoints" => {
+                for addr in &self.breakpoints {
+                    println!("{:#06X}", addr);
+                }
+                for addr in &self.temporary_breakpoints {
+                    println!("{:#06X} (temporary)", addr);
+                }
+                for region in &self.region_breakpoints {
+                    println!("{:#06X}-{:#06X} (region)", region.start, region.end);
+                }
+            },
+            "run" | "r" | "continue" | "c" => {
+                let cycles = match args.first() {
+                    Some(cycles) => cycles.parse::<u64>().map_err(|_| format!("Invalid cycle count {}", cycles))?,
+                    None => DEFAULT_RUN_LIMIT,
+                };
+                let stop = self.run(cycles);
+                self.report(stop);
+            },
+            "step" | "s" => {
+                let count = match args.first() {
+                    Some(count) => parse_number(count)?,
+                    None => 1,
+                };
+                for _ in 0..(count as u64).min(self.run_limit) {
+                    if let Some(stop) = self.step() {
+                        self.report(stop);
+                        break;
+                    }
+                }
+                self.chip8.print_history(count as usize);
+            },
+            "frame" => {
+                let count = match args.first() {
+                    Some(count) => parse_number(count)? as u64,
+                    None => 1,
+                };
+                if count == 0 {
+                    return Err(String::from("frame needs at least 1 frame to run"));
+                }
+                //The frame in progress counts as the first
+                let (cycles, per_tick) = (self.chip8.cycles(), self.chip8.cycles_per_tick());
+                let end = (cycles / per_tick + count) * per_tick;
+                let stop = self.run(end - cycles);
+                self.report(stop);
+            },
+            "next" | "n" => {
+                let stop = self.next();
+                self.report(stop);
+            },
+            "finish" | "f" => {
+                let stop = self.finish()?;
+                self.report(stop);
+            },
+            "regs" => self.chip8.print_registers(),
+            "stack" => self.print_stack(),
+            "sprite" => match self.chip8.last_draw_rect() {
+                Some(rect) => println!("Last sprite: {}x{} at {},{}{}", rect.width, rect.height, rect.x, rect.y,
+                                       if self.chip8.last_collision() { ", collided" } else { "" }),
+                None => println!("No sprite drawn yet"),
+            },
+            "dump" => {
+                let addr = self.address(argument(&args, 0)?)? as usize;
+                let len = match args.get(1) {
+                    Some(len) => parse_number(len)? as usize,
+                    None => 16,
+                };
+                self.dump(addr, len);
+            },
+            "history" => {
+                let count = match args.first() {
+                    Some(count) => parse_number(count)?,
+                    None => 20,
+                };
+                self.chip8.print_history(count as usize);
+            },
+            "poke" => poke::parse_poke(&self.annotations.resolve(&args.concat())?)?.apply(&mut self.chip8),
+            "region" => {
+                let (name, region) = self.annotations.add_region(&args.join(" "))?;
+                println!("Region {} is {:#06X}-{:#06X}", name, region.start, region.end);
+            },
+            "unregion" => self.annotations.remove_region(argument(&args, 0)?)?,
+            "regions" => {
+                for (name, region) in self.annotations.regions() {
+                    println!("{:20} {:#06X}-{:#06X} ({} bytes)", name, region.start, region.end, region.end - region.start + 1);
+                }
+            },
+            "key" => {
+                let key = parse_number(argument(&args, 0)?)?;
+                if key > 0xF {
+                    return Err(format!("Invalid key {}", key));
+                }
+                let pressed = match argument(&args, 1)? {
+                    "down" => true,
+                    "up" => false,
+                    other => return Err(format!("Expected up or down, got {}", other)),
+                };
+                self.chip8.press_key(key as u8, pressed);
+            },
+            "assert" => self.assert(line.trim_start()[command.len()..].trim())?,
+            "trace" if args.len() > 1 => {
+                let addr = self.address(args[0])?;
+                //The format is the rest of the line, spaces and all
+                let format = line.trim_start()[command.len()..].trim_start()[args[0].len()..].trim();
+                let format = format.strip_prefix('"').and_then(|format| format.strip_suffix('"')).unwrap_or(format);
+                self.tracepoints.insert(addr, Tracepoint::parse(format, &self.annotations)?);
+                println!("Tracepoint set at {:#06X}", addr);
+            },
+            "untrace" => {
+                let addr = self.address(argument(&args, 0)?)?;
+                if self.tracepoints.remove(&addr).is_none() {
+                    return Err(format!("No tracepoint at {:#06X}", addr));
+                }
+            },
+            "tracepoints" => {
+                for (addr, tracepoint) in &self.tracepoints {
+                    println!("{:#06X} \"{}\"", addr, tracepoint.format);
+                }
+            },
+            "watch" => {
+                let addr = self.address(argument(&args, 0)?)?;
+                self.watchpoints.insert(addr);
+                println!("Watchpoint set at {:#06X}", addr);
+            },
+            "unwatch" => {
+                let addr = self.address(argument(&args, 0)?)?;
+                if !self.watchpoints.remove(&addr) {
+                    return Err(format!("No watchpoint at {:#06X}", addr));
+                }
+            },
+            "watchpoints" => {
+                for addr in &self.watchpoints {
+                    println!("{:#06X} = {:#04X}", addr, self.chip8.read_byte(*addr));
+                }
+            },
+            "cheat" => self.add_cheat(&args.concat())?,
+            "uncheat" => {
+                let addr = self.address(argument(&args, 0)?)?;
+                if self.cheats.remove(&addr).is_none() {
+                    return Err(format!("No cheat at {:#06X}", addr));
+                }
+            },
+            "cheats" => {
+                for (text, _) in self.cheats.values() {
+                    println!("{}", text);
+                }
+            },
+            "search" => {
+                let matches = match args.first() {
+                    Some(&"op") => self.search_opcode(argument(&args, 1)?)?,
+                    _ => self.search_bytes(&args.concat())?,
+                };
+                for addr in &matches {
+                    let opcode = self.chip8.opcode_at(*addr);
+                    let names = self.annotations.regions_in(*addr, *addr);
+                    let line = format!("{:#06X}: {:04X}  {:20} {}", addr, opcode, disassemble(opcode, self.chip8.variant()),
+                                       if names.is_empty() { String::new() } else { format!("<{}>", names.join(", ")) });
+                    println!("{}", line.trim_end());
+                }
+                println!("{} matches", matches.len());
+            },
+            "export" => {
+                let path = argument(&args, 0)?;
+                fs::write(path, format!("{}\n", self.export_setup()))
+                    .map_err(|err| format!("Could not write {}: {}", path, err))?;
+            },
+            "import" => {
+                let path = argument(&args, 0)?;
+                let text = fs::read_to_string(path).map_err(|err| format!("Could not read {}: {}", path, err))?;
+                let setup = Json::parse(&text).map_err(|err| format!("{}: {}", path, err))?;
+                self.import_setup(&setup).map_err(|err| format!("{}: {}", path, err))?;
+            },
+            "trace" => {
+                self.chip8.trace = match argument(&args, 0)? {
+                    "on" => true,
+                    "off" => false,
+                    other => return Err(format!("Expected on or off, got {}", other)),
+                };
+            },
+            "smc" => {
+                self.smc_mode = match argument(&args, 0)? {
+                    "off" => SmcMode::Off,
+                    "log" => SmcMode::Log,
+                    "break" => SmcMode::Break,
+                    other => return Err(format!("Expected off, log or break, got {}", other)),
+                };
+            },
+            "save" => {
+                let path = argument(&args, 0)?;
+                fs::write(path, self.chip8.save_state()).map_err(|err| format!("Could not write {}: {}", path, err))?;
+            },
+            "load" => {
+                let path = argument(&args, 0)?;
+                let data = fs::read(path).map_err(|err| format!("Could not read {}: {}", path, err))?;
+                self.chip8.load_state(&data).map_err(|err| format!("{}: {}", path, err))?;
+            },
+            "hook" if args.len() > 1 => {
+                let addr = self.address(args[0])?;
+                //The command is the rest of the line
+                let hook = line.trim_start()[command.len()..].trim_start()[args[0].len()..].trim();
+                self.add_hook(addr, hook)?;
+                println!("Hook set at {:#06X}", addr);
+            },
+            "unhook" => {
+                let addr = self.address(argument(&args, 0)?)?;
+                if self.hooks.remove(&addr).is_none() {
+                    return Err(format!("No hook at {:#06X}", addr));
+                }
+            },
+            "hooks" => {
+                for (addr, hook) in &self.hooks {
+                    println!("{:#06X} {}", addr, hook);
+                }
+            },
+            "quit" | "q" => return Ok(Flow::Quit),
+            _ => return Err(format!("Unknown command {}", command)),
+        }
+
+        Ok(Flow::Continue)
+    }
+
+    //Adds a cheat, or replaces the one for the same address, and applies it right away
+    fn add_cheat(&mut self, text: &str) -> Result<(), String> {
+        let poke = poke::parse_poke(&self.annotations.resolve(text)?)?;
+        poke.apply(&mut self.chip8);
+        println!("Cheat set at {:#06X}", poke.addr);
+        self.cheats.insert(poke.addr, (text.to_string(), poke));
+        Ok(())
+    }
+
+    fn add_hook(&mut self, addr: u16, hook: &str) -> Result<(), String> {
+        match hook.split_whitespace().next() {
+            Some(command) if RUNNING_COMMANDS.contains(&command) || command == "quit" || command == "q" => {
+                Err(format!("{} can't be a hook", command))
+            },
+            Some(_) => {
+                self.hooks.insert(addr, hook.to_string());
+                Ok(())
+            },
+            None => Err(String::from("Missing argument")),
+        }
+    }
+
+    //Addresses where the bytes in hex (?? for any byte) appear
+    fn search_bytes(&self, text: &str) -> Result<Vec<u16>, String> {
+        if text.is_empty() || !text.len().is_multiple_of(2) || !text.is_ascii() || text.len() > 2 * 4096 {
+            return Err(String::from("Usage: search HEX... (whole bytes, ?? for any)"));
+        }
+        let pattern: Vec<Option<u8>> = (0..text.len()).step_by(2).map(|pos| match &text[pos..pos + 2] {
+            "??" => Ok(None),
+            byte => u8::from_str_radix(byte, 16).map(Some).map_err(|_| format!("Invalid byte {}", byte)),
+        }).collect::<Result<_, _>>()?;
+
+        Ok((0..=4096 - pattern.len()).filter(|start| {
+            pattern.iter().enumerate().all(|(i, byte)| byte.is_none_or(|byte| self.chip8.read_byte((start + i) as u16) == byte))
+        }).map(|start| start as u16).collect())
+    }
+
+    //Addresses holding an opcode matching a pattern like DXYN, where X, Y, N and K match any digit
+    fn search_opcode(&self, pattern: &str) -> Result<Vec<u16>, String> {
+        if pattern.len() != 4 {
+            return Err(format!("Opcode patterns have 4 digits, got {}", pattern));
+        }
+        let mut mask = 0u16;
+        let mut value = 0u16;
+        for c in pattern.chars() {
+            mask <<= 4;
+            value <<= 4;
+            match c.to_ascii_uppercase() {
+                'X' | 'Y' | 'N' | 'K' => {},
+                c => {
+                    let digit = c.to_digit(16).ok_or_else(|| format!("Invalid opcode pattern {}", pattern))?;
+                    mask |= 0xF;
+                    value |= digit as u16;
+                },
+            }
+        }
+        Ok((0..4095).filter(|addr| self.chip8.opcode_at(*addr) & mask == value).collect())
+    }
+
+    fn export_setup(&self) -> Json {
+        let addresses = |addrs: &BTreeSet<u16>| addrs.iter().map(|addr| format!("{:#05X}", addr)).collect::<Vec<String>>();
+        let tracepoints: Vec<Json> = self.tracepoints.iter().map(|(addr, tracepoint)| Json::object()
+            .field("addr", format!("{:#05X}", addr))
+            .field("format", tracepoint.format.as_str())
+        ).collect();
+        let region_breakpoints: Vec<String> = self.region_breakpoints.iter()
+            .map(|region| format!("{:#05X}-{:#05X}", region.start, region.end))
+            .collect();
+        let cheats: Vec<&str> = self.cheats.values().map(|(text, _)| text.as_str()).collect();
+        let regions: Vec<Json> = self.annotations.regions().iter().map(|(name, region)| Json::object()
+            .field("name", name.as_str())
+            .field("start", format!("{:#05X}", region.start))
+            .field("end", format!("{:#05X}", region.end))
+        ).collect();
+        let hooks: Vec<Json> = self.hooks.iter().map(|(addr, hook)| Json::object()
+            .field("addr", format!("{:#05X}", addr))
+            .field("command", hook.as_str())
+        ).collect();
+
+        Json::object()
+            .field("version", SETUP_VERSION as u64)
+            .field("breakpoints", addresses(&self.breakpoints))
+            .field("temporary_breakpoints", addresses(&self.temporary_breakpoints))
+            .field("region_breakpoints", region_breakpoints)
+            .field("watchpoints", addresses(&self.watchpoints))
+            .field("tracepoints", tracepoints)
+            .field("cheats", cheats)
+            .field("regions", regions)
+            .field("hooks", hooks)
+    }
+
+    //Adds an exported setup to the current one. Regions come first, so everything else can use their names
+    fn import_setup(&mut self, setup: &Json) -> Result<(), String> {
+        match setup.get("version").and_then(Json::as_int) {
+            Some(SETUP_VERSION) => {},
+            Some(version) => return Err(format!("Unsupported setup version {}", version)),
+            None => return Err(String::from("Not a debugger setup")),
+        }
+
+        for region in list(setup, "regions")? {
+            let name = text_field(region, "name")?;
+            let range = format!("{}-{}", address_field(region, "start")?, address_field(region, "end")?);
+            self.annotations.add_region(&format!("{} {}", name, range))?;
+        }
+        for addr in list(setup, "breakpoints")? {
+            let addr = self.address(&address_text(addr)?)?;
+            self.breakpoints.insert(addr);
+        }
+        for addr in list(setup, "temporary_breakpoints")? {
+            let addr = self.address(&address_text(addr)?)?;
+            self.temporary_breakpoints.insert(addr);
+        }
+        for range in list(setup, "region_breakpoints")? {
+            let text = range.as_str().ok_or("Region breakpoints must be strings")?;
+            let region = self.range(text)?.ok_or_else(|| format!("Invalid memory range {}", text))?;
+            self.add_region_breakpoint(region);
+        }
+        for addr in list(setup, "watchpoints")? {
+            let addr = self.address(&address_text(addr)?)?;
+            self.watchpoints.insert(addr);
+        }
+        for tracepoint in list(setup, "tracepoints")? {
+            let addr = self.address(&address_field(tracepoint, "addr")?)?;
+            let parsed = Tracepoint::parse(text_field(tracepoint, "format")?, &self.annotations)?;
+            self.tracepoints.insert(addr, parsed);
+        }
+        for cheat in list(setup, "cheats")? {
+            self.add_cheat(cheat.as_str().ok_or("Cheats must be strings")?)?;
+        }
+        for hook in list(setup, "hooks")? {
+            let addr = self.address(&address_field(hook, "addr")?)?;
+            self.add_hook(addr, text_field(hook, "command")?)?;
+        }
+        Ok(())
+    }
+
+    //Newest call first, so the top of the stack (where SP points) is the first line
+    fn print_stack(&self) {
+        let calls = self.chip8.call_stack();
+        println!("SP: {}", self.chip8.sp());
+        if calls.is_empty() {
+            println!("Stack is empty, not inside a subroutine");
+        }
+        for (depth, call) in calls.iter().enumerate().rev() {
+            let ret = call.wrapping_add(2);
+            let marker = if depth + 1 == calls.len() { "<- SP" } else { "" };
+            let line = format!("#{:<2} {:#06X} {:16} returns to {:#06X} {:16} {}", depth + 1, call,
+                               disassemble(self.chip8.opcode_at(*call), self.chip8.variant()), ret, disassemble(self.chip8.opcode_at(ret), self.chip8.variant()), marker);
+            println!("{}", line.trim_end());
+        }
+    }
+
+    //Prints memory as rows of 16 bytes, each row prefixed with its address
+    fn dump(&self, addr: usize, len: usize) {
+        let end = (addr + len).min(4096);
+        let mut row = addr;
+        while row < end {
+            print!("{:#06X}:", row);
+            let row_end = (row + 16).min(end);
+            for byte in row..row_end {
+                print!(" {:02X}", self.chip8.read_byte(byte as u16));
+            }
+            //Named regions the row is part of
+            let names = self.annotations.regions_in(row as u16, row_end as u16 - 1);
+            if !names.is_empty() {
+                print!("{}  <{}>", "   ".repeat(16 - (row_end - row)), names.join(", "));
+            }
+            println!();
+            row += 16;
+        }
+    }
+
+    //Evaluates "EXPR OP EXPR" and fails if the comparison does not hold
+    fn assert(&self, text: &str) -> Result<(), String> {
+        let usage = || String::from("Usage: assert EXPR OP EXPR");
+        //The first comparison operator splits the expressions, they may contain spaces (pixel(10, 5))
+        let pos = text.find(|c| "=!<>".contains(c)).ok_or_else(usage)?;
+        let op_len = if text[pos + 1..].starts_with('=') { 2 } else { 1 };
+        let (left_text, op, right_text) = (text[..pos].trim(), &text[pos..pos + op_len], text[pos + op_len..].trim());
+        if left_text.is_empty() || right_text.is_empty() {
+            return Err(usage());
+        }
+        let left = Expr::parse(&self.annotations.resolve(left_text)?)?.eval(&self.chip8);
+        let right = Expr::parse(&self.annotations.resolve(right_text)?)?.eval(&self.chip8);
+
+        let passed = match op {
+            "==" => left == right,
+            "!=" => left != right,
+            "<" => left < right,
+            "<=" => left <= right,
+            ">" => left > right,
+            ">=" => left >= right,
+            other => return Err(format!("Unknown comparison {}", other)),
+        };
+
+        if passed {
+            Ok(())
+        } else {
+            Err(format!("Assertion failed: {} ({}) {} {} ({}) at {:#06X}, cycle {}", left_text, show(left), op,
+                        right_text, show(right), self.chip8.pc(), self.chip8.cycles()))
+        }
+    }
+
+    //Runs every command in a script, stopping at the first one that fails
+    pub fn run_script(&mut self, path: &str) -> Result<(), String> {
+        let file = File::open(path).map_err(|err| format!("Could not open script {}: {}", path, err))?;
+
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|err| format!("Could not read script {}: {}", path, err))?;
+            match self.execute(&line) {
+                Ok(Flow::Continue) => {},
+                Ok(Flow::Quit) => break,
+                Err(err) => return Err(format!("{}:{}: {}", path, number + 1, err)),
+            }
+        }
+
+        Ok(())
+    }
+
+    //Like run_interactive, but also takes commands from the session's copilots and tells every client
+    //what was run. Only the local terminal can quit or use files, and clients' runs are capped
+    pub fn run_shared(&mut self, share: &Share) {
+        print!("(chip8) ");
+        io::stdout().flush().unwrap();
+        while let Some((source, line)) = share.next_command() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Source::Remote(ref peer) = source {
+                println!("[{}] {}", peer, line);
+            }
+            share.broadcast(&format!("> {}", line.trim()));
+
+            let command = line.split_whitespace().next().unwrap_or("");
+            let result = if source == Source::Local {
+                self.execute(&line)
+            } else if LOCAL_COMMANDS.contains(&command) {
+                Err(format!("Only the debugger's own terminal can {}", command))
+            } else {
+                self.run_limit = REMOTE_RUN_LIMIT;
+                let result = self.execute(&line);
+                self.run_limit = u64::MAX;
+                result
+            };
+            match result {
+                Ok(Flow::Continue) => share.broadcast(&state_line(&self.chip8)),
+                Ok(Flow::Quit) => {
+                    share.broadcast("session ended");
+                    break;
+                },
+                Err(err) => {
+                    println!("{}", err);
+                    share.broadcast(&format!("error: {}", err));
+                },
+            }
+            print!("(chip8) ");
+            io::stdout().flush().unwrap();
+        }
+    }
+
+    //Reads commands from stdin until "quit" or end of input
+    pub fn run_interactive(&mut self) {
+        let stdin = io::stdin();
+        loop {
+            print!("(chip8) ");
+            io::stdout().flush().unwrap();
+
+            let mut line = String::new();
+            if stdin.lock().read_line(&mut line).unwrap_or(0) == 0 {
+                break;
+            }
+            match self.execute(&line) {
+                Ok(Flow::Continue) => {},
+                Ok(Flow::Quit) => break,
+                Err(err) => println!("{}", err),
+            }
+        }
+    }
+}
+
+//Values too big for an address (hashes) are easier to compare in hex
+fn show(value: u64) -> String {
+    if value > 0xFFFF {
+        format!("{:#X}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+//One line summary of the machine, what shared session clients see after each command
+fn state_line(chip8: &Chip8) -> String {
+    let v: Vec<String> = (0..16).map(|reg| format!("{:02X}", chip8.register(reg))).collect();
+    format!("pc={:#06X} i={:#06X} sp={} dt={} st={} v={}", chip8.pc(), chip8.index(), chip8.sp(),
+            chip8.delay_timer(), chip8.sound_timer(), v.join(" "))
+}
+
+fn argument<'a>(args: &[&'a str], index: usize) -> Result<&'a str, String> {
+    match args.get(index) {
+        Some(arg) => Ok(*arg),
+        None => Err(String::from("Missing argument")),
+    }
+}
+
+//An array field of an exported setup, missing ones count as empty
+fn list<'a>(setup: &'a Json, name: &str) -> Result<&'a [Json], String> {
+    match setup.get(name) {
+        Some(value) => value.as_array().ok_or_else(|| format!("{} must be an array", name)),
+        None => Ok(&[]),
+    }
+}
+
+fn text_field<'a>(object: &'a Json, name: &str) -> Result<&'a str, String> {
+    object.get(name).and_then(Json::as_str).ok_or_else(|| format!("Missing or invalid {}", name))
+}
+
+//Addresses can be numbers or strings (hex, decimal or region names)
+fn address_text(value: &Json) -> Result<String, String> {
+    match (value.as_str(), value.as_int()) {
+        (Some(text), _) => Ok(text.to_string()),
+        (_, Some(addr)) => Ok(addr.to_string()),
+        _ => Err(String::from("Addresses must be numbers or strings")),
+    }
+}
+
+fn address_field(object: &Json, name: &str) -> Result<String, String> {
+    address_text(object.get(name).ok_or_else(|| format!("Missing {}", name))?)
+}
+
+//Entry point for "chip8 debug ...", with the arguments following "debug"
+pub fn run(args: &[String]) {
+    let mut args = args.to_vec();
+    let options = take_value(&mut args, "--script").and_then(|script| {
+        let share = take_value(&mut args, "--share")?;
+        Options::parse(&args).map(|options| (script, share, options))
+    });
+    let (script, share, options) = match options {
+        Ok(result) => result,
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::FAILURE);
+        }
+    };
+
+    let chip8 = match create_chip8(&options, &options.rom) {
+        Ok(chip8) => chip8,
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::ROM_LOAD);
+        }
+    };
+    let annotations = match Annotations::load(&options.rom) {
+        Ok(annotations) => annotations,
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::FAILURE);
+        }
+    };
+    let mut debugger = Debugger::new(chip8, annotations);
+    match (script, share) {
+        (Some(_), Some(_)) => {
+            println!("--script and --share can't be used together. Aborting");
+            process::exit(exitcode::FAILURE);
+        },
+        (Some(path), None) => {
+            if let Err(err) = debugger.run_script(&path) {
+                println!("{}", err);
+                process::exit(exitcode::FAILURE);
+            }
+        },
+        (None, Some(addr)) => match Share::listen(&addr) {
+            Ok(share) => debugger.run_shared(&share),
+            Err(err) => {
+                println!("{}. Aborting", err);
+                process::exit(exitcode::FAILURE);
+            }
+        },
+        (None, None) => debugger.run_interactive(),
+    }
+}
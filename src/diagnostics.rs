@@ -0,0 +1,75 @@
+/************
+Deduplicated warnings
+
+A broken program tends to make the same mistake every cycle: an unknown opcode the program
+counter never gets past, or a --protect warn access inside a loop, used to print hundreds of
+lines a second. Warnings go through here instead, and are told apart by what they are about and
+the instruction that caused them. The first one is printed, repeats are only counted, with a
+"seen N times" line whenever the count reaches 10, 100, 1000 and so on. Frontends print a summary
+of everything that repeated when the run ends. Quiet diagnostics only count, for machines run
+in the background (ie the launcher's thumbnails, see library.rs).
+************/
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq)]
+struct Key {
+    kind: &'static str,
+    pc: u16,
+    detail: u16,    //Whatever else tells two warnings of the same kind apart, ie the address accessed
+}
+
+struct Entry {
+    message: String,
+    count: u64,
+}
+
+#[derive(Default)]
+pub struct Diagnostics {
+    seen: HashMap<Key, Entry>,
+    quiet: bool,
+}
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics::default()
+    }
+
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.quiet = quiet;
+    }
+
+    //Reports a warning made by the instruction at pc. The message is only built (and printed) the
+    //first time, so warning in a hot loop stays cheap
+    pub fn warn<F: FnOnce() -> String>(&mut self, kind: &'static str, pc: u16, detail: u16, message: F) {
+        let quiet = self.quiet;
+        let entry = self.seen.entry(Key { kind, pc, detail }).or_insert_with(|| {
+            let message = message();
+            if !quiet {
+                println!("{}", message);
+            }
+            Entry { message, count: 0 }
+        });
+        entry.count += 1;
+
+        let mut milestone = 10;
+        while milestone < entry.count {
+            milestone *= 10;
+        }
+        if entry.count == milestone && !quiet {
+            println!("{} (seen {} times)", entry.message, entry.count);
+        }
+    }
+
+    //Prints every warning that came up more than once, most frequent first
+    pub fn print_summary(&self) {
+        let mut repeated: Vec<&Entry> = self.seen.values().filter(|entry| entry.count > 1).collect();
+        if repeated.is_empty() {
+            return;
+        }
+        repeated.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.message.cmp(&b.message)));
+        println!("Repeated warnings:");
+        for entry in repeated {
+            println!("{:8}x {}", entry.count, entry.message);
+        }
+    }
+}
@@ -0,0 +1,14 @@
+/************
+Disassembler
+
+Turns opcodes back into the usual CHIP-8 assembly mnemonics (as in Cowgod's technical reference),
+ie 0x2206 -> "CALL 0x206" and 0x6105 -> "LD V1, 0x05", through the core's own decoder (see
+instruction.rs), so what it shows is what the machine runs. Opcodes the core doesn't know come out
+as "DW 0xXXXX", a raw data word. SUPER-CHIP's opcodes only come out as such for that variant.
+************/
+use instruction::decode_for;
+use variant::Variant;
+
+pub fn disassemble(opcode: u16, variant: Variant) -> String {
+    decode_for(opcode, variant).to_string()
+}
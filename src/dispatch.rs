@@ -0,0 +1,806 @@
+/************
+Opcode dispatch
+
+The core runs an opcode by looking its handler up in tables of function pointers instead of going
+through one big match: MAIN is indexed by the opcode's top nibble, and the families sharing a top
+nibble have sub-tables of their own, SYSTEM (0NNN) and ARITHMETIC (8XYN) by the low nibble, KEYS
+(EXNN) and MISC (FXNN) by the low byte. The tables are built once at compile time, so a lookup is
+two array indexes at most. SUPER-CHIP's opcodes (see variant.rs) are in tables of their own,
+SCHIP_SYSTEM (00NN) and SCHIP_MISC (FXNN), looked at first for that variant only, so a CHIP-8
+program runs exactly as it did before they existed.
+
+Every handler is a plain function taking the machine and the whole opcode, pulling its operands
+out of the nibbles itself, which makes each instruction callable on its own:
+
+    dispatch::add_vx_vy(&mut chip8, 0x8014)?;   //V0 += V1, carry in VF
+
+The tables group opcodes the way decode_for() in instruction.rs does (any 0NN0 clears the screen,
+5XYN and 9XYN ignore N), and handler() is None for what it calls Sys or Unknown. Tracing still
+prints the decoded Instruction, and the disassembler only ever decodes, the tables are just for
+running.
+************/
+use rand::Rng;
+
+use error::Chip8Error;
+use framebuffer::DrawRect;
+use sprite::ClipCollision;
+use variant::Variant;
+use {Chip8, FOURTH_NIBBLE_MASK, LAST_THREE_MASK, LAST_TWO_MASK, SECOND_NIBBLE_MASK, THIRD_NIBBLE_MASK};
+
+pub type Handler = fn(&mut Chip8, u16) -> Result<(), Chip8Error>;
+
+//What the top nibble leads to
+enum Entry {
+    Handler(Handler),
+    ByLowNibble(&'static [Option<Handler>; 16]),
+    ByLowByte(&'static [Option<Handler>; 256]),
+}
+
+static MAIN: [Entry; 16] = [
+    Entry::ByLowNibble(&SYSTEM),
+    Entry::Handler(jp),
+    Entry::Handler(call),
+    Entry::Handler(se_vx_byte),
+    Entry::Handler(sne_vx_byte),
+    Entry::Handler(se_vx_vy),
+    Entry::Handler(ld_vx_byte),
+    Entry::Handler(add_vx_byte),
+    Entry::ByLowNibble(&ARITHMETIC),
+    Entry::Handler(sne_vx_vy),
+    Entry::Handler(ld_i),
+    Entry::Handler(jp_v0),
+    Entry::Handler(rnd),
+    Entry::Handler(drw),
+    Entry::ByLowByte(&KEYS),
+    Entry::ByLowByte(&MISC),
+];
+
+static SYSTEM: [Option<Handler>; 16] = table(&[(0x0, cls), (0xE, ret)]);
+
+static ARITHMETIC: [Option<Handler>; 16] = table(&[
+    (0x0, ld_vx_vy),
+    (0x1, or_vx_vy),
+    (0x2, and_vx_vy),
+    (0x3, xor_vx_vy),
+    (0x4, add_vx_vy),
+    (0x5, sub_vx_vy),
+    (0x6, shr_vx),
+    (0x7, subn_vx_vy),
+    (0xE, shl_vx),
+]);
+
+static KEYS: [Option<Handler>; 256] = table(&[(0x9E, skp), (0xA1, sknp)]);
+
+static MISC: [Option<Handler>; 256] = table(&[
+    (0x07, ld_vx_dt),
+    (0x0A, ld_vx_k),
+    (0x15, ld_dt_vx),
+    (0x18, ld_st_vx),
+    (0x1E, add_i_vx),
+    (0x29, ld_f_vx),
+    (0x33, ld_b_vx),
+    (0x55, ld_i_vx),
+    (0x65, ld_vx_i),
+]);
+
+static SCHIP_SYSTEM: [Option<Handler>; 256] = table(&[
+    (0xC0, scd), (0xC1, scd), (0xC2, scd), (0xC3, scd),
+    (0xC4, scd), (0xC5, scd), (0xC6, scd), (0xC7, scd),
+    (0xC8, scd), (0xC9, scd), (0xCA, scd), (0xCB, scd),
+    (0xCC, scd), (0xCD, scd), (0xCE, scd), (0xCF, scd),
+    (0xFB, scr),
+    (0xFC, scl),
+    (0xFD, exit),
+    (0xFE, low),
+    (0xFF, high),
+]);
+
+static SCHIP_MISC: [Option<Handler>; 256] = table(&[(0x30, ld_hf_vx), (0x75, ld_r_vx), (0x85, ld_vx_r)]);
+
+//A sub-table with the given entries filled in, None everywhere else
+const fn table<const N: usize>(entries: &[(usize, Handler)]) -> [Option<Handler>; N] {
+    let mut table = [None; N];
+    let mut i = 0;
+    while i < entries.len() {
+        table[entries[i].0] = Some(entries[i].1);
+        i += 1;
+    }
+    table
+}
+
+//The handler running the opcode on the variant, None for SYS calls and opcodes that mean nothing
+pub fn handler(opcode: u16, variant: Variant) -> Option<Handler> {
+    if variant == Variant::Schip {
+        let schip = match opcode & 0xFF00 {
+            0x0000 => SCHIP_SYSTEM[(opcode & LAST_TWO_MASK) as usize],
+            //There are only 8 flags, FX75 and FX85 past V7 mean nothing
+            _ if opcode & 0xF8FF == 0xF875 || opcode & 0xF8FF == 0xF885 => None,
+            _ if opcode & 0xF000 == 0xF000 => SCHIP_MISC[(opcode & LAST_TWO_MASK) as usize],
+            _ => None,
+        };
+        if schip.is_some() {
+            return schip;
+        }
+    }
+    match MAIN[(opcode >> 12) as usize] {
+        Entry::Handler(handler) => Some(handler),
+        Entry::ByLowNibble(table) => table[(opcode & FOURTH_NIBBLE_MASK) as usize],
+        Entry::ByLowByte(table) => table[(opcode & LAST_TWO_MASK) as usize],
+    }
+}
+
+fn x_of(opcode: u16) -> usize {
+    ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize
+}
+
+fn y_of(opcode: u16) -> usize {
+    ((opcode & THIRD_NIBBLE_MASK) >> 4) as usize
+}
+
+fn n_of(opcode: u16) -> usize {
+    (opcode & FOURTH_NIBBLE_MASK) as usize
+}
+
+fn kk_of(opcode: u16) -> u8 {
+    (opcode & LAST_TWO_MASK) as u8
+}
+
+fn nnn_of(opcode: u16) -> u16 {
+    opcode & LAST_THREE_MASK
+}
+
+//0x00E0 opcode (clear screen)
+pub fn cls(chip8: &mut Chip8, _opcode: u16) -> Result<(), Chip8Error> {
+    traceln!(chip8, "Clear Screen");
+    chip8.clear_screenbuf();
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x00EE opcode (return from sub-process)
+pub fn ret(chip8: &mut Chip8, _opcode: u16) -> Result<(), Chip8Error> {
+    //Set program counter to the address at the top of the stack, "popping" it
+    if let Some(addr) = chip8.pop_call() {
+        traceln!(chip8, "Returning to {:#06X}", addr);
+        chip8.pc = addr;
+        chip8.next_instruction();
+    }
+    Ok(())
+}
+
+//0x00CN opcode (scroll the screen down N pixels), SUPER-CHIP only
+pub fn scd(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let n = n_of(opcode);
+    traceln!(chip8, "Scroll down {} pixels", n);
+    let (width, height) = (chip8.screen_width(), chip8.screen_height());
+    let screen = &mut chip8.screen[..width * height];
+    //Rows move down, the ones scrolled in at the top are blank
+    screen.copy_within(..width * (height - n), width * n);
+    screen[..width * n].fill(0);
+    chip8.draw_flag = true;
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x00FB opcode (scroll the screen right 4 pixels), SUPER-CHIP only
+pub fn scr(chip8: &mut Chip8, _opcode: u16) -> Result<(), Chip8Error> {
+    traceln!(chip8, "Scroll right 4 pixels");
+    let (width, height) = (chip8.screen_width(), chip8.screen_height());
+    for row in chip8.screen[..width * height].chunks_mut(width) {
+        row.copy_within(..width - 4, 4);
+        row[..4].fill(0);
+    }
+    chip8.draw_flag = true;
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x00FC opcode (scroll the screen left 4 pixels), SUPER-CHIP only
+pub fn scl(chip8: &mut Chip8, _opcode: u16) -> Result<(), Chip8Error> {
+    traceln!(chip8, "Scroll left 4 pixels");
+    let (width, height) = (chip8.screen_width(), chip8.screen_height());
+    for row in chip8.screen[..width * height].chunks_mut(width) {
+        row.copy_within(4.., 0);
+        row[width - 4..].fill(0);
+    }
+    chip8.draw_flag = true;
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x00FD opcode (exit the interpreter), SUPER-CHIP only. The PC stays on the exit, so the program
+//goes no further
+pub fn exit(chip8: &mut Chip8, _opcode: u16) -> Result<(), Chip8Error> {
+    traceln!(chip8, "Exit");
+    chip8.exited = true;
+    Ok(())
+}
+
+//0x00FE opcode (64 x 32 screen), SUPER-CHIP only
+pub fn low(chip8: &mut Chip8, _opcode: u16) -> Result<(), Chip8Error> {
+    traceln!(chip8, "Low resolution");
+    chip8.set_hires(false);
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x00FF opcode (128 x 64 screen), SUPER-CHIP only
+pub fn high(chip8: &mut Chip8, _opcode: u16) -> Result<(), Chip8Error> {
+    traceln!(chip8, "High resolution");
+    chip8.set_hires(true);
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x1NNN opcode (jmp nnn)
+pub fn jp(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let nnn = nnn_of(opcode);
+    chip8.pc = nnn;
+    traceln!(chip8, "Jumping to {:#06X}", chip8.pc);
+    Ok(())
+}
+
+//0x2NNN opcode (call subroutine: push pc to stack, jmp nnn)
+pub fn call(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let nnn = nnn_of(opcode);
+    //Push the current program counter onto the stack, then jump to address NNN
+    if chip8.push_call(chip8.pc, nnn) {
+        chip8.pc = nnn;
+        traceln!(chip8, "Call routine at {:#06X}", chip8.pc);
+    }
+    Ok(())
+}
+
+//0x3XKK opcode (Skp next instruction if Vx == kk)
+pub fn se_vx_byte(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let kk = kk_of(opcode);
+    traceln!(chip8, "SE V[{}] ({}), {}", x, chip8.v[x], kk);
+    if chip8.v[x] == kk {
+        //Skip next instruction by adding 2 to the program counter (skipping 2 bytes or 1 opcode)
+        chip8.next_instruction();
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x4XKK opcode (Skp next instruction if Vx != kk)
+pub fn sne_vx_byte(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let kk = kk_of(opcode);
+    traceln!(chip8, "SNE V[{}] ({}), {}", x, chip8.v[x], kk);
+    if chip8.v[x] != kk {
+        //Skip next instruction by adding 2 to the program counter (skipping 2 bytes or 1 opcode)
+        chip8.next_instruction();
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x5XY0 (Skp next instruction if Vx == Vy)
+pub fn se_vx_vy(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let y = y_of(opcode);
+    traceln!(chip8, "SE V[{}] ({}), V[{}] ({})", x, chip8.v[x], y, chip8.v[y]);
+    if chip8.v[x] == chip8.v[y] {
+        chip8.next_instruction();
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x6XKK (Load Vx with kk)
+pub fn ld_vx_byte(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let kk = kk_of(opcode);
+    traceln!(chip8, "Load V[{}] ({}) with {}", x, chip8.v[x], kk);
+    chip8.v[x] = kk;
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x7XKK (Add Vx, kk)
+pub fn add_vx_byte(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let kk = kk_of(opcode);
+    traceln!(chip8, "Add V[{}] ({}) with {}", x, chip8.v[x], kk);
+    //Add and keep only the last byte
+    chip8.v[x] = chip8.v[x].wrapping_add(kk);
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x8XY0 (MOV v[x], v[y])
+pub fn ld_vx_vy(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let y = y_of(opcode);
+    traceln!(chip8, "Mov V[{}] ({}), V[{}] ({})", x, chip8.v[x], y, chip8.v[y]);
+    chip8.v[x] = chip8.v[y];
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x8XY1 (OR v[x], v[y])
+pub fn or_vx_vy(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let y = y_of(opcode);
+    traceln!(chip8, "Or V[{}] ({}), V[{}] ({})", x, chip8.v[x], y, chip8.v[y]);
+    chip8.v[x] |= chip8.v[y];
+    if chip8.quirks.vf_reset {
+        chip8.v[0x0f] = 0;
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x8XY2 (AND v[x], v[y])
+pub fn and_vx_vy(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let y = y_of(opcode);
+    traceln!(chip8, "And V[{}] ({}), V[{}] ({})", x, chip8.v[x], y, chip8.v[y]);
+    chip8.v[x] &= chip8.v[y];
+    if chip8.quirks.vf_reset {
+        chip8.v[0x0f] = 0;
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x8XY3 (XOR v[x], v[y])
+pub fn xor_vx_vy(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let y = y_of(opcode);
+    traceln!(chip8, "Xor V[{}] ({}), V[{}] ({})", x, chip8.v[x], y, chip8.v[y]);
+    chip8.v[x] ^= chip8.v[y];
+    if chip8.quirks.vf_reset {
+        chip8.v[0x0f] = 0;
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x8XY4 (ADD v[x], v[y])
+pub fn add_vx_vy(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let y = y_of(opcode);
+    traceln!(chip8, "Add V[{}] ({}), V[{}] ({})", x, chip8.v[x], y, chip8.v[y]);
+    //Set carry if addition goes over 8 bits
+    let (new_value, overflow) = chip8.v[x].overflowing_add(chip8.v[y]);
+    chip8.v[x] = new_value;
+    if overflow {
+        chip8.v[0x0f] = 1;
+    } else {
+        chip8.v[0x0f] = 0;
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x8XY5 (SUB v[x], v[y])
+pub fn sub_vx_vy(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let y = y_of(opcode);
+    traceln!(chip8, "Sub V[{}] ({}), V[{}] ({})", x, chip8.v[x], y, chip8.v[y]);
+    if chip8.v[x] > chip8.v[y] {
+        chip8.v[0x0f] = 1;
+    } else {
+        chip8.v[0x0f] = 0;
+    }
+    chip8.v[x] = chip8.v[x].overflowing_sub(chip8.v[y]).0;
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x8XY6 (SHR v[x], 1), or v[x] = v[y] >> 1 with the shift-vy quirk
+pub fn shr_vx(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let source = if chip8.quirks.shift_uses_vy { y_of(opcode) } else { x };
+    traceln!(chip8, "Shift Right V[{}] ({}) into V[{}], 1", source, chip8.v[source], x);
+    //The Least Significant Bit goes to VF, set last so it wins when X is F
+    let value = chip8.v[source];
+    chip8.v[x] = value >> 1;
+    chip8.v[0x0f] = value & 0b0000_0001;
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x8XY7 (SUBN v[x], v[y])
+pub fn subn_vx_vy(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let y = y_of(opcode);
+    traceln!(chip8, "Subn V[{}] ({}), V[{}] ({})", x, chip8.v[x], y, chip8.v[y]);
+    if chip8.v[y] > chip8.v[x] {
+        chip8.v[0x0f] = 1;
+    } else {
+        chip8.v[0x0f] = 0;
+    }
+    chip8.v[x] = chip8.v[y].overflowing_sub(chip8.v[x]).0;
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x8XYE (SHL v[x], 1), or v[x] = v[y] << 1 with the shift-vy quirk
+pub fn shl_vx(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let source = if chip8.quirks.shift_uses_vy { y_of(opcode) } else { x };
+    traceln!(chip8, "Shift Left V[{}] ({}) into V[{}], 1", source, chip8.v[source], x);
+    //The Most Significant Bit goes to VF, set last so it wins when X is F
+    let value = chip8.v[source];
+    chip8.v[x] = value << 1;
+    chip8.v[0x0f] = value >> 7;
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0x9XY0 (Skip next instruction if Vx != Vy
+pub fn sne_vx_vy(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let y = y_of(opcode);
+    traceln!(chip8, "SNE V[{}] ({}), V[{}] ({})", x, chip8.v[x], y, chip8.v[y]);
+    if chip8.v[x] != chip8.v[y] {
+        chip8.next_instruction();
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0xANNN opcode (mv i, NNN)
+pub fn ld_i(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let nnn = nnn_of(opcode);
+    chip8.i = nnn;
+    traceln!(chip8, "Changing index to {:}d", chip8.i);
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0xBNNN opcode (jmp NNN + V0), or jmp NNN + VX with the jump-vx quirk
+pub fn jp_v0(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let nnn = nnn_of(opcode);
+    let reg = if chip8.quirks.jump_with_vx { x_of(opcode) } else { 0 };
+    traceln!(chip8, "Jmp NNN + V[{}]", reg);
+    chip8.pc = nnn + chip8.v[reg] as u16;
+    Ok(())
+}
+
+//0xCXNN opcode (rnd Vx, byte AND NN)
+pub fn rnd(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let kk = kk_of(opcode);
+    let rand = chip8.rng.gen::<u16>();
+
+    traceln!(chip8, "V[{}] ({}) = n: {} & {}", x, chip8.v[x], kk, rand);
+    chip8.v[x] = (rand as u8) & kk;
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0xDxyn opcode. SUPER-CHIP draws a 16 x 16 sprite for DXY0, two bytes per row
+pub fn drw(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let y = y_of(opcode);
+    let n = n_of(opcode);
+    //Tell the screen that it has to refresh after this operation
+    chip8.draw_flag = true;
+
+    let (screen_width, screen_height) = (chip8.screen_width(), chip8.screen_height());
+    //X Coord to draw at, wrapped onto the screen
+    let x = chip8.v[x] as usize % screen_width;
+    //Y Coord to draw at, wrapped onto the screen
+    let y = chip8.v[y] as usize % screen_height;
+    let big = n == 0 && chip8.variant == Variant::Schip;
+    let (width, rows) = if big { (16, 16) } else { (8, n) };
+    //Only the rows that pass the draw checks, a strict check stops the program right here instead
+    //(the line height of the sprite, width is 8 unless it's a big one)
+    let height = match chip8.sprite_rows(rows, width / 8) {
+        Some(rows) => rows,
+        //sprite_rows() only refuses after setting the fault
+        None => return Err(chip8.fault.unwrap().into()),
+    };
+
+    //Unset our collision flag
+    chip8.v[0x0F] = 0;
+
+    traceln!(chip8, "Draw Sprite starting at mem[{}] at loc x:{}, y:{} with height:{}", chip8.i, x, y, height);
+
+    //Holds the current pixel data, left aligned
+    let mut pixel_line: u16;
+    //Whether some of the sprite fell off the screen
+    let mut clipped = false;
+    //Rows that collided or were clipped, for --clip-collision rows
+    let mut hit_rows = 0;
+
+    //For each line in the sprite from 0 to the sprite's height
+    for yline in 0..height {
+        let mut row_hit = false;
+        //Grab our sprite's 8-bit (or 16-bit) pixel line at this spot
+        pixel_line = if big {
+            let at = chip8.i as usize + yline * 2;
+            (chip8.load(at) as u16) << 8 | chip8.load(at + 1) as u16
+        } else {
+            (chip8.load(chip8.i as usize + yline) as u16) << 8
+        };
+        //For each pixel (bit) in the line...
+        for xline in 0..width {
+            //If the current bit is set...
+            if (pixel_line >> (15 - xline)) & 0b00000001 != 0 { //this hack separates each bit in the pixel line by masking it and then rotating the bits to the right until they are in the 1s place
+
+                let (mut pixel_x, mut pixel_y) = (x + xline, y + yline);
+                if pixel_x >= screen_width || pixel_y >= screen_height {
+                    //Off the right or bottom edge, the clip quirk decides between dropping it and wrapping it around
+                    if chip8.quirks.clip {
+                        clipped = true;
+                        row_hit = true;
+                        continue;
+                    }
+                    pixel_x %= screen_width;
+                    pixel_y %= screen_height;
+                }
+                let index = pixel_x + pixel_y * screen_width;
+
+                //Check for pixel collision
+                if chip8.screen[index] == 1 {
+                    //If there is a collision, set the collision register VF to 1
+                    chip8.v[0xF] = 1;
+                    row_hit = true;
+                }
+                //Set the value of the line by XORing our sprite's current line onto it
+                chip8.screen[index] ^= 1;
+            }
+        }
+        hit_rows += row_hit as u8;
+    }
+    match chip8.clip_collision {
+        ClipCollision::None => {},
+        ClipCollision::Flag => chip8.v[0xF] |= clipped as u8,
+        ClipCollision::Rows => chip8.v[0xF] = hit_rows,
+    }
+    chip8.last_draw = Some(DrawRect {
+        x,
+        y,
+        width: width.min(screen_width.saturating_sub(x)),
+        height: height.min(screen_height.saturating_sub(y)),
+    });
+    chip8.last_collision = chip8.v[0xF] != 0;
+    chip8.trace_event(|| message!("draw {}x{} at {},{} collision={}", width, height, x, y, chip8.v[0xF]));
+    if clipped {
+        chip8.warn("offscreen_draw", 0, || message!("Sprite drawn past the edge of the screen, clipping it"));
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0xEx9E Skip next instruct if key with value of Vx is pressed
+pub fn skp(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    //Only the low nibble names a key, the original interpreter ignored the rest
+    let key = chip8.v[x] & 0xF;
+    traceln!(chip8, "SN if Key[{}] (v={}) is pressed", key, x);
+    if chip8.key[key as usize] == 1 {
+        chip8.observed_key = Some(key);
+        chip8.next_instruction();
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0xExA1 Skip next instruct if key with value of Vx is not pressed
+pub fn sknp(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    let key = chip8.v[x] & 0xF;
+    traceln!(chip8, "SN if Key[{}] (v={}) is not pressed", key, x);
+    if chip8.key[key as usize] == 0 {
+        chip8.next_instruction();
+    } else {
+        chip8.observed_key = Some(key);
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0xFX07 (mv v[x], delay_timer)
+pub fn ld_vx_dt(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    traceln!(chip8, "Mv V[{}] ({}), delay_timer", x, chip8.v[x]);
+    chip8.v[x] = chip8.delay_timer;
+    chip8.next_instruction();
+    Ok(())
+}
+
+//Wait for key press, store value of key in Vx
+//All execution stops until a key is pressed
+pub fn ld_vx_k(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    traceln!(chip8, "Wait for key press to store in v[{}]", x);
+    chip8.halt_flag = true;
+    chip8.halt_reg = x as u8;
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0xFX15 (mov delay_timer, v[x])
+pub fn ld_dt_vx(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    traceln!(chip8, "Mov delay_timer, V[{}] ({})", x, chip8.v[x]);
+    chip8.delay_timer = chip8.v[x];
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0xFX18 (mov sound_timer, v[x])
+pub fn ld_st_vx(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    traceln!(chip8, "Mov sound_timer, V[{}] ({})", x, chip8.v[x]);
+    chip8.sound_timer = chip8.v[x];
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0xFX1E (add i, v[x])
+pub fn add_i_vx(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    traceln!(chip8, "Add V[{}] ({}) to index", x, chip8.v[x]);
+    //I can be pushed past 0xFFF and wrap at 16 bits, the memory's address mode decides what that means
+    chip8.i = chip8.i.wrapping_add(chip8.v[x] as u16);
+    chip8.next_instruction();
+    Ok(())
+}
+
+pub fn ld_f_vx(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    traceln!(chip8, "Set I = location of sprite for digit Vx");
+    chip8.i = chip8.font_addr + chip8.v[x] as u16 * 5;
+    chip8.next_instruction();
+    Ok(())
+}
+
+//0xFX30 (point I at the big font sprite for the digit in Vx), SUPER-CHIP only
+pub fn ld_hf_vx(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    traceln!(chip8, "Set I = location of big sprite for digit Vx");
+    chip8.i = chip8.big_font_addr() + chip8.v[x] as u16 * 10;
+    chip8.next_instruction();
+    Ok(())
+}
+
+pub fn ld_r_vx(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    traceln!(chip8, "Store registers V0 through V{} in the RPL flags", x);
+    chip8.rpl[..=x].copy_from_slice(&chip8.v[..=x]);
+    chip8.rpl_written = true;
+    chip8.next_instruction();
+    Ok(())
+}
+
+pub fn ld_vx_r(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    traceln!(chip8, "Read registers V0 through V{} from the RPL flags", x);
+    chip8.v[..=x].copy_from_slice(&chip8.rpl[..=x]);
+    chip8.next_instruction();
+    Ok(())
+}
+
+pub fn ld_b_vx(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    traceln!(chip8, "Store BCD of Vx in memory at location i, i+1, i+2");
+    //Take each numbers place in V[x] and separate them to store in separate memory locations
+    let bcd = chip8.v[x];
+    let i = chip8.i as usize;
+    chip8.store(i, bcd / 100);
+    chip8.store(i + 1, (bcd / 10) % 10);
+    chip8.store(i + 2, (bcd % 100) % 10);
+
+    chip8.next_instruction();
+    Ok(())
+}
+
+pub fn ld_i_vx(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    traceln!(chip8, "Stores registers V0 through V{} in memory starting at location {:#06X}", x, chip8.i);
+    for n in 0..=x {
+       let value = chip8.v[n];
+       chip8.store(chip8.i as usize + n, value);
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+pub fn ld_vx_i(chip8: &mut Chip8, opcode: u16) -> Result<(), Chip8Error> {
+    let x = x_of(opcode);
+    traceln!(chip8, "Read registers V0 through Vx from memory starting at location I");
+    for n in 0..=x {
+        chip8.v[n] = chip8.load(chip8.i as usize + n);
+    }
+    chip8.next_instruction();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_i_vx_wraps_instead_of_overflowing() {
+        let mut chip8 = Chip8::new();
+        chip8.set_index(0xFFF0);
+        chip8.set_register(0, 0x20);
+        add_i_vx(&mut chip8, 0xF01E).unwrap();
+        assert_eq!(chip8.index(), 0x0010);
+
+        //A000 60FF F01E 1204, I keeps growing by 255 until it wraps
+        let mut chip8 = Chip8::new();
+        chip8.load_rom_bytes(&[0xA0, 0x00, 0x60, 0xFF, 0xF0, 0x1E, 0x12, 0x04]);
+        for _ in 0..2000 {
+            chip8.step().unwrap();
+        }
+        //999 FX1Es ran, the rest were the A000, the 60FF and the jumps
+        assert_eq!(chip8.index() as u32, 0xFF * 999 % 0x10000);
+    }
+
+    //A machine with an all lit sprite of `rows` rows at I, drawn at x, y by D01N
+    fn sprite_machine(mode: ClipCollision, rows: u16, x: u8, y: u8) -> Chip8 {
+        let mut chip8 = Chip8::new();
+        chip8.set_clip_collision(mode);
+        for row in 0..rows {
+            chip8.write_byte(0x300 + row, 0xFF);
+        }
+        chip8.set_index(0x300);
+        chip8.set_register(0, x);
+        chip8.set_register(1, y);
+        chip8
+    }
+
+    const MODES: [ClipCollision; 3] = [ClipCollision::None, ClipCollision::Flag, ClipCollision::Rows];
+
+    #[test]
+    fn drw_clips_at_the_bottom_edge() {
+        //4 rows at y=30, 2 on screen and 2 clipped
+        for (&mode, &vf) in MODES.iter().zip([0, 1, 2].iter()) {
+            let mut chip8 = sprite_machine(mode, 4, 0, 30);
+            drw(&mut chip8, 0xD014).unwrap();
+            assert!((0..8).all(|x| chip8.pixel(x, 30) && chip8.pixel(x, 31)), "{:?}", mode);
+            assert!((0..8).all(|x| !chip8.pixel(x, 0) && !chip8.pixel(x, 1)), "{:?} wrapped to the top", mode);
+            assert_eq!(chip8.register(0xF), vf, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn drw_clips_at_the_right_edge() {
+        //8 pixels wide at x=60, 4 columns on screen and 4 clipped, on both rows
+        for (&mode, &vf) in MODES.iter().zip([0, 1, 2].iter()) {
+            let mut chip8 = sprite_machine(mode, 2, 60, 0);
+            drw(&mut chip8, 0xD012).unwrap();
+            assert!((60..64).all(|x| chip8.pixel(x, 0) && chip8.pixel(x, 1)), "{:?}", mode);
+            assert!((0..4).all(|x| !chip8.pixel(x, 0) && !chip8.pixel(x, 1)), "{:?} wrapped to the left", mode);
+            assert_eq!(chip8.register(0xF), vf, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn drw_counts_collisions_with_clipped_rows() {
+        //Drawn twice: the 2 rows on screen collide and are erased, the 2 below are clipped
+        for (&mode, &vf) in MODES.iter().zip([1, 1, 4].iter()) {
+            let mut chip8 = sprite_machine(mode, 4, 0, 30);
+            drw(&mut chip8, 0xD014).unwrap();
+            drw(&mut chip8, 0xD014).unwrap();
+            assert!(chip8.screen().iter().all(|&pixel| pixel == 0), "{:?}", mode);
+            assert_eq!(chip8.register(0xF), vf, "{:?}", mode);
+        }
+    }
+
+    #[test]
+    fn key_skips_use_the_low_nibble_of_vx() {
+        //60 20 E0 9E: V0 = 0x20 names key 0
+        for &(opcode, pressed, skipped) in [(0xE09E, true, true), (0xE09E, false, false), (0xE0A1, true, false), (0xE0A1, false, true)].iter() {
+            let mut chip8 = Chip8::new();
+            chip8.set_register(0, 0x20);
+            chip8.set_key(0, pressed as u8);
+            let pc = chip8.pc();
+            if opcode == 0xE09E {
+                skp(&mut chip8, opcode).unwrap();
+            } else {
+                sknp(&mut chip8, opcode).unwrap();
+            }
+            assert_eq!(chip8.pc(), pc + if skipped { 4 } else { 2 }, "{:04X} pressed={}", opcode, pressed);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+/************
+Display output settings
+
+How the 64 x 32 screen (128 x 64 in SUPER-CHIP's hi-res mode, see variant.rs) is put in the
+window. --rotate turns the output by 90, 180 or 270 degrees (clockwise) for portrait and rotated
+screens, F7 turns it another 90 degrees while running. Only the output turns, the program still
+sees a landscape screen.
+
+The screen is scaled by whole numbers of window points and centred. On high-DPI screens a point
+can be a fraction of a pixel apart, so pixel edges can still land between physical pixels and
+bleed into their neighbours. --sharp does the scaling in physical pixels instead, so every CHIP-8
+pixel is exactly N x N physical pixels with no blending, the same on every GPU (screenshots stay
+reproducible).
+
+Most CHIP-8 games move a sprite by erasing it (XOR) and drawing it again, so a frame shown between
+the two has it missing, which flickers. --deflicker (or "deflicker" in the ROM database for a single
+ROM, F6 toggles it while running) shows every pixel lit in either this frame or the one before,
+which hides the gaps at the cost of a frame of ghosting.
+************/
+pub const SCREEN_WIDTH: usize = 64;
+pub const SCREEN_HEIGHT: usize = 32;
+//SUPER-CHIP's hi-res mode (00FF)
+pub const HIRES_WIDTH: usize = 128;
+pub const HIRES_HEIGHT: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rotation {
+    None,
+    Right,      //90 degrees clockwise
+    UpsideDown,
+    Left,       //270 degrees clockwise
+}
+
+impl Rotation {
+    #[cfg(feature = "std")]
+    pub fn parse(text: &str) -> Result<Rotation, String> {
+        match text {
+            "0" => Ok(Rotation::None),
+            "90" => Ok(Rotation::Right),
+            "180" => Ok(Rotation::UpsideDown),
+            "270" => Ok(Rotation::Left),
+            _ => Err(format!("Invalid rotation {} (expected 0, 90, 180 or 270)", text)),
+        }
+    }
+
+    pub fn degrees(self) -> u32 {
+        match self {
+            Rotation::None => 0,
+            Rotation::Right => 90,
+            Rotation::UpsideDown => 180,
+            Rotation::Left => 270,
+        }
+    }
+
+    //Another 90 degrees clockwise
+    pub fn next(self) -> Rotation {
+        match self {
+            Rotation::None => Rotation::Right,
+            Rotation::Right => Rotation::UpsideDown,
+            Rotation::UpsideDown => Rotation::Left,
+            Rotation::Left => Rotation::None,
+        }
+    }
+
+    //Width and height of a width x height screen once rotated, in CHIP-8 pixels
+    pub fn size(self, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Rotation::None | Rotation::UpsideDown => (width, height),
+            Rotation::Right | Rotation::Left => (height, width),
+        }
+    }
+
+    //Where pixel x, y of a width x height screen ends up in the rotated output
+    pub fn apply(self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Rotation::None => (x, y),
+            Rotation::Right => (height - 1 - y, x),
+            Rotation::UpsideDown => (width - 1 - x, height - 1 - y),
+            Rotation::Left => (y, width - 1 - x),
+        }
+    }
+
+    //The screen pixel that ends up at x, y of the rotated output, the other way round from apply()
+    pub fn unapply(self, x: usize, y: usize, width: usize, height: usize) -> (usize, usize) {
+        match self {
+            Rotation::None => (x, y),
+            Rotation::Right => (y, height - 1 - x),
+            Rotation::UpsideDown => (width - 1 - x, height - 1 - y),
+            Rotation::Left => (width - 1 - y, x),
+        }
+    }
+}
+
+//How the settings can change while running
+#[derive(Clone, Copy, Debug)]
+pub struct DisplaySettings {
+    pub rotation: Rotation,
+    pub sharp: bool,
+    pub deflicker: bool,
+}
+
+//Where the screen goes in the window, in window points
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layout {
+    pub pixel_size: f64,
+    pub x_offset: f64,
+    pub y_offset: f64,
+}
+
+#[cfg(feature = "std")]
+impl Layout {
+    //Fits the (rotated) width x height screen into a window of `view` points, which are
+    //`device_scale` physical pixels each
+    pub fn fit(view: [f64; 2], device_scale: f64, settings: DisplaySettings, (width, height): (usize, usize)) -> Layout {
+        let (columns, rows) = settings.rotation.size(width, height);
+        //Sharp mode works in physical pixels, converted back to points at the end
+        let scale = if settings.sharp && device_scale > 0.0 { device_scale } else { 1.0 };
+        let (width, height) = (view[0] * scale, view[1] * scale);
+
+        let pixel_size = (width / columns as f64).min(height / rows as f64).floor().max(1.0);
+        let x_offset = ((width - pixel_size * columns as f64) / 2.0).floor();
+        let y_offset = ((height - pixel_size * rows as f64) / 2.0).floor();
+        Layout {
+            pixel_size: pixel_size / scale,
+            x_offset: x_offset / scale,
+            y_offset: y_offset / scale,
+        }
+    }
+
+    //The pixel of a width x height screen under a point of the window, None outside the screen
+    pub fn screen_pixel(&self, point: [f64; 2], rotation: Rotation, (width, height): (usize, usize)) -> Option<(usize, usize)> {
+        let (columns, rows) = rotation.size(width, height);
+        let column = ((point[0] - self.x_offset) / self.pixel_size).floor();
+        let row = ((point[1] - self.y_offset) / self.pixel_size).floor();
+        if column < 0.0 || row < 0.0 || column >= columns as f64 || row >= rows as f64 {
+            return None;
+        }
+        Some(rotation.unapply(column as usize, row as usize, width, height))
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    //Whether a length in points is a whole number of physical pixels
+    fn whole(points: f64, device_scale: f64) -> bool {
+        let physical = points * device_scale;
+        (physical - physical.round()).abs() < 1e-9
+    }
+
+    #[test]
+    fn sharp_layout_keeps_pixels_on_physical_pixels() {
+        let rotations = [Rotation::None, Rotation::Right, Rotation::UpsideDown, Rotation::Left];
+        for &device_scale in [1.25, 1.5].iter() {
+            for &view in [[640.0, 320.0], [637.0, 419.0], [801.0, 603.0]].iter() {
+                for &rotation in rotations.iter() {
+                    for &size in [(SCREEN_WIDTH, SCREEN_HEIGHT), (HIRES_WIDTH, HIRES_HEIGHT)].iter() {
+                        let settings = DisplaySettings { rotation, sharp: true, deflicker: false };
+                        let layout = Layout::fit(view, device_scale, settings, size);
+                        assert!(whole(layout.pixel_size, device_scale), "pixel size {:?} at {}", layout, device_scale);
+                        assert!(whole(layout.x_offset, device_scale) && whole(layout.y_offset, device_scale),
+                                "offsets {:?} at {}", layout, device_scale);
+
+                        //Each pixel's rect, as draw_screen() places it, ends exactly where the next one starts
+                        let (columns, rows) = rotation.size(size.0, size.1);
+                        for &(offset, count) in [(layout.x_offset, columns), (layout.y_offset, rows)].iter() {
+                            for i in 0..count - 1 {
+                                let end = ((offset + i as f64 * layout.pixel_size + layout.pixel_size) * device_scale).round();
+                                let next = ((offset + (i + 1) as f64 * layout.pixel_size) * device_scale).round();
+                                assert_eq!(end, next, "gap or overlap after pixel {} in {:?}", i, layout);
+                                assert!(whole(offset + i as f64 * layout.pixel_size, device_scale));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,267 @@
+/************
+Setup checks
+
+Usage: chip8 doctor [--keymap FILE] [--rom-db FILE] [--profiles FILE] [--audio-device NAME]
+
+Checks what the emulator needs before a game can start and prints a line per check, with what to
+do about the ones that fail. Give it the same options the emulator is started with, so it checks
+the files actually used:
+
+    graphics        A window can be opened. A small window flashes up for a moment
+    audio           The --audio-device (bell by default) can be used, see audio.rs
+    config files    The keymap, ROM database, profiles, library.dirs and stats.db parse, and the
+                    library directories exist
+    keymap          Every CHIP-8 key can still be pressed, and no bound key is taken by a hotkey
+    data directory  It exists (or can be made) and files can be written to it, see stats.rs
+
+ok is fine, WARN works but probably not the way it was meant to, FAIL means the emulator won't
+start or won't keep what it saves. Exits with 1 if anything failed.
+
+The window is opened by a second chip8 process: without a working display the window backend
+can abort the whole process rather than return an error.
+************/
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::{self, Command};
+
+use piston_window::*;
+
+use audio::{self, DEFAULT_DEVICE};
+use exitcode;
+use input::{button_name, Keymap};
+use library::{self, DIRS_FILE};
+use metadata::{RomDb, DEFAULT_DB};
+use options::take_value;
+use profile::{self, Profiles};
+use stats::{self, Stats};
+use storage::FileStorage;
+
+//Given to the second process to only open a window
+const WINDOW_PROBE: &str = "--open-window";
+
+#[derive(Default)]
+struct Report {
+    warnings: usize,
+    failures: usize,
+}
+
+impl Report {
+    fn ok(&mut self, what: &str) {
+        println!("ok    {}", what);
+    }
+
+    fn warn(&mut self, what: &str, fix: &str) {
+        println!("WARN  {}\n      fix: {}", what, fix);
+        self.warnings += 1;
+    }
+
+    fn fail(&mut self, what: &str, fix: &str) {
+        println!("FAIL  {}\n      fix: {}", what, fix);
+        self.failures += 1;
+    }
+}
+
+//Entry point for "chip8 doctor ...", with the arguments following "doctor"
+pub fn run(args: &[String]) {
+    if args.len() == 1 && args[0] == WINDOW_PROBE {
+        return open_window();
+    }
+
+    let mut args = args.to_vec();
+    let checked = match parse_options(&mut args) {
+        Ok(options) => options,
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::FAILURE);
+        }
+    };
+    if let Some(arg) = args.first() {
+        println!("Unexpected argument {}. Aborting", arg);
+        process::exit(exitcode::FAILURE);
+    }
+
+    let mut report = Report::default();
+    check_graphics(&mut report);
+    check_audio(&mut report, checked.audio_device.as_deref().unwrap_or(DEFAULT_DEVICE));
+    let keymap = check_config(&mut report, &checked);
+    check_keymap(&mut report, &keymap);
+    check_data_dir(&mut report);
+
+    println!();
+    match (report.failures, report.warnings) {
+        (0, 0) => println!("Everything looks fine"),
+        (failures, warnings) => println!("{} failed, {} warning{}", failures, warnings, if warnings == 1 { "" } else { "s" }),
+    }
+    if report.failures > 0 {
+        process::exit(exitcode::FAILURE);
+    }
+}
+
+//The files and device the emulator would be started with
+struct Checked {
+    keymap: Option<String>,
+    rom_db: Option<String>,
+    profiles: Option<String>,
+    audio_device: Option<String>,
+}
+
+fn parse_options(args: &mut Vec<String>) -> Result<Checked, String> {
+    Ok(Checked {
+        keymap: take_value(args, "--keymap")?,
+        rom_db: take_value(args, "--rom-db")?,
+        profiles: take_value(args, "--profiles")?,
+        audio_device: take_value(args, "--audio-device")?,
+    })
+}
+
+//Run in the second process: opens a window and closes it again
+fn open_window() {
+    let built: Result<PistonWindow, String> = WindowSettings::new("Chip8 doctor", [64, 32]).build();
+    if let Err(err) = built {
+        println!("Could not open a window: {}", err);
+        process::exit(exitcode::FAILURE);
+    }
+}
+
+fn check_graphics(report: &mut Report) {
+    if cfg!(all(unix, not(target_os = "macos"))) && env::var_os("DISPLAY").is_none() && env::var_os("WAYLAND_DISPLAY").is_none() {
+        return report.fail("graphics: no display server, DISPLAY and WAYLAND_DISPLAY are both unset",
+            "run chip8 from a desktop session (or ssh -X), or use chip8 headless or chip8 terminal, which need no window");
+    }
+    let exe = match env::current_exe() {
+        Ok(exe) => exe,
+        Err(err) => return report.warn(&format!("graphics: not checked, could not find the chip8 executable: {}", err),
+            "run chip8 keytest, it opens a window"),
+    };
+    let output = match Command::new(exe).args(["doctor", WINDOW_PROBE]).env("RUST_BACKTRACE", "0").output() {
+        Ok(output) => output,
+        Err(err) => return report.warn(&format!("graphics: not checked, could not start chip8 again: {}", err),
+            "run chip8 keytest, it opens a window"),
+    };
+    if output.status.success() {
+        return report.ok("graphics: a window can be opened");
+    }
+
+    //The first line that says what went wrong, the panic location and backtrace notes don't
+    let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr));
+    let reason = text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with("thread ") && !line.starts_with("note:"))
+        .map_or_else(|| output.status.to_string(), str::to_string);
+    report.fail(&format!("graphics: could not open a window ({})", reason),
+        "check the OpenGL drivers are installed (ie glxinfo should work), or use chip8 headless or chip8 terminal");
+}
+
+fn check_audio(report: &mut Report, device: &str) {
+    match audio::check_device(device) {
+        Ok(()) if device == "null" => report.warn("audio: the null device plays no sound", "leave out --audio-device null to hear the buzzer"),
+        Ok(()) => report.ok(&format!("audio: the {} device can be used", device)),
+        Err(err) => report.warn(&format!("audio: {}, games will be silent", err),
+            &if device == "bell" {
+                String::from("start chip8 from a terminal, the bell rings there")
+            } else {
+                format!("pick one of {} with --audio-device", audio::device_names().join(", "))
+            }),
+    }
+}
+
+//Loads every file the emulator would read at start. Returns the keymap, the default one if it didn't load
+fn check_config(report: &mut Report, checked: &Checked) -> Keymap {
+    let (rom_db, profiles) = (checked.rom_db.as_deref(), checked.profiles.as_deref());
+    let fix_file = |path: &str| format!("correct the line the error points at, or move {} out of the way", path);
+
+    let keymap = match checked.keymap.as_deref() {
+        Some(path) => match Keymap::load(path) {
+            Ok(keymap) => {
+                report.ok(&format!("keymap {} loads", path));
+                keymap
+            },
+            Err(err) => {
+                report.fail(&err, &fix_file(path));
+                Keymap::default()
+            },
+        },
+        None => Keymap::default(),
+    };
+
+    match (rom_db, RomDb::load(rom_db.unwrap_or(DEFAULT_DB))) {
+        (None, _) if !Path::new(DEFAULT_DB).exists() => report.ok(&format!("ROM database: none ({} isn't in this directory)", DEFAULT_DB)),
+        (path, Ok(_)) => report.ok(&format!("ROM database {} loads", path.unwrap_or(DEFAULT_DB))),
+        (path, Err(err)) => report.fail(&err, &fix_file(path.unwrap_or(DEFAULT_DB))),
+    }
+
+    match (profiles, Profiles::load(profiles.unwrap_or(profile::DEFAULT_FILE))) {
+        (None, _) if !Path::new(profile::DEFAULT_FILE).exists() =>
+            report.ok(&format!("profiles: none ({} isn't in this directory)", profile::DEFAULT_FILE)),
+        (path, Ok(_)) => report.ok(&format!("profiles {} load", path.unwrap_or(profile::DEFAULT_FILE))),
+        (path, Err(err)) => report.fail(&err, &fix_file(path.unwrap_or(profile::DEFAULT_FILE))),
+    }
+
+    let dirs_file = stats::data_dir().join(DIRS_FILE);
+    match library::directories(&[]) {
+        Ok(dirs) => {
+            let missing: Vec<&String> = dirs.iter().filter(|dir| !Path::new(dir).is_dir()).collect();
+            if missing.is_empty() {
+                report.ok(&format!("library: {} director{} in {}", dirs.len(), if dirs.len() == 1 { "y" } else { "ies" }, dirs_file.display()));
+            } else {
+                for dir in missing {
+                    report.warn(&format!("library: {} (from {}) is not a directory", dir, dirs_file.display()),
+                        "fix the path or remove the line, the launcher shows no ROMs from it");
+                }
+            }
+        },
+        Err(err) => report.fail(&err, &format!("make {} readable, or remove it", dirs_file.display())),
+    }
+
+    match Stats::load(&FileStorage) {
+        Ok(_) => report.ok(&format!("play statistics {} load", Stats::path().display())),
+        Err(err) => report.warn(&err, &format!("correct or remove {}, it's rewritten when a game ends", Stats::path().display())),
+    }
+
+    keymap
+}
+
+fn check_keymap(report: &mut Report, keymap: &Keymap) {
+    let mut fine = true;
+    for key in 0..16 {
+        if keymap.buttons_for(key).is_empty() {
+            report.warn(&format!("keymap: nothing is bound to CHIP-8 key {:X}, games can't press it", key),
+                &format!("bind a button to it in the keymap, ie \"NumPad0 {:X}\"", key));
+            fine = false;
+        }
+    }
+    if let Some(key) = keymap.lookup(Button::Keyboard(Key::Backspace)) {
+        report.warn(&format!("keymap: Backspace is bound to CHIP-8 key {:X} but rewinds instead while --rewind is on", key),
+            "bind another button to that key, or leave out --rewind");
+        fine = false;
+    }
+    if keymap.lookup(Button::Keyboard(Key::P)).is_some() {
+        for &ctrl in [Key::LCtrl, Key::RCtrl].iter() {
+            if let Some(key) = keymap.lookup(Button::Keyboard(ctrl)) {
+                report.warn(&format!("keymap: {} is bound to CHIP-8 key {:X}, held with P it opens the command palette", button_name(Button::Keyboard(ctrl)), key),
+                    "bind another button to that key");
+                fine = false;
+            }
+        }
+    }
+    if fine {
+        report.ok("keymap: all 16 CHIP-8 keys are bound and none clash with hotkeys");
+    }
+}
+
+fn check_data_dir(report: &mut Report) {
+    let dir = stats::data_dir();
+    let fix = "set CHIP8_DATA_DIR to a directory you can write to";
+    if let Err(err) = fs::create_dir_all(&dir) {
+        return report.fail(&format!("data directory {} can't be created: {}", dir.display(), err), fix);
+    }
+    let probe = dir.join(".doctor");
+    match fs::write(&probe, b"chip8 doctor\n") {
+        Ok(()) => {
+            fs::remove_file(&probe).unwrap_or(());
+            report.ok(&format!("data directory {} is writable", dir.display()));
+        },
+        Err(err) => report.fail(&format!("data directory {} isn't writable: {}", dir.display(), err), fix),
+    }
+}
@@ -0,0 +1,370 @@
+/************
+Emulation thread
+
+The window's game runs on a thread of its own, paced by the wall clock at the machine's clock
+rate (--ips, CYCLES_PER_SECOND by default), so how fast a game runs doesn't depend on how often
+the window backend delivers events. Every SLICE the thread runs the cycles that are due and sends
+the window a Snapshot of the screen and the buzzer, which the window draws whenever it's asked
+for a frame. Key events go the other way through a channel, and reach the machine at the start
+of the next slice.
+
+Everything else the window does to the game (save states, the memory viewer, switching profiles
+or games) goes through the Emulation behind the mutex, which the window holds while it handles
+an event and the thread holds while it runs a slice, so neither sees the game half changed.
+************/
+use std::mem;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use budget::FrameBudget;
+use clock::{Clock, Pacer, RealClock};
+use kiosk::Kiosk;
+use latency::LatencyMeter;
+use options::Options;
+use replay::Replay;
+use rpl;
+use storage::Storage;
+use {next_kiosk_game, start_game, Game};
+
+//How often the thread looks at the clock. A key waits at most this long to reach the machine
+const SLICE: Duration = Duration::from_millis(4);
+
+//What the window needs to present a frame
+#[derive(Clone, Default)]
+pub struct Snapshot {
+    pub screen: Vec<u8>,
+    pub width: usize,           //64 x 32, or 128 x 64 in SUPER-CHIP's hi-res mode
+    pub height: usize,
+    pub last_frame: Vec<u8>,    //The screen at the end of the previous 60Hz frame, for deflickering
+    pub sound_active: bool,
+    pub sound_timer: u8,
+    pub beep: bool,             //Whether to beep: the buzzer, or the recording's while one is played back
+}
+
+//The game and everything emulating it needs from one slice to the next
+pub struct Emulation {
+    pub game: Game,
+    pub kiosk: Option<Kiosk>,
+    pub recording: Replay,
+    pub latency: Option<LatencyMeter>,
+    pub budget: FrameBudget,
+    pub clock: RealClock,
+    pub paused: bool,           //Load menu, memory viewer or the window in the background
+    pub turbo: bool,
+    pub rewinding: bool,
+    pub profile_speed: f64,
+    pub redraw: bool,           //Send a frame even while paused, ie after the window changed the screen
+    pub stopped: bool,          //The program counter left memory or the program exited, the window closes
+    pub fatal: bool,            //The program died, the screen stays up to show where
+    pub run_to: Option<u16>,    //Temporary breakpoint, run to cursor in the disassembly panel (see panels.rs)
+    pub held: bool,             //Stopped at run_to, until the disassembly panel lets the game go on
+    pub storage: Box<dyn Storage>,  //Save states and the SUPER-CHIP flags of the game (see rpl.rs)
+    pacer: Pacer,
+    sound: bool,                //The buzzer as last logged to the recordings
+    played_sound: Option<bool>, //The buzzer as recorded, while a recording is played back
+    rewind_due: u64,            //Cycles counting towards the next step back
+    last_slice: Instant,
+    quit: bool,
+}
+
+impl Emulation {
+    pub fn new(mut game: Game, kiosk: Option<Kiosk>, latency: Option<LatencyMeter>, storage: Box<dyn Storage>) -> Emulation {
+        rpl::load(storage.as_ref(), &mut game.chip8, &game.rom);
+        let mut pacer = Pacer::new(0.25);
+        pacer.set_rate(game.chip8.clock_hz() as f64);
+        Emulation {
+            recording: Replay::new(game.chip8.seed(), game.chip8.variant()),
+            sound: game.chip8.sound_active(),
+            game,
+            kiosk,
+            latency,
+            budget: FrameBudget::new(),
+            clock: RealClock::new(),
+            paused: false,
+            turbo: false,
+            rewinding: false,
+            profile_speed: 1.0,
+            redraw: false,
+            stopped: false,
+            fatal: false,
+            run_to: None,
+            held: false,
+            storage,
+            //Never try to catch up on more than a quarter second, ie after the window was dragged
+            pacer,
+            played_sound: None,
+            rewind_due: 0,
+            last_slice: Instant::now(),
+            quit: false,
+        }
+    }
+
+    //Switches to another game, ie from the playlist or the library
+    pub fn set_game(&mut self, mut game: Game) {
+        self.game.stats.finish(self.storage.as_mut());
+        rpl::save_written(self.storage.as_mut(), &mut self.game.chip8, &self.game.rom);
+        rpl::load(self.storage.as_ref(), &mut game.chip8, &game.rom);
+        self.pacer.set_rate(game.chip8.clock_hz() as f64);
+        self.game = game;
+        //Addresses in the last game mean nothing in this one
+        self.run_to = None;
+        self.held = false;
+    }
+
+    fn key(&mut self, key: u8, state: u8) {
+        //A replay is doing the typing
+        if self.game.player.is_some() {
+            return;
+        }
+        let cycles = self.game.chip8.cycles();
+        self.game.chip8.handle_key(key, state);
+        self.recording.record(cycles, key, state);
+        self.game.session.record(cycles, key, state);
+        if let Some(ref mut latency) = self.latency {
+            latency.key_event(key, state, cycles);
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let chip8 = &self.game.chip8;
+        //Fast-forwarded beeps would just be noise. Playback beeps as recorded
+        let beep = self.played_sound.unwrap_or_else(|| chip8.sound_active());
+        Snapshot {
+            screen: chip8.screen().to_vec(),
+            width: chip8.screen_width(),
+            height: chip8.screen_height(),
+            last_frame: chip8.last_frame().to_vec(),
+            sound_active: chip8.sound_active(),
+            sound_timer: chip8.sound_timer(),
+            beep: beep && !self.turbo && !self.rewinding,
+        }
+    }
+
+    //Runs the cycles that came due since the last slice. Returns false while paused or stopped
+    fn run_slice(&mut self, options: &Options) -> bool {
+        let dt = self.last_slice.elapsed().as_secs_f64();
+        self.last_slice = Instant::now();
+        //After a fatal error the screen stays up, the faulting instruction isn't run again
+        if self.paused || self.held || self.stopped || self.fatal {
+            self.clock.tick();
+            return false;
+        }
+
+        let speed = self.profile_speed * if self.turbo { options.turbo as f64 } else { 1.0 };
+        let (mut due, dropped) = self.pacer.advance(&mut self.clock, speed);
+        let started = Instant::now();
+
+        if self.rewinding {
+            self.rewind_due += due;
+            let per_frame = self.game.chip8.cycles_per_tick();
+            if let Some(ref mut rewind) = self.game.rewind {
+                while self.rewind_due >= per_frame {
+                    self.rewind_due -= per_frame;
+                    if let Some(state) = rewind.back() {
+                        match self.game.chip8.load_state(&state) {
+                            Ok(()) => self.game.session.restart(&self.game.chip8),
+                            Err(err) => println!("{}", err),
+                        }
+                    }
+                }
+            }
+            return true;
+        }
+
+        //Switch to the next ROM of the playlist once the current one has had its turn
+        if let Some(ref mut kiosk) = self.kiosk {
+            if let Some(entry) = kiosk.update(dt).cloned() {
+                println!("Kiosk: loading {}", entry.rom);
+                let game = match start_game(options, &entry) {
+                    Ok(game) => game,
+                    Err(err) => {
+                        println!("{}, skipping it", err);
+                        next_kiosk_game(kiosk, options)
+                    },
+                };
+                self.set_game(game);
+            }
+        }
+
+        while due > 0 {
+            let game = &mut self.game;
+            //While the program counter is within an acceptable range...
+            if game.chip8.pc() > 4096 {
+                println!("Accessing invalid memory, aborting");
+                println!("Last instructions executed:");
+                game.chip8.print_history(50);
+
+                //A broken ROM shouldn't take down the whole kiosk, just skip it
+                match self.kiosk.as_mut() {
+                    Some(kiosk) => {
+                        let game = next_kiosk_game(kiosk, options);
+                        self.set_game(game);
+                        continue;
+                    },
+                    None => {
+                        self.fatal = true;
+                        self.stopped = true;
+                        break;
+                    },
+                }
+            }
+
+            if let Some(ref mut player) = game.player {
+                player.apply(&mut game.chip8);
+            }
+            if let Some(ref mut narrator) = game.narrator {
+                narrator.update(&game.chip8);
+            }
+            //Emulate a CPU cycle
+            let result = game.chip8.step();
+            due -= 1;
+            //Right away, games store the flags just before they exit (00FD) or end
+            rpl::save_written(self.storage.as_mut(), &mut game.chip8, &game.rom);
+            if game.chip8.sound_active() != self.sound {
+                self.sound = game.chip8.sound_active();
+                self.recording.record_sound(game.chip8.cycles(), self.sound);
+                game.session.record_sound(game.chip8.cycles(), self.sound);
+            }
+            let chip8 = &game.chip8;
+            self.played_sound = game.player.as_mut().map(|player| player.sound(chip8));
+            let frame_done = game.chip8.cycles().is_multiple_of(game.chip8.cycles_per_tick());
+            if let Some(ref mut rewind) = game.rewind {
+                if frame_done {
+                    rewind.push(game.chip8.save_state());
+                }
+            }
+            if let Some(ref mut latency) = self.latency {
+                latency.update(&game.chip8);
+            }
+
+            if let Some(err) = result.err().filter(|err| err.is_fatal()) {
+                println!("{}, stopping", err);
+                println!("Last instructions executed:");
+                game.chip8.print_history(50);
+
+                match self.kiosk.as_mut() {
+                    Some(kiosk) => {
+                        let game = next_kiosk_game(kiosk, options);
+                        self.set_game(game);
+                        continue;
+                    },
+                    //Leave the screen up so the user can see where the program stopped
+                    None => {
+                        self.fatal = true;
+                        break;
+                    },
+                }
+            }
+
+            //SUPER-CHIP's 00FD, the program is done
+            if game.chip8.has_exited() {
+                println!("Program exited at {:#06X}", game.chip8.pc());
+                match self.kiosk.as_mut() {
+                    Some(kiosk) => {
+                        let game = next_kiosk_game(kiosk, options);
+                        self.set_game(game);
+                        continue;
+                    },
+                    None => {
+                        self.stopped = true;
+                        break;
+                    },
+                }
+            }
+
+            if frame_done {
+                game.stats.update(&game.chip8);
+            }
+            if game.game_over.update(&game.chip8) {
+                println!("Game over detected at {:#06X}", game.chip8.pc());
+
+                //Kiosk mode doesn't wait around for the timer, the next game starts right away
+                if let Some(ref mut kiosk) = self.kiosk {
+                    let game = next_kiosk_game(kiosk, options);
+                    self.set_game(game);
+                }
+            }
+
+            //Checked after the step, so the instruction at the PC always runs first and a breakpoint
+            //on the row the game is held at runs through the loop it's in
+            if self.run_to.is_some() && self.run_to == Some(self.game.chip8.pc()) {
+                println!("Reached {:#06X}, holding the game", self.game.chip8.pc());
+                self.run_to = None;
+                self.held = true;
+                break;
+            }
+        }
+
+        self.budget.add_emulation(started.elapsed().as_secs_f64(), dropped);
+        self.budget.update(self.turbo);
+        true
+    }
+}
+
+pub struct EmulationThread {
+    emulation: Arc<Mutex<Emulation>>,
+    keys: Sender<(u8, u8)>,
+    frames: Receiver<Snapshot>,
+    latest: Snapshot,
+    thread: JoinHandle<()>,
+}
+
+impl EmulationThread {
+    pub fn start(emulation: Emulation, options: Arc<Options>) -> EmulationThread {
+        let latest = emulation.snapshot();
+        let emulation = Arc::new(Mutex::new(emulation));
+        let (keys, key_events) = mpsc::channel();
+        let (frame_sender, frames) = mpsc::channel();
+
+        let shared = Arc::clone(&emulation);
+        let thread = thread::spawn(move || loop {
+            thread::sleep(SLICE);
+            let mut emulation = shared.lock().unwrap();
+            if emulation.quit {
+                return;
+            }
+            for (key, state) in key_events.try_iter() {
+                emulation.key(key, state);
+            }
+            let ran = emulation.run_slice(&options);
+            if (ran || mem::take(&mut emulation.redraw)) && frame_sender.send(emulation.snapshot()).is_err() {
+                return;
+            }
+        });
+        EmulationThread { emulation, keys, frames, latest, thread }
+    }
+
+    //The game, for as long as the guard is held. The thread waits for it before the next slice
+    pub fn lock(&self) -> MutexGuard<'_, Emulation> {
+        self.emulation.lock().unwrap()
+    }
+
+    //Passes a CHIP-8 key change on to the machine
+    pub fn send_key(&self, key: u8, state: u8) {
+        let _ = self.keys.send((key, state));
+    }
+
+    //Takes in the frames the thread sent since the last call
+    pub fn receive_frames(&mut self) {
+        if let Some(latest) = self.frames.try_iter().last() {
+            self.latest = latest;
+        }
+    }
+
+    //The last frame received
+    pub fn frame(&self) -> &Snapshot {
+        &self.latest
+    }
+
+    //Stops the thread after its current slice, and hands back the game
+    pub fn stop(self) -> Emulation {
+        self.lock().quit = true;
+        self.thread.join().unwrap();
+        match Arc::try_unwrap(self.emulation) {
+            Ok(emulation) => emulation.into_inner().unwrap(),
+            Err(_) => unreachable!("The emulation thread has stopped"),
+        }
+    }
+}
@@ -0,0 +1,199 @@
+/************
+Agent episodes
+
+Usage: chip8 agent <romfile> [--agent idle|random] [--keys KEYS] [--episodes N] [--frames N] [--batch]
+                   [--frame-skip N] [--sticky-keys P] [--scale N] [options]
+
+Plays the ROM with one of the built-in agents (see agent.rs) for N episodes (default 1) of up to
+N frames each (default 60 seconds worth), without a window and as fast as the machine runs, and
+prints how each episode ended with its final screen hash and registers. It's a harness for trying
+out ROMs with agents and for timing them, agents of your own go through run_episode in the library.
+
+    idle   - never presses a key (the default)
+    random - holds a random one of KEYS (hex digits, ie 14 for the left Pong paddle, all 16 by
+             default) or none, a new pick every frame
+
+--batch runs all the episodes at the same time, a frame of every one before the next frame,
+through the batch runner training setups use (see batch.rs), to compare its throughput.
+
+--frame-skip, --sticky-keys and --scale are the AgentSettings (see agent.rs): the agent acts every
+Nth frame, the keys stay as they were with chance P every frame, and observations are shrunk N
+times. --frames still counts the machine's frames.
+
+With --seed S, episode N runs the machine, the random agent and the sticky keys with seed S + N - 1,
+so episodes can be repeated.
+************/
+use std::process;
+use std::time::Instant;
+
+use agent::{run_episode_with, Action, Agent, AgentSettings, IdleAgent, Observation, RandomAgent, StickyKeys};
+use batch::BatchRunner;
+use exitcode;
+use frames::FrameEvent;
+use options::{take_value, Options};
+use {create_chip8, Chip8, TIMER_HZ};
+
+const DEFAULT_FRAMES: u64 = (TIMER_HZ * 60.0) as u64;
+
+fn count(value: Option<String>, default: u64, what: &str) -> Result<u64, String> {
+    match value {
+        Some(value) => match value.parse::<u64>() {
+            Ok(count) if count > 0 => Ok(count),
+            _ => Err(format!("Invalid {} count {}", what, value)),
+        },
+        None => Ok(default),
+    }
+}
+
+fn parse_keys(text: &str) -> Result<Vec<u8>, String> {
+    let keys: Option<Vec<u8>> = text.chars().map(|key| key.to_digit(16).map(|key| key as u8)).collect();
+    match keys {
+        Some(ref keys) if !keys.is_empty() => Ok(keys.clone()),
+        _ => Err(format!("Invalid keys {} (expected hex digits)", text)),
+    }
+}
+
+//The sticky keys get their own seed, so they don't roll the same numbers as the random agent
+fn sticky_seed(seed: u64) -> u64 {
+    seed.rotate_left(32) ^ 0x5EED
+}
+
+fn parse_settings(args: &mut Vec<String>) -> Result<AgentSettings, String> {
+    let mut settings = AgentSettings::default();
+    if let Some(skip) = take_value(args, "--frame-skip")? {
+        settings.frame_skip = count(Some(skip), 1, "frame skip")?;
+    }
+    if let Some(chance) = take_value(args, "--sticky-keys")? {
+        settings.sticky_keys = match chance.parse::<f64>() {
+            Ok(chance) if (0.0..=1.0).contains(&chance) => chance,
+            _ => return Err(format!("Invalid sticky key chance {} (expected 0 to 1)", chance)),
+        };
+    }
+    if let Some(scale) = take_value(args, "--scale")? {
+        settings.scale = match scale.parse::<usize>() {
+            Ok(scale) if [1, 2, 4, 8, 16, 32].contains(&scale) => scale,
+            _ => return Err(format!("Invalid scale {} (expected 1, 2, 4, 8, 16 or 32)", scale)),
+        };
+    }
+    Ok(settings)
+}
+
+fn make_agent(name: &str, seed: u64, keys: &[u8]) -> Result<Box<dyn Agent>, String> {
+    match name {
+        "idle" => Ok(Box::new(IdleAgent)),
+        "random" => Ok(Box::new(RandomAgent::new(seed, keys))),
+        _ => Err(format!("Unknown agent {} (available: idle, random)", name)),
+    }
+}
+
+//Entry point for "chip8 agent ...", with the arguments following "agent"
+pub fn run(args: &[String]) {
+    let mut args = args.to_vec();
+    let parsed = take_value(&mut args, "--agent").and_then(|agent| {
+        let keys = match take_value(&mut args, "--keys")? {
+            Some(keys) => parse_keys(&keys)?,
+            None => (0..16).collect(),
+        };
+        let episodes = count(take_value(&mut args, "--episodes")?, 1, "episode")?;
+        let frames = count(take_value(&mut args, "--frames")?, DEFAULT_FRAMES, "frame")?;
+        let settings = parse_settings(&mut args)?;
+        let batch = match args.iter().position(|arg| arg == "--batch") {
+            Some(index) => {
+                args.remove(index);
+                true
+            },
+            None => false,
+        };
+        let agent = agent.unwrap_or_else(|| String::from("idle"));
+        //Checked up front, so a typo doesn't wait for the ROM to load
+        make_agent(&agent, 0, &keys)?;
+        let options = Options::parse(&args)?;
+        Ok((agent, keys, episodes, frames, batch, settings, options))
+    });
+    let (agent_name, keys, episodes, frames, batch, settings, options) = match parsed {
+        Ok(result) => result,
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::FAILURE);
+        }
+    };
+
+    let machines: Vec<(Chip8, u64)> = (0..episodes).map(|episode| start(&options, episode)).collect();
+    let started = Instant::now();
+    let total_frames: u64 = if batch {
+        run_batch(machines, &agent_name, &keys, frames, &settings)
+    } else {
+        machines.into_iter().enumerate().map(|(episode, (mut chip8, seed))| {
+            let mut agent = make_agent(&agent_name, seed, &keys).unwrap();
+            let settings = AgentSettings { seed: sticky_seed(seed), ..settings };
+            let result = run_episode_with(&mut chip8, agent.as_mut(), frames, &settings);
+            let end = match result.end {
+                Some(event) => event.to_string(),
+                None => String::from("frame limit"),
+            };
+            report(episode, result.frames, &end, &chip8);
+            result.frames
+        }).sum()
+    };
+
+    let seconds = started.elapsed().as_secs_f64();
+    println!("{} episodes, {} frames in {:.2}s ({:.0} frames per second)",
+             episodes, total_frames, seconds, total_frames as f64 / seconds.max(1e-9));
+}
+
+//A fresh machine for an episode, with its seed
+fn start(options: &Options, episode: u64) -> (Chip8, u64) {
+    let mut chip8 = match create_chip8(options, &options.rom) {
+        Ok(chip8) => chip8,
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::ROM_LOAD);
+        }
+    };
+    chip8.trace = false;
+    let seed = match options.seed {
+        Some(seed) => seed.wrapping_add(episode),
+        None => chip8.seed(),
+    };
+    chip8.set_seed(seed);
+    (chip8, seed)
+}
+
+fn report(episode: usize, frames: u64, end: &str, chip8: &Chip8) {
+    let v: Vec<String> = (0..16).map(|reg| format!("{:02X}", chip8.register(reg))).collect();
+    println!("episode {}: {} frames, ended by {}, screen {:#018X}, seed {:#X}, v={}",
+             episode + 1, frames, end, chip8.screen_hash(), chip8.seed(), v.join(" "));
+}
+
+//Runs every episode at once through the batch runner, returns the number of frames run
+fn run_batch(machines: Vec<(Chip8, u64)>, agent_name: &str, keys: &[u8], frames: u64, settings: &AgentSettings) -> u64 {
+    let mut agents: Vec<Box<dyn Agent>> = machines.iter().map(|(_, seed)| make_agent(agent_name, *seed, keys).unwrap()).collect();
+    let mut sticky: Vec<StickyKeys> = machines.iter().map(|(_, seed)| StickyKeys::new(sticky_seed(*seed), settings.sticky_keys)).collect();
+    let mut runner = BatchRunner::new(machines.into_iter().map(|(chip8, _)| chip8).collect());
+    let mut actions = vec![Action::none(); runner.len()];
+    let mut masks = vec![0; runner.len()];
+
+    for frame in 0..frames {
+        if runner.done().iter().all(|done| *done) {
+            break;
+        }
+        for (index, agent) in agents.iter_mut().enumerate() {
+            if frame.is_multiple_of(settings.frame_skip) {
+                actions[index] = agent.act(&Observation::scaled(runner.machine(index), frame, Vec::new(), settings.scale));
+            }
+            masks[index] = Action { keys: sticky[index].apply(actions[index].keys) }.key_mask();
+        }
+        runner.step(&masks);
+    }
+
+    for index in 0..runner.len() {
+        let chip8 = runner.machine(index);
+        let end = match (runner.done()[index], chip8.fault()) {
+            (false, _) => String::from("frame limit"),
+            (true, Some(fault)) => FrameEvent::Fault(fault).to_string(),
+            (true, None) => FrameEvent::InvalidPc(chip8.pc()).to_string(),
+        };
+        report(index, runner.frames()[index], &end, chip8);
+    }
+    runner.frames().iter().sum()
+}
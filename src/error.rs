@@ -0,0 +1,84 @@
+/************
+Errors
+
+What can go wrong loading a ROM and running it, as values a frontend can match on instead of
+messages it can only print. load_rom() returns them, and emulate_cycle() (so step()) returns one
+whenever the instruction didn't run as written:
+
+    Io                  - the ROM file couldn't be read
+    RomTooLarge         - the ROM doesn't fit between the load address and the end of memory
+    InvalidFontAddr     - the font wouldn't fit below 0x200 there (set_font_addr(), Chip8Builder)
+    InvalidLoadAddr     - a load address outside 0x200-0xFFF (set_load_addr(), Chip8Builder)
+    InvalidOpcode       - not an instruction the core (or an extension) knows. The machine carries
+                          on as before: 8XYN opcodes are skipped, others are stuck on
+    StackOverflow       - a call with the stack full (see stack.rs)
+    MemoryOutOfBounds   - the PC, I or a sprite pointing past the end of memory
+    Fault               - the other checks that stop the program (see protect.rs)
+
+Everything but InvalidOpcode leaves the machine stopped (is_fatal()), it keeps returning the error
+until it's reset or another state is loaded. The rest of the crate reports errors as Strings, and
+Chip8Error turns into one with ?.
+************/
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
+
+use protect::{FaultKind, MemoryFault};
+
+#[derive(Debug)]
+pub enum Chip8Error {
+    #[cfg(feature = "std")]
+    Io { path: String, error: io::Error },
+    RomTooLarge { size: usize, room: usize, addr: u16 },
+    InvalidFontAddr { addr: u16 },
+    InvalidLoadAddr { addr: u16 },
+    InvalidOpcode { opcode: u16, pc: u16 },
+    StackOverflow { target: u16, pc: u16 },
+    MemoryOutOfBounds { addr: usize, pc: u16 },
+    Fault(MemoryFault),
+}
+
+impl Chip8Error {
+    //Whether the machine stopped, rather than just stepping over something it didn't understand
+    pub fn is_fatal(&self) -> bool {
+        !matches!(*self, Chip8Error::InvalidOpcode { .. })
+    }
+}
+
+impl From<MemoryFault> for Chip8Error {
+    fn from(fault: MemoryFault) -> Chip8Error {
+        match fault.kind {
+            FaultKind::StackOverflow => Chip8Error::StackOverflow { target: fault.addr, pc: fault.pc },
+            FaultKind::OutOfBounds | FaultKind::SpriteOutOfMemory => Chip8Error::MemoryOutOfBounds { addr: fault.addr as usize, pc: fault.pc },
+            _ => Chip8Error::Fault(fault),
+        }
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            #[cfg(feature = "std")]
+            Chip8Error::Io { ref path, ref error } => write!(f, "Could not read ROM {}: {}", path, error),
+            Chip8Error::RomTooLarge { size, room, addr } =>
+                write!(f, "ROM is too big ({} bytes, at most {} fit at {:#06X})", size, room, addr),
+            Chip8Error::InvalidFontAddr { addr } => write!(f, "Invalid font address {:#06X} (the font must fit below 0x200)", addr),
+            Chip8Error::InvalidLoadAddr { addr } => write!(f, "Invalid load address {:#06X} (must be between 0x200 and 0xFFF)", addr),
+            Chip8Error::InvalidOpcode { opcode, pc } => write!(f, "Unknown opcode {:#06X} at {:#06X}", opcode, pc),
+            Chip8Error::StackOverflow { target, pc } =>
+                write!(f, "{}", MemoryFault { kind: FaultKind::StackOverflow, addr: target, pc }),
+            Chip8Error::MemoryOutOfBounds { addr, pc } => write!(f, "Access past the end of memory at {:#06X} by instruction at {:#06X}", addr, pc),
+            Chip8Error::Fault(fault) => write!(f, "{}", fault),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Chip8Error {}
+
+#[cfg(feature = "std")]
+impl From<Chip8Error> for String {
+    fn from(error: Chip8Error) -> String {
+        error.to_string()
+    }
+}
@@ -0,0 +1,14 @@
+/************
+Process exit codes
+
+So wrapper scripts can tell how a run ended without parsing the output:
+    0  normal quit (window closed, run finished, all tests passed)
+    1  failure: bad arguments or files, failed checks (debugger assertions, test-suite failures)
+    2  the ROM couldn't be loaded
+    3  fatal emulation error: the program left memory or was stopped by memory protection
+    4  watchdog timeout: a headless run took longer than its --timeout
+************/
+pub const FAILURE: i32 = 1;
+pub const ROM_LOAD: i32 = 2;
+pub const EMULATION_ERROR: i32 = 3;
+pub const WATCHDOG: i32 = 4;
@@ -0,0 +1,119 @@
+/************
+Instruction set extensions
+
+For prototyping CHIP-8 variants on top of this core without touching its decoder. An extension
+implements OpcodeExtension and gets offered every opcode the standard instruction set doesn't
+define (see is_known_opcode), before the core gives up on it as unknown. Extensions are asked in
+the order they were added, the first one that returns true has executed the opcode.
+
+An extension works on the machine through the Chip8's public accessors, and has to move the
+PC itself, the same way every built-in instruction does (set_pc(pc + 2) to go on to the next).
+
+For a single opcode there's no need for a type of its own: chip8.register_opcode(mask, pattern,
+handler) adds an extension running handler(opcode, chip8) for every opcode where opcode & mask ==
+pattern. The PC already points at the next instruction when the handler runs, so it only has to
+touch it to jump or skip, ie a host I/O trap on 0FNN:
+
+    chip8.register_opcode(0xFF00, 0x0F00, |opcode, chip8| println!("trap {:02X}", opcode & 0xFF));
+
+To make an extension available from the command line (--extension NAME), add it to by_name().
+Extensions live inside the Chip8, so like the rest of it they have to be Send + Sync.
+
+Bundled extensions:
+    skip-greater   5XY1 - skip the next instruction if VX > VY
+************/
+use variant::Variant;
+use Chip8;
+
+pub trait OpcodeExtension: Send + Sync {
+    fn name(&self) -> &str;
+    //Executes an opcode the core doesn't know, returns false to leave it alone
+    fn execute(&mut self, opcode: u16, chip8: &mut Chip8) -> bool;
+}
+
+//Whether the opcode is part of the standard instruction set of the variant
+pub fn is_known_opcode(opcode: u16, variant: Variant) -> bool {
+    let n = opcode & 0x000F;
+    let nn = opcode & 0x00FF;
+    if variant == Variant::Schip && is_schip_opcode(opcode) {
+        return true;
+    }
+    match opcode & 0xF000 {
+        0x0000 => opcode == 0x00E0 || opcode == 0x00EE,
+        0x1000 | 0x2000 | 0x3000 | 0x4000 | 0x6000 | 0x7000 => true,
+        0x5000 | 0x9000 => n == 0,
+        0x8000 => n <= 0x7 || n == 0xE,
+        0xA000 | 0xB000 | 0xC000 | 0xD000 => true,
+        0xE000 => nn == 0x9E || nn == 0xA1,
+        0xF000 => [0x07, 0x0A, 0x15, 0x18, 0x1E, 0x29, 0x33, 0x55, 0x65].contains(&nn),
+        _ => false,
+    }
+}
+
+//Whether the opcode is one SUPER-CHIP adds: 00CN, 00FB-00FF, FX30, and FX75/FX85 up to V7
+fn is_schip_opcode(opcode: u16) -> bool {
+    opcode & 0xFFF0 == 0x00C0 || (0x00FB..=0x00FF).contains(&opcode) || opcode & 0xF0FF == 0xF030
+        || opcode & 0xF8FF == 0xF075 || opcode & 0xF8FF == 0xF085
+}
+
+//An extension made of a single handler, see Chip8::register_opcode
+pub struct OpcodeHandler<F> {
+    mask: u16,
+    pattern: u16,
+    name: String,
+    handler: F,
+}
+
+impl<F: FnMut(u16, &mut Chip8) + Send + Sync> OpcodeHandler<F> {
+    pub fn new(mask: u16, pattern: u16, handler: F) -> OpcodeHandler<F> {
+        let pattern = pattern & mask;
+        OpcodeHandler { mask, pattern, name: format!("opcode {:04X}/{:04X}", pattern, mask), handler }
+    }
+}
+
+impl<F: FnMut(u16, &mut Chip8) + Send + Sync> OpcodeExtension for OpcodeHandler<F> {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn execute(&mut self, opcode: u16, chip8: &mut Chip8) -> bool {
+        if opcode & self.mask != self.pattern {
+            return false;
+        }
+        let pc = chip8.pc();
+        chip8.set_pc(pc + 2);
+        (self.handler)(opcode, chip8);
+        true
+    }
+}
+
+//5XY1: skip the next instruction if VX > VY
+pub struct SkipGreater;
+
+impl OpcodeExtension for SkipGreater {
+    fn name(&self) -> &str {
+        "skip-greater"
+    }
+
+    fn execute(&mut self, opcode: u16, chip8: &mut Chip8) -> bool {
+        if opcode & 0xF00F != 0x5001 {
+            return false;
+        }
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let skip = if chip8.register(x) > chip8.register(y) { 4 } else { 2 };
+        let pc = chip8.pc();
+        chip8.set_pc(pc + skip);
+        true
+    }
+}
+
+//The extensions by_name() knows
+pub const NAMES: &[&str] = &["skip-greater"];
+
+pub fn by_name(name: &str) -> Result<Box<dyn OpcodeExtension>, String> {
+    match name {
+        "skip-greater" => Ok(Box::new(SkipGreater)),
+        _ => Err(format!("Unknown extension {} (expected skip-greater)", name)),
+    }
+}
@@ -0,0 +1,91 @@
+/************
+Feature report
+
+Usage: chip8 --features
+
+Prints what this build of the emulator supports as a single JSON object, for frontends and test
+harnesses driving it to adapt instead of guessing from the version number:
+
+    report        - version of this report's layout, bumped when fields change meaning or go away
+    version       - the emulator's version
+    variants      - machine variants (see variant.rs), and which opcode sets exist
+    display       - screen size, whether there's a hi-res mode and how many bit planes
+    audio         - whether there's the buzzer and programmable sound, and the --audio-device names
+    memory        - size, the --load-addr range, the default --font-addr, the --address-mode values
+                    and how many SUPER-CHIP flags FX75/FX85 keep
+    quirks        - every quirk option and the values it takes
+    extensions    - --extension names, --mmio devices
+    backends      - how the core can run programs: the interpreter, and the block translator behind --jit
+    formats       - versions of the save state and session files this build writes
+
+New fields may be added without bumping report, so consumers should ignore the ones they don't know.
+************/
+use audio;
+use display::{HIRES_HEIGHT, HIRES_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+use extension;
+use json::Json;
+use memory::{AddressMode, MEMORY_SIZE};
+use protect::Protection;
+use quirks::Quirks;
+use savestate;
+use session;
+use sprite::{ClipCollision, DrawCheck, MAX_SPRITE_HEIGHT};
+use stack::{StackMode, STACK_SIZE};
+use variant::Variant;
+use {FONT_ADDR, PROGRAM_START, RPL_FLAGS};
+
+const REPORT_VERSION: u64 = 1;
+
+fn names(names: &[&str]) -> Json {
+    Json::from(names.to_vec())
+}
+
+fn report() -> Json {
+    let variants: Vec<&str> = Variant::ALL.iter().map(|variant| variant.name()).collect();
+    Json::object()
+        .field("report", REPORT_VERSION)
+        .field("version", env!("CARGO_PKG_VERSION"))
+        .field("variants", variants)
+        .field("opcodes", Json::object()
+            .field("chip8", true)
+            .field("schip", true)
+            .field("xo-chip", false))
+        .field("display", Json::object()
+            .field("width", SCREEN_WIDTH)
+            .field("height", SCREEN_HEIGHT)
+            .field("hires", true)
+            .field("hires_width", HIRES_WIDTH)
+            .field("hires_height", HIRES_HEIGHT)
+            .field("planes", 1u8))
+        .field("audio", Json::object()
+            .field("buzzer", true)
+            .field("patterns", false)
+            .field("devices", audio::device_names()))
+        .field("memory", Json::object()
+            .field("size", MEMORY_SIZE)
+            .field("load_addr", Json::object().field("min", PROGRAM_START).field("max", (MEMORY_SIZE - 1) as u16))
+            .field("font_addr", FONT_ADDR)
+            .field("address_mode", names(AddressMode::NAMES))
+            .field("rpl_flags", RPL_FLAGS))
+        .field("quirks", Json::object()
+            .field("stack", names(StackMode::NAMES))
+            .field("stack_size", STACK_SIZE)
+            .field("draw_check", names(DrawCheck::NAMES))
+            .field("sprite_limit", Json::object().field("min", 1u8).field("max", MAX_SPRITE_HEIGHT))
+            .field("clip_collision", names(ClipCollision::NAMES))
+            .field("quirks", names(Quirks::NAMES))
+            .field("quirk_presets", names(Quirks::PRESETS))
+            .field("protect", names(Protection::NAMES)))
+        .field("extensions", Json::object()
+            .field("opcodes", names(extension::NAMES))
+            .field("mmio", names(&["serial", "timer"])))
+        .field("backends", names(&["interpreter", "jit"]))
+        .field("formats", Json::object()
+            .field("save_state", savestate::VERSION)
+            .field("session", session::HEADER))
+}
+
+//Entry point for "chip8 --features"
+pub fn run() {
+    println!("{}", report());
+}
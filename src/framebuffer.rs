@@ -0,0 +1,92 @@
+/************
+Palette-indexed framebuffer
+
+The screen as frontends should see it: one palette index per pixel, row by row. The standard
+machine has a single bit plane, so its indices are 0 (unlit) and 1 (lit). XO-CHIP style machines
+draw on two planes, giving 4 indices: 0 neither, 1 first plane, 2 second plane, 3 both.
+Frontends turn indices into colors with the Palette (see palette.rs), either one pixel at a time
+with color() or all at once with to_rgba(), instead of each assuming the screen is 0s and 1s.
+************/
+#[cfg(feature = "std")]
+use palette::{Color, Palette};
+
+//Part of the screen a sprite was drawn on, in pixels, clipped to the screen
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrawRect {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorDepth {
+    OnePlane,   //2 colors
+    TwoPlanes,  //4 colors
+}
+
+impl ColorDepth {
+    pub fn colors(self) -> usize {
+        match self {
+            ColorDepth::OnePlane => 2,
+            ColorDepth::TwoPlanes => 4,
+        }
+    }
+}
+
+pub struct Framebuffer<'a> {
+    pixels: &'a [u8],
+    width: usize,
+    height: usize,
+    depth: ColorDepth,
+}
+
+impl<'a> Framebuffer<'a> {
+    pub fn new(pixels: &'a [u8], width: usize, height: usize, depth: ColorDepth) -> Framebuffer<'a> {
+        assert_eq!(pixels.len(), width * height, "framebuffer size doesn't match its pixels");
+        Framebuffer { pixels, width, height, depth }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    //Palette index of a pixel, masked to the colors the depth has
+    pub fn index(&self, x: usize, y: usize) -> u8 {
+        self.pixels[x + y * self.width] & (self.depth.colors() as u8 - 1)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn color(&self, x: usize, y: usize, palette: &Palette) -> Color {
+        palette.color(self.index(x, y))
+    }
+
+    //A 64-bit FNV-1a hash of the palette indices, stable across runs and builds so it can be compared
+    //with saved results
+    pub fn hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        for pixel in self.pixels.iter() {
+            hash ^= *pixel as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+        }
+        hash
+    }
+
+    //The whole screen as 8-bit RGBA, 4 bytes per pixel, row by row
+    #[cfg(feature = "std")]
+    pub fn to_rgba(&self, palette: &Palette) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(self.pixels.len() * 4);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                for channel in self.color(x, y, palette).iter() {
+                    rgba.push((channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+                }
+            }
+        }
+        rgba
+    }
+}
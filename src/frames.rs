@@ -0,0 +1,123 @@
+/************
+Frame iterator
+
+chip8.frames() runs the machine one 60Hz frame at a time (cycles_per_tick() cycles, ending
+on a timer tick) and yields every completed frame: a copy of the screen at the end of the frame
+plus what happened during it. Recorders, streamers and golden-frame tests only have to look at
+what comes out instead of counting cycles themselves:
+
+    for frame in chip8.frames().take(600) { ... }
+
+The iterator is endless while the program runs (waiting for a key is just a string of frames
+with nothing going on). It ends after the frame in which the program faulted, its program
+counter left memory or it exited (SUPER-CHIP's 00FD), that last frame carries the reason as an event.
+************/
+use std::fmt;
+
+use framebuffer::{ColorDepth, Framebuffer};
+use protect::MemoryFault;
+use Chip8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameEvent {
+    SoundStarted,
+    SoundStopped,
+    WaitingForKey,  //The program started waiting for a key press (FX0A)
+    Fault(MemoryFault),
+    InvalidPc(u16),
+    Exited,         //The program ran SUPER-CHIP's exit (00FD)
+}
+
+//Named like the headless stop reasons
+impl fmt::Display for FrameEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FrameEvent::SoundStarted => write!(f, "sound_started"),
+            FrameEvent::SoundStopped => write!(f, "sound_stopped"),
+            FrameEvent::WaitingForKey => write!(f, "waiting_for_key"),
+            FrameEvent::Fault(fault) => write!(f, "memory_fault ({})", fault),
+            FrameEvent::InvalidPc(pc) => write!(f, "invalid_pc ({:#06X})", pc),
+            FrameEvent::Exited => write!(f, "exited"),
+        }
+    }
+}
+
+pub struct Frame {
+    pub number: u64,            //Counting from 1
+    pub screen: Vec<u8>,        //Palette indices, as in Chip8::framebuffer()
+    pub width: usize,           //64 x 32, or 128 x 64 in SUPER-CHIP's hi-res mode
+    pub height: usize,
+    pub events: Vec<FrameEvent>,
+}
+
+impl Frame {
+    pub fn framebuffer(&self) -> Framebuffer<'_> {
+        Framebuffer::new(&self.screen, self.width, self.height, ColorDepth::OnePlane)
+    }
+}
+
+pub struct Frames<'a> {
+    chip8: &'a mut Chip8,
+    number: u64,
+    done: bool,
+}
+
+impl<'a> Frames<'a> {
+    pub fn new(chip8: &'a mut Chip8) -> Frames<'a> {
+        Frames { chip8, number: 0, done: false }
+    }
+}
+
+impl<'a> Iterator for Frames<'a> {
+    type Item = Frame;
+
+    fn next(&mut self) -> Option<Frame> {
+        if self.done {
+            return None;
+        }
+
+        let sound = self.chip8.sound_active();
+        let halted = self.chip8.is_halted();
+        let mut events = Vec::new();
+
+        loop {
+            let pc = self.chip8.pc();
+            if pc as usize + 1 >= 4096 {
+                events.push(FrameEvent::InvalidPc(pc));
+                self.done = true;
+                break;
+            }
+            if let (Err(_), Some(fault)) = (self.chip8.step(), self.chip8.fault()) {
+                events.push(FrameEvent::Fault(fault));
+                self.done = true;
+                break;
+            }
+            if self.chip8.has_exited() {
+                events.push(FrameEvent::Exited);
+                self.done = true;
+                break;
+            }
+            if self.chip8.cycles().is_multiple_of(self.chip8.cycles_per_tick()) {
+                break;
+            }
+        }
+
+        match (sound, self.chip8.sound_active()) {
+            (false, true) => events.insert(0, FrameEvent::SoundStarted),
+            (true, false) => events.insert(0, FrameEvent::SoundStopped),
+            _ => {},
+        }
+        if !halted && self.chip8.is_halted() {
+            events.insert(0, FrameEvent::WaitingForKey);
+        }
+
+        self.number += 1;
+        Some(Frame {
+            number: self.number,
+            screen: self.chip8.screen().to_vec(),
+            width: self.chip8.screen_width(),
+            height: self.chip8.screen_height(),
+            events,
+        })
+    }
+}
@@ -0,0 +1,100 @@
+/************
+Frontend traits
+
+What the core needs from whatever shows it, split in three so frontends can mix and match:
+
+    DisplaySink  - gets the screen at the end of every 60Hz frame
+    InputSource  - asked which keys are held before every frame
+    AudioSink    - told whether the buzzer is sounding after every frame
+
+A Frontend drives a machine one frame at a time through them, so a new frontend only has to
+implement the traits and call run_frame() at 60Hz (or as fast as it likes):
+
+    let mut frontend = Frontend::new(Box::new(MyScreen), Box::new(NoInput), Box::new(Silence));
+    while frontend.run_frame(&mut chip8).is_none() {}
+
+The window's InputState and audio devices implement InputSource and AudioSink. The window itself
+(main.rs) runs its machine on a thread of its own (see emulation.rs): key changes are sent to
+it as they happen rather than once a frame, for less latency, and the window draws the latest
+screen the thread sent whenever it's asked for a frame. The terminal frontend (terminal.rs) goes
+through a Frontend entirely. NullDisplay, NoInput and Silence stand in for the parts a frontend
+doesn't have, ie all three for a headless run.
+************/
+use framebuffer::Framebuffer;
+use frames::{FrameEvent, Frames};
+use Chip8;
+
+pub trait DisplaySink {
+    fn present(&mut self, screen: &Framebuffer);
+}
+
+pub trait InputSource {
+    //The keys held right now, key K at index K
+    fn keys(&mut self) -> [bool; 16];
+}
+
+pub trait AudioSink {
+    //Called every frame with whether the buzzer should currently be sounding
+    fn set_beep(&mut self, on: bool);
+}
+
+//Shows nothing
+pub struct NullDisplay;
+
+impl DisplaySink for NullDisplay {
+    fn present(&mut self, _screen: &Framebuffer) {}
+}
+
+//Never holds a key
+pub struct NoInput;
+
+impl InputSource for NoInput {
+    fn keys(&mut self) -> [bool; 16] {
+        [false; 16]
+    }
+}
+
+//Plays nothing
+pub struct Silence;
+
+impl AudioSink for Silence {
+    fn set_beep(&mut self, _on: bool) {}
+}
+
+pub struct Frontend {
+    display: Box<dyn DisplaySink>,
+    input: Box<dyn InputSource>,
+    audio: Box<dyn AudioSink>,
+    held: [bool; 16],
+}
+
+impl Frontend {
+    pub fn new(display: Box<dyn DisplaySink>, input: Box<dyn InputSource>, audio: Box<dyn AudioSink>) -> Frontend {
+        Frontend { display, input, audio, held: [false; 16] }
+    }
+
+    //Runs one frame: pulls the keys, runs the machine to the next timer tick, presents the screen
+    //and updates the beep. Returns the fault, invalid PC or exit if the program stopped during it
+    pub fn run_frame(&mut self, chip8: &mut Chip8) -> Option<FrameEvent> {
+        let keys = self.input.keys();
+        for (key, (pressed, was)) in keys.iter().zip(self.held.iter()).enumerate() {
+            if pressed != was {
+                chip8.press_key(key as u8, *pressed);
+            }
+        }
+        self.held = keys;
+
+        let events = match Frames::new(chip8).next() {
+            Some(frame) => frame.events,
+            None => Vec::new(),
+        };
+        self.display.present(&chip8.framebuffer());
+        self.audio.set_beep(chip8.sound_active());
+        events.into_iter().find(|event| matches!(event, FrameEvent::Fault(_) | FrameEvent::InvalidPc(_) | FrameEvent::Exited))
+    }
+
+    //Silences the audio, for when the frontend stops or pauses
+    pub fn stop(&mut self) {
+        self.audio.set_beep(false);
+    }
+}
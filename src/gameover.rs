@@ -0,0 +1,92 @@
+/************
+Game-over detection
+
+Lets the frontend find out when a game has ended, so kiosk mode (and anything else that cares
+about sessions) can move on automatically. There are two ways to detect it:
+
+Address - configured per ROM: the game is over as soon as the program counter reaches the given
+          address, ie the start of the ROM's "game over" routine.
+Heuristic - used when nothing is configured: the screen hasn't changed for STATIC_CYCLES cycles
+          while the program counter stays within a few bytes (a spin loop). Most ROMs end up like
+          this once the game is over, waiting forever or for a key press.
+
+The detector fires once per game, a new game gets a new detector.
+************/
+use {Chip8, CYCLES_PER_SECOND};
+
+//How long the screen has to stay the same before the heuristic fires
+const STATIC_CYCLES: u64 = (CYCLES_PER_SECOND * 10.0) as u64;
+//How many bytes of code the spin loop may cover
+const SPIN_LOOP_SIZE: u16 = 16;
+//How often the screen is compared, hashing it every cycle would be wasteful
+const CHECK_INTERVAL: u64 = 60;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameOverRule {
+    Address(u16),
+    Heuristic,
+}
+
+pub struct GameOverDetector {
+    rule: GameOverRule,
+    fired: bool,
+
+    //Heuristic state: what the screen looked like and where the PC has been since it last changed
+    screen_hash: u64,
+    static_since: u64,
+    pc_min: u16,
+    pc_max: u16,
+}
+
+impl GameOverDetector {
+    pub fn new(rule: GameOverRule) -> GameOverDetector {
+        GameOverDetector {
+            rule,
+            fired: false,
+            screen_hash: 0,
+            static_since: 0,
+            pc_min: u16::MAX,
+            pc_max: 0,
+        }
+    }
+
+    //Should be called after every cycle. Returns true on the cycle the game over is detected
+    pub fn update(&mut self, chip8: &Chip8) -> bool {
+        if self.fired {
+            return false;
+        }
+
+        let game_over = match self.rule {
+            GameOverRule::Address(addr) => chip8.pc() == addr,
+            GameOverRule::Heuristic => self.update_heuristic(chip8),
+        };
+
+        if game_over {
+            self.fired = true;
+        }
+        game_over
+    }
+
+    fn update_heuristic(&mut self, chip8: &Chip8) -> bool {
+        let pc = chip8.pc();
+        self.pc_min = self.pc_min.min(pc);
+        self.pc_max = self.pc_max.max(pc);
+
+        if !chip8.cycles().is_multiple_of(CHECK_INTERVAL) {
+            return false;
+        }
+
+        let hash = chip8.screen_hash();
+
+        //If anything was drawn, or the PC wandered off, start watching again from here
+        if hash != self.screen_hash || self.pc_max - self.pc_min > SPIN_LOOP_SIZE {
+            self.screen_hash = hash;
+            self.static_since = chip8.cycles();
+            self.pc_min = pc;
+            self.pc_max = pc;
+            return false;
+        }
+
+        chip8.cycles() - self.static_since >= STATIC_CYCLES
+    }
+}
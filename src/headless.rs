@@ -0,0 +1,255 @@
+/************
+Headless runs
+
+Usage: chip8 headless <romfile> [--cycles N | --frames N] [--timeout SECS] [--screenshot FILE] [--format text|json] [--jit] [options]
+
+Runs the ROM without a window for N cycles (default 10 seconds worth), or until it waits for a
+key press, leaves memory or exits (SUPER-CHIP's 00FD), then prints the final machine state. --timeout is a watchdog on the
+wall clock time the run may take, for wrapper scripts that can't afford a hang.
+The exit code tells how the run ended (see exitcode.rs). With --format json the result
+is a single JSON object, so other programs (compatibility dashboards, grading scripts) can
+consume it without parsing text. --frames runs N 60Hz frames instead (see frames.rs) and logs
+every frame where the screen changed or something happened (sound, key waits), with its screen
+hash, ready to be compared with golden frames. --screenshot saves the final screen as a 64 x 32 (or 128 x 64) PPM image, in
+the colors given with --fg/--bg. --jit runs the cycles on the experimental block translation backend
+(see jit.rs) instead of the interpreter.
+************/
+use std::fs;
+use std::process;
+use std::time::Instant;
+
+use clock::{Pacer, VirtualClock};
+use exitcode;
+use frames::FrameEvent;
+use jit::Jit;
+use json::Json;
+use metadata::rom_file_hash;
+use options::{take_value, Options, OutputFormat};
+use palette::Palette;
+use {create_chip8, Chip8, CYCLES_PER_SECOND};
+
+const DEFAULT_CYCLES: u64 = (CYCLES_PER_SECOND * 10.0) as u64;
+//How many cycles run between looks at the watchdog
+const WATCHDOG_CHUNK: u64 = 10_000;
+
+//Runs up to `cycles` cycles and returns why the run ended
+pub fn run_cycles(chip8: &mut Chip8, cycles: u64) -> &'static str {
+    for _ in 0..cycles {
+        if chip8.pc() as usize + 1 >= 4096 {
+            return "invalid_pc";
+        }
+        if chip8.is_halted() {
+            return "waiting_for_key";
+        }
+        if chip8.has_exited() {
+            return "exited";
+        }
+        if chip8.step().is_err_and(|err| err.is_fatal()) {
+            return "memory_fault";
+        }
+    }
+    "finished"
+}
+
+//run_cycles on the block translation backend
+pub fn run_cycles_jit(chip8: &mut Chip8, jit: &mut Jit, cycles: u64) -> &'static str {
+    let end = chip8.cycles() + cycles;
+    while chip8.cycles() < end {
+        if chip8.pc() as usize + 1 >= 4096 {
+            return "invalid_pc";
+        }
+        if chip8.is_halted() {
+            return "waiting_for_key";
+        }
+        if chip8.has_exited() {
+            return "exited";
+        }
+        if jit.run(chip8, end - chip8.cycles()).is_err_and(|err| err.is_fatal()) {
+            return "memory_fault";
+        }
+    }
+    "finished"
+}
+
+//Writes the screen as a binary PPM, which is simple enough to not need an image library
+fn save_screenshot(chip8: &Chip8, palette: &Palette, path: &str) -> Result<(), String> {
+    let screen = chip8.framebuffer();
+    let mut image = format!("P6\n{} {}\n255\n", screen.width(), screen.height()).into_bytes();
+    for pixel in screen.to_rgba(palette).chunks(4) {
+        image.extend_from_slice(&pixel[..3]);
+    }
+    fs::write(path, image).map_err(|err| format!("Could not write screenshot {}: {}", path, err))
+}
+
+//The state of a finished run, as JSON
+pub fn state_json(chip8: &Chip8, rom: &str, stop: &str) -> Json {
+    let registers: Vec<u8> = (0..16).map(|reg| chip8.register(reg)).collect();
+
+    Json::object()
+        .field("rom", rom)
+        .field("rom_hash", rom_file_hash(rom).map(|hash| format!("{:#018X}", hash)).unwrap_or_default())
+        .field("variant", chip8.variant().name())
+        .field("stop", stop)
+        .field("load_addr", chip8.load_addr())
+        .field("cycles", chip8.cycles())
+        .field("pc", chip8.pc())
+        .field("i", chip8.index())
+        .field("sp", chip8.sp())
+        .field("v", registers)
+        .field("delay_timer", chip8.delay_timer())
+        .field("sound_timer", chip8.sound_timer())
+        .field("screen_hash", format!("{:#018X}", chip8.screen_hash()))
+        .field("pixels_lit", chip8.screen().iter().filter(|pixel| **pixel != 0).count())
+        .field("code_writes", chip8.code_write_count())
+        .field("fault", chip8.fault().map(|fault| fault.to_string()).unwrap_or_default())
+}
+
+//Entry point for "chip8 headless ...", with the arguments following "headless"
+pub fn run(args: &[String]) {
+    let mut args = args.to_vec();
+    let parsed = take_value(&mut args, "--cycles").and_then(|cycles| {
+        let format = take_value(&mut args, "--format")?;
+        let timeout = match take_value(&mut args, "--timeout")? {
+            Some(value) => match value.parse::<f64>() {
+                Ok(seconds) if seconds > 0.0 => Some(seconds),
+                _ => return Err(format!("Invalid timeout {}", value)),
+            },
+            None => None,
+        };
+        let screenshot = take_value(&mut args, "--screenshot")?;
+        let frames = match take_value(&mut args, "--frames")? {
+            Some(value) => Some(value.parse::<usize>().map_err(|_| format!("Invalid frame count {}", value))?),
+            None => None,
+        };
+        let jit = match args.iter().position(|arg| arg == "--jit") {
+            Some(index) => {
+                args.remove(index);
+                true
+            },
+            None => false,
+        };
+        let options = Options::parse(&args)?;
+        Ok((cycles, format, timeout, screenshot, frames, jit, options))
+    });
+    let (cycles, format, timeout, screenshot, frames, jit, options) = match parsed {
+        Ok(result) => result,
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::FAILURE);
+        }
+    };
+
+    let cycles = match cycles {
+        Some(cycles) => match cycles.parse::<u64>() {
+            Ok(cycles) => cycles,
+            Err(_) => {
+                println!("Invalid cycle count {}. Aborting", cycles);
+                process::exit(exitcode::FAILURE);
+            }
+        },
+        None => DEFAULT_CYCLES,
+    };
+    let format = match format.map(|format| OutputFormat::parse(&format)) {
+        Some(Ok(format)) => format,
+        Some(Err(err)) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::FAILURE);
+        },
+        None => OutputFormat::Text,
+    };
+
+    let mut chip8 = match create_chip8(&options, &options.rom) {
+        Ok(chip8) => chip8,
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::ROM_LOAD);
+        }
+    };
+    chip8.trace = false;
+
+    //Run in chunks of virtual time, so the watchdog gets a look in every now and then
+    let started = Instant::now();
+    let mut stop = "finished";
+    let mut frame_log = Vec::new();
+    if let Some(count) = frames {
+        let mut last_hash = None;
+        for frame in chip8.frames().take(count) {
+            let hash = frame.framebuffer().hash();
+            for event in &frame.events {
+                match *event {
+                    FrameEvent::Fault(_) => stop = "memory_fault",
+                    FrameEvent::InvalidPc(_) => stop = "invalid_pc",
+                    FrameEvent::Exited => stop = "exited",
+                    _ => {},
+                }
+            }
+            if last_hash != Some(hash) || !frame.events.is_empty() {
+                let events: Vec<String> = frame.events.iter().map(|event| event.to_string()).collect();
+                frame_log.push((frame.number, hash, events));
+            }
+            last_hash = Some(hash);
+            if timeout.is_some_and(|timeout| started.elapsed().as_secs_f64() >= timeout) {
+                stop = "timeout";
+                break;
+            }
+        }
+    }
+    let mut clock = VirtualClock::cycles(WATCHDOG_CHUNK);
+    let mut pacer = Pacer::new(f64::INFINITY);
+    let mut jit = if jit { Some(Jit::new()) } else { None };
+    while frames.is_none() && chip8.cycles() < cycles {
+        let (due, _) = pacer.advance(&mut clock, 1.0);
+        let chunk = due.min(cycles - chip8.cycles());
+        stop = match jit {
+            Some(ref mut jit) => run_cycles_jit(&mut chip8, jit, chunk),
+            None => run_cycles(&mut chip8, chunk),
+        };
+        if stop != "finished" {
+            break;
+        }
+        if timeout.is_some_and(|timeout| started.elapsed().as_secs_f64() >= timeout) {
+            stop = "timeout";
+            break;
+        }
+    }
+
+    match format {
+        OutputFormat::Json => {
+            let mut json = state_json(&chip8, &options.rom, stop);
+            if frames.is_some() {
+                let log: Vec<Json> = frame_log.iter().map(|(number, hash, events)| Json::object()
+                    .field("frame", *number)
+                    .field("screen_hash", format!("{:#018X}", hash))
+                    .field("events", events.clone())).collect();
+                json = json.field("frames", log);
+            }
+            println!("{}", json);
+        },
+        OutputFormat::Text => {
+            for (number, hash, events) in &frame_log {
+                let line = format!("frame {:5}: {:#018X} {}", number, hash, events.join(", "));
+                println!("{}", line.trim_end());
+            }
+            println!("{}: stopped ({}) after {} cycles", options.rom, stop, chip8.cycles());
+            if let Some(fault) = chip8.fault() {
+                println!("{}", fault);
+            }
+            chip8.print_registers();
+            println!("Screen hash: {:#018X}", chip8.screen_hash());
+            chip8.diagnostics().print_summary();
+        }
+    }
+
+    if let Some(path) = screenshot {
+        if let Err(err) = save_screenshot(&chip8, &options.palette, &path) {
+            println!("{}", err);
+            process::exit(exitcode::FAILURE);
+        }
+    }
+
+    match stop {
+        "invalid_pc" | "memory_fault" => process::exit(exitcode::EMULATION_ERROR),
+        "timeout" => process::exit(exitcode::WATCHDOG),
+        _ => {},
+    }
+}
@@ -0,0 +1,90 @@
+/************
+Instruction history
+
+Keeps the last N executed instructions in a ring buffer so that, when something goes wrong,
+we can see how execution got there without having printed a full trace.
+
+Each entry stores the address the opcode was fetched from, the opcode itself, and only the
+registers that the instruction changed (a "register diff"), which keeps entries small.
+************/
+use std::collections::VecDeque;
+
+//Default number of instructions remembered
+pub const DEFAULT_HISTORY_SIZE: usize = 256;
+
+//A register that changed while executing an instruction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RegChange {
+    V(usize, u8, u8),   //Register index, old value, new value
+    I(u16, u16),        //Old index, new index
+}
+
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub pc: u16,              //Address the opcode was fetched from
+    pub opcode: u16,
+    pub changes: Vec<RegChange>,
+}
+
+impl HistoryEntry {
+    //Builds an entry by comparing the registers from before and after an instruction ran
+    pub fn new(pc: u16, opcode: u16, old_v: &[u8; 16], new_v: &[u8; 16], old_i: u16, new_i: u16) -> HistoryEntry {
+        let mut changes = Vec::new();
+
+        for reg in 0..16 {
+            if old_v[reg] != new_v[reg] {
+                changes.push(RegChange::V(reg, old_v[reg], new_v[reg]));
+            }
+        }
+        if old_i != new_i {
+            changes.push(RegChange::I(old_i, new_i));
+        }
+
+        HistoryEntry { pc, opcode, changes }
+    }
+}
+
+pub struct History {
+    entries: VecDeque<HistoryEntry>,
+    capacity: usize,
+}
+
+impl History {
+    pub fn new(capacity: usize) -> History {
+        History {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    //Adds an entry, dropping the oldest one if the buffer is full
+    pub fn push(&mut self, entry: HistoryEntry) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    //Returns up to `count` of the most recent entries, oldest first
+    pub fn last(&self, count: usize) -> impl Iterator<Item = &HistoryEntry> {
+        let skip = self.entries.len().saturating_sub(count);
+        self.entries.iter().skip(skip)
+    }
+
+    //Prints up to `count` of the most recent entries, oldest first
+    pub fn print(&self, count: usize) {
+        for entry in self.last(count) {
+            print!("{:#06X}: {:#06X}", entry.pc, entry.opcode);
+            for change in &entry.changes {
+                match *change {
+                    RegChange::V(reg, old, new) => print!("  V[{}] {} -> {}", reg, old, new),
+                    RegChange::I(old, new) => print!("  I {:#06X} -> {:#06X}", old, new),
+                }
+            }
+            println!();
+        }
+    }
+}
@@ -0,0 +1,24 @@
+/************
+Execution hooks
+
+For code that wants to follow or steer a running program from the outside: cheats, automated
+playtesting bots, tools measuring what a ROM does. A Hooks implementation is called around every
+step() and gets the whole machine:
+
+    pre_cycle    before the instruction at the PC executes
+    post_cycle   after it executed (and after the timers, if this cycle ticked them)
+    on_draw      right after a DXYN drew, before post_cycle
+    on_key       when a key was pressed or released through handle_key()/press_key()
+
+Every method does nothing by default, so a hook only implements the ones it needs. Hooks are run
+in the order they were added. Like extensions they live inside the Chip8, so they have to be
+Send + Sync, and can't add more hooks while they run.
+************/
+use Chip8;
+
+pub trait Hooks: Send + Sync {
+    fn pre_cycle(&mut self, _chip8: &mut Chip8) {}
+    fn post_cycle(&mut self, _chip8: &mut Chip8) {}
+    fn on_draw(&mut self, _chip8: &mut Chip8) {}
+    fn on_key(&mut self, _chip8: &mut Chip8, _key: u8, _pressed: bool) {}
+}
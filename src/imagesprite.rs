@@ -0,0 +1,201 @@
+/************
+Image to sprite converter
+
+Usage: chip8 rom sprite <pngfile> [--rom OUTFILE] [--out FILE] [--at ADDR] [--x X] [--y Y] [--invert] [--subroutine]
+
+Turns a small monochrome PNG (at most the 64 x 32 screen) into sprite tables and the code that
+draws them, for title screens and other pictures in homebrew. Light pixels are drawn and dark or
+transparent ones aren't, --invert swaps that for dark pictures on a light background.
+
+DXYN draws 8 pixels wide and at most 15 high, so the image is cut into 8 x 15 tiles. Empty tiles
+are left out and the empty rows at the top and bottom of the others are trimmed off. The code
+clears the screen and draws every tile with V0 and V1 as its position:
+
+    00E0                  CLS
+    ANNN 60XX 61YY D01N   for every tile: I = its table, V0/V1 = where it goes, draw N rows
+    1NNN                  jump to itself, or 00EE (return) with --subroutine
+
+followed by the tables. Everything is laid out from ADDR (default 0x200) and the image is centred
+unless --x and --y place its top left corner. The listing (opcodes with their mnemonics, then the
+tables as bytes) is printed or written to --out, --rom writes the bytes as a ROM that shows the
+picture, to check it with the emulator before pasting it into a program.
+************/
+use std::fs::{self, File};
+
+use png::{self, ColorType};
+
+use benchrom::assemble;
+use disasm::disassemble;
+use display::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use options::{parse_number, take_value};
+use variant::Variant;
+
+const TILE_WIDTH: usize = 8;
+const TILE_HEIGHT: usize = 15;
+
+//The image as lit pixels, row by row
+struct Bitmap {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+impl Bitmap {
+    fn load(path: &str, invert: bool) -> Result<Bitmap, String> {
+        let file = File::open(path).map_err(|err| format!("Could not open {}: {}", path, err))?;
+        let (info, mut reader) = png::Decoder::new(file).read_info().map_err(|err| format!("Could not read {}: {}", path, err))?;
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data).map_err(|err| format!("Could not read {}: {}", path, err))?;
+
+        let (width, height) = (info.width as usize, info.height as usize);
+        if width > SCREEN_WIDTH || height > SCREEN_HEIGHT {
+            return Err(format!("{} is {} x {}, more than the {} x {} screen", path, width, height, SCREEN_WIDTH, SCREEN_HEIGHT));
+        }
+        //The decoder expands everything to 8 bits per channel, palettes included
+        let channels = match info.color_type {
+            ColorType::Grayscale => 1,
+            ColorType::GrayscaleAlpha => 2,
+            ColorType::RGB => 3,
+            ColorType::RGBA => 4,
+            ColorType::Indexed => return Err(format!("Could not read {}: unexpanded palette", path)),
+        };
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let row = &data[y * info.line_size..];
+            for x in 0..width {
+                let pixel = &row[x * channels..(x + 1) * channels];
+                let (light, alpha) = match *pixel {
+                    [gray] => (gray as u32, 255),
+                    [gray, alpha] => (gray as u32, alpha),
+                    [r, g, b] => ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000, 255),
+                    [r, g, b, alpha] => ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000, alpha),
+                    _ => unreachable!(),
+                };
+                pixels.push(alpha >= 128 && (light >= 128) != invert);
+            }
+        }
+        Ok(Bitmap { width, height, pixels })
+    }
+
+    fn lit(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.pixels[y * self.width + x]
+    }
+}
+
+//One DXYN worth of the image
+struct Tile {
+    x: usize,   //Offset in the image
+    y: usize,
+    rows: Vec<u8>,
+}
+
+fn tiles(bitmap: &Bitmap) -> Vec<Tile> {
+    let mut tiles = Vec::new();
+    for top in (0..bitmap.height).step_by(TILE_HEIGHT) {
+        for left in (0..bitmap.width).step_by(TILE_WIDTH) {
+            let rows: Vec<u8> = (top..bitmap.height.min(top + TILE_HEIGHT))
+                .map(|y| (0..TILE_WIDTH).fold(0, |row, x| row << 1 | bitmap.lit(left + x, y) as u8))
+                .collect();
+            let first = match rows.iter().position(|&row| row != 0) {
+                Some(first) => first,
+                None => continue,
+            };
+            let last = rows.iter().rposition(|&row| row != 0).unwrap();
+            tiles.push(Tile { x: left, y: top + first, rows: rows[first..=last].to_vec() });
+        }
+    }
+    tiles
+}
+
+//Where the pieces went, for the listing
+struct Program {
+    opcodes: Vec<u16>,
+    tables: Vec<(u16, Tile)>,
+    bytes: Vec<u8>,
+}
+
+fn build(tiles: Vec<Tile>, at: u16, x: usize, y: usize, subroutine: bool) -> Result<Program, String> {
+    let code_size = 2 + tiles.len() * 8 + 2;
+    let data_size: usize = tiles.iter().map(|tile| tile.rows.len()).sum();
+    if at as usize + code_size + data_size > 4096 {
+        return Err(format!("The picture takes {} bytes, that doesn't fit in memory from {:#05X}", code_size + data_size, at));
+    }
+
+    let mut opcodes = vec![0x00E0];
+    let mut tables = Vec::new();
+    let mut addr = at + code_size as u16;
+    for tile in tiles {
+        opcodes.push(0xA000 | addr);
+        opcodes.push(0x6000 | (x + tile.x) as u16);
+        opcodes.push(0x6100 | (y + tile.y) as u16);
+        opcodes.push(0xD010 | tile.rows.len() as u16);
+        addr += tile.rows.len() as u16;
+        tables.push((addr - tile.rows.len() as u16, tile));
+    }
+    let end = at + code_size as u16 - 2;
+    opcodes.push(if subroutine { 0x00EE } else { 0x1000 | end });
+
+    let mut bytes = assemble(&opcodes);
+    for (_, tile) in &tables {
+        bytes.extend_from_slice(&tile.rows);
+    }
+    Ok(Program { opcodes, tables, bytes })
+}
+
+fn listing(program: &Program, path: &str, bitmap: &Bitmap, at: u16, x: usize, y: usize) -> String {
+    let mut text = format!("; {}, {} x {} pixels drawn at {},{} with V0 and V1: {} sprites, {} bytes\n",
+                           path, bitmap.width, bitmap.height, x, y, program.tables.len(), program.bytes.len());
+    for (index, opcode) in program.opcodes.iter().enumerate() {
+        text.push_str(&format!("{:#05X}: {:04X}  {}\n", at as usize + index * 2, opcode, disassemble(*opcode, Variant::Chip8)));
+    }
+    for (addr, tile) in &program.tables {
+        let bytes: Vec<String> = tile.rows.iter().map(|row| format!("0x{:02X}", row)).collect();
+        text.push_str(&format!("{:#05X}: ; {} rows at {},{}\n    {}\n", addr, tile.rows.len(), x + tile.x, y + tile.y, bytes.join(", ")));
+    }
+    text
+}
+
+//"chip8 rom sprite ...", with the arguments following "sprite"
+pub fn convert(args: &[String]) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let rom = take_value(&mut args, "--rom")?;
+    let out = take_value(&mut args, "--out")?;
+    let at = take_value(&mut args, "--at")?.map(|at| parse_number(&at)).transpose()?.unwrap_or(0x200);
+    let x = take_value(&mut args, "--x")?.map(|x| parse_number(&x)).transpose()?;
+    let y = take_value(&mut args, "--y")?.map(|y| parse_number(&y)).transpose()?;
+    let mut flag = |name: &str| match args.iter().position(|arg| arg == name) {
+        Some(index) => {
+            args.remove(index);
+            true
+        },
+        None => false,
+    };
+    let invert = flag("--invert");
+    let subroutine = flag("--subroutine");
+    if args.len() != 1 {
+        return Err(String::from("Usage: chip8 rom sprite <pngfile> [--rom OUTFILE] [--out FILE] [--at ADDR] [--x X] [--y Y] [--invert] [--subroutine]"));
+    }
+    if at >= 4096 || !at.is_multiple_of(2) {
+        return Err(format!("Invalid address {:#X}, it must be even and in memory", at));
+    }
+
+    let bitmap = Bitmap::load(&args[0], invert)?;
+    let x = x.map(|x| x as usize).unwrap_or((SCREEN_WIDTH - bitmap.width) / 2);
+    let y = y.map(|y| y as usize).unwrap_or((SCREEN_HEIGHT - bitmap.height) / 2);
+    if x + bitmap.width > SCREEN_WIDTH || y + bitmap.height > SCREEN_HEIGHT {
+        return Err(format!("A {} x {} picture at {},{} doesn't fit on the screen", bitmap.width, bitmap.height, x, y));
+    }
+
+    let program = build(tiles(&bitmap), at, x, y, subroutine)?;
+    let text = listing(&program, &args[0], &bitmap, at, x, y);
+    match out {
+        Some(ref path) => fs::write(path, &text).map_err(|err| format!("Could not write {}: {}", path, err))?,
+        None => print!("{}", text),
+    }
+    if let Some(path) = rom {
+        fs::write(&path, &program.bytes).map_err(|err| format!("Could not write {}: {}", path, err))?;
+        println!("Wrote {} ({} bytes, load it at {:#05X})", path, program.bytes.len(), at);
+    }
+    Ok(())
+}
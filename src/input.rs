@@ -0,0 +1,193 @@
+/************
+Input mapping
+
+Host buttons (keyboard keys, gamepad buttons) are mapped onto the 16 CHIP-8 keys by a Keymap. Any
+number of host buttons can be bound to the same CHIP-8 key, which helps with cheap keyboards that
+drop some key combinations: bind an alternate key that doesn't ghost with the others.
+
+InputState merges all the sources: a CHIP-8 key is down as long as at least one of the host
+buttons bound to it is held, and only changes of that merged state reach the Chip8. Releasing
+one of two held buttons for the same key doesn't release the key.
+
+Keymap file (--keymap FILE), one binding per line, added on top of the default 1234/qwer/asdf/zxcv
+layout. Binding a host button again replaces its default binding:
+    BUTTON KEY       - ie "Up 5", "Space 6" or "pad0:3 A" (gamepad 0, button 3). KEY is hex
+    # comment
+
+Button names are letters, digits, NumPad0-NumPad9, Up, Down, Left, Right, Space, Return,
+Backspace, Minus, Comma, Period, LShift, RShift, LCtrl, RCtrl, LAlt and RAlt. Gamepad buttons
+are only seen if the window backend reports them.
+************/
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use piston_window::{Button, ButtonArgs, ButtonState, ControllerButton, Key};
+
+use frontend::InputSource;
+
+const DEFAULT_LAYOUT: [(Key, u8); 16] = [
+    (Key::D1, 0x1), (Key::D2, 0x2), (Key::D3, 0x3), (Key::D4, 0xC),
+    (Key::Q, 0x4), (Key::W, 0x5), (Key::E, 0x6), (Key::R, 0xD),
+    (Key::A, 0x7), (Key::S, 0x8), (Key::D, 0x9), (Key::F, 0xE),
+    (Key::Z, 0xA), (Key::X, 0x0), (Key::C, 0xB), (Key::V, 0xF),
+];
+
+const KEY_NAMES: [(&str, Key); 62] = [
+    ("A", Key::A), ("B", Key::B), ("C", Key::C), ("D", Key::D), ("E", Key::E), ("F", Key::F),
+    ("G", Key::G), ("H", Key::H), ("I", Key::I), ("J", Key::J), ("K", Key::K), ("L", Key::L),
+    ("M", Key::M), ("N", Key::N), ("O", Key::O), ("P", Key::P), ("Q", Key::Q), ("R", Key::R),
+    ("S", Key::S), ("T", Key::T), ("U", Key::U), ("V", Key::V), ("W", Key::W), ("X", Key::X),
+    ("Y", Key::Y), ("Z", Key::Z),
+    ("0", Key::D0), ("1", Key::D1), ("2", Key::D2), ("3", Key::D3), ("4", Key::D4),
+    ("5", Key::D5), ("6", Key::D6), ("7", Key::D7), ("8", Key::D8), ("9", Key::D9),
+    ("NumPad0", Key::NumPad0), ("NumPad1", Key::NumPad1), ("NumPad2", Key::NumPad2),
+    ("NumPad3", Key::NumPad3), ("NumPad4", Key::NumPad4), ("NumPad5", Key::NumPad5),
+    ("NumPad6", Key::NumPad6), ("NumPad7", Key::NumPad7), ("NumPad8", Key::NumPad8),
+    ("NumPad9", Key::NumPad9),
+    ("Up", Key::Up), ("Down", Key::Down), ("Left", Key::Left), ("Right", Key::Right),
+    ("Space", Key::Space), ("Return", Key::Return), ("Backspace", Key::Backspace),
+    ("LShift", Key::LShift), ("RShift", Key::RShift), ("LCtrl", Key::LCtrl), ("RCtrl", Key::RCtrl),
+    ("LAlt", Key::LAlt), ("RAlt", Key::RAlt),
+    ("Minus", Key::Minus), ("Comma", Key::Comma), ("Period", Key::Period),
+];
+
+//Parses a host button name, case insensitive
+fn parse_button(name: &str) -> Result<Button, String> {
+    if let Some(pad) = name.strip_prefix("pad") {
+        let mut parts = pad.splitn(2, ':');
+        let id = parts.next().and_then(|id| id.parse::<i32>().ok());
+        let button = parts.next().and_then(|button| button.parse::<u8>().ok());
+        return match (id, button) {
+            (Some(id), Some(button)) => Ok(Button::Controller(ControllerButton { id, button })),
+            _ => Err(format!("Invalid gamepad button {} (expected padID:BUTTON)", name)),
+        };
+    }
+
+    KEY_NAMES.iter()
+        .find(|&&(key_name, _)| key_name.eq_ignore_ascii_case(name))
+        .map(|&(_, key)| Button::Keyboard(key))
+        .ok_or_else(|| format!("Unknown key name {}", name))
+}
+
+//Name of a host button, as used in keymap files
+pub fn button_name(button: Button) -> String {
+    match button {
+        Button::Keyboard(key) => match KEY_NAMES.iter().find(|&&(_, named)| named == key) {
+            Some(&(name, _)) => name.to_string(),
+            None => format!("{:?}", key),
+        },
+        Button::Controller(pad) => format!("pad{}:{}", pad.id, pad.button),
+        other => format!("{:?}", other),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    bindings: Vec<(Button, u8)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap {
+            bindings: DEFAULT_LAYOUT.iter().map(|&(key, chip8_key)| (Button::Keyboard(key), chip8_key)).collect(),
+        }
+    }
+}
+
+impl Keymap {
+    //Binds a host button to a CHIP-8 key, replacing what it was bound to before
+    pub fn bind(&mut self, button: Button, key: u8) {
+        self.bindings.retain(|&(bound, _)| bound != button);
+        self.bindings.push((button, key));
+    }
+
+    pub fn lookup(&self, button: Button) -> Option<u8> {
+        self.bindings.iter().find(|&&(bound, _)| bound == button).map(|&(_, key)| key)
+    }
+
+    //Every host button bound to a CHIP-8 key, most recently bound first
+    pub fn buttons_for(&self, key: u8) -> Vec<Button> {
+        self.bindings.iter().rev().filter(|&&(_, bound)| bound == key).map(|&(button, _)| button).collect()
+    }
+
+    //The default layout plus the bindings from a keymap file
+    pub fn load(path: &str) -> Result<Keymap, String> {
+        let file = File::open(path).map_err(|err| format!("Could not open keymap {}: {}", path, err))?;
+        let mut keymap = Keymap::default();
+
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|err| format!("Could not read keymap {}: {}", path, err))?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let location = format!("{}:{}", path, number + 1);
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words.len() != 2 {
+                return Err(format!("{}: Invalid keymap line (expected BUTTON KEY)", location));
+            }
+            let button = parse_button(words[0]).map_err(|err| format!("{}: {}", location, err))?;
+            let key = match u8::from_str_radix(words[1], 16) {
+                Ok(key) if key <= 0xF => key,
+                _ => return Err(format!("{}: Invalid CHIP-8 key {}", location, words[1])),
+            };
+            keymap.bind(button, key);
+        }
+
+        Ok(keymap)
+    }
+}
+
+//Merged state of every input source
+pub struct InputState {
+    keymap: Keymap,
+    held: Vec<Button>,  //Bound host buttons that are currently down
+}
+
+impl InputState {
+    pub fn new(keymap: Keymap) -> InputState {
+        InputState {
+            keymap,
+            held: Vec::new(),
+        }
+    }
+
+    //Whether any host button bound to the CHIP-8 key is held
+    pub fn is_down(&self, key: u8) -> bool {
+        self.held.iter().any(|&button| self.keymap.lookup(button) == Some(key))
+    }
+
+    //Feeds in a host button event. Returns the CHIP-8 key and its new state (1 down, 0 up) if the
+    //merged state of that key changed, None for unbound buttons, key repeats and covered releases
+    pub fn button(&mut self, args: ButtonArgs) -> Option<(u8, u8)> {
+        let key = self.keymap.lookup(args.button)?;
+        let was_down = self.is_down(key);
+
+        match args.state {
+            ButtonState::Press => {
+                if !self.held.contains(&args.button) {
+                    self.held.push(args.button);
+                }
+            },
+            ButtonState::Release => self.held.retain(|&button| button != args.button),
+        }
+
+        let down = self.is_down(key);
+        if down == was_down {
+            return None;
+        }
+        Some((key, down as u8))
+    }
+}
+
+//For frontends that pull the keys once a frame, the window pushes every change as it happens
+impl InputSource for InputState {
+    fn keys(&mut self) -> [bool; 16] {
+        let mut keys = [false; 16];
+        for (key, down) in keys.iter_mut().enumerate() {
+            *down = self.is_down(key as u8);
+        }
+        keys
+    }
+}
@@ -0,0 +1,101 @@
+/************
+Pixel inspector
+
+--inspect turns the mouse into a probe for draw bugs: the CHIP-8 pixel under the cursor gets an
+outline, and the window title shows its coordinate (as the program sees it, whatever --rotate and
+F7 did to the output) and whether it's lit, in decimal and hex since that's what VX and VY hold.
+
+    Right click   Pause/resume the program
+    Left click    While paused (by the right click or anything else), flip the pixel under the cursor
+
+A flipped pixel is part of the screen from then on, sprites drawn over it collide with it.
+************/
+use piston_window::*;
+
+use display::{DisplaySettings, Layout};
+use Chip8;
+
+const OUTLINE: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+
+pub struct PixelInspector {
+    cursor: [f64; 2],   //Last mouse position, in window points
+    hovered: Option<(usize, usize)>,
+    shown: String,      //What the title says, so it's only set when it changes
+    pub paused: bool,
+}
+
+//Where the screen is in the window right now, as draw_screen() puts it
+fn layout(window: &PistonWindow, settings: DisplaySettings, chip8: &Chip8) -> Layout {
+    let size = window.size();
+    let draw_size = window.draw_size();
+    let device_scale = if size.width > 0 { draw_size.width as f64 / size.width as f64 } else { 1.0 };
+    Layout::fit([size.width as f64, size.height as f64], device_scale, settings, (chip8.screen_width(), chip8.screen_height()))
+}
+
+impl PixelInspector {
+    pub fn new() -> PixelInspector {
+        println!("Pixel inspector: hover over the screen, right click to pause, left click to flip a pixel while paused");
+        PixelInspector { cursor: [-1.0, -1.0], hovered: None, shown: String::new(), paused: false }
+    }
+
+    //Follows the mouse, the layout changes with the window size, the display settings and the resolution
+    pub fn hover(&mut self, cursor: [f64; 2], window: &PistonWindow, settings: DisplaySettings, chip8: &Chip8) {
+        self.cursor = cursor;
+        let size = (chip8.screen_width(), chip8.screen_height());
+        self.hovered = layout(window, settings, chip8).screen_pixel(cursor, settings.rotation, size);
+    }
+
+    //Picks the pixel under the last mouse position again, after the window or the settings changed
+    pub fn refresh(&mut self, window: &PistonWindow, settings: DisplaySettings, chip8: &Chip8) {
+        let cursor = self.cursor;
+        self.hover(cursor, window, settings, chip8);
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+        println!("Pixel inspector: program {}", if self.paused { "paused" } else { "resumed" });
+    }
+
+    //Flips the pixel under the cursor. Returns whether there was one
+    pub fn flip(&self, chip8: &mut Chip8) -> bool {
+        match self.hovered {
+            Some((x, y)) => {
+                let lit = chip8.pixel(x, y);
+                chip8.set_pixel(x, y, !lit);
+                println!("Pixel inspector: {} {},{}", if lit { "cleared" } else { "lit" }, x, y);
+                true
+            },
+            None => false,
+        }
+    }
+
+    //The new window title, None while it hasn't changed
+    pub fn title(&mut self, chip8: &Chip8) -> Option<String> {
+        let text = match self.hovered {
+            Some((x, y)) => format!("pixel {},{} ({:#04X},{:#04X}) {}{}", x, y, x, y,
+                if chip8.pixel(x, y) { "lit" } else { "unlit" }, if self.paused { ", paused" } else { "" }),
+            None if self.paused => String::from("paused"),
+            None => String::new(),
+        };
+        if text == self.shown {
+            return None;
+        }
+        self.shown = text;
+        Some(if self.shown.is_empty() { String::from("Chip8") } else { format!("Chip8 - {}", self.shown) })
+    }
+
+    pub fn draw(&self, chip8: &Chip8, window: &mut PistonWindow, event: &Event, settings: DisplaySettings) {
+        let (width, height) = (chip8.screen_width(), chip8.screen_height());
+        //Until the mouse moves, the pixel can be left over from before SUPER-CHIP switched resolution
+        let (x, y) = match self.hovered {
+            Some((x, y)) if x < width && y < height => (x, y),
+            _ => return,
+        };
+        let Layout { pixel_size, x_offset, y_offset } = layout(window, settings, chip8);
+        let (out_x, out_y) = settings.rotation.apply(x, y, width, height);
+        window.draw_2d(event, |c, g| {
+            let square = [x_offset + out_x as f64 * pixel_size, y_offset + out_y as f64 * pixel_size, pixel_size, pixel_size];
+            Rectangle::new_border(OUTLINE, (pixel_size / 8.0).max(1.0)).draw(square, &c.draw_state, c.transform, g);
+        });
+    }
+}
@@ -0,0 +1,197 @@
+/************
+Instruction decoder
+
+decode() turns an opcode into a typed Instruction, with its operands pulled out of the nibbles,
+and Display prints it as the usual CHIP-8 assembly mnemonic (as in Cowgod's technical reference),
+ie 0x2206 -> "CALL 0x206" and 0x6105 -> "LD V1, 0x05". The disassembler and tracing share this
+decoder, and the core's dispatch tables (see dispatch.rs) group opcodes the same way, so what's
+printed is what runs.
+
+Decoding follows what this core executes rather than the strict instruction set (is_known_opcode
+in extension.rs has that): any 0NN0 clears the screen and any 0NNE returns, and 5XYN and 9XYN
+compare registers whatever N is. Other 0NNN opcodes are SYS calls, which the core doesn't run, and
+opcodes that mean nothing at all come out as Unknown, printed as "DW 0xXXXX", a raw data word.
+
+decode_for() decodes for a variant (see variant.rs). For SUPER-CHIP it picks out the opcodes it
+adds first, so 00FE is LOW rather than a RET. For CHIP-8 it's the same as decode().
+************/
+use core::fmt;
+
+use variant::Variant;
+use {FIRST_NIBBLE_MASK, FOURTH_NIBBLE_MASK, LAST_THREE_MASK, LAST_TWO_MASK, SECOND_NIBBLE_MASK, THIRD_NIBBLE_MASK};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Instruction {
+    Cls,                                    //00E0
+    Ret,                                    //00EE
+    Sys { nnn: u16 },                       //0NNN
+    Jp { nnn: u16 },                        //1NNN
+    Call { nnn: u16 },                      //2NNN
+    SeVxByte { x: usize, kk: u8 },          //3XKK
+    SneVxByte { x: usize, kk: u8 },         //4XKK
+    SeVxVy { x: usize, y: usize },          //5XY0
+    LdVxByte { x: usize, kk: u8 },          //6XKK
+    AddVxByte { x: usize, kk: u8 },         //7XKK
+    LdVxVy { x: usize, y: usize },          //8XY0
+    OrVxVy { x: usize, y: usize },          //8XY1
+    AndVxVy { x: usize, y: usize },         //8XY2
+    XorVxVy { x: usize, y: usize },         //8XY3
+    AddVxVy { x: usize, y: usize },         //8XY4
+    SubVxVy { x: usize, y: usize },         //8XY5
+    ShrVx { x: usize, y: usize },           //8XY6
+    SubnVxVy { x: usize, y: usize },        //8XY7
+    ShlVx { x: usize, y: usize },           //8XYE
+    SneVxVy { x: usize, y: usize },         //9XY0
+    LdI { nnn: u16 },                       //ANNN
+    JpV0 { nnn: u16 },                      //BNNN
+    Rnd { x: usize, kk: u8 },               //CXKK
+    Drw { x: usize, y: usize, n: usize },   //DXYN
+    Skp { x: usize },                       //EX9E
+    Sknp { x: usize },                      //EXA1
+    LdVxDt { x: usize },                    //FX07
+    LdVxK { x: usize },                     //FX0A
+    LdDtVx { x: usize },                    //FX15
+    LdStVx { x: usize },                    //FX18
+    AddIVx { x: usize },                    //FX1E
+    LdFVx { x: usize },                     //FX29
+    LdBVx { x: usize },                     //FX33
+    LdIVx { x: usize },                     //FX55
+    LdVxI { x: usize },                     //FX65
+    ScrollDown { n: usize },                //00CN, SUPER-CHIP only
+    ScrollRight,                            //00FB, SUPER-CHIP only
+    ScrollLeft,                             //00FC, SUPER-CHIP only
+    Exit,                                   //00FD, SUPER-CHIP only
+    Low,                                    //00FE, SUPER-CHIP only
+    High,                                   //00FF, SUPER-CHIP only
+    LdHfVx { x: usize },                    //FX30, SUPER-CHIP only
+    LdRVx { x: usize },                     //FX75, SUPER-CHIP only, X up to 7
+    LdVxR { x: usize },                     //FX85, SUPER-CHIP only, X up to 7
+    Unknown(u16),
+}
+
+pub fn decode(opcode: u16) -> Instruction {
+    let x = ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize;
+    let y = ((opcode & THIRD_NIBBLE_MASK) >> 4) as usize;
+    let n = (opcode & FOURTH_NIBBLE_MASK) as usize;
+    let kk = (opcode & LAST_TWO_MASK) as u8;
+    let nnn = opcode & LAST_THREE_MASK;
+
+    match opcode & FIRST_NIBBLE_MASK {
+        0x0000 => match n {
+            0x0 => Instruction::Cls,
+            0xE => Instruction::Ret,
+            _ => Instruction::Sys { nnn },
+        },
+        0x1000 => Instruction::Jp { nnn },
+        0x2000 => Instruction::Call { nnn },
+        0x3000 => Instruction::SeVxByte { x, kk },
+        0x4000 => Instruction::SneVxByte { x, kk },
+        0x5000 => Instruction::SeVxVy { x, y },
+        0x6000 => Instruction::LdVxByte { x, kk },
+        0x7000 => Instruction::AddVxByte { x, kk },
+        0x8000 => match n {
+            0x0 => Instruction::LdVxVy { x, y },
+            0x1 => Instruction::OrVxVy { x, y },
+            0x2 => Instruction::AndVxVy { x, y },
+            0x3 => Instruction::XorVxVy { x, y },
+            0x4 => Instruction::AddVxVy { x, y },
+            0x5 => Instruction::SubVxVy { x, y },
+            0x6 => Instruction::ShrVx { x, y },
+            0x7 => Instruction::SubnVxVy { x, y },
+            0xE => Instruction::ShlVx { x, y },
+            _ => Instruction::Unknown(opcode),
+        },
+        0x9000 => Instruction::SneVxVy { x, y },
+        0xA000 => Instruction::LdI { nnn },
+        0xB000 => Instruction::JpV0 { nnn },
+        0xC000 => Instruction::Rnd { x, kk },
+        0xD000 => Instruction::Drw { x, y, n },
+        0xE000 => match kk {
+            0x9E => Instruction::Skp { x },
+            0xA1 => Instruction::Sknp { x },
+            _ => Instruction::Unknown(opcode),
+        },
+        _ => match kk {
+            0x07 => Instruction::LdVxDt { x },
+            0x0A => Instruction::LdVxK { x },
+            0x15 => Instruction::LdDtVx { x },
+            0x18 => Instruction::LdStVx { x },
+            0x1E => Instruction::AddIVx { x },
+            0x29 => Instruction::LdFVx { x },
+            0x33 => Instruction::LdBVx { x },
+            0x55 => Instruction::LdIVx { x },
+            0x65 => Instruction::LdVxI { x },
+            _ => Instruction::Unknown(opcode),
+        },
+    }
+}
+
+pub fn decode_for(opcode: u16, variant: Variant) -> Instruction {
+    if variant != Variant::Schip {
+        return decode(opcode);
+    }
+    match opcode {
+        0x00C0..=0x00CF => Instruction::ScrollDown { n: (opcode & FOURTH_NIBBLE_MASK) as usize },
+        0x00FB => Instruction::ScrollRight,
+        0x00FC => Instruction::ScrollLeft,
+        0x00FD => Instruction::Exit,
+        0x00FE => Instruction::Low,
+        0x00FF => Instruction::High,
+        _ if opcode & 0xF0FF == 0xF030 => Instruction::LdHfVx { x: ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize },
+        _ if opcode & 0xF8FF == 0xF075 => Instruction::LdRVx { x: ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize },
+        _ if opcode & 0xF8FF == 0xF085 => Instruction::LdVxR { x: ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize },
+        _ => decode(opcode),
+    }
+}
+
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instruction::Cls => write!(f, "CLS"),
+            Instruction::Ret => write!(f, "RET"),
+            Instruction::Sys { nnn } => write!(f, "SYS {:#05X}", nnn),
+            Instruction::Jp { nnn } => write!(f, "JP {:#05X}", nnn),
+            Instruction::Call { nnn } => write!(f, "CALL {:#05X}", nnn),
+            Instruction::SeVxByte { x, kk } => write!(f, "SE V{:X}, {:#04X}", x, kk),
+            Instruction::SneVxByte { x, kk } => write!(f, "SNE V{:X}, {:#04X}", x, kk),
+            Instruction::SeVxVy { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Instruction::LdVxByte { x, kk } => write!(f, "LD V{:X}, {:#04X}", x, kk),
+            Instruction::AddVxByte { x, kk } => write!(f, "ADD V{:X}, {:#04X}", x, kk),
+            Instruction::LdVxVy { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Instruction::OrVxVy { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Instruction::AndVxVy { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Instruction::XorVxVy { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Instruction::AddVxVy { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Instruction::SubVxVy { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Instruction::ShrVx { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Instruction::SubnVxVy { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Instruction::ShlVx { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Instruction::SneVxVy { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Instruction::LdI { nnn } => write!(f, "LD I, {:#05X}", nnn),
+            Instruction::JpV0 { nnn } => write!(f, "JP V0, {:#05X}", nnn),
+            Instruction::Rnd { x, kk } => write!(f, "RND V{:X}, {:#04X}", x, kk),
+            Instruction::Drw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Instruction::Skp { x } => write!(f, "SKP V{:X}", x),
+            Instruction::Sknp { x } => write!(f, "SKNP V{:X}", x),
+            Instruction::LdVxDt { x } => write!(f, "LD V{:X}, DT", x),
+            Instruction::LdVxK { x } => write!(f, "LD V{:X}, K", x),
+            Instruction::LdDtVx { x } => write!(f, "LD DT, V{:X}", x),
+            Instruction::LdStVx { x } => write!(f, "LD ST, V{:X}", x),
+            Instruction::AddIVx { x } => write!(f, "ADD I, V{:X}", x),
+            Instruction::LdFVx { x } => write!(f, "LD F, V{:X}", x),
+            Instruction::LdBVx { x } => write!(f, "LD B, V{:X}", x),
+            Instruction::LdIVx { x } => write!(f, "LD [I], V{:X}", x),
+            Instruction::LdVxI { x } => write!(f, "LD V{:X}, [I]", x),
+            Instruction::ScrollDown { n } => write!(f, "SCD {}", n),
+            Instruction::ScrollRight => write!(f, "SCR"),
+            Instruction::ScrollLeft => write!(f, "SCL"),
+            Instruction::Exit => write!(f, "EXIT"),
+            Instruction::Low => write!(f, "LOW"),
+            Instruction::High => write!(f, "HIGH"),
+            Instruction::LdHfVx { x } => write!(f, "LD HF, V{:X}", x),
+            Instruction::LdRVx { x } => write!(f, "LD R, V{:X}", x),
+            Instruction::LdVxR { x } => write!(f, "LD V{:X}, R", x),
+            Instruction::Unknown(opcode) => write!(f, "DW {:#06X}", opcode),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+/************
+Block translation backend (experimental)
+
+An alternative to step() for programs that need millions of instructions per second, ie XO-CHIP
+demos. The first time the program gets to an address, translate() follows the code from there to
+the end of its basic block and keeps the block, cached by its start address, as the list of its
+opcodes with their handlers (see dispatch.rs). Running the block again skips the fetch, the decode,
+the table lookups and the checks emulate_cycle() makes around every instruction.
+
+A block is the straight run of instructions that can't branch, fault, wait or write memory:
+    00E0, 6XKK, 7XKK, 8XY0-8XY7, 8XYE, ANNN, CXKK, FX07, FX15, FX18, FX1E, FX29
+It ends at the first instruction that can (jumps, calls, returns, skips, draws, memory reads and
+writes, FX0A and anything unknown), which then runs in the interpreter through step(), so faults,
+key waits and extensions behave exactly as they always do.
+
+Blocks are threaded code, not machine code: a code generator (ie Cranelift) could take over
+translate() and keep the cache and the invalidation as they are.
+
+Self-modifying code: translating marks the block's bytes as executed, so the machine counts
+writes into them (code_write_count(), see protect.rs). When that count moves, every block whose
+bytes were written after it was translated is dropped, to be translated again the next time. A
+start address whose block keeps getting rewritten (INVALIDATION_LIMIT times) is left to the
+interpreter from then on.
+
+Anything that needs to see every instruction makes run() use step() all the way: tracing, hooks,
+memory protection and memory-mapped devices. Translated instructions aren't recorded in the
+instruction history. Timers still tick every cycles_per_tick() cycles, so a run ends in the same
+state as it would in the interpreter, cycle for cycle.
+
+    let mut jit = Jit::new();
+    jit.run(&mut chip8, 1_000_000)?;
+************/
+use std::collections::{HashMap, HashSet};
+
+use dispatch::{self, Handler};
+use error::Chip8Error;
+use extension::is_known_opcode;
+use instruction::{decode_for, Instruction};
+use memory::MEMORY_SIZE;
+use protect::Protection;
+use variant::Variant;
+use Chip8;
+
+//How many times a block may be dropped for self-modifying writes before its address is left to the interpreter
+pub const INVALIDATION_LIMIT: u32 = 4;
+//Longest block translated in one go, longer straight runs carry on in the next block
+const MAX_BLOCK_LEN: usize = 64;
+
+//A translated basic block, empty if the instruction at its start can't be translated
+struct Block {
+    ops: Vec<(u16, Handler)>,
+    end: usize,         //First address past the block's bytes
+    translated: u64,    //Cycle count when it was translated
+}
+
+//How the backend has been doing, for benchmarks
+#[derive(Clone, Copy, Debug, Default)]
+pub struct JitStats {
+    pub blocks: usize,              //Blocks in the cache right now
+    pub translations: u64,
+    pub invalidations: u64,         //Blocks dropped because the program wrote over them
+    pub translated_cycles: u64,     //Cycles run from blocks
+    pub interpreted_cycles: u64,    //Cycles run by step()
+}
+
+pub struct Jit {
+    blocks: HashMap<u16, Block>,
+    invalidated: HashMap<u16, u32>,     //How often the block at each address was dropped
+    interpreted: HashSet<u16>,          //Addresses past INVALIDATION_LIMIT
+    code_writes: u64,                   //The machine's code_write_count() when the blocks were last checked
+    stats: JitStats,
+}
+
+impl Default for Jit {
+    fn default() -> Jit {
+        Jit::new()
+    }
+}
+
+impl Jit {
+    pub fn new() -> Jit {
+        Jit {
+            blocks: HashMap::new(),
+            invalidated: HashMap::new(),
+            interpreted: HashSet::new(),
+            code_writes: 0,
+            stats: JitStats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> JitStats {
+        JitStats { blocks: self.blocks.len(), ..self.stats }
+    }
+
+    //Forgets every block, ie after loading another ROM or a save state into the machine
+    pub fn clear(&mut self) {
+        self.blocks.clear();
+        self.invalidated.clear();
+        self.interpreted.clear();
+    }
+
+    //Runs up to max_cycles cycles. Stops early, returning the error, when an interpreted instruction fails
+    //(see error.rs), and when the program waits for a key
+    pub fn run(&mut self, chip8: &mut Chip8, max_cycles: u64) -> Result<(), Chip8Error> {
+        let end = chip8.cycles.saturating_add(max_cycles);
+        while chip8.cycles < end {
+            if chip8.halt_flag {
+                return Ok(());
+            }
+            if !translatable(chip8) || self.interpreted.contains(&chip8.pc) {
+                self.stats.interpreted_cycles += 1;
+                chip8.step()?;
+                continue;
+            }
+            self.check_code_writes(chip8);
+
+            let pc = chip8.pc;
+            let stats = &mut self.stats;
+            let block = self.blocks.entry(pc).or_insert_with(|| {
+                stats.translations += 1;
+                translate(chip8, pc)
+            });
+            if block.ops.is_empty() {
+                self.stats.interpreted_cycles += 1;
+                chip8.step()?;
+                continue;
+            }
+
+            chip8.code_write = None;
+            chip8.observed_key = None;
+            let started = chip8.cycles;
+            for &(opcode, handler) in block.ops.iter().take((end - chip8.cycles) as usize) {
+                handler(chip8, opcode)?;
+                chip8.count_cycle();
+            }
+            self.stats.translated_cycles += chip8.cycles - started;
+        }
+        Ok(())
+    }
+
+    //Drops the blocks the program wrote over since they were translated
+    fn check_code_writes(&mut self, chip8: &Chip8) {
+        if chip8.code_write_count == self.code_writes {
+            return;
+        }
+        self.code_writes = chip8.code_write_count;
+
+        let stale: Vec<u16> = self.blocks.iter()
+            .filter(|&(&start, block)| (start as usize..block.end).any(|addr| chip8.written[addr] > block.translated))
+            .map(|(&start, _)| start)
+            .collect();
+        for start in stale {
+            self.blocks.remove(&start);
+            self.stats.invalidations += 1;
+            let count = self.invalidated.entry(start).or_insert(0);
+            *count += 1;
+            if *count >= INVALIDATION_LIMIT {
+                self.interpreted.insert(start);
+            }
+        }
+    }
+}
+
+//Whether the machine can run translated code at all right now
+fn translatable(chip8: &Chip8) -> bool {
+    !chip8.trace
+        && chip8.fault.is_none()
+        && chip8.hooks.is_empty()
+        && chip8.mmio.is_none()
+        && chip8.protection == Protection::Off
+        && (chip8.pc as usize) + 1 < MEMORY_SIZE
+}
+
+//Whether the opcode can go in the middle of a block
+fn straight_line(opcode: u16, variant: Variant) -> bool {
+    is_known_opcode(opcode, variant) && matches!(decode_for(opcode, variant),
+        Instruction::Cls
+        | Instruction::LdVxByte { .. }
+        | Instruction::AddVxByte { .. }
+        | Instruction::LdVxVy { .. }
+        | Instruction::OrVxVy { .. }
+        | Instruction::AndVxVy { .. }
+        | Instruction::XorVxVy { .. }
+        | Instruction::AddVxVy { .. }
+        | Instruction::SubVxVy { .. }
+        | Instruction::ShrVx { .. }
+        | Instruction::SubnVxVy { .. }
+        | Instruction::ShlVx { .. }
+        | Instruction::LdI { .. }
+        | Instruction::Rnd { .. }
+        | Instruction::LdVxDt { .. }
+        | Instruction::LdDtVx { .. }
+        | Instruction::LdStVx { .. }
+        | Instruction::AddIVx { .. }
+        | Instruction::LdFVx { .. })
+}
+
+//Follows the code from start to the end of its basic block
+fn translate(chip8: &mut Chip8, start: u16) -> Block {
+    let mut ops = Vec::new();
+    let mut addr = start as usize;
+    while addr + 1 < MEMORY_SIZE && ops.len() < MAX_BLOCK_LEN {
+        let opcode = chip8.opcode_at(addr as u16);
+        if !straight_line(opcode, chip8.variant) {
+            break;
+        }
+        //Every straight line opcode has a handler
+        ops.push((opcode, dispatch::handler(opcode, chip8.variant).unwrap()));
+        addr += 2;
+    }
+    for executed in chip8.executed[start as usize..addr].iter_mut() {
+        *executed = true;
+    }
+    Block { ops, end: addr, translated: chip8.cycles }
+}
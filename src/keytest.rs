@@ -0,0 +1,104 @@
+/************
+Keypad tester
+
+Usage: chip8 keytest [--keymap FILE]
+
+A diagnostic screen for checking key bindings before blaming the game. No ROM runs: the window
+shows the 16 CHIP-8 keys, lit while they're held, and the raw code of the last host key event
+in hex, next to the CHIP-8 key it's bound to. Every host key event is printed as well.
+The keymap is the same one the emulator would use with --keymap (see input.rs).
+************/
+use std::process;
+
+use piston_window::*;
+
+use controls::{draw_digit, draw_keypad};
+use exitcode;
+use input::{button_name, InputState, Keymap};
+use options::take_value;
+
+//The last host event: its raw code and the CHIP-8 key it is bound to
+struct LastEvent {
+    code: u32,
+    key: Option<u8>,
+}
+
+fn raw_code(button: Button) -> u32 {
+    match button {
+        Button::Keyboard(key) => key.code() as u32,
+        Button::Controller(pad) => pad.button as u32,
+        Button::Mouse(mouse) => mouse as u32,
+        _ => 0,
+    }
+}
+
+//Entry point for "chip8 keytest ...", with the arguments following "keytest"
+pub fn run(args: &[String]) {
+    let mut args = args.to_vec();
+    let keymap = match take_value(&mut args, "--keymap").and_then(|path| match path {
+        Some(path) => Keymap::load(&path),
+        None => Ok(Keymap::default()),
+    }) {
+        Ok(keymap) => keymap,
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::FAILURE);
+        }
+    };
+    if let Some(arg) = args.first() {
+        println!("Unexpected argument {}. Aborting", arg);
+        process::exit(exitcode::FAILURE);
+    }
+
+    let mut window: PistonWindow = WindowSettings::new("Chip8 keypad tester", [64 * 8, 32 * 8])
+        .exit_on_esc(true)
+        .build()
+        .unwrap();
+
+    let mut input = InputState::new(keymap.clone());
+    let mut last: Option<LastEvent> = None;
+
+    while let Some(e) = window.next() {
+        if let Some(button) = e.button_args() {
+            input.button(button);
+
+            let key = keymap.lookup(button.button);
+            let state = if button.state == ButtonState::Press { "pressed" } else { "released" };
+            match key {
+                Some(key) => println!("{} {} (host code {:#X}) -> CHIP-8 key {:X}",
+                                      button_name(button.button), state, raw_code(button.button), key),
+                None => println!("{} {} (host code {:#X}) -> not bound",
+                                 button_name(button.button), state, raw_code(button.button)),
+            }
+            last = Some(LastEvent { code: raw_code(button.button), key });
+        }
+
+        if e.render_args().is_some() {
+            let mut lit = [false; 16];
+            for (key, lit) in lit.iter_mut().enumerate() {
+                *lit = input.is_down(key as u8);
+            }
+
+            window.draw_2d(&e, |c, g| {
+                clear([0.0, 0.0, 0.0, 1.0], g);
+                let view = c.get_view_size();
+                let cell = (view[1] / 5.0).floor();
+                let pixel = (cell / 8.0).floor().max(1.0);
+                let top = ((view[1] - cell * 4.0) / 2.0).floor();
+                draw_keypad(&lit, [cell / 2.0, top], cell, c, g);
+
+                //Raw code as 8 hex digits, the bound CHIP-8 key underneath
+                if let Some(ref last) = last {
+                    let left = cell * 5.0;
+                    for digit in 0..8 {
+                        let value = (last.code >> ((7 - digit) * 4)) as u8 & 0xF;
+                        draw_digit(value, [left + digit as f64 * pixel * 5.0, top], pixel, [1.0, 1.0, 1.0, 1.0], c, g);
+                    }
+                    if let Some(key) = last.key {
+                        draw_digit(key, [left, top + pixel * 8.0], pixel * 2.0, [1.0, 0.8, 0.0, 1.0], c, g);
+                    }
+                }
+            });
+        }
+    }
+}
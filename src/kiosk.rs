@@ -0,0 +1,114 @@
+/************
+Kiosk mode
+
+For retro display installations: the window runs fullscreen and borderless with the cursor hidden,
+escape no longer quits, and a playlist of ROMs is cycled through on a timer, or as soon as a
+game over is detected (see gameover.rs).
+
+The playlist is a text file with one ROM per line. Blank lines and lines starting with # are ignored.
+A line can also configure how to detect the ROM's game over, ie:
+    roms/pong.ch8
+    roms/tetris.ch8 game-over=0x2F0
+************/
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use options::parse_number;
+
+pub const DEFAULT_INTERVAL: f64 = 300.0; //Seconds each ROM gets before moving on to the next
+
+#[derive(Clone, Debug)]
+pub struct PlaylistEntry {
+    pub rom: String,
+    pub game_over: Option<u16>, //Address of the ROM's game over routine, if known
+}
+
+impl PlaylistEntry {
+    pub fn new(rom: &str, game_over: Option<u16>) -> PlaylistEntry {
+        PlaylistEntry {
+            rom: rom.to_string(),
+            game_over,
+        }
+    }
+
+    //Parses a playlist line, "ROM [game-over=ADDR]"
+    fn parse(line: &str) -> Result<PlaylistEntry, String> {
+        let mut words = line.split_whitespace();
+        let rom = words.next().unwrap_or("");
+        let mut game_over = None;
+
+        for word in words {
+            if let Some(addr) = word.strip_prefix("game-over=") {
+                game_over = Some(parse_number(addr)?);
+            } else {
+                return Err(format!("Unknown playlist setting {}", word));
+            }
+        }
+
+        Ok(PlaylistEntry::new(rom, game_over))
+    }
+}
+
+pub struct Kiosk {
+    playlist: Vec<PlaylistEntry>,
+    current: usize,
+    interval: f64,
+    elapsed: f64,      //Seconds the current ROM has been running
+}
+
+impl Kiosk {
+    pub fn new(playlist: Vec<PlaylistEntry>, interval: f64) -> Kiosk {
+        Kiosk {
+            playlist,
+            current: 0,
+            interval,
+            elapsed: 0.0,
+        }
+    }
+
+    pub fn playlist_len(&self) -> usize {
+        self.playlist.len()
+    }
+
+    pub fn current(&self) -> &PlaylistEntry {
+        &self.playlist[self.current]
+    }
+
+    //Moves on to the next ROM in the playlist, wrapping around at the end
+    pub fn advance(&mut self) -> &PlaylistEntry {
+        self.current = (self.current + 1) % self.playlist.len();
+        self.elapsed = 0.0;
+        self.current()
+    }
+
+    //Counts up the time the current ROM has been running
+    //Returns the next ROM to load once the current one has used up its time
+    pub fn update(&mut self, dt: f64) -> Option<&PlaylistEntry> {
+        self.elapsed += dt;
+        if self.elapsed >= self.interval {
+            Some(self.advance())
+        } else {
+            None
+        }
+    }
+}
+
+//Reads a playlist file into a list of ROMs
+pub fn load_playlist(path: &str) -> Result<Vec<PlaylistEntry>, String> {
+    let file = File::open(path).map_err(|err| format!("Could not open playlist {}: {}", path, err))?;
+    let mut roms = Vec::new();
+
+    for (number, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.map_err(|err| format!("Could not read playlist {}: {}", path, err))?;
+        let line = line.trim();
+        if !line.is_empty() && !line.starts_with('#') {
+            let entry = PlaylistEntry::parse(line).map_err(|err| format!("{}:{}: {}", path, number + 1, err))?;
+            roms.push(entry);
+        }
+    }
+
+    if roms.is_empty() {
+        return Err(format!("Playlist {} is empty", path));
+    }
+    Ok(roms)
+}
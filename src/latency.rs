@@ -0,0 +1,81 @@
+/************
+Input latency measurement
+
+A diagnostic mode (--latency) for tuning how responsive the frontend is. Every key press coming
+from the host is timestamped, both in wall clock time and in emulated cycles. The first time the
+program then tests that key with EX9E/EXA1 and finds it held, the delay is printed, in emulated
+frames (60Hz timer ticks), cycles and milliseconds.
+
+The last few measurements are also drawn as an overlay of bars in the bottom left corner, one bar
+per key press and one block per frame of latency: green up to 1 frame, yellow up to 3, red above.
+Keys waited for with FX0A don't show up, the wait ends right in the key event.
+************/
+use std::time::Instant;
+
+use piston_window::*;
+
+use {Chip8, CYCLES_PER_TIMER_TICK};
+
+//How many measurements the overlay shows
+const SHOWN: usize = 16;
+const BLOCK: f64 = 6.0;
+
+pub struct LatencyMeter {
+    pending: [Option<(u64, Instant)>; 16],  //Cycle and time each key was pressed at, until it's observed
+    frames: Vec<u64>,                       //Recent measurements, oldest first
+}
+
+impl LatencyMeter {
+    pub fn new() -> LatencyMeter {
+        LatencyMeter {
+            pending: [None; 16],
+            frames: Vec::new(),
+        }
+    }
+
+    //Should be called for every key event from the host
+    pub fn key_event(&mut self, key: u8, state: u8, cycle: u64) {
+        //A key released before the program looked at it was never observed
+        self.pending[key as usize] = if state == 1 { Some((cycle, Instant::now())) } else { None };
+    }
+
+    //Should be called after every cycle
+    pub fn update(&mut self, chip8: &Chip8) {
+        let key = match chip8.observed_key() {
+            Some(key) => key,
+            None => return,
+        };
+        let (cycle, time) = match self.pending[key as usize].take() {
+            Some(pressed) => pressed,
+            None => return,
+        };
+
+        //Saturating, a save state or the next kiosk game may have moved the cycle count back
+        let cycles = chip8.cycles().saturating_sub(cycle);
+        let frames = cycles / CYCLES_PER_TIMER_TICK;
+        println!("Latency: key {:X} observed after {} frames ({} cycles, {:.1} ms)",
+                 key, frames, cycles, time.elapsed().as_secs_f64() * 1000.0);
+
+        self.frames.push(frames);
+        if self.frames.len() > SHOWN {
+            self.frames.remove(0);
+        }
+    }
+
+    pub fn draw(&self, window: &mut PistonWindow, event: &Event) {
+        window.draw_2d(event, |c, g| {
+            let bottom = c.get_view_size()[1] - BLOCK;
+            for (n, frames) in self.frames.iter().enumerate() {
+                let color = match *frames {
+                    0..=1 => [0.0, 1.0, 0.0, 0.8],
+                    2..=3 => [1.0, 1.0, 0.0, 0.8],
+                    _ => [1.0, 0.0, 0.0, 0.8],
+                };
+                //A press observed within the same frame still gets a sliver, so it can be seen
+                let height = (*frames as f64 * BLOCK).max(2.0);
+                let left = BLOCK + n as f64 * BLOCK * 1.5;
+                rectangle(color, [left, bottom - height, BLOCK, height], c.transform, g);
+            }
+        });
+    }
+}
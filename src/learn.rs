@@ -0,0 +1,176 @@
+/************
+Tutorial lessons
+
+Usage: chip8 learn                   List the lessons
+       chip8 learn <lesson> [options]  Run a lesson (by number or name)
+
+A handful of tiny teaching ROMs, built into the emulator, each showing one thing a CHIP-8 program
+does. A lesson runs in the window like any ROM (the ROM is written to the temp directory, so it can
+be opened in the debugger too), with narration: the lesson's introduction is printed when it
+starts, and every instruction explains itself on the terminal the first time it runs. Each lesson
+ends with an exercise, printed once every instruction has been explained.
+
+Every instruction is listed in the lesson, so the narration can follow along. Lessons are picked
+with --lesson when running the written ROM directly.
+************/
+use std::collections::BTreeSet;
+use std::env;
+use std::fs;
+
+use benchrom::assemble;
+use romtag::RomTag;
+use Chip8;
+
+pub struct Lesson {
+    pub name: &'static str,
+    title: &'static str,
+    intro: &'static str,
+    //Opcodes from 0x200 on, with what each does. Data words have no note
+    code: &'static [(u16, &'static str)],
+    exercise: &'static str,
+}
+
+pub const LESSONS: [Lesson; 3] = [
+    Lesson {
+        name: "sprite",
+        title: "Draw a sprite",
+        intro: "Sprites are 8 pixels wide and 1 to 15 rows tall, one byte per row, stored in memory. DXYN \
+                draws N rows from where I points, at the position held in VX and VY.",
+        code: &[
+            (0x00E0, "00E0 clears the screen"),
+            (0xA20C, "ANNN points I at address NNN, here the sprite data at 0x20C"),
+            (0x601C, "6XNN puts NN in VX: V0 = 28 is the X position"),
+            (0x610C, "V1 = 12 is the Y position"),
+            (0xD015, "DXYN draws N = 5 rows from I at (V0, V1). Pixels are XORed onto the screen"),
+            (0x120A, "1NNN jumps to NNN. Jumping to itself keeps the program here, with the sprite on screen"),
+            (0x2070, ""),
+            (0xF870, ""),
+            (0x2000, ""),
+        ],
+        exercise: "Move the diamond to the top left corner, by changing the 6XNN instructions: \
+                   chip8 learn sprite --poke 0x205=0,0x207=0",
+    },
+    Lesson {
+        name: "keys",
+        title: "Read the keypad",
+        intro: "The keypad has 16 keys, 0 to F (1234, QWER, ASDF, ZXCV on the keyboard). FX0A waits for a key \
+                press, and the font has a sprite for every hex digit.",
+        code: &[
+            (0x611E, "V1 = 30 is the X position of the digit"),
+            (0x620D, "V2 = 13 is the Y position"),
+            (0xF00A, "FX0A stops until a key is pressed and puts the key in VX, press one!"),
+            (0x00E0, "Clear the last digit away"),
+            (0xF029, "FX29 points I at the font sprite for the digit in VX"),
+            (0xD125, "Draw the 5 rows of the digit at (V1, V2)"),
+            (0x1204, "Jump back to wait for the next key"),
+        ],
+        exercise: "Show the digit in the top left corner instead: chip8 learn keys --poke 0x201=0,0x203=0",
+    },
+    Lesson {
+        name: "timer",
+        title: "Use the delay timer",
+        intro: "The delay timer counts down by one 60 times a second until it reaches 0. Setting it and waiting \
+                for 0 is how programs keep time.",
+        code: &[
+            (0xA214, "I points at a sprite of a single pixel, at 0x214"),
+            (0x601F, "V0 = 31, the X position"),
+            (0x610F, "V1 = 15, the Y position"),
+            (0xD011, "Draw 1 row: since drawing XORs, every second draw takes the dot away again"),
+            (0x623C, "V2 = 60, one second worth of timer ticks"),
+            (0xF215, "FX15 sets the delay timer to VX"),
+            (0xF307, "FX07 reads the delay timer into VX"),
+            (0x3300, "3XNN skips the next instruction if VX == NN, so once the timer hits 0..."),
+            (0x120C, "...this jump, back to reading the timer, is skipped"),
+            (0x1206, "Jump back to the draw, toggling the dot once a second"),
+            (0x8000, ""),
+        ],
+        exercise: "Make it blink twice as fast, with 30 ticks in V2: chip8 learn timer --poke 0x209=30",
+    },
+];
+
+fn find(name: &str) -> Option<&'static Lesson> {
+    match name.parse::<usize>() {
+        Ok(number) if number >= 1 => LESSONS.get(number - 1),
+        _ => LESSONS.iter().find(|lesson| lesson.name == name),
+    }
+}
+
+pub fn parse(name: &str) -> Result<&'static Lesson, String> {
+    find(name).ok_or_else(|| {
+        let names: Vec<&str> = LESSONS.iter().map(|lesson| lesson.name).collect();
+        format!("Unknown lesson {} (available: {})", name, names.join(", "))
+    })
+}
+
+impl Lesson {
+    pub fn rom(&self) -> Vec<u8> {
+        let opcodes: Vec<u16> = self.code.iter().map(|(opcode, _)| *opcode).collect();
+        let mut rom = assemble(&opcodes);
+        let tag = RomTag {
+            title: Some(format!("Lesson: {}", self.title)),
+            author: None,
+            profile: Some(String::from("chip8")),
+        };
+        //Nothing over 64K of text here
+        tag.append_to(&mut rom).unwrap();
+        rom
+    }
+
+    fn introduce(&self) {
+        println!("== {} ==", self.title);
+        println!("{}", self.intro);
+    }
+}
+
+//Explains every instruction of a lesson the first time it runs
+pub struct Narrator {
+    lesson: &'static Lesson,
+    told: BTreeSet<u16>,
+}
+
+impl Narrator {
+    pub fn new(lesson: &'static Lesson) -> Narrator {
+        lesson.introduce();
+        Narrator { lesson, told: BTreeSet::new() }
+    }
+
+    //Called before every cycle
+    pub fn update(&mut self, chip8: &Chip8) {
+        let pc = chip8.pc();
+        let index = (pc as usize).wrapping_sub(0x200) / 2;
+        let note = match self.lesson.code.get(index) {
+            Some((_, note)) if pc.is_multiple_of(2) && !note.is_empty() => note,
+            _ => return,
+        };
+        if self.told.insert(pc) {
+            println!("[{:#05X}] {}", pc, note);
+            if self.told.len() == self.lesson.code.iter().filter(|(_, note)| !note.is_empty()).count() {
+                println!("Exercise: {}", self.lesson.exercise);
+            }
+        }
+    }
+}
+
+//Entry point for "chip8 learn ...": lists the lessons, or turns "chip8 learn <lesson> [options]"
+//into the command line running it. None if there's nothing to run
+pub fn command_line(args: &[String]) -> Result<Option<Vec<String>>, String> {
+    let name = match args.first() {
+        Some(name) => name,
+        None => {
+            println!("Lessons (chip8 learn <number or name>):");
+            for (number, lesson) in LESSONS.iter().enumerate() {
+                println!("  {}. {:8} {}", number + 1, lesson.name, lesson.title);
+            }
+            return Ok(None);
+        },
+    };
+    let lesson = parse(name)?;
+
+    let path = env::temp_dir().join(format!("chip8-lesson-{}.ch8", lesson.name));
+    fs::write(&path, lesson.rom()).map_err(|err| format!("Could not write {}: {}", path.display(), err))?;
+    println!("Lesson ROM written to {}", path.display());
+
+    let mut command_line = vec![path.display().to_string(), String::from("--lesson"), lesson.name.to_string()];
+    command_line.extend(args[1..].iter().cloned());
+    Ok(Some(command_line))
+}
@@ -0,0 +1,1260 @@
+/************
+CHIP8 Memory Map:
+0x000-0x1FF - Chip 8 interpreter (contains font set in emu)
+0x050-0x09F - Used for the built in 4x5 pixel font set (0-F), FX29 points into it (moved with --font-addr)
+0x0A0-0x13F - SUPER-CHIP only: the big 8x10 font (0-F) right after it, FX30 points into it
+0x200-0xFFF - Program ROM and work RAM (ROMs for the ETI-660 start at 0x600, see --load-addr)
+
+V Regs are 1 byte long (u8)
+Opcodes are 2 bytes long (u16). This means we must combine 2 1-byte numbers in memory into a single 2-byte number
+This is done by rotating the leading number (the big end) by 8 bits. This will create a 2-byte number with 1 byte of zeros at the little end
+We then bitwise OR our 2 byte number and our 1-byte number that we want to combine. All of the 1s in the 1-byte number are kept in the final result
+
+Rust does not allow Hex literals in code, so most hex will be converted to decimal before being entered.
+Their decimal equivalence and purpose should be noted in the comments or via constants
+
+To extract nibbles as individual numbers, we mask the nibble and then rotate that nibble to the right until it is in the "1"s place
+************/
+
+/************
+The std feature
+
+On by default. Without it (default-features = false) the library is #![no_std] and only the
+interpreter is left, for microcontrollers: the Chip8 struct is fixed arrays and doesn't allocate,
+ROMs are loaded with load_rom_bytes() from a slice (ie include_bytes!), and the frontend reads
+screen() and calls press_key(), step() and update_timers() itself. Everything that needs an
+allocator, files, a clock or the window goes away with std:
+
+    loading ROMs from files, the load address and segments (they report errors as Strings), the builder
+    tracing and the print_* debugging output, the instruction history and diagnostics
+    the grow stack mode (a full stack always faults), extensions, hooks and memory-mapped devices
+    parsing settings from text, palettes, machine and save states, frames, agents and the frontend traits
+    seeding CXNN randomly, a no_std machine starts from seed 0 until set_seed() is called
+
+The window feature (also on by default) is only there for the binary, which needs piston_window (and png, rhai).
+The library never uses piston, so frontends that bring their own window (ffi, wasm) depend on it
+with default-features = false, plus features = ["std"] if they want the rest of the library.
+
+The serde feature (off by default, it turns on std) makes Chip8 Serialize and Deserialize, see state.rs.
+************/
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "std")]
+extern crate core;
+extern crate rand;
+#[cfg(feature = "serde")]
+extern crate serde;
+
+#[cfg(feature = "std")]
+use std::fs::File;
+#[cfg(feature = "std")]
+use std::io::Read;
+#[cfg(feature = "std")]
+use std::ops::Range;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
+
+//Prints trace output for the given Chip8, only when its tracing is turned on
+#[cfg(feature = "std")]
+macro_rules! trace {
+    ($chip8:expr, $($arg:tt)*) => {
+        if $chip8.trace {
+            print!($($arg)*);
+        }
+    };
+}
+
+#[cfg(feature = "std")]
+macro_rules! traceln {
+    ($chip8:expr, $($arg:tt)*) => {
+        if $chip8.trace {
+            println!($($arg)*);
+        }
+    };
+}
+
+//Nowhere to print to without std, the arguments are still checked
+#[cfg(not(feature = "std"))]
+macro_rules! trace {
+    ($chip8:expr, $($arg:tt)*) => {
+        if $chip8.trace {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+#[cfg(not(feature = "std"))]
+macro_rules! traceln {
+    ($chip8:expr, $($arg:tt)*) => {
+        trace!($chip8, $($arg)*)
+    };
+}
+
+//The text of a warning or trace event, which needs an allocator, so without std it's never made
+#[cfg(feature = "std")]
+macro_rules! message {
+    ($($arg:tt)*) => {
+        format!($($arg)*)
+    };
+}
+
+#[cfg(not(feature = "std"))]
+macro_rules! message {
+    ($($arg:tt)*) => {
+        { let _ = format_args!($($arg)*); }
+    };
+}
+
+#[cfg(feature = "std")]
+pub mod agent;
+#[cfg(feature = "std")]
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod builder;
+#[cfg(feature = "std")]
+pub mod diagnostics;
+pub mod dispatch;
+pub mod display;
+pub mod error;
+#[cfg(feature = "std")]
+pub mod extension;
+pub mod framebuffer;
+#[cfg(feature = "std")]
+pub mod frames;
+#[cfg(feature = "std")]
+pub mod frontend;
+#[cfg(feature = "std")]
+pub mod history;
+#[cfg(feature = "std")]
+pub mod hooks;
+pub mod instruction;
+#[cfg(feature = "std")]
+pub mod jit;
+pub mod memory;
+#[cfg(feature = "std")]
+pub mod mmio;
+#[cfg(feature = "std")]
+pub mod palette;
+pub mod protect;
+pub mod quirks;
+#[cfg(feature = "std")]
+pub mod savestate;
+#[cfg(feature = "std")]
+pub mod snapshot;
+pub mod sprite;
+pub mod stack;
+#[cfg(feature = "std")]
+pub mod state;
+pub mod variant;
+#[cfg(feature = "std")]
+use diagnostics::Diagnostics;
+use display::{HIRES_HEIGHT, HIRES_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+use error::Chip8Error;
+#[cfg(feature = "std")]
+use extension::{is_known_opcode, OpcodeExtension, OpcodeHandler};
+use framebuffer::{ColorDepth, DrawRect, Framebuffer};
+#[cfg(feature = "std")]
+use frames::Frames;
+#[cfg(feature = "std")]
+use history::{History, HistoryEntry, DEFAULT_HISTORY_SIZE};
+#[cfg(feature = "std")]
+use hooks::Hooks;
+use instruction::decode_for;
+use memory::{AddressMode, Memory, MEMORY_SIZE};
+#[cfg(feature = "std")]
+use mmio::MmioBus;
+use protect::{CodeWrite, FaultKind, MemoryFault, Protection, INTERPRETER_END};
+use quirks::Quirks;
+use sprite::{ClipCollision, DrawCheck, MAX_SPRITE_HEIGHT};
+use stack::{StackMode, STACK_SIZE};
+use variant::Variant;
+
+const FIRST_NIBBLE_MASK: u16 = 0xF000;  //Grabs first nibble only
+const SECOND_NIBBLE_MASK: u16 = 0x0F00; //Grabs second nibble only
+const THIRD_NIBBLE_MASK: u16 = 0x00F0;
+const FOURTH_NIBBLE_MASK: u16 = 0x000F;
+
+const LAST_TWO_MASK: u16 = 0x00FF;      //Grabs the last two nibbles
+const LAST_THREE_MASK: u16 = 0x0FFF;    //Grabs last three nibbles only
+
+pub const PROGRAM_START: u16 = 0x200;         //Where programs are loaded and start unless --load-addr says otherwise
+pub const FONT_ADDR: u16 = 0x50;              //Where the font sprites go unless --font-addr says otherwise
+pub const CYCLES_PER_SECOND: f64 = 600.0;  //How many opcodes we execute per second
+pub const TIMER_HZ: f64 = 60.0;            //Delay and sound timers always count down at 60Hz
+pub const CYCLES_PER_TIMER_TICK: u64 = (CYCLES_PER_SECOND / TIMER_HZ) as u64;
+pub const RPL_FLAGS: usize = 8;            //SUPER-CHIP's HP48 user flags FX75/FX85 store V0 to V7 in
+
+//Hex digit sprites 0-F, 4 x 5 pixels each
+pub const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+//SUPER-CHIP's big hex digit sprites 0-F, 8 x 10 pixels each, for FX30. SUPER-CHIP 1.1 only had 0-9,
+//A-F are the ones Octo uses
+pub const BIG_FONT: [u8; 160] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xE0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+/************
+Threading model
+
+Chip8 is Send + Sync. It owns all of its state: no Rc, no RefCell or Cell, no globals, and the
+only trait objects it holds (extensions, hooks, memory-mapped devices) must be Send + Sync themselves.
+Since there's no interior mutability, every method that changes the machine takes &mut self,
+so the borrow checker enforces the contract:
+    a Chip8 can be moved to another thread and run there (test-suite runs one per worker)
+    &Chip8 can be shared between threads for reading (registers, screen, memory)
+    to run a machine on one thread and look at it from another, put it behind a Mutex
+
+The window isn't part of the core, this library doesn't depend on piston at all. Frontends draw
+from framebuffer() (the binary's draw_screen), and piston's window has to stay on the main thread.
+The binary runs its machine on a separate thread, behind a Mutex (see emulation.rs in the binary).
+************/
+pub struct Chip8 {
+    memory: Memory,     //General purpose memory, bounds checked (see memory.rs)
+    v: [u8; 16],        //General purpose registers. Register 16 is the "carry flag"
+
+    i: u16,             //Index register
+    pc: u16,            //Program counter (instruction pointer)
+
+    screen: [u8; HIRES_WIDTH * HIRES_HEIGHT], //Array for storing screen pixels. Screen is 64 x 32 pixels, 128 x 64 in hi-res mode
+    last_frame: [u8; HIRES_WIDTH * HIRES_HEIGHT],  //The screen at the end of the last 60Hz frame, for deflickering
+    hires: bool,        //SUPER-CHIP's 128 x 64 mode (00FF), only the first width x height bytes of the arrays are used
+    exited: bool,       //The program ran SUPER-CHIP's exit (00FD)
+    rpl: [u8; RPL_FLAGS],   //SUPER-CHIP's flags (FX75/FX85), which the HP48 kept after the program ended
+    rpl_written: bool,      //FX75 ran since the frontend last asked
+    draw_flag: bool,
+    last_draw: Option<DrawRect>,    //Where the last DXYN drew
+    last_collision: bool,           //Whether it turned off a lit pixel
+
+    halt_flag: bool,
+    halt_reg: u8,
+
+    delay_timer: u8,    //Counts down at 60Hz speed to zero
+    sound_timer: u8,    //Same as above, system buzzer sounds when it reaches zero
+
+    stack: [u16; STACK_SIZE],   //Stack for program execution. Use to return to calling program after called program is finished
+    sp: u16,            //Stack pointer, the number of entries on the stack
+    stack_mode: StackMode,      //What happens when the stack runs full (see stack.rs)
+    draw_check: DrawCheck,      //What happens to sprites that can't be drawn (see sprite.rs)
+    sprite_limit: usize,
+    clip_collision: ClipCollision,  //Whether pixels clipped off the screen count towards VF
+    quirks: Quirks,             //Which interpreter's take on the ambiguous instructions to follow (see quirks.rs)
+    #[cfg(feature = "std")]
+    deep_stack: Vec<u16>,       //Entries past the end of the stack, in grow mode
+
+    key: [u8; 16],     //Hex based keypad
+
+    #[cfg(feature = "std")]
+    history: History,  //Ring buffer of recently executed instructions (for debugging)
+    pub trace: bool,   //Print every executed opcode
+
+    #[cfg(feature = "std")]
+    created: Instant,  //Host time trace events are timestamped from
+
+    rng: SmallRng,     //Source for CXNN. Seeded, so runs can be reproduced
+    seed: u64,
+    cycles: u64,       //Cycles run with step() so far
+    cycles_per_tick: u64,  //Cycles between timer ticks, the clock rate over TIMER_HZ
+
+    protection: Protection,      //What to do about suspicious memory accesses (see protect.rs)
+    initialized: [bool; 4096],   //Which memory locations have been loaded or written
+    fault: Option<MemoryFault>,  //Set when protection stopped the program
+
+    executed: [bool; 4096],          //Which memory locations have been fetched as an opcode
+    written: [u64; 4096],            //Cycle count after the program last wrote each location, 0 if it never did
+    code_write: Option<CodeWrite>,   //First self-modifying write made by the last instruction
+    code_write_count: u64,
+
+    observed_key: Option<u8>,        //Key seen held by an EX9E/EXA1 test during the last instruction
+
+    #[cfg(feature = "std")]
+    mmio: Option<MmioBus>,           //Pseudo-peripherals mapped over memory, off by default (see mmio.rs)
+    #[cfg(feature = "std")]
+    extensions: Vec<Box<dyn OpcodeExtension>>,  //Handlers for opcodes the core doesn't know (see extension.rs)
+    #[cfg(feature = "std")]
+    hooks: Vec<Box<dyn Hooks>>,     //Called around every step (see hooks.rs)
+
+    variant: Variant,  //Which flavour of CHIP-8 this is (see variant.rs)
+    load_addr: u16,    //Where the ROM was loaded and the program starts
+    font_addr: u16,    //Where the font sprites start, FX29 points into them
+    #[cfg(feature = "std")]
+    diagnostics: Diagnostics,   //Warnings the program caused, each printed once (see diagnostics.rs)
+}
+
+//Fails to compile if something that isn't thread safe sneaks into Chip8
+fn _assert_chip8_is_send_sync() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Chip8>();
+}
+
+impl Default for Chip8 {
+    fn default() -> Chip8 {
+        Chip8::new()
+    }
+}
+
+impl Chip8 {
+    pub fn new() -> Chip8 {
+        let mut chip8 = Chip8 {
+            memory: Memory::new(), //Initialize our memory
+            v: [0; 16],        //Zero out our registers
+            i: 0,
+            pc: PROGRAM_START, //program counter starts at 0x200 (system data comes before)
+            screen: [0; HIRES_WIDTH * HIRES_HEIGHT],
+            last_frame: [0; HIRES_WIDTH * HIRES_HEIGHT],
+            hires: false,
+            exited: false,
+            rpl: [0; RPL_FLAGS],
+            rpl_written: false,
+            draw_flag: false,
+            last_draw: None,
+            last_collision: false,
+            halt_flag: false,
+            halt_reg: 0,
+            delay_timer: 0,
+            sound_timer: 0,
+            stack: [0; STACK_SIZE],
+            sp: 0,
+            stack_mode: StackMode::Strict,
+            draw_check: DrawCheck::Warn,
+            sprite_limit: MAX_SPRITE_HEIGHT,
+            clip_collision: ClipCollision::None,
+            quirks: Quirks::DEFAULT,
+            #[cfg(feature = "std")]
+            deep_stack: Vec::new(),
+            key: [0; 16],
+            #[cfg(feature = "std")]
+            history: History::new(DEFAULT_HISTORY_SIZE),
+            trace: true,
+            #[cfg(feature = "std")]
+            created: Instant::now(),
+            rng: SmallRng::from_seed([0; 16]),
+            seed: 0,
+            cycles: 0,
+            cycles_per_tick: CYCLES_PER_TIMER_TICK,
+            protection: Protection::Off,
+            initialized: [false; 4096],
+            fault: None,
+            executed: [false; 4096],
+            written: [0; 4096],
+            code_write: None,
+            code_write_count: 0,
+            observed_key: None,
+            #[cfg(feature = "std")]
+            mmio: None,
+            #[cfg(feature = "std")]
+            extensions: Vec::new(),
+            #[cfg(feature = "std")]
+            hooks: Vec::new(),
+            variant: Variant::Chip8,
+            load_addr: PROGRAM_START,
+            font_addr: FONT_ADDR,
+            #[cfg(feature = "std")]
+            diagnostics: Diagnostics::new(),
+        };
+        #[cfg(feature = "std")]
+        let seed = rand::random::<u64>();
+        #[cfg(not(feature = "std"))]
+        let seed = 0;
+        chip8.set_seed(seed);
+        chip8
+    }
+
+    //Restarts the random number generator used by CXNN from the given seed
+    pub fn set_seed(&mut self, seed: u64) {
+        let mut bytes = [0; 16];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = (seed >> ((i % 8) * 8)) as u8;
+        }
+        //The generator can't start from all zeros, so always set a bit
+        bytes[15] |= 1;
+
+        self.rng = SmallRng::from_seed(bytes);
+        self.seed = seed;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    //How many instructions step() runs per second of machine time, CYCLES_PER_SECOND unless changed.
+    //The timers tick every clock_hz / TIMER_HZ cycles, so the rate is rounded to a multiple of 60Hz
+    pub fn set_clock_hz(&mut self, hz: u32) {
+        self.cycles_per_tick = ((hz as f64 / TIMER_HZ + 0.5) as u64).max(1);
+    }
+
+    pub fn clock_hz(&self) -> u32 {
+        (self.cycles_per_tick * TIMER_HZ as u64) as u32
+    }
+
+    //Cycles in one 60Hz frame, for frontends running the machine a frame at a time
+    pub fn cycles_per_tick(&self) -> u64 {
+        self.cycles_per_tick
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    //Switches to another flavour of CHIP-8 (see variant.rs). Should be done before the ROM runs: it
+    //goes back to the 64 x 32 screen, clears it, and loads (or removes) the SUPER-CHIP big font
+    pub fn set_variant(&mut self, variant: Variant) {
+        self.unload_font();
+        self.variant = variant;
+        self.load_font();
+        self.hires = false;
+        self.exited = false;
+        self.clear_screenbuf();
+    }
+
+    pub fn initialize(&mut self) {
+        //Load up our font into reserved system memory
+        self.load_font();
+    }
+
+    //Increments the program counter to pull the next opcode
+    fn next_instruction(&mut self) {
+        self.pc += 2;
+    }
+
+    //Loads the font sprites into the interpreter area, at the font address (0x050 unless moved),
+    //followed by the big font for SUPER-CHIP
+    pub fn load_font(&mut self) {
+        let start = self.font_addr as usize;
+        let big_font: &[u8] = if self.variant == Variant::Schip { &BIG_FONT } else { &[] };
+        for (i, byte) in FONT.iter().chain(big_font).enumerate() {
+            //set_font_addr() keeps the fonts inside memory
+            self.memory.write8(start + i, *byte).unwrap_or(());
+            self.initialized[start + i] = true;
+        }
+    }
+
+    //How many bytes of the interpreter area the fonts take up
+    fn font_size(&self) -> usize {
+        if self.variant == Variant::Schip { FONT.len() + BIG_FONT.len() } else { FONT.len() }
+    }
+
+    //Clears the fonts out of memory again, before they move
+    fn unload_font(&mut self) {
+        let old = self.font_addr as usize;
+        for i in old..old + self.font_size() {
+            self.memory.write8(i, 0).unwrap_or(());
+            self.initialized[i] = false;
+        }
+    }
+
+    //Where FX30 points, the big font after the normal one
+    pub fn big_font_addr(&self) -> u16 {
+        self.font_addr + FONT.len() as u16
+    }
+
+    //Moves the font sprites (and where FX29 points) to addr. Interpreters differ, 0x050 is the most
+    //common but some put the font at 0x000. The font has to stay inside the interpreter area
+    #[cfg(feature = "std")]
+    pub fn set_font_addr(&mut self, addr: u16) -> Result<(), Chip8Error> {
+        if addr as usize + self.font_size() > PROGRAM_START as usize {
+            return Err(Chip8Error::InvalidFontAddr { addr });
+        }
+        self.unload_font();
+        self.font_addr = addr;
+        self.load_font();
+        Ok(())
+    }
+
+    pub fn font_addr(&self) -> u16 {
+        self.font_addr
+    }
+
+    pub fn set_key(&mut self, key: u8, value: u8) {
+        self.key[key as usize] = value;
+        traceln!(self, "key {} set to {}", key, value);
+    }
+
+    //Sets a key's state and, if execution is waiting on a key press (FX0A), stores the key and resumes
+    pub fn handle_key(&mut self, key: u8, value: u8) {
+        self.trace_event(|| message!("key {:X} {}", key, if value == 1 { "down" } else { "up" }));
+        self.set_key(key, value);
+        #[cfg(feature = "std")]
+        self.run_hooks(|hooks, chip8| hooks.on_key(chip8, key, value != 0));
+        if self.halt_flag {
+            self.v[self.halt_reg as usize] = key;
+            self.halt_flag = false;
+        }
+    }
+
+    //handle_key for frontends that think of keys as pressed or released
+    pub fn press_key(&mut self, key: u8, pressed: bool) {
+        self.handle_key(key, pressed as u8);
+    }
+
+    //Where ROMs get loaded and the program starts, 0x200 normally. Some historical machines load
+    //elsewhere (the ETI-660 at 0x600). Also moves the program counter there
+    #[cfg(feature = "std")]
+    pub fn set_load_addr(&mut self, addr: u16) -> Result<(), Chip8Error> {
+        if addr < PROGRAM_START || addr as usize >= 4096 {
+            return Err(Chip8Error::InvalidLoadAddr { addr });
+        }
+        self.load_addr = addr;
+        self.pc = addr;
+        Ok(())
+    }
+
+    pub fn load_addr(&self) -> u16 {
+        self.load_addr
+    }
+
+    //Loads a ROM into memory starting at the load address (0x0200 unless changed)
+    #[cfg(feature = "std")]
+    pub fn load_rom(&mut self, rom_path: &str) -> Result<(), Chip8Error> {
+        let mut bytes = Vec::new();
+        File::open(rom_path)
+            .and_then(|mut rom| rom.read_to_end(&mut bytes))
+            .map_err(|error| Chip8Error::Io { path: rom_path.to_string(), error })?;
+        let room = 4096 - self.load_addr as usize;
+        if bytes.len() > room {
+            return Err(Chip8Error::RomTooLarge { size: bytes.len(), room, addr: self.load_addr });
+        }
+
+        self.load_rom_bytes(&bytes);
+
+        /*Print a small memory map for debugging purposes
+        for i in 512..550 {
+            println!("{}: {:#04X}", i, self.read_byte(i))
+        }*/
+        Ok(())
+    }
+
+    //Loads a ROM that's already in memory (ie generated, or embedded) starting at the load address.
+    //Whatever doesn't fit before the end of memory is left out
+    pub fn load_rom_bytes(&mut self, bytes: &[u8]) {
+        let start = self.load_addr as usize;
+        for (i, byte) in bytes.iter().take(MEMORY_SIZE - start).enumerate() {
+            self.memory.write8(start + i, *byte).unwrap_or(());
+            self.initialized[start + i] = true;
+        }
+    }
+
+    //Loads extra data (a data bank, a test fixture, an overlay over part of the ROM) at addr. Unlike
+    //the ROM it has to fit completely
+    #[cfg(feature = "std")]
+    pub fn load_segment(&mut self, addr: u16, bytes: &[u8]) -> Result<(), String> {
+        let start = addr as usize;
+        if start < PROGRAM_START as usize || start + bytes.len() > 4096 {
+            return Err(format!("{} bytes don't fit at {:#06X} (0x200-0xFFF)", bytes.len(), addr));
+        }
+        for (i, byte) in bytes.iter().enumerate() {
+            self.memory.write8(start + i, *byte).map_err(|err| format!("{:#06X} is past the end of memory", err.addr))?;
+            self.initialized[start + i] = true;
+        }
+        Ok(())
+    }
+
+    pub fn register(&self, x: usize) -> u8 {
+        self.v[x]
+    }
+
+    pub fn index(&self) -> u16 {
+        self.i
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.v[x] = value;
+    }
+
+    pub fn set_index(&mut self, value: u16) {
+        self.i = value;
+    }
+
+    pub fn set_pc(&mut self, value: u16) {
+        self.pc = value;
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    //The addresses of the calls (2NNN) on the stack, oldest first. Each call returns to the
+    //instruction after it
+    #[cfg(feature = "std")]
+    pub fn call_stack(&self) -> Vec<u16> {
+        let mut calls = self.stack[..(self.sp as usize).min(STACK_SIZE)].to_vec();
+        calls.extend(&self.deep_stack);
+        calls
+    }
+
+    pub fn set_stack_mode(&mut self, mode: StackMode) {
+        self.stack_mode = mode;
+    }
+
+    pub fn set_draw_check(&mut self, check: DrawCheck, sprite_limit: usize) {
+        self.draw_check = check;
+        self.sprite_limit = sprite_limit;
+    }
+
+    pub fn set_clip_collision(&mut self, mode: ClipCollision) {
+        self.clip_collision = mode;
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+
+    //How many rows of a DXYN sprite of the given height (and bytes per row) can be drawn, None if the draw faulted (see sprite.rs)
+    fn sprite_rows(&mut self, height: usize, row_bytes: usize) -> Option<usize> {
+        let i = self.i as usize;
+        let mut rows = height;
+        let mut problem = None;
+        //The limit is on what DXYN asks for, SUPER-CHIP's 16 x 16 sprites are always 16 rows
+        if row_bytes == 1 && rows > self.sprite_limit {
+            rows = self.sprite_limit;
+            problem = Some(FaultKind::SpriteTooTall);
+        }
+        if i + rows * row_bytes > MEMORY_SIZE && self.memory.mode() == AddressMode::Fault {
+            rows = MEMORY_SIZE.saturating_sub(i) / row_bytes;
+            problem = Some(FaultKind::SpriteOutOfMemory);
+        }
+
+        let kind = match problem {
+            Some(kind) => kind,
+            None => return Some(rows),
+        };
+        let fault = MemoryFault { kind, addr: self.i, pc: self.pc };
+        match self.draw_check {
+            DrawCheck::Warn => {
+                self.warn("bad_sprite", self.i, move || message!("{} ({} rows asked for), drawing {}", fault, height, rows));
+                Some(rows)
+            },
+            DrawCheck::Strict => {
+                self.fault = Some(fault);
+                None
+            },
+        }
+    }
+
+    //Pushes a return address for a call to target. Faults instead if the stack is full in strict mode
+    fn push_call(&mut self, addr: u16, target: u16) -> bool {
+        if (self.sp as usize) < STACK_SIZE {
+            self.stack[self.sp as usize] = addr;
+        } else {
+            let grown = match self.stack_mode {
+                StackMode::Grow => self.grow_stack(addr, target),
+                StackMode::Strict => false,
+            };
+            if !grown {
+                self.fault = Some(MemoryFault { kind: FaultKind::StackOverflow, addr: target, pc: self.pc });
+                return false;
+            }
+        }
+        self.sp += 1;
+        true
+    }
+
+    //Pops the address the current subroutine returns to, faulting if there is none
+    fn pop_call(&mut self) -> Option<u16> {
+        if self.sp == 0 {
+            self.fault = Some(MemoryFault { kind: FaultKind::StackUnderflow, addr: self.pc, pc: self.pc });
+            return None;
+        }
+        self.sp -= 1;
+        match self.pop_deep() {
+            Some(addr) => Some(addr),
+            None => Some(self.stack[self.sp as usize]),
+        }
+    }
+
+    //Pushes past the end of the stack, grow mode. Returns false if it can't grow
+    #[cfg(feature = "std")]
+    fn grow_stack(&mut self, addr: u16, target: u16) -> bool {
+        self.deep_stack.push(addr);
+        let depth = self.sp + 1;
+        self.warn("stack", 0, move || message!("Stack grew past {} entries (depth {}) calling {:#06X}", STACK_SIZE, depth, target));
+        true
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn grow_stack(&mut self, _addr: u16, _target: u16) -> bool {
+        false
+    }
+
+    #[cfg(feature = "std")]
+    fn pop_deep(&mut self) -> Option<u16> {
+        self.deep_stack.pop()
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn pop_deep(&mut self) -> Option<u16> {
+        None
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    //True while the buzzer should be sounding
+    pub fn sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    //True while execution is stopped waiting for a key press (FX0A)
+    pub fn is_halted(&self) -> bool {
+        self.halt_flag
+    }
+
+    //Peeks at memory for tools, addresses past the end wrap around
+    pub fn read_byte(&self, addr: u16) -> u8 {
+        self.memory.read8(addr as usize % MEMORY_SIZE).unwrap_or(0)
+    }
+
+    //The two bytes at addr as an opcode, without touching the protection bookkeeping
+    pub fn opcode_at(&self, addr: u16) -> u16 {
+        (self.read_byte(addr) as u16) << 8 | self.read_byte(addr.wrapping_add(1)) as u16
+    }
+
+    pub fn write_byte(&mut self, addr: u16, value: u8) {
+        let addr = addr as usize % MEMORY_SIZE;
+        self.memory.write8(addr, value).unwrap_or(());
+        self.initialized[addr] = true;
+    }
+
+    //What data accesses past the end of memory do (see memory.rs)
+    pub fn set_address_mode(&mut self, mode: AddressMode) {
+        self.memory.set_mode(mode);
+    }
+
+    pub fn address_mode(&self) -> AddressMode {
+        self.memory.mode()
+    }
+
+    pub fn set_protection(&mut self, protection: Protection) {
+        self.protection = protection;
+    }
+
+    #[cfg(feature = "std")]
+    pub fn set_mmio(&mut self, mmio: Option<MmioBus>) {
+        self.mmio = mmio;
+    }
+
+    #[cfg(feature = "std")]
+    pub fn add_extension(&mut self, extension: Box<dyn OpcodeExtension>) {
+        self.extensions.push(extension);
+    }
+
+    //Runs handler for the opcodes where opcode & mask == pattern (see extension.rs). Only opcodes
+    //the standard set leaves undefined ever get there, built-in instructions can't be replaced
+    #[cfg(feature = "std")]
+    pub fn register_opcode(&mut self, mask: u16, pattern: u16, handler: impl FnMut(u16, &mut Chip8) + Send + Sync + 'static) {
+        self.add_extension(Box::new(OpcodeHandler::new(mask, pattern, handler)));
+    }
+
+    #[cfg(feature = "std")]
+    pub fn add_hooks(&mut self, hooks: Box<dyn Hooks>) {
+        self.hooks.push(hooks);
+    }
+
+    //Calls every hook, each with the whole machine
+    #[cfg(feature = "std")]
+    fn run_hooks(&mut self, mut call: impl FnMut(&mut dyn Hooks, &mut Chip8)) {
+        if self.hooks.is_empty() {
+            return;
+        }
+        let mut hooks = std::mem::take(&mut self.hooks);
+        for hook in hooks.iter_mut() {
+            call(hook.as_mut(), self);
+        }
+        self.hooks = hooks;
+    }
+
+    //Offers an opcode outside the standard set to the extensions. Returns true if one executed it
+    #[cfg(feature = "std")]
+    fn run_extension(&mut self, opcode: u16) -> bool {
+        if self.extensions.is_empty() || is_known_opcode(opcode, self.variant) {
+            return false;
+        }
+        //Taken out while they run, so they can have the whole machine
+        let mut extensions = std::mem::take(&mut self.extensions);
+        let mut handled = false;
+        for extension in extensions.iter_mut() {
+            if extension.execute(opcode, self) {
+                traceln!(self, "{} extension opcode", extension.name());
+                handled = true;
+                break;
+            }
+        }
+        self.extensions = extensions;
+        handled
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn run_extension(&mut self, _opcode: u16) -> bool {
+        false
+    }
+
+    //The access that made protection stop the program, if it did
+    pub fn fault(&self) -> Option<MemoryFault> {
+        self.fault
+    }
+
+    //The first write the last instruction made into memory that had been executed before, if any
+    pub fn code_write(&self) -> Option<CodeWrite> {
+        self.code_write
+    }
+
+    //How many self-modifying writes the program has made so far
+    pub fn code_write_count(&self) -> u64 {
+        self.code_write_count
+    }
+
+    pub fn observed_key(&self) -> Option<u8> {
+        self.observed_key
+    }
+
+    fn unknown_opcode(&mut self, opcode: u16) {
+        let pc = self.pc;
+        self.warn("unknown_opcode", opcode, move || message!("Unknown opcode {:#06X} at {:#06X}", opcode, pc));
+    }
+
+    //Warns about something the instruction at the PC did, once (see diagnostics.rs)
+    #[cfg(feature = "std")]
+    fn warn<F: FnOnce() -> String>(&mut self, kind: &'static str, detail: u16, message: F) {
+        let pc = self.pc;
+        self.diagnostics.warn(kind, pc, detail, message);
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn warn<F: FnOnce() -> M, M>(&mut self, _kind: &'static str, _detail: u16, _message: F) {}
+
+    #[cfg(feature = "std")]
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    //Keeps counting warnings without printing them
+    #[cfg(feature = "std")]
+    pub fn set_quiet(&mut self, quiet: bool) {
+        self.diagnostics.set_quiet(quiet);
+    }
+
+    //Reports a suspicious access. Returns false if it has to be refused (fault mode)
+    fn protection_check(&mut self, kind: FaultKind, addr: usize) -> bool {
+        let fault = MemoryFault { kind, addr: addr as u16, pc: self.pc };
+        match self.protection {
+            Protection::Off => true,
+            Protection::Warn => {
+                self.warn("protection", addr as u16, move || message!("Protection: {}", fault));
+                true
+            },
+            Protection::Fault => {
+                if self.fault.is_none() {
+                    self.fault = Some(fault);
+                }
+                false
+            },
+        }
+    }
+
+    //Stops the program for an access past the end of memory, whatever the protection mode
+    fn out_of_bounds(&mut self, addr: usize) {
+        if self.fault.is_none() {
+            self.fault = Some(MemoryFault { kind: FaultKind::OutOfBounds, addr: addr as u16, pc: self.pc });
+        }
+    }
+
+    //Memory read made by the running program
+    fn load(&mut self, addr: usize) -> u8 {
+        let addr = match self.memory.resolve(addr) {
+            Ok(addr) => addr,
+            Err(err) => {
+                self.out_of_bounds(err.addr);
+                return 0;
+            },
+        };
+        #[cfg(feature = "std")]
+        if let Some(ref mut mmio) = self.mmio {
+            if mmio.contains(addr) {
+                return mmio.read(addr, self.cycles);
+            }
+        }
+        if self.protection != Protection::Off && !self.initialized[addr]
+            && !self.protection_check(FaultKind::UninitializedRead, addr) {
+            return 0;
+        }
+        match self.memory.read8(addr) {
+            Ok(value) => value,
+            Err(err) => {
+                self.out_of_bounds(err.addr);
+                0
+            },
+        }
+    }
+
+    //Memory write made by the running program
+    fn store(&mut self, addr: usize, value: u8) {
+        let addr = match self.memory.resolve(addr) {
+            Ok(addr) => addr,
+            Err(err) => return self.out_of_bounds(err.addr),
+        };
+        if self.protection != Protection::Off && addr < INTERPRETER_END
+            && !self.protection_check(FaultKind::InterpreterWrite, addr) {
+            return;
+        }
+        //Once the program has faulted, the rest of the instruction doesn't happen either
+        if self.fault.is_some() {
+            return;
+        }
+        #[cfg(feature = "std")]
+        if let Some(ref mut mmio) = self.mmio {
+            if mmio.contains(addr) {
+                mmio.write(addr, value, self.cycles);
+                return;
+            }
+        }
+        if self.executed[addr] {
+            if self.code_write.is_none() {
+                self.code_write = Some(CodeWrite { addr: addr as u16, pc: self.pc });
+            }
+            self.code_write_count += 1;
+        }
+        if let Err(err) = self.memory.write8(addr, value) {
+            return self.out_of_bounds(err.addr);
+        }
+        self.initialized[addr] = true;
+        self.written[addr] = self.cycles + 1;
+    }
+
+    //The cycle count at which the program last wrote to addr, None if it never did
+    pub fn last_write(&self, addr: u16) -> Option<u64> {
+        match self.written[addr as usize] {
+            0 => None,
+            cycle => Some(cycle),
+        }
+    }
+
+    //Reads two bytes from memory and combines them into a single opcode number
+    fn read_opcode(&mut self) -> u16 {
+        let pc = self.pc as usize;
+        //Grab the first half of the opcode as 2-byte, shifted 8 bits left
+        let opcode1: u16 = (self.load(pc) as u16) << 8;
+        //Grab second half of opcode as 2-byte
+        let opcode2: u16 = self.load(pc + 1) as u16;
+        //OR the two two-byte numbers (one "big end" and one "small end") to combine them
+        opcode1 | opcode2
+    }
+
+    //The screen as screen_width() x screen_height() pixels (64 x 32, or 128 x 64 in hi-res mode),
+    //one byte per pixel (0 or 1), row by row
+    pub fn screen(&self) -> &[u8] {
+        &self.screen[..self.screen_width() * self.screen_height()]
+    }
+
+    //The screen as it was at the end of the last 60Hz frame
+    pub fn last_frame(&self) -> &[u8] {
+        &self.last_frame[..self.screen_width() * self.screen_height()]
+    }
+
+    pub fn screen_width(&self) -> usize {
+        if self.hires { HIRES_WIDTH } else { SCREEN_WIDTH }
+    }
+
+    pub fn screen_height(&self) -> usize {
+        if self.hires { HIRES_HEIGHT } else { SCREEN_HEIGHT }
+    }
+
+    //Whether SUPER-CHIP's 128 x 64 mode is on
+    pub fn is_hires(&self) -> bool {
+        self.hires
+    }
+
+    //Switches between the 64 x 32 and 128 x 64 screens, clearing it like 00FE/00FF do
+    fn set_hires(&mut self, hires: bool) {
+        self.hires = hires;
+        self.clear_screenbuf();
+        self.last_frame = self.screen;
+        self.draw_flag = true;
+    }
+
+    //Whether the program ran SUPER-CHIP's exit (00FD). It stays on the exit from then on
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    //SUPER-CHIP's flags. They outlive the program on the HP48, so frontends keep them between runs
+    //(the window does, see rpl.rs in the binary) and put them back before it starts
+    pub fn rpl_flags(&self) -> [u8; RPL_FLAGS] {
+        self.rpl
+    }
+
+    pub fn set_rpl_flags(&mut self, flags: [u8; RPL_FLAGS]) {
+        self.rpl = flags;
+    }
+
+    //Whether FX75 stored the flags since the last call, ie whether they need saving
+    pub fn take_rpl_written(&mut self) -> bool {
+        core::mem::replace(&mut self.rpl_written, false)
+    }
+
+    //Whether the pixel at x, y is lit, pixels off the screen never are
+    pub fn pixel(&self, x: usize, y: usize) -> bool {
+        x < self.screen_width() && y < self.screen_height() && self.screen[x + y * self.screen_width()] != 0
+    }
+
+    //Lights or clears the pixel at x, y, for debuggers. Pixels off the screen are ignored
+    pub fn set_pixel(&mut self, x: usize, y: usize, lit: bool) {
+        if x < self.screen_width() && y < self.screen_height() {
+            let width = self.screen_width();
+            self.screen[x + y * width] = lit as u8;
+            self.draw_flag = true;
+        }
+    }
+
+    //The area the last sprite was drawn on, None if nothing has been drawn yet
+    pub fn last_draw_rect(&self) -> Option<DrawRect> {
+        self.last_draw
+    }
+
+    //Whether the last sprite drawn collided with lit pixels (the VF it set, even if VF has changed since)
+    pub fn last_collision(&self) -> bool {
+        self.last_collision
+    }
+
+    //The screen as palette indices, what frontends should draw from
+    pub fn framebuffer(&self) -> Framebuffer<'_> {
+        Framebuffer::new(self.screen(), self.screen_width(), self.screen_height(), ColorDepth::OnePlane)
+    }
+
+    //A 64-bit FNV-1a hash of the screen, stable across runs and builds so it can be compared with saved results
+    pub fn screen_hash(&self) -> u64 {
+        self.framebuffer().hash()
+    }
+
+    //Runs the machine frame by frame, see frames.rs
+    #[cfg(feature = "std")]
+    pub fn frames(&mut self) -> Frames<'_> {
+        Frames::new(self)
+    }
+
+    fn clear_screenbuf(&mut self) {
+        self.screen = [0; HIRES_WIDTH * HIRES_HEIGHT];
+    }
+
+    //Pulls the current opcode in memory (at program counter) and performs it's required operations.
+    //Fails if the instruction didn't run as written (see error.rs)
+    pub fn emulate_cycle(&mut self) -> Result<(), Chip8Error> {
+
+        if let Some(fault) = self.fault {
+            return Err(fault.into());
+        }
+        if self.halt_flag {
+            return Ok(());
+        }
+        if self.pc as usize + 1 >= 4096 {
+            return Err(Chip8Error::MemoryOutOfBounds { addr: self.pc as usize, pc: self.pc });
+        }
+
+        self.code_write = None;
+        self.observed_key = None;
+
+        //Fetch opcode
+        let opcode = self.read_opcode();
+        if let Some(fault) = self.fault {
+            return Err(fault.into());
+        }
+        self.executed[self.pc as usize] = true;
+        self.executed[self.pc as usize + 1] = true;
+
+        //Remember the state before execution so the history can record what changed
+        let old_pc = self.pc;
+        #[cfg(feature = "std")]
+        let (old_v, old_i) = (self.v, self.i);
+
+        //Print opcode as a 6-digit hex number, including leading zeros and "0x" notation, and its mnemonic
+        trace!(self, "Opcode: {:#06X} ({}) - ", opcode, decode_for(opcode, self.variant)); //ie 0x6012 (LD V0, 0x12)
+
+        let extended = self.run_extension(opcode);
+        let handler = dispatch::handler(opcode, self.variant);
+
+        //Execute the opcode (see dispatch.rs)
+        match handler {
+            //Already executed by an extension
+            _ if extended => {},
+            Some(handler) => handler(self, opcode)?,
+            //The 8XYN group always moves on, even past the ones it doesn't know
+            None if opcode & FIRST_NIBBLE_MASK == 0x8000 => {
+                self.unknown_opcode(opcode);
+                self.next_instruction();
+            },
+            None => self.unknown_opcode(opcode),
+        }
+        let invalid = !extended && handler.is_none();
+
+        //Protection stopped the instruction, stay on it so the PC points at the culprit
+        if self.fault.is_some() {
+            self.pc = old_pc;
+        }
+
+        #[cfg(feature = "std")]
+        self.history.push(HistoryEntry::new(old_pc, opcode, &old_v, &self.v, old_i, self.i));
+
+        if self.draw_flag {
+
+            //Draw the screen
+            //self.draw(window, event);
+
+            //Unset our draw flag for the next op
+            self.draw_flag = false;
+            #[cfg(feature = "std")]
+            self.run_hooks(|hooks, chip8| hooks.on_draw(chip8));
+        }
+
+        if let Some(fault) = self.fault {
+            return Err(fault.into());
+        }
+        if invalid {
+            return Err(Chip8Error::InvalidOpcode { opcode, pc: old_pc });
+        }
+        Ok(())
+    }
+
+    //Prints an event line to the trace, stamped with the host time (microseconds since the machine was made)
+    //and the cycle count, so traces can be lined up with recordings and checked for latency afterwards:
+    //    event host_us=1523042 cycle=9120 key 5 down
+    #[cfg(feature = "std")]
+    fn trace_event<F: FnOnce() -> String>(&self, event: F) {
+        if self.trace {
+            println!("event host_us={} cycle={} {}", self.created.elapsed().as_micros(), self.cycles, event());
+        }
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn trace_event<F: FnOnce() -> M, M>(&self, _event: F) {}
+
+    //Runs a single cycle and counts the timers down every cycles_per_tick() cycles
+    //Tying the timers to the cycle count (rather than to wall-clock time) keeps runs reproducible
+    pub fn step(&mut self) -> Result<(), Chip8Error> {
+        #[cfg(feature = "std")]
+        self.run_hooks(|hooks, chip8| hooks.pre_cycle(chip8));
+        let result = self.emulate_cycle();
+
+        self.count_cycle();
+        #[cfg(feature = "std")]
+        self.run_hooks(|hooks, chip8| hooks.post_cycle(chip8));
+        result
+    }
+
+    //Counts a cycle done, ticking the timers (and keeping the frame for deflickering) every cycles_per_tick()
+    fn count_cycle(&mut self) {
+        self.cycles += 1;
+        if self.cycles.is_multiple_of(self.cycles_per_tick) {
+            self.update_timers();
+            self.last_frame = self.screen;
+        }
+    }
+
+    //Steps until `pred` holds (checked before every instruction), for up to `max_cycles` instructions,
+    //ie chip8.run_until(|chip8| chip8.register(0) == 5, 10_000). Returns whether `pred` was met.
+    //Gives up early when the program can't get any further: waiting for a key, the program counter
+    //outside memory, or a memory fault
+    pub fn run_until(&mut self, pred: impl Fn(&Chip8) -> bool, max_cycles: u64) -> bool {
+        for _ in 0..max_cycles {
+            if pred(self) {
+                return true;
+            }
+            if self.halt_flag {
+                return false;
+            }
+            if self.step().is_err_and(|err| err.is_fatal()) {
+                return pred(self);
+            }
+        }
+        pred(self)
+    }
+
+    //Counts both timers down by one. Should be called at TIMER_HZ, independent of the CPU speed
+    pub fn update_timers(&mut self) {
+        if self.delay_timer > 0 {
+            self.delay_timer -= 1;
+        }
+
+        if self.sound_timer > 0 {
+            if self.sound_timer == 1 {
+                traceln!(self, "BEEP!");
+            }
+            self.sound_timer -= 1;
+        }
+    }
+
+    //Print the bytes in memory between the given range (for debugging purposes)
+    #[cfg(feature = "std")]
+    pub fn print_memory(&self, range: Range<usize>) {
+        for i in range {
+            println!("{:#04X}", self.read_byte(i as u16));
+        }
+    }
+
+    //Print all registers, timers and pointers (for debugging purposes)
+    #[cfg(feature = "std")]
+    pub fn print_registers(&self) {
+        for row in 0..2 {
+            for reg in row * 8..row * 8 + 8 {
+                print!("V[{:X}]: {:#04X}  ", reg, self.v[reg]);
+            }
+            println!();
+        }
+        println!("I: {:#06X}  PC: {:#06X}  SP: {}  DT: {}  ST: {}", self.i, self.pc, self.sp, self.delay_timer, self.sound_timer);
+    }
+
+    //Print the last `count` executed instructions and the registers they changed (for debugging purposes)
+    #[cfg(feature = "std")]
+    pub fn print_history(&self, count: usize) {
+        self.history.print(count);
+    }
+}
@@ -0,0 +1,245 @@
+/************
+ROM library
+
+A launcher for picking a game inside the window: the .ch8/.c8 files of the library directories
+are shown as a grid of thumbnails, with the title from the ROM database (see metadata.rs) and the
+play statistics (see stats.rs) of the selected one in the window title. The library directories
+are the --library DIR options, or without any, the lines of library.dirs in the data directory
+(see stats.rs). Without a romfile the window starts on the launcher, F12 opens it during a game.
+
+A thumbnail is the picture chip8 thumbs saved next to the ROM (see thumbs.rs), shrunk like save
+state thumbnails. ROMs without one are run without a window the same way, and their thumbnails
+cached in thumbnails/ in the data directory by ROM hash, so only new ROMs are run. The cache goes
+through the storage backend (see storage.rs), with --storage memory it only lasts for the session.
+
+Keys while the launcher is open (the CHIP-8 keys go through the keymap, so gamepads work too):
+    Arrows, or keys 2/4/6/8     Move the selection
+    Return, or key 5            Start the selected ROM
+    F12                         Close the launcher, back to the game (or quit if there is none)
+************/
+use std::fs;
+
+use piston_window::*;
+
+use input::{InputState, Keymap};
+use metadata::{self, RomDb};
+use savestate::{thumbnail, THUMB_HEIGHT, THUMB_WIDTH};
+use stats::{self, Stats};
+use storage::Storage;
+use testsuite::find_roms;
+use thumbs::{self, load_png, png_path, representative_frame};
+
+pub const DIRS_FILE: &str = "library.dirs";
+const COLUMNS: usize = 4;
+
+//The library directories: the given ones, or the ones listed in library.dirs
+pub fn directories(given: &[String]) -> Result<Vec<String>, String> {
+    if !given.is_empty() {
+        return Ok(given.to_vec());
+    }
+    let path = stats::data_dir().join(DIRS_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = fs::read_to_string(&path).map_err(|err| format!("Could not read {}: {}", path.display(), err))?;
+    Ok(text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect())
+}
+
+pub struct LibraryEntry {
+    pub path: String,
+    pub title: String,      //From the ROM database, the file name otherwise
+    pub details: String,    //What's shown next to the title
+    thumbnail: Option<Vec<u8>>,
+}
+
+//Where a thumbnail is cached, as a storage key (see storage.rs)
+fn thumbnail_key(hash: u64) -> String {
+    stats::data_dir().join("thumbnails").join(format!("{:016X}.thumb", hash)).to_string_lossy().into_owned()
+}
+
+fn cached_thumbnail(rom: &[u8], hash: u64, storage: &mut dyn Storage) -> Option<Vec<u8>> {
+    let key = thumbnail_key(hash);
+    if let Ok(Some(thumb)) = storage.read(&key) {
+        if thumb.len() == THUMB_WIDTH * THUMB_HEIGHT {
+            return Some(thumb);
+        }
+    }
+    let thumb = thumbnail(&representative_frame(rom, thumbs::DEFAULT_SECONDS)?);
+    //Without the cache the thumbnail just gets rendered again next time
+    if let Err(err) = storage.write(&key, &thumb) {
+        println!("Could not cache the thumbnail of a ROM: {}", err);
+    }
+    Some(thumb)
+}
+
+//Everything in the library directories, sorted by title. Directories that can't be read are skipped
+pub fn scan(dirs: &[String], rom_db: &RomDb, storage: &mut dyn Storage) -> Vec<LibraryEntry> {
+    let stats = Stats::load(storage).unwrap_or_else(|err| {
+        println!("{}", err);
+        Stats::default()
+    });
+    let mut entries = Vec::new();
+
+    for dir in dirs {
+        let paths = match find_roms(dir) {
+            Ok(paths) => paths,
+            Err(err) => {
+                println!("{}", err);
+                continue;
+            },
+        };
+
+        for path in paths {
+            let rom = match fs::read(&path) {
+                Ok(rom) => rom,
+                Err(_) => continue,
+            };
+            let hash = metadata::rom_hash(&rom);
+            let name = path.file_stem().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            let title = rom_db.lookup(hash).and_then(|info| info.title.clone()).unwrap_or(name);
+            let details = match stats.get(hash) {
+                Some(played) => format!("{} bytes, played {} times ({})", rom.len(), played.launches, stats::format_playtime(played.playtime)),
+                None => format!("{} bytes, never played", rom.len()),
+            };
+            entries.push(LibraryEntry {
+                path: path.to_string_lossy().into_owned(),
+                title,
+                details,
+                thumbnail: match load_png(&png_path(&path, None)) {
+                    Ok(screen) => Some(thumbnail(&screen)),
+                    Err(_) => cached_thumbnail(&rom, hash, storage),
+                },
+            });
+        }
+    }
+    entries.sort_by_key(|entry| entry.title.to_lowercase());
+    entries
+}
+
+pub struct Launcher {
+    entries: Vec<LibraryEntry>,
+    selected: usize,
+    top_row: usize,     //First row on screen, the grid scrolls to keep the selection visible
+}
+
+impl Launcher {
+    pub fn open(dirs: &[String], rom_db: &RomDb, storage: &mut dyn Storage) -> Launcher {
+        Launcher { entries: scan(dirs, rom_db, storage), selected: 0, top_row: 0 }
+    }
+
+    //Moves the selection by columns and rows, stopping at the ends
+    fn select(&mut self, dx: isize, dy: isize) {
+        let target = self.selected as isize + dx + dy * COLUMNS as isize;
+        if target >= 0 && (target as usize) < self.entries.len() {
+            self.selected = target as usize;
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self.entries.get(self.selected) {
+            Some(entry) => format!("{} - {} ({}/{})", entry.title, entry.details, self.selected + 1, self.entries.len()),
+            None => String::from("No ROMs in the library"),
+        }
+    }
+
+    fn draw(&mut self, window: &mut PistonWindow, event: &Event) {
+        let (entries, selected) = (&self.entries, self.selected);
+        let top_row = &mut self.top_row;
+        window.draw_2d(event, |c, g| {
+            clear([0.0, 0.0, 0.0, 1.0], g);
+            let view = c.get_view_size();
+            let cell_width = view[0] / COLUMNS as f64;
+            let pixel = ((cell_width - 16.0) / THUMB_WIDTH as f64).floor().max(1.0);
+            let thumb_width = pixel * THUMB_WIDTH as f64;
+            let thumb_height = pixel * THUMB_HEIGHT as f64;
+            let cell_height = thumb_height + 16.0;
+
+            let rows = ((view[1] / cell_height).floor() as usize).max(1);
+            let row = selected / COLUMNS;
+            if row < *top_row {
+                *top_row = row;
+            } else if row >= *top_row + rows {
+                *top_row = row + 1 - rows;
+            }
+
+            for (index, entry) in entries.iter().enumerate().skip(*top_row * COLUMNS).take(rows * COLUMNS) {
+                let left = ((index % COLUMNS) as f64 * cell_width + (cell_width - thumb_width) / 2.0).floor();
+                let top = ((index / COLUMNS - *top_row) as f64 * cell_height + 8.0).floor();
+
+                let frame = if index == selected { [1.0, 0.8, 0.0, 1.0] } else { [0.4, 0.4, 0.4, 1.0] };
+                rectangle(frame, [left - 2.0, top - 2.0, thumb_width + 4.0, thumb_height + 4.0], c.transform, g);
+                rectangle([0.0, 0.0, 0.0, 1.0], [left, top, thumb_width, thumb_height], c.transform, g);
+
+                match entry.thumbnail {
+                    Some(ref thumb) => {
+                        for y in 0..THUMB_HEIGHT {
+                            for x in 0..THUMB_WIDTH {
+                                if thumb[x + y * THUMB_WIDTH] != 0 {
+                                    let square = [left + x as f64 * pixel, top + y as f64 * pixel, pixel, pixel];
+                                    rectangle([1.0, 1.0, 1.0, 1.0], square, c.transform, g);
+                                }
+                            }
+                        }
+                    },
+                    //Crossed out like an empty save slot, the ROM didn't load
+                    None => {
+                        let color = [0.4, 0.4, 0.4, 1.0];
+                        line(color, 1.0, [left, top, left + thumb_width, top + thumb_height], c.transform, g);
+                        line(color, 1.0, [left, top + thumb_height, left + thumb_width, top], c.transform, g);
+                    },
+                }
+            }
+        });
+    }
+
+    //Shows the launcher in the window until a ROM is picked. None if it was closed instead
+    pub fn run(mut self, window: &mut PistonWindow, keymap: &Keymap) -> Option<String> {
+        println!("Library: {} ROMs, arrows to pick one, Return to start it, F12 to close", self.entries.len());
+        let mut input = InputState::new(keymap.clone());
+        let mut title = String::new();
+        let mut picked = None;
+
+        while let Some(e) = window.next() {
+            if e.render_args().is_some() {
+                self.draw(window, &e);
+            }
+
+            if let Some(button) = e.button_args() {
+                let pressed = button.state == ButtonState::Press;
+                //The CHIP-8 keys, for playing with whatever the games are played with
+                let key = match (button.button, pressed) {
+                    (Button::Keyboard(Key::Up), true) => Some(0x2),
+                    (Button::Keyboard(Key::Left), true) => Some(0x4),
+                    (Button::Keyboard(Key::Right), true) => Some(0x6),
+                    (Button::Keyboard(Key::Down), true) => Some(0x8),
+                    (Button::Keyboard(Key::Return), true) => Some(0x5),
+                    (Button::Keyboard(Key::F12), true) => break,
+                    _ => input.button(button).filter(|&(_, state)| state != 0).map(|(key, _)| key),
+                };
+                match key {
+                    Some(0x2) => self.select(0, -1),
+                    Some(0x4) => self.select(-1, 0),
+                    Some(0x6) => self.select(1, 0),
+                    Some(0x8) => self.select(0, 1),
+                    Some(0x5) if !self.entries.is_empty() => {
+                        picked = Some(self.entries[self.selected].path.clone());
+                        break;
+                    },
+                    _ => {},
+                }
+            }
+
+            let description = self.describe();
+            if description != title {
+                window.set_title(format!("Chip8 - {}", description));
+                title = description;
+            }
+        }
+        window.set_title(String::from("Chip8"));
+        picked
+    }
+}
@@ -1,680 +1,864 @@
 /************
-CHIP8 Memory Map:
-0x000-0x1FF - Chip 8 interpreter (contains font set in emu)
-0x050-0x0A0 - Used for the built in 4x5 pixel font set (0-F)
-0x200-0xFFF - Program ROM and work RAM
+CHIP-8 emulator frontend
 
-V Regs are 1 byte long (u8)
-Opcodes are 2 bytes long (u16). This means we must combine 2 1-byte numbers in memory into a single 2-byte number
-This is done by rotating the leading number (the big end) by 8 bits. This will create a 2-byte number with 1 byte of zeros at the little end
-We then bitwise OR our 2 byte number and our 1-byte number that we want to combine. All of the 1s in the 1-byte number are kept in the final result
-
-Rust does not allow Hex literals in code, so most hex will be converted to decimal before being entered.
-Their decimal equivalence and purpose should be noted in the comments or via constants
-
-To extract nibbles as individual numbers, we mask the nibble and then rotate that nibble to the right until it is in the "1"s place
+The window, the command line and the subcommands (debugger, headless runs, tools). The machine
+itself, memory, registers, timers, screen and keypad, is the chip8 library (lib.rs), which the
+frontend drives through step(), load_rom(), framebuffer() and press_key().
 ************/
-#![feature(duration_as_u128)]
+extern crate chip8;
 extern crate piston_window;
-extern crate rand;
+extern crate png;
+extern crate rhai;
 
-
-use std::fs::File;
-use std::ops::Range;
-use std::io::Read;
+use std::collections::VecDeque;
 use std::env;
+use std::process;
+use std::sync::Arc;
+use std::time::Instant;
 
 use piston_window::*;
 
-const FIRST_NIBBLE_MASK: u16 = 0xF000;  //Grabs first nibble only
-const SECOND_NIBBLE_MASK: u16 = 0x0F00; //Grabs second nibble only
-const THIRD_NIBBLE_MASK: u16 = 0x00F0;
-const FOURTH_NIBBLE_MASK: u16 = 0x000F;
-
-const LAST_TWO_MASK: u16 = 0x00FF;      //Grabs the last two nibbles
-const LAST_THREE_MASK: u16 = 0x0FFF;    //Grabs last three nibbles only
-
-pub struct Chip8 {
-    memory: [u8; 4096], //General purpose memory
-    v: [u8; 16],        //General purpose registers. Register 16 is the "carry flag"
-
-    i: u16,             //Index register
-    pc: u16,            //Program counter (instruction pointer)
+use chip8::{agent, batch, display, extension, framebuffer, frames, frontend, hooks, instruction, jit, memory, mmio, palette, protect, quirks, savestate, snapshot, sprite, stack, variant};
+use chip8::{Chip8, BIG_FONT, CYCLES_PER_SECOND, CYCLES_PER_TIMER_TICK, FONT, FONT_ADDR, PROGRAM_START, RPL_FLAGS, TIMER_HZ};
+
+mod analyze;
+mod annotations;
+mod audio;
+mod benchrom;
+mod budget;
+mod clock;
+mod commands;
+mod controls;
+mod debugger;
+mod disasm;
+mod doctor;
+mod emulation;
+mod episodes;
+mod exitcode;
+mod features;
+mod gameover;
+mod headless;
+mod imagesprite;
+mod input;
+mod inspector;
+mod json;
+mod keytest;
+mod kiosk;
+mod latency;
+mod learn;
+mod library;
+mod memview;
+mod metadata;
+mod options;
+mod panels;
+mod poke;
+mod profile;
+mod replay;
+mod romtag;
+mod romtool;
+mod rpl;
+mod scripting;
+mod rumble;
+mod selftest;
+mod session;
+mod share;
+mod slots;
+mod statediff;
+mod stats;
+mod storage;
+mod terminal;
+mod testsuite;
+mod thumbs;
+use clock::Clock;
+use commands::{Action, CommandPalette};
+use controls::ControlsOverlay;
+use display::{DisplaySettings, Layout, SCREEN_HEIGHT, SCREEN_WIDTH};
+use emulation::{Emulation, EmulationThread, Snapshot};
+use framebuffer::{ColorDepth, Framebuffer};
+use gameover::{GameOverDetector, GameOverRule};
+use input::InputState;
+use inspector::PixelInspector;
+use kiosk::{Kiosk, PlaylistEntry};
+use latency::LatencyMeter;
+use learn::Narrator;
+use library::Launcher;
+use memview::MemoryViewer;
+use mmio::MmioBus;
+use options::Options;
+use palette::{FrameInfo, Palette, PaletteEffects};
+use panels::Panels;
+use replay::{Replay, ReplayPlayer};
+use romtag::RomTag;
+use rumble::Rumble;
+use scripting::ScriptHooks;
+use session::SessionRecorder;
+use slots::LoadMenu;
+use snapshot::Rewind;
+use stats::PlayTracker;
+use storage::Storage;
+use variant::Variant;
+
+const TURBO_FRAME_SKIP: u64 = 8;            //While fast-forwarding, one in this many frames is drawn
+
+
+//Creates and initializes a Chip8 running the given ROM, with the pokes given in the options
+fn create_chip8(options: &Options, rom: &str) -> Result<Chip8, String> {
+    //Load up our ROM into program memory
+    let mut builder = Chip8::builder()
+        .clock_hz(options.ips)
+        .font_addr(options.font_addr)
+        .load_addr(options.load_addr)
+        .rom(rom)
+        .protection(options.protection)
+        .stack_mode(options.stack_mode)
+        .draw_check(options.draw_check, options.sprite_limit)
+        .clip_collision(options.clip_collision)
+        .variant(options.variant)
+        .quirks(options.quirks)
+        .address_mode(options.address_mode);
+    if let Some(seed) = options.seed {
+        builder = builder.seed(seed);
+    }
+    for name in &options.extensions {
+        //The names were checked when the options were parsed
+        builder = builder.extension(extension::by_name(name).unwrap());
+    }
+    if let Some(base) = options.mmio {
+        match MmioBus::standard(base) {
+            Ok(bus) => builder = builder.mmio(bus),
+            Err(err) => println!("{}, running without them", err),
+        }
+    }
+    let mut chip8 = builder.build()?;
+    if let Some(ref path) = options.hooks {
+        chip8.add_hooks(Box::new(ScriptHooks::load(path)?));
+    }
 
-    screen: [u8; 64 * 32], //Array for storing screen pixels. Screen is 64 x 32 pixels
-    draw_flag: bool,
+    for segment in &options.segments {
+        let bytes = std::fs::read(&segment.path).map_err(|err| format!("Could not read {}: {}", segment.path, err))?;
+        chip8.load_segment(segment.addr, &bytes).map_err(|err| format!("Could not load {}: {}", segment.path, err))?;
+    }
 
-    halt_flag: bool,
-    halt_reg: u8,
+    //Apply any memory pokes on top of the loaded ROM
+    for poke in &options.pokes {
+        poke.apply(&mut chip8);
+    }
 
-    delay_timer: u8,    //Counts down at 60Hz speed to zero
-    sound_timer: u8,    //Same as above, system buzzer sounds when it reaches zero
+    Ok(chip8)
+}
 
-    stack: [u16; 16],   //Stack for program execution. Use to return to calling program after called program is finished
-    sp: u16,            //Stack pointer, to keep track of what is currently the "top"
+//Draws the machine's screen, as the emulation thread last sent it, into the window, scaled and centred
+fn draw_screen(snapshot: &Snapshot, window: &mut PistonWindow, event: &Event, palette: &Palette, settings: DisplaySettings) {
+    //Physical pixels per window point, more than 1 on high-DPI screens
+    let device_scale = match event.render_args() {
+        Some(args) if args.width > 0 => args.draw_width as f64 / args.width as f64,
+        _ => 1.0,
+    };
+    let rotation = settings.rotation;
+
+    //Clear old screen
+    window.draw_2d(event, |_context, graphics| {
+        clear(palette.background, graphics);
+    });
+
+    //Draw new screen
+    window.draw_2d(event, |c, g| {
+        //Scale the screen to fit the window (8x in a normal window, bigger when fullscreen)
+        //and centre it in whatever space is left over
+        let size = (snapshot.width, snapshot.height);
+        let Layout { pixel_size, x_offset, y_offset } = Layout::fit(c.get_view_size(), device_scale, settings, size);
+
+        //Pixels lit in either of the last two frames, when deflickering
+        let blended: Vec<u8> = snapshot.screen.iter().zip(snapshot.last_frame.iter()).map(|(now, last)| now | last).collect();
+        let pixels = if settings.deflicker { &blended } else { &snapshot.screen };
+        let screen = Framebuffer::new(pixels, snapshot.width, snapshot.height, ColorDepth::OnePlane);
+        //Step over each y "pixel" for each x above
+        for y in 0..screen.height() {
+            //Step over each x "pixel"
+            for x in 0..screen.width() {
+                //Unlit pixels are already the background color
+                if screen.index(x, y) != 0 {
+                    let (out_x, out_y) = rotation.apply(x, y, snapshot.width, snapshot.height);
+                    let x_pos = x_offset + out_x as f64 * pixel_size;
+                    let y_pos = y_offset + out_y as f64 * pixel_size;
+                    //println!("Drawing rect at x:{} ({}), y:{} ({})", x_pos, x, y_pos, y);
+                    Rectangle::new(screen.color(x, y, palette))
+                        .draw([x_pos, y_pos, pixel_size, pixel_size], &c.draw_state, c.transform, g)
+                }
+            }
+        }
+    });
+}
 
-    key: [u8; 16],     //Hex based keypad
+//A running ROM and everything that follows along with it
+struct Game {
+    rom: String,
+    chip8: Chip8,
+    player: Option<ReplayPlayer>,
+    game_over: GameOverDetector,
+    undo_state: Option<Vec<u8>>,    //The state from right before the last save state load
+    input: InputState,
+    controls: Option<ControlsOverlay>,  //The ROM's controls, shown for a while when it starts
+    session: SessionRecorder,
+    deflicker: bool,    //From --deflicker or the ROM database, F6 toggles it
+    narrator: Option<Narrator>,     //Explains a tutorial lesson as it runs (see learn.rs)
+    rewind: Option<Rewind>,         //A snapshot per frame for Backspace to step back through, with --rewind
+    stats: PlayTracker,             //Adds the game to the play statistics when it's finished (see stats.rs)
 }
 
-impl Chip8 {
-    pub fn new() -> Chip8 {
-        Chip8 {
-            memory: [0; 4096], //Initialize our memory
-            v: [0; 16],        //Zero out our registers
-            i: 0,
-            pc: 512,           //program counter starts at 0x200 (system data comes before)
-            screen: [0; 64 * 32],
-            draw_flag: false,
-            halt_flag: false,
-            halt_reg: 0,
-            delay_timer: 0,
-            sound_timer: 0,
-            stack: [0; 16],
-            sp: 0,
-            key: [0; 16],
+//Starts a ROM from the playlist (or the command line)
+fn start_game(options: &Options, entry: &PlaylistEntry) -> Result<Game, String> {
+    let mut chip8 = create_chip8(options, &entry.rom)?;
+
+    //In attract mode, play back the recorded inputs for the ROM (if it has any)
+    let player = if options.attract {
+        Replay::for_rom(&entry.rom).and_then(|replay| match ReplayPlayer::new(replay, &mut chip8) {
+            Ok(player) => Some(player),
+            Err(err) => {
+                println!("{}", err);
+                None
+            }
+        })
+    } else {
+        None
+    };
+
+    //ROMs can say what they are themselves
+    if let Some((tag, _)) = std::fs::read(&entry.rom).ok().and_then(|rom| RomTag::read(&rom)) {
+        println!("{}", tag.describe());
+        if let Some(ref profile) = tag.profile {
+            if Variant::parse(profile).ok() != Some(chip8.variant()) {
+                println!("Warning: the ROM was made for {}, it's running as {} (see --variant)", profile, chip8.variant());
+            }
         }
     }
 
-    pub fn initialize(&mut self) {
-        //Load up our font into reserved system memory
-        self.load_font();
+    //Tell the player how to play, if the ROM database knows
+    let mut keymap = options.keymap.clone();
+    let mut controls = None;
+    let mut deflicker = options.deflicker;
+    if let Some(hash) = metadata::rom_file_hash(&entry.rom) {
+        deflicker |= options.rom_db.lookup(hash).is_some_and(|info| info.deflicker);
+        match options.rom_db.lookup(hash) {
+            Some(info) if !info.controls.is_empty() => {
+                if options.auto_map {
+                    controls::auto_map(info, &mut keymap);
+                }
+                println!("{}", controls::describe(info, &keymap));
+                controls = Some(ControlsOverlay::new(info));
+            },
+            Some(_) => {},
+            None => println!("{} is not in the ROM database (hash {:#018X})", entry.rom, hash),
+        }
     }
 
-    //Increments the program counter to pull the next opcode
-    fn next_instruction(&mut self) {
-        self.pc += 2;
+    let rule = match entry.game_over {
+        Some(addr) => GameOverRule::Address(addr),
+        None => GameOverRule::Heuristic,
+    };
+
+    let session = SessionRecorder::new(&chip8, options.emulation_args.clone());
+    Ok(Game {
+        rom: entry.rom.clone(),
+        stats: stats::track(&entry.rom, &options.rom_db),
+        session,
+        deflicker,
+        narrator: options.lesson.map(Narrator::new),
+        rewind: options.rewind.map(|seconds| Rewind::new((seconds * TIMER_HZ).ceil() as usize)),
+        chip8,
+        player,
+        game_over: GameOverDetector::new(rule),
+        undo_state: None,
+        input: InputState::new(keymap),
+        controls,
+    })
+}
+
+//Moves kiosk mode on to the next ROM of the playlist that loads. Broken ROMs are skipped, if none
+//of them load there's nothing left to show
+fn next_kiosk_game(kiosk: &mut Kiosk, options: &Options) -> Game {
+    for _ in 0..kiosk.playlist_len() {
+        let entry = kiosk.advance();
+        println!("Kiosk: loading {}", entry.rom);
+        match start_game(options, entry) {
+            Ok(game) => return game,
+            Err(err) => println!("{}, skipping it", err),
+        }
     }
+    println!("No ROM of the playlist could be loaded. Aborting");
+    process::exit(exitcode::ROM_LOAD);
+}
 
-    //Loads font sprites into memory starting at location 0x0000 to 0x01FF
-    pub fn load_font(&mut self) {
-        let font = [
-            0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-            0x20, 0x60, 0x20, 0x20, 0x70, // 1
-            0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-            0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-            0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-            0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-            0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-            0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-            0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-            0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-            0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-            0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-            0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-            0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-            0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-            0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-        ];
-        let mut i = 0;
-
-        for byte in font.iter() {
-            self.memory[i] = *byte;
-            i += 1;
+//Shows the library launcher until a ROM picked from it starts. None if it was closed first
+fn launch_from_library(window: &mut PistonWindow, options: &Options, storage: &mut dyn Storage) -> Option<Game> {
+    loop {
+        let rom = Launcher::open(&options.library, &options.rom_db, storage).run(window, &options.keymap)?;
+        println!("Starting {}", rom);
+        match start_game(options, &PlaylistEntry::new(&rom, None)) {
+            Ok(game) => return Some(game),
+            Err(err) => println!("{}, pick another one", err),
         }
     }
+}
 
-    pub fn set_key(&mut self, key: u8, value: u8) {
-        self.key[key as usize] = value;
-        println!("key {} set to {}", key, value);
+//Handles the emulator's own hotkeys (save states, undo load, session recording). Returns false for buttons that aren't hotkeys
+fn handle_hotkey(button: ButtonArgs, game: &mut Game, menu: &mut Option<LoadMenu>, storage: &mut dyn Storage) -> bool {
+    let key = match button.button {
+        Button::Keyboard(key) => key,
+        _ => return false,
+    };
+    let pressed = button.state == ButtonState::Press;
+
+    //While the load menu is open it gets every key
+    if let Some(mut open_menu) = menu.take() {
+        if pressed {
+            match key {
+                Key::Left => open_menu.left(),
+                Key::Right => open_menu.right(),
+                Key::Return => {
+                    let slot = open_menu.selected;
+                    //Keep the current state around so a stale slot can't destroy a good run
+                    let before = game.chip8.save_state();
+                    match slots::load_slot(storage, &mut game.chip8, &game.rom, slot) {
+                        Ok(()) => {
+                            println!("Loaded state from slot {} (F10 to undo)", slot + 1);
+                            game.undo_state = Some(before);
+                            game.session.restart(&game.chip8);
+                        },
+                        Err(err) => println!("{}", err),
+                    }
+                    return true;
+                },
+                Key::F9 => return true,
+                _ => {},
+            }
+        }
+        *menu = Some(open_menu);
+        return true;
     }
 
-    //Loads a ROM into memory starting at location 0x0200
-    pub fn load_rom(&mut self, rom_path: &str) {
-        let rom = File::open(rom_path).unwrap();
-        let mut i = 512;
+    let slot = match key {
+        Key::F1 => 0,
+        Key::F2 => 1,
+        Key::F3 => 2,
+        Key::F4 => 3,
+        Key::F9 => {
+            if pressed {
+                *menu = Some(LoadMenu::open(storage, &game.rom));
+            }
+            return true;
+        },
+        //Save the session recorded so far, for bug reports
+        Key::F8 => {
+            if pressed {
+                if game.player.is_some() {
+                    println!("Can't record a session while inputs are played back");
+                    return true;
+                }
+                match game.session.save(&game.rom) {
+                    Ok(path) => println!("Saved session to {}", path),
+                    Err(err) => println!("{}", err),
+                }
+            }
+            return true;
+        },
+        //Undo the last load. The state we leave becomes the new undo, so pressing it again redoes the load
+        Key::F10 => {
+            if pressed {
+                match game.undo_state.take() {
+                    Some(state) => {
+                        let current = game.chip8.save_state();
+                        match game.chip8.load_state(&state) {
+                            Ok(()) => {
+                                println!("Undid state load");
+                                game.undo_state = Some(current);
+                                game.session.restart(&game.chip8);
+                            },
+                            Err(err) => println!("{}", err),
+                        }
+                    },
+                    None => println!("No state load to undo"),
+                }
+            }
+            return true;
+        },
+        _ => return false,
+    };
 
-        for byte in rom.bytes() {
-            self.memory[i] = byte.unwrap();
-            i += 1;
+    if pressed {
+        match slots::save_slot(storage, &game.chip8, &game.rom, slot) {
+            Ok(()) => println!("Saved state to slot {}", slot + 1),
+            Err(err) => println!("{}", err),
         }
+    }
+    true
+}
 
-        /*Print a small memory map for debugging purposes
-        for i in 512..550 {
-            println!("{}: {:#04X}", i, self.memory[i])
-        }*/
+fn main() {
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    //"chip8 debug <romfile> ..." starts a debugger session instead of the game window
+    if args.first().map(|arg| arg.as_str()) == Some("debug") {
+        debugger::run(&args[1..]);
+        return;
     }
 
-    //Reads two bytes from memory and combines them into a single opcode number
-    fn read_opcode(&mut self) -> u16 {
-        //Grab the first half of the opcode as 2-byte, shifted 8 bits left
-        let opcode1: u16 = (self.memory[self.pc as usize] as u16) << 8;
-        //Grab second half of opcode as 2-byte
-        let opcode2: u16 = self.memory[(self.pc + 1) as usize] as u16;
-        //OR the two two-byte numbers (one "big end" and one "small end") to combine them
-        let opcode = opcode1 | opcode2;
+    //"chip8 headless <romfile> ..." runs the ROM without a window and prints the final state
+    if args.first().map(|arg| arg.as_str()) == Some("headless") {
+        headless::run(&args[1..]);
+        return;
+    }
 
-        opcode
+    //"chip8 bench-rom <kind> <outfile>" writes a synthetic benchmark ROM, "chip8 bench" times them all,
+    //"chip8 keytest" opens the keypad tester, "chip8 test-suite <dir>" runs a directory of ROMs headless,
+    //"chip8 rom trim|pad|export" prepares ROM files, "chip8 selftest" checks the core against the spec,
+    //"chip8 state-diff" compares two save states, "chip8 agent <romfile>" plays episodes with a built-in agent,
+    //"chip8 terminal <romfile>" shows the ROM in the terminal, "chip8 thumbs <dir>" saves a picture of every ROM,
+    //"chip8 doctor" checks the setup the window needs, "chip8 --features" reports what this build supports
+    match args.first().map(|arg| arg.as_str()) {
+        Some("bench-rom") => return benchrom::run_generate(&args[1..]),
+        Some("bench") => return benchrom::run_bench(&args[1..]),
+        Some("keytest") => return keytest::run(&args[1..]),
+        Some("doctor") => return doctor::run(&args[1..]),
+        Some("test-suite") => return testsuite::run(&args[1..]),
+        Some("rom") => return romtool::run(&args[1..]),
+        Some("selftest") => return selftest::run(&args[1..]),
+        Some("state-diff") => return statediff::run(&args[1..]),
+        Some("stats") => return stats::run(&args[1..]),
+        Some("thumbs") => return thumbs::run(&args[1..]),
+        Some("agent") => return episodes::run(&args[1..]),
+        Some("terminal") => return terminal::run(&args[1..]),
+        Some("--features") => return features::run(),
+        _ => {},
     }
 
-    pub fn draw(&mut self, window: &mut PistonWindow, event: &Event) {
-        let pixel_size = 8.0;
-        let x_size = 64;
-        let y_size = 32;
-
-        //Clear old screen
-        self.clear(window, event);
-
-        //Draw new screen
-        window.draw_2d(event, |c, g| {
-
-            //Step over each y "pixel" for each x above
-            for y in 0..y_size as usize {
-                //Step over each x "pixel"
-                for x in 0..x_size as usize {
-                    //If the screen contains a 1 at the current pixel...
-                    let index = x + (y * x_size as usize);
-                    if self.screen[index] == 1 {
-                        //println!("Found sprite at x:{} y:{} (index: {})", x, y, index);
-                        let x_pos = x as f64 * pixel_size;
-                        let y_pos = y as f64 * pixel_size;
-                        //println!("Drawing rect at x:{} ({}), y:{} ({})", x_pos, x, y_pos, y);
-                        Rectangle::new([1.0, 1.0, 1.0, 1.0])
-                            .draw([x_pos, y_pos, pixel_size, pixel_size], &c.draw_state, c.transform, g)
-                    }
-                }
+    //"chip8 session <file> [options]" runs the ROM of a session recording with its options, and plays it back
+    if args.first().map(|arg| arg.as_str()) == Some("session") {
+        args = match session::command_line(&args[1..]) {
+            Ok(args) => args,
+            Err(err) => {
+                println!("{}. Aborting", err);
+                process::exit(exitcode::FAILURE);
             }
-        });
+        };
     }
 
-    fn clear(&mut self, window: &mut PistonWindow, event: &Event) {
-        window.draw_2d(event, |_context, graphics| {
-            clear(color::BLACK, graphics);
-        });
+    //"chip8 learn [lesson] [options]" runs one of the built-in tutorial ROMs with narration
+    if args.first().map(|arg| arg.as_str()) == Some("learn") {
+        args = match learn::command_line(&args[1..]) {
+            Ok(Some(args)) => args,
+            Ok(None) => return,
+            Err(err) => {
+                println!("{}. Aborting", err);
+                process::exit(exitcode::FAILURE);
+            }
+        };
     }
 
-    fn clear_screenbuf(&mut self) {
-        self.screen = [0; 64 * 32];
+    //Load rom and options from arguments
+    let options = match Options::parse(&args) {
+        Ok(options) => Arc::new(options),
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::FAILURE);
+        }
+    };
+
+    if options.safe {
+        println!("Safe mode: plain CHIP-8 with the default keys, no per-ROM settings, profiles, pokes, scripts or extensions");
     }
 
-    //Pulls the current opcode in memory (at program counter) and performs it's required operations
-    pub fn emulate_cycle(&mut self) {
+    //screen size, portrait when the output is rotated sideways. A hi-res screen fits in at half the scale
+    let (columns, rows) = options.rotation.size(SCREEN_WIDTH, SCREEN_HEIGHT);
+    let width: u32 = columns as u32 * 8;
+    let height: u32 = rows as u32 * 8;
 
-        if self.halt_flag {
-            return;
-        }
+    //Kiosk mode runs fullscreen and borderless, and escape shouldn't let visitors quit
+    let mut window: PistonWindow = WindowSettings::new(
+        "Chip8",
+        [width, height]
+    )
+    .exit_on_esc(options.exit_on_esc && !options.kiosk)
+    .fullscreen(options.kiosk)
+    .decorated(!options.kiosk)
+    .build()
+    .unwrap();
 
-        //Fetch opcode
-        let opcode = self.read_opcode();
-
-        //Print opcode as a 6-digit hex number, including leading zeros and "0x" notation.
-        print!("Opcode: {:#06X} - ", opcode); //ie 0x0012
-
-        //Decode and execute opcode
-        //Check our first hex digit (nibble)
-        match opcode & FIRST_NIBBLE_MASK {
-            //0x0NNN opcodes
-            0x0000 => {
-                match opcode & FOURTH_NIBBLE_MASK {
-                    //0x0000 opcode (clear screen)
-                    0x0000 => {
-                        println!("Clear Screen");
-                        self.clear_screenbuf();
-                        self.next_instruction();
-                    },
-                    //0x00EE opcode (return from sub-process)
-                    0x000E => {
-                        println!("Returning to {:#06X}", self.stack[self.sp as usize]);
-                        //Set program counter to the address at the top of the stack
-                        self.pc = self.stack[self.sp as usize];
-                        //Move the stack pointer down one to "pop" the previous stack information
-                        self.sp -= 1;
-                        self.next_instruction()
-                    },
-                    _ => { println!("Unknown 0x000N opcode")}
-                }
-            },
-            //0x1NNN opcode (jmp nnn)
-            0x1000 => {
-                self.pc = opcode & LAST_THREE_MASK;
-                println!("Jumping to {:#06X}", self.pc);
-            },
-            //0x2NNN opcode (call subroutine: push pc to stack, jmp nnn)
-            0x2000 => {
-                //Move stack pointer up one because we are "pushing" data in
-                self.sp += 1;
-                //Push the current program counter into the stack at the "top"
-                self.stack[self.sp as usize] = self.pc;
-                //Jump to address NNN
-                self.pc = opcode & LAST_THREE_MASK;
-                println!("Call routine at {:#06X}", self.pc-512);
+    let mut kiosk = if options.kiosk {
+        //Hides the cursor
+        window.set_capture_cursor(true);
+        Some(Kiosk::new(options.playlist.clone(), options.kiosk_interval))
+    } else {
+        None
+    };
+
+    //Keep sending update events, even when no input is given, so the window keeps showing new frames
+    window.set_lazy(false);
+
+    //Where save states, SUPER-CHIP flags, play statistics and library thumbnails go
+    let mut storage = storage::by_name(&options.storage).unwrap();
+    if storage.name() != "file" {
+        println!("Keeping save states, SUPER-CHIP flags, play statistics and thumbnails in {} storage", storage.name());
+    }
+
+    //Create and initialize our Chip8 object, or let the player pick one from the library first
+    let first_game = if options.rom.is_empty() {
+        match launch_from_library(&mut window, &options, storage.as_mut()) {
+            Some(game) => Ok(game),
+            None => return,
+        }
+    } else {
+        start_game(&options, &PlaylistEntry::new(&options.rom, options.game_over_addr))
+    };
+    let mut game = match first_game {
+        Ok(game) => game,
+        Err(err) => match kiosk.as_mut() {
+            //The rest of the playlist may still work
+            Some(kiosk) => {
+                println!("{}, skipping it", err);
+                next_kiosk_game(kiosk, &options)
             },
-            //0x3XKK opcode (Skp next instruction if Vx == kk)
-            0x3000 => {
-                let x = ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize;
-                let kk = (opcode & LAST_TWO_MASK) as u8;
-                println!("SE V[{}] ({}), {}", x, self.v[x], kk);
-                if self.v[x] == kk {
-                    //Skip next instruction by adding 2 to the program counter (skipping 2 bytes or 1 opcode)
-                    self.next_instruction();
-                }
-                self.next_instruction();
+            None => {
+                println!("{}. Aborting", err);
+                process::exit(exitcode::ROM_LOAD);
             },
-            //0x4XKK opcode (Skp next instruction if Vx != kk)
-            0x4000 => {
-                let x = ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize;
-                let kk = (opcode & LAST_TWO_MASK) as u8;
-                println!("SNE V[{}] ({}), {}", x, self.v[x], kk);
-                if self.v[x] != kk {
-                    //Skip next instruction by adding 2 to the program counter (skipping 2 bytes or 1 opcode)
-                    self.next_instruction();
-                }
-                self.next_instruction();
+        },
+    };
+
+    //Play back recorded inputs instead of reading the keyboard
+    if let Some(ref path) = options.replay {
+        match Replay::load(path).and_then(|replay| ReplayPlayer::new(replay, &mut game.chip8)) {
+            Ok(player) => game.player = Some(player),
+            Err(err) => {
+                println!("{}. Aborting", err);
+                process::exit(exitcode::FAILURE);
+            }
+        }
+    }
+    if let Some(ref path) = options.session {
+        match session::start_playback(path, &mut game.chip8) {
+            Ok(player) => {
+                println!("Playing back session {}", path);
+                game.player = Some(player);
             },
-            //0x5XY0 (Skp next instruction if Vx == Vy)
-            0x5000 => {
-                let x = ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize;
-                let y = ((opcode & THIRD_NIBBLE_MASK) >> 4) as usize;
-                println!("SE V[{}] ({}), V[{}] ({})", x, self.v[x], y, self.v[y]);
-                if self.v[x] == self.v[y] {
-                    self.next_instruction();
+            Err(err) => {
+                println!("{}. Aborting", err);
+                process::exit(exitcode::FAILURE);
+            }
+        }
+    }
+    game.session.restart(&game.chip8);
+
+    let mut audio = audio::open_device(&options.audio_device);
+    println!("Using audio device {}", audio.name());
+
+    let mut rumble = options.rumble.map(|strength| {
+        //The name was checked when the options were parsed
+        let backend = rumble::open_device(&options.rumble_device).unwrap();
+        println!("Using rumble device {} (rumble is experimental, no backend drives real motors yet)", backend.name());
+        Rumble::new(backend, strength)
+    });
+
+    let latency = if options.latency { Some(LatencyMeter::new()) } else { None };
+
+    //From here on the game runs on its own thread (see emulation.rs), following the wall clock
+    let mut emulation = EmulationThread::start(Emulation::new(game, kiosk, latency, storage), Arc::clone(&options));
+
+    //The load-state menu, while it's open
+    let mut menu: Option<LoadMenu> = None;
+    //The memory viewer (F11), while it's open
+    let mut viewer: Option<MemoryViewer> = None;
+    //The command palette (Ctrl+P), while it's open, and the hotkey presses of the command it ran
+    let mut command_palette: Option<CommandPalette> = None;
+    let mut pending: VecDeque<Event> = VecDeque::new();
+    let mut ctrl = false;
+    //--inspect, the pixel under the mouse
+    let mut inspector = if options.inspect { Some(PixelInspector::new()) } else { None };
+    //--panels, kept up to date along with the game window
+    let mut panels = Panels::open(&options.panels, &emulation.lock().game.chip8);
+    //Cleared while the window is in the background, which pauses the game unless --no-focus-pause
+    let mut focused = true;
+
+    //Fast-forward runs while Tab is held. Only every TURBO_FRAME_SKIP-th frame is shown then, presenting
+    //every frame at 20x speed just makes the window lag behind
+    let mut turbo = false;
+    let mut frame: u64 = 0;
+
+    //Colors, and the effects changing them from frame to frame
+    let mut palette = PaletteEffects::new(options.palette);
+    for name in &options.palette_effects {
+        palette.add(palette::by_name(name).unwrap());
+    }
+    let started = Instant::now();
+
+    //F5 switches between the profiles, the current one's speed applies on top of turbo
+    let mut profiles = options.profiles.clone();
+
+    //F7 turns the output another 90 degrees
+    let mut display = DisplaySettings {
+        rotation: options.rotation,
+        sharp: options.sharp,
+        deflicker: false,   //Per game, see Game
+    };
+
+    while let Some(e) = pending.pop_front().or_else(|| window.next()) {
+        emulation.receive_frames();
+        let snapshot = emulation.frame();
+        //The thread waits while the window handles the event
+        let mut guard = emulation.lock();
+        let emu = &mut *guard;
+
+        //Only draw when the window asks for a new frame
+        if e.render_args().is_some() {
+            frame += 1;
+            let skip = turbo && menu.is_none() && !frame.is_multiple_of(TURBO_FRAME_SKIP);
+            //Not swapping keeps the last frame on screen instead of an undrawn buffer
+            window.set_swap_buffers(!skip);
+            //Before the game window draws, which leaves its context the current one
+            panels.update(emu);
+            if !skip {
+                let render_started = Instant::now();
+                let colors = palette.frame(&FrameInfo {
+                    frame,
+                    seconds: started.elapsed().as_secs_f64(),
+                    sound: snapshot.sound_active,
+                    sound_timer: snapshot.sound_timer,
+                });
+                draw_screen(snapshot, &mut window, &e, &colors, DisplaySettings { deflicker: emu.game.deflicker, ..display });
+                if let Some(ref controls) = emu.game.controls {
+                    controls.draw(&mut window, &e);
                 }
-                self.next_instruction();
-            },
-            //0x6XKK (Load Vx with kk)
-            0x6000 => {
-                let x = ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize;
-                let kk = (opcode & LAST_TWO_MASK) as u8;
-                println!("Load V[{}] ({}) with {}", x, self.v[x], kk);
-                self.v[x] = kk;
-                self.next_instruction();
-            },
-            //0x7XKK (Add Vx, kk)
-            0x7000 => {
-                let x = ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize;
-                let kk = (opcode & LAST_TWO_MASK) as u16;
-                println!("Add V[{}] ({}) with {}", x, self.v[x], kk);
-                //Add and keep only the last byte by masking.
-                self.v[x] = (self.v[x] as u16).overflowing_add(kk).0 as u8;
-                self.next_instruction();
-            },
-            //0x8XYN (Vx/Vy operations)
-            0x8000 => {
-                let x = ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize;
-                let y = ((opcode & THIRD_NIBBLE_MASK) >> 4) as usize;
-                //println!("X: {}, Y: {}", x, y );
-                match opcode & FOURTH_NIBBLE_MASK  {
-                    //0x8XY0 (MOV v[x], v[y])
-                    0x0000 => {
-                        println!("Mov V[{}] ({}), V[{}] ({})", x, self.v[x], y, self.v[y]);
-                        self.v[x] = self.v[y];
-                    },
-                    //0x8XY1 (OR v[x], v[y])
-                    0x0001 => {
-                        println!("Or V[{}] ({}), V[{}] ({})", x, self.v[x], y, self.v[y]);
-                        self.v[x] = self.v[x] | self.v[y];
-                    },
-                    //0x8XY2 (AND v[x], v[y])
-                    0x0002 => {
-                        println!("And V[{}] ({}), V[{}] ({})", x, self.v[x], y, self.v[y]);
-                        self.v[x] = self.v[x] & self.v[y];
-                    },
-                    //0x8XY3 (XOR v[x], v[y])
-                    0x0003 => {
-                        println!("Xor V[{}] ({}), V[{}] ({})", x, self.v[x], y, self.v[y]);
-                        self.v[x] = self.v[x] ^ self.v[y];
-                    },
-                    //0x8XY4 (ADD v[x], v[y])
-                    0x0004 => {
-                        println!("Add V[{}] ({}), V[{}] ({})", x, self.v[x], y, self.v[y]);
-                        //Set carry if addition goes over 8 bits
-                        let (new_value, overflow) = self.v[x].overflowing_add(self.v[y]);
-                        self.v[x] = new_value;
-                        if overflow {
-                            self.v[0x0f] = 1;
-                        } else {
-                            self.v[0x0f] = 0;
-                        }
-                    },
-                    //0x8XY5 (SUB v[x], v[y])
-                    0x0005 => {
-                        println!("Sub V[{}] ({}), V[{}] ({})", x, self.v[x], y, self.v[y]);
-                        if self.v[x] > self.v[y] {
-                            self.v[0x0f] = 1;
-                        } else {
-                            self.v[0x0f] = 0;
-                        }
-                        self.v[x] = self.v[x].overflowing_sub(self.v[y]).0;
-                    },
-                    //0x8XY6 (SHR v[x], 1)
-                    0x0006 => {
-                        println!("Shift Right V[{}] ({}), 1", x, self.v[x]);
-                        //If Most Significant Bit is 1, set VF to 1
-                        if(opcode & 0b1000_0000) == 0b1000_0000 {
-                            self.v[0x0f] = 1;
-                        }
-                        self.v[x] = self.v[x] >> 1;
-                    },
-                    //0x8XY7 (SUBN v[x], v[y])
-                    0x0007 => {
-                        println!("Subn V[{}] ({}), V[{}] ({})", x, self.v[x], y, self.v[y]);
-                        if self.v[y] > self.v[x] {
-                            self.v[0x0f] = 1;
-                        } else {
-                            self.v[0x0f] = 0;
-                        }
-                        self.v[x] = self.v[y].overflowing_sub(self.v[x]).0;
-                    },
-                    //0x8XY6 (SHL v[x], 1)
-                    0x000E => {
-                        println!("Shift Left V[{}] ({}), 1", x, self.v[x]);
-                        //If Least Significant Bit is 1, set VF to 1
-                        if (opcode & 0b0000_0001) == 0b0000_0001 {
-                            self.v[0x0f] = 1;
-                        }
-                        self.v[x] = self.v[x] << 1;
-                    },
-                    _ => { println!("Unknown 0x800N opcode")}
+                if let Some(ref latency) = emu.latency {
+                    latency.draw(&mut window, &e);
                 }
-                //None of the 8NNN opcodes affect the PC, so we can increment it at the end no matter what
-                self.next_instruction();
-            },
-            //0x9XY0 (Skip next instruction if Vx != Vy
-            0x9000 => {
-                let x = ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize;
-                let y = ((opcode & THIRD_NIBBLE_MASK) >> 4) as usize;
-
-                println!("SNE V[{}] ({}), V[{}] ({})", x, self.v[x], y, self.v[y]);
-                if self.v[x] != self.v[y] {
-                    self.next_instruction();
+                emu.budget.draw(&mut window, &e);
+                if let Some(ref inspector) = inspector {
+                    inspector.draw(&emu.game.chip8, &mut window, &e, display);
                 }
-                self.next_instruction();
-            },
-            //0xANNN opcode (mv i, NNN)
-            0xA000 => {
-                self.i = opcode & LAST_THREE_MASK;
-                println!("Changing index to {:}d", self.i);
-                self.next_instruction();
-            },
-            //0xBNNN opcode (jmp NNN + V0)
-            0xB000 => {
-                println!("Jmp NNN + V[0]");
-                self.pc = (opcode & LAST_THREE_MASK) + self.v[0] as u16;
-            },
-            //0xCXNN opcode (rnd Vx, byte AND NN)
-            0xC000 => {
-                let x = (opcode & SECOND_NIBBLE_MASK) >> 8;
-                let n = opcode & LAST_TWO_MASK;
-                let rand = rand::random::<u16>();
-
-                println!("V[{}] ({}) = n: {} & {}", x, self.v[x as usize], n, rand);
-                self.v[x as usize] = (rand & n) as u8;
-                self.next_instruction();
-
-            }
-            //0xDxyn opcode
-            0xD000 => {
-                //Tell the screen that it has to refresh after this operation
-                self.draw_flag = true;
-
-                //X Coord to draw at
-                let x = self.v[((opcode & SECOND_NIBBLE_MASK) >> 8) as usize] as usize;
-                //Y Coord to draw at
-                let y = self.v[((opcode & THIRD_NIBBLE_MASK) >> 4) as usize] as usize;
-                //line height of the sprite (width is ALWAYS 8)
-                let height = (opcode & FOURTH_NIBBLE_MASK) as usize;
-
-                //Unset our collision flag
-                self.v[0x0F] = 0;
-
-                println!("Draw Sprite starting at mem[{}] at loc x:{}, y:{} with height:{}", self.i, x, y, height);
-
-                //Holds the current pixel data
-                let mut pixel_line: u8;
-
-                //For each line in the sprite from 0 to the sprite's height
-                for yline in 0..height {
-                    //Grab our sprite's 8-bit pixel line at this spot
-                    pixel_line = self.memory[self.i as usize + yline];
-                    //For each pixel (bit) in the line... (always width of 8, remember!)
-                    for xline in 0..8 {
-                        //If the current bit is set...
-                        if (pixel_line >> (7 - xline)) & 0b00000001 != 0 { //this hack separates each bit in the pixel line by masking it and then rotating the bits to the right until they are in the 1s place
-
-                            let index: usize =  x + xline + ((y + yline) * 64);
-                            if index >= 2048 {
-                                //break;
-                                continue;
-                            }
-
-                            //Check for pixel collision
-                            if self.screen[index] == 1 {
-                                //If there is a collision, set the collision register VF to 1
-                                self.v[0xF] = 1;
-                            }
-                            //Set the value of the line by XORing our sprite's current line onto it
-                            self.screen[index] ^= 1;
-                        }
-                    }
+                if let Some(ref viewer) = viewer {
+                    viewer.draw(&emu.game.chip8, &mut window, &e);
                 }
-                self.next_instruction();
-            },
-            //0xE0NN opcodes
-            0xE000 => {
-                match opcode & LAST_TWO_MASK {
-                    //0xEx9E Skip next instruct if key with value of Vx is pressed
-                    0x009E => {
-                        let x = ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize;
-                        println!("SN if Key[{}] (v={}) is pressed", self.v[x], x);
-                        if self.key[self.v[x] as usize] == 1 {
-                            self.next_instruction();
-                        }
-                        self.next_instruction();
-                    },
-                    //0xEx9E Skip next instruct if key with value of Vx is not pressed
-                    0x00A1 => {
-                        let x = ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize;
-                        println!("SN if Key[{}] (v={}) is not pressed", self.v[x], x);
-                        if self.key[self.v[x] as usize] == 0 {
-                            self.next_instruction();
-                        }
-                        self.next_instruction();
-                    },
-                    _ => {
-                        println!("Unknown 0xE000 opcode");
-                    }
+                if let Some(ref menu) = menu {
+                    menu.draw(&mut window, &e);
                 }
-            },
-            //0xFXNN opcodes
-            0xF000 => {
-                let x = ((opcode & SECOND_NIBBLE_MASK) >> 8) as usize;
-                match opcode & LAST_TWO_MASK  {
-                    //0xFX07 (mv v[x], delay_timer)
-                    0x0007 => {
-                        println!("Mv V[{}] ({}), delay_timer", x, self.v[x]);
-                        self.v[x] = self.delay_timer;
-                        self.next_instruction();
-                    },
-                    //Wait for key press, store value of key in Vx
-                    //All execution stops until a key is pressed
-                    0x000A => {
-                        let x = (opcode & THIRD_NIBBLE_MASK) >> 8;
-                        println!("Wait for key press to store in v[{}]", x);
-                        self.halt_flag = true;
-                        self.halt_reg = x as u8;
-                        self.next_instruction();
-                    },
-                    //0xFX15 (mov delay_timer, v[x])
-                    0x0015 => {
-                        println!("Mov delay_timer, V[{}] ({})", x, self.v[x]);
-                        self.delay_timer = self.v[x];
-                        self.next_instruction();
-                    },
-                    //0xFX18 (mov sound_timer, v[x])
-                    0x0018 => {
-                        println!("Mov sound_timer, V[{}] ({})", x, self.v[x]);
-                        self.sound_timer = self.v[x];
-                        self.next_instruction();
-                    },
-                    //0xFX1E (add i, v[x])
-                    0x001E => {
-                        println!("Add V[{}] ({}) to index", x, self.v[x]);
-                        self.i += self.v[x] as u16;
-                        self.next_instruction();
-                    },
-                    0x0029 => {
-                        println!("Set I = location of sprite for digit Vx");
-                        self.i = self.v[x] as u16 * 5;
-                        self.next_instruction();
-                    },
-                    0x0033 => {
-                        println!("Store BCD of Vx in memory at location i, i+1, i+2");
-                        //Take each numbers place in V[x] and separate them to store in separate memory locations
-                        let bcd = self.v[x];
-                        self.memory[self.i as usize] = bcd / 100;
-                        self.memory[self.i as usize + 1] = (bcd / 10) % 10;
-                        self.memory[self.i as usize + 2] = (bcd % 100) % 10;
-
-                        self.next_instruction();
-                    },
-                    0x0055 => {
-                        println!("Stores registers V0 through V{} in memory starting at location {:#06X}", x, self.i);
-                        for n in 0..x {
-                           self.memory[self.i as usize + n] = self.v[n];
-                        }
-                        self.next_instruction();
-                    },
-                    0x0065 => {
-                        println!("Read registers V0 through Vx from memory starting at location I");
-                        for n in 0..x {
-                            self.v[n] = self.memory[self.i as usize + n];
-                        }
-                        self.next_instruction();
-                    },
-                    _ => { println!("Unknown 0xF0NN opcode")},
+                if let Some(ref command_palette) = command_palette {
+                    command_palette.draw(&mut window, &e);
                 }
+                emu.budget.add_render(render_started.elapsed().as_secs_f64());
             }
-            _ => {
-                println!("Unknown opcode {:}", opcode);
-            },
         }
 
-        //Update timer(s)
-        if self.delay_timer > 0 {
-            self.delay_timer -= 1;
+        if let Some(focus) = e.focus_args() {
+            if options.focus_pause && focus != focused {
+                println!("{}", if focus { "Resumed" } else { "Paused while the window is in the background" });
+                //Nothing else gets to turn the sound off while paused
+                audio.set_beep(false);
+                if let Some(ref mut rumble) = rumble {
+                    rumble.update(false);
+                }
+            }
+            focused = focus;
         }
 
-        if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                //Make a beep noise
-                println!("BEEP!");
+        //Gamepads only show up once they're used
+        if let Some(ref mut rumble) = rumble {
+            match (e.button_args().map(|button| button.button), e.controller_axis_args()) {
+                (Some(Button::Controller(button)), _) => rumble.pad_seen(button.id),
+                (_, Some(axis)) => rumble.pad_seen(axis.id),
+                _ => {},
             }
-            self.sound_timer -= 1;
         }
 
-        if self.draw_flag == true {
-
-            //Draw the screen
-            //self.draw(window, event);
-
-            //Unset our draw flag for the next op
-            self.draw_flag = false;
+        if let (Some(cursor), Some(inspector)) = (e.mouse_cursor_args(), inspector.as_mut()) {
+            inspector.hover(cursor, &window, display, &emu.game.chip8);
+        }
+        if let (Some(_), Some(inspector)) = (e.resize_args(), inspector.as_mut()) {
+            inspector.refresh(&window, display, &emu.game.chip8);
         }
 
-    }
-
-    //Print the bytes in memory between the given range (for debugging purposes)
-    pub fn print_memory(&self, range: Range<usize>) {
-        for i in range {
-            println!("{:#04X}", self.memory[i]);
+        if let (Some(text), Some(open)) = (e.text_args(), command_palette.as_mut()) {
+            open.text(&text);
+            window.set_title(format!("Chip8 - {}", open.describe()));
         }
-    }
-}
 
-fn key_translator(button: ButtonArgs) -> Result<(u8, u8), String> {
+        //Set/unset keys, unless a replay is doing the typing
+        if let Some(button) = e.button_args() {
+            let pressed = button.state == ButtonState::Press;
+            if matches!(button.button, Button::Keyboard(Key::LCtrl) | Button::Keyboard(Key::RCtrl)) {
+                ctrl = pressed;
+            }
+            //Ctrl+P opens and closes the command palette, which gets every key while it's open
+            if ctrl && button.button == Button::Keyboard(Key::P) {
+                if pressed {
+                    command_palette = match command_palette {
+                        Some(_) => None,
+                        None => Some(CommandPalette::open()),
+                    };
+                    let title = command_palette.as_ref().map_or(String::new(), |open| format!(" - {}", open.describe()));
+                    window.set_title(format!("Chip8{}", title));
+                }
+                continue;
+            }
+            if let (Some(open), Button::Keyboard(key)) = (command_palette.as_mut(), button.button) {
+                if !pressed {
+                    continue;
+                }
+                let action = open.key(key);
+                window.set_title(format!("Chip8 - {}", open.describe()));
+                match action {
+                    Some(Action::Hotkey(key)) => {
+                        for state in [ButtonState::Press, ButtonState::Release] {
+                            let button = ButtonArgs { state, button: Button::Keyboard(key), scancode: None };
+                            pending.push_back(Event::Input(Input::Button(button)));
+                        }
+                    },
+                    Some(Action::Speed(speed)) => {
+                        emu.profile_speed = speed;
+                        println!("Speed set to {}x", speed);
+                    },
+                    Some(Action::Panel(kind)) => panels.toggle(kind, &emu.game.chip8),
+                    None => continue,
+                }
+                command_palette = None;
+                window.set_title(String::from("Chip8"));
+                continue;
+            }
+            if button.button == Button::Keyboard(Key::Tab) {
+                turbo = button.state == ButtonState::Press;
+                emu.turbo = turbo;
+                continue;
+            }
+            if let (Button::Keyboard(Key::Backspace), Some(rewind)) = (button.button, emu.game.rewind.as_ref()) {
+                let pressed = button.state == ButtonState::Press;
+                if pressed && !emu.rewinding {
+                    println!("Rewinding, {} frames kept in {} bytes", rewind.len(), rewind.bytes());
+                }
+                emu.rewinding = pressed;
+                continue;
+            }
+            if button.button == Button::Keyboard(Key::F5) {
+                if button.state == ButtonState::Press {
+                    if profiles.is_empty() {
+                        println!("No profiles to switch between (see profile.rs)");
+                    } else {
+                        let profile = profiles.next();
+                        let settings = profile.apply(&options, &mut emu.game.chip8);
+                        palette.set_base(settings.palette);
+                        emu.profile_speed = settings.speed;
+                        println!("Switched to {}", profile.name);
+                    }
+                }
+                continue;
+            }
+            if button.button == Button::Keyboard(Key::F6) {
+                if button.state == ButtonState::Press {
+                    emu.game.deflicker = !emu.game.deflicker;
+                    println!("Deflicker {}", if emu.game.deflicker { "on" } else { "off" });
+                }
+                continue;
+            }
+            if button.button == Button::Keyboard(Key::F7) {
+                if button.state == ButtonState::Press {
+                    display.rotation = display.rotation.next();
+                    println!("Display rotated {} degrees", display.rotation.degrees());
+                    if let Some(ref mut inspector) = inspector {
+                        inspector.refresh(&window, display, &emu.game.chip8);
+                    }
+                }
+                continue;
+            }
+            if button.button == Button::Keyboard(Key::F12) && !options.library.is_empty() && emu.kiosk.is_none() {
+                if button.state == ButtonState::Press {
+                    audio.set_beep(false);
+                    if let Some(picked) = launch_from_library(&mut window, &options, emu.storage.as_mut()) {
+                        emu.set_game(picked);
+                        emu.game.session.restart(&emu.game.chip8);
+                        viewer = None;
+                        menu = None;
+                    }
+                    //Time spent in the launcher isn't owed to the game
+                    emu.clock.tick();
+                }
+                continue;
+            }
+            if button.button == Button::Keyboard(Key::F11) {
+                if button.state == ButtonState::Press {
+                    viewer = match viewer {
+                        Some(_) => None,
+                        None => Some(MemoryViewer::open(&emu.game.chip8)),
+                    };
+                }
+                continue;
+            }
+            if let (Some(ref mut viewer), Button::Keyboard(key)) = (viewer.as_mut(), button.button) {
+                if button.state == ButtonState::Press && viewer.key(key, &mut emu.game.chip8) {
+                    continue;
+                }
+            }
+            if let (Some(ref mut inspector), Button::Mouse(mouse)) = (inspector.as_mut(), button.button) {
+                if pressed {
+                    match mouse {
+                        MouseButton::Right => inspector.toggle_pause(),
+                        MouseButton::Left if emu.paused => emu.redraw |= inspector.flip(&mut emu.game.chip8),
+                        MouseButton::Left => println!("Pixel inspector: pause first (right click) to flip pixels"),
+                        _ => {},
+                    }
+                }
+                continue;
+            }
+            if handle_hotkey(button, &mut emu.game, &mut menu, emu.storage.as_mut()) {
+                continue;
+            }
 
-    let state = match button.state {
-        ButtonState::Press => 1,
-        ButtonState::Release => 0,
-    };
+            //Key translation (1234, qwer, asdf, zxcv hex keyboard, plus the --keymap bindings)
+            if let Some((key, state)) = emu.game.input.button(button) {
+                emulation.send_key(key, state);
+            }
+        };
 
-    let key = match button.button {
-        Button::Keyboard(Key::D1) => 1,
-        Button::Keyboard(Key::D2) => 2,
-        Button::Keyboard(Key::D3) => 3,
-        Button::Keyboard(Key::D4) => 0x0C,
-        Button::Keyboard(Key::Q) => 4,
-        Button::Keyboard(Key::W) => 5,
-        Button::Keyboard(Key::E) => 6,
-        Button::Keyboard(Key::R) => 0x0D,
-        Button::Keyboard(Key::A) => 7,
-        Button::Keyboard(Key::S) => 8,
-        Button::Keyboard(Key::D) => 9,
-        Button::Keyboard(Key::F) => 0x0E,
-        Button::Keyboard(Key::Z) => 0x0A,
-        Button::Keyboard(Key::X) => 0,
-        Button::Keyboard(Key::C) => 0x0B,
-        Button::Keyboard(Key::V) => 0x0F,
-        _ => 255,
-    };
+        if let Some(update) = e.update_args() {
+            if let (Some(inspector), None) = (inspector.as_mut(), command_palette.as_ref()) {
+                if let Some(title) = inspector.title(&emu.game.chip8) {
+                    window.set_title(title);
+                }
+            }
 
-    if key == 255 {
-        return Err(String::from("Unknown key"));
-    }
+            //The game is paused while the load menu is open, the memory viewer or the pixel inspector paused it or the
+            //window is in the background (a panel having the focus doesn't count)
+            let inspector_paused = inspector.as_ref().is_some_and(|inspector| inspector.paused);
+            let viewer_paused = viewer.as_ref().is_some_and(|viewer| viewer.paused) || panels.paused();
+            emu.paused = menu.is_some() || command_palette.is_some() || viewer_paused || inspector_paused || (options.focus_pause && !focused && !panels.focused());
+            if emu.paused {
+                continue;
+            }
 
+            if emu.game.controls.as_mut().is_some_and(|controls| !controls.update(update.dt)) {
+                emu.game.controls = None;
+            }
 
-    Ok((key, state))
-}
+            audio.set_beep(snapshot.beep);
+            if let Some(ref mut rumble) = rumble {
+                rumble.update(snapshot.beep);
+            }
 
-fn main() {
-    //Load rom from arguments
-    let args: Vec<String> = env::args().collect();
-    let romname: &str;
-    if args.len() == 1 {
-        println!("No Romfile given. Aborting");
-        return;
-    } else {
-        romname = &args[1];
+            if emu.stopped {
+                break;
+            }
+        }
     }
 
-    //screen size
-    let width: u32 = 64 * 8;
-    let height: u32 = 32 * 8;
-
-    let mut window: PistonWindow = WindowSettings::new(
-        "Chip8",
-        [width, height]
-    )
-    .exit_on_esc(true)
-    .build()
-    .unwrap();
-
-    //Update screen, even when no input is given
-    //This makes sure our emulation cycle (which is tied to game loop) keeps running
-    window.set_lazy(false);
-
-    //Create and initialize our Chip8 object
-    let mut chip8 = Chip8::new();
-    chip8.initialize();
-
-    //Load up our ROM into program memory
-    chip8.load_rom(romname);
-
-    while let Some(e) = window.next() {
-
-        //Always draw the screen
-        chip8.draw(&mut window, &e);
-
-        //Set/unset keys
-        if let Some(button) = e.button_args() {
-            //Key translation (1234, qwer, asdf, zxcv hex keyboard)
-            match key_translator(button) {
-                Ok((key, state)) => {
-                    chip8.set_key(key, state);
-                    if chip8.halt_flag {
-                        chip8.v[chip8.halt_reg as usize] = key;
-                        chip8.halt_flag = false;
-                    }
-                },
-                Err(err) => println!("{}", err)
-            }
-        };
+    let mut emulation = emulation.stop();
+    emulation.game.chip8.diagnostics().print_summary();
 
-        //While the program counter is within an acceptable range...
-        if chip8.pc > 4096 {
-            println!("Accessing invalid memory, aborting");
-            return;
+    if let Some(ref path) = options.record {
+        match emulation.recording.save(path) {
+            Ok(()) => println!("Saved inputs to {}", path),
+            Err(err) => println!("{}", err),
         }
-        //Emulate a CPU cycle
-        chip8.emulate_cycle();
+    }
+
+    emulation.game.stats.finish(emulation.storage.as_mut());
+    if emulation.fatal {
+        process::exit(exitcode::EMULATION_ERROR);
     }
 }
@@ -0,0 +1,98 @@
+/************
+Memory bus
+
+The 4KB of RAM, behind a type that checks every address so no opcode (FX55 or FX33 after an FX1E
+pushed I up, a tall DXYN near the end) can index past the array and take the process down. What
+an address past 0xFFF means is picked with --address-mode:
+
+fault - the access is refused with OutOfBounds, which the core turns into a fault that stops the
+        program at the instruction (the default, see error.rs)
+wrap  - the address wraps around to 0x000, like interpreters that only keep 12 bits of I. Sprites
+        running off the end continue at the start of memory instead of being cut short
+
+Only data accesses go through the mode. A program counter past the end of memory always stops
+the program, and the read_byte()/opcode_at() peeks for tools always wrap.
+************/
+pub const MEMORY_SIZE: usize = 4096;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AddressMode {
+    Fault,
+    Wrap,
+}
+
+impl AddressMode {
+    pub const NAMES: &'static [&'static str] = &["fault", "wrap"];
+
+    #[cfg(feature = "std")]
+    pub fn parse(text: &str) -> Result<AddressMode, String> {
+        match text {
+            "fault" => Ok(AddressMode::Fault),
+            "wrap" => Ok(AddressMode::Wrap),
+            _ => Err(format!("Unknown address mode {} (expected fault or wrap)", text)),
+        }
+    }
+}
+
+//An access to an address the memory doesn't have
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutOfBounds {
+    pub addr: usize,
+}
+
+#[derive(Clone)]
+pub struct Memory {
+    bytes: [u8; MEMORY_SIZE],
+    mode: AddressMode,
+}
+
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory::new()
+    }
+}
+
+impl Memory {
+    //Zeroed memory in fault mode
+    pub fn new() -> Memory {
+        Memory { bytes: [0; MEMORY_SIZE], mode: AddressMode::Fault }
+    }
+
+    pub fn mode(&self) -> AddressMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: AddressMode) {
+        self.mode = mode;
+    }
+
+    //Where in the array an address lands
+    pub fn resolve(&self, addr: usize) -> Result<usize, OutOfBounds> {
+        match self.mode {
+            _ if addr < MEMORY_SIZE => Ok(addr),
+            AddressMode::Wrap => Ok(addr % MEMORY_SIZE),
+            AddressMode::Fault => Err(OutOfBounds { addr }),
+        }
+    }
+
+    pub fn read8(&self, addr: usize) -> Result<u8, OutOfBounds> {
+        Ok(self.bytes[self.resolve(addr)?])
+    }
+
+    pub fn write8(&mut self, addr: usize, value: u8) -> Result<(), OutOfBounds> {
+        let index = self.resolve(addr)?;
+        self.bytes[index] = value;
+        Ok(())
+    }
+
+    //All of memory, for copying it out whole (save states)
+    pub fn as_slice(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    //For restoring a save state, everything else writes through write8()
+    #[cfg(feature = "std")]
+    pub(crate) fn as_mut_slice(&mut self) -> &mut [u8] {
+        &mut self.bytes
+    }
+}
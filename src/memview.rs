@@ -0,0 +1,151 @@
+/************
+Memory viewer
+
+F11 opens a hex view of memory over the game, ROWS rows of ROW_BYTES bytes each, read straight
+from the machine every frame so it follows along while the program runs. Bytes the program wrote
+in the last second light up (fading out), the bytes at the PC are green and the cursor is blue.
+Only hex is shown, the font sprites are the only glyphs there are to draw with.
+
+Keys while the viewer is open:
+    Up/Down/Left/Right   Move the cursor, the view scrolls along
+    PageUp/PageDown      Scroll a page
+    Return               Pause/resume the program
+    0-9, A-F             While paused, type over the byte under the cursor (high digit first)
+    F11                  Close the viewer
+************/
+use piston_window::*;
+
+use controls::draw_digit;
+use {Chip8, CYCLES_PER_SECOND};
+
+const ROWS: usize = 16;
+const ROW_BYTES: usize = 8;
+const PAGE: usize = ROWS * ROW_BYTES;
+
+//How long a write stays highlighted
+const HIGHLIGHT_CYCLES: u64 = CYCLES_PER_SECOND as u64;
+
+//Layout in font pixels: a digit is 4 wide plus 1 of spacing, rows are 5 high plus 2
+const DIGIT: f64 = 5.0;
+const ROW_HEIGHT: f64 = 7.0;
+const BYTE_WIDTH: f64 = DIGIT * 2.0 + 2.0;
+const ADDR_WIDTH: f64 = DIGIT * 3.0 + 3.0;
+const MARGIN: f64 = 2.0;
+
+pub struct MemoryViewer {
+    top: usize,     //Address of the first byte shown
+    cursor: usize,
+    low_digit: bool,    //Whether the next typed digit is the low one of the cursor byte
+    pub paused: bool,
+}
+
+impl MemoryViewer {
+    //Opens at the page the program is running in
+    pub fn open(chip8: &Chip8) -> MemoryViewer {
+        let cursor = chip8.pc() as usize % 4096;
+        MemoryViewer {
+            top: (cursor / PAGE) * PAGE,
+            cursor,
+            low_digit: false,
+            paused: false,
+        }
+    }
+
+    fn move_cursor(&mut self, offset: isize) {
+        self.cursor = (self.cursor as isize + offset).rem_euclid(4096) as usize;
+        self.low_digit = false;
+        //Scroll just far enough to keep the cursor in view
+        if self.cursor < self.top {
+            self.top = self.cursor / ROW_BYTES * ROW_BYTES;
+        } else if self.cursor >= self.top + PAGE {
+            self.top = (self.cursor / ROW_BYTES + 1) * ROW_BYTES - PAGE;
+        }
+    }
+
+    //Handles a key press. Returns false for keys the viewer doesn't use, so they reach the game
+    pub fn key(&mut self, key: Key, chip8: &mut Chip8) -> bool {
+        match key {
+            Key::Up => self.move_cursor(-(ROW_BYTES as isize)),
+            Key::Down => self.move_cursor(ROW_BYTES as isize),
+            Key::Left => self.move_cursor(-1),
+            Key::Right => self.move_cursor(1),
+            Key::PageUp => self.move_cursor(-(PAGE as isize)),
+            Key::PageDown => self.move_cursor(PAGE as isize),
+            Key::Return => {
+                self.paused = !self.paused;
+                println!("Memory viewer: program {}", if self.paused { "paused" } else { "resumed" });
+            },
+            _ => match hex_digit(key) {
+                Some(digit) if self.paused => {
+                    let addr = self.cursor as u16;
+                    let old = chip8.read_byte(addr);
+                    let value = if self.low_digit { old & 0xF0 | digit } else { old & 0x0F | digit << 4 };
+                    chip8.write_byte(addr, value);
+                    if self.low_digit {
+                        self.move_cursor(1);
+                    } else {
+                        self.low_digit = true;
+                    }
+                },
+                _ => return false,
+            },
+        }
+        true
+    }
+
+    pub fn draw(&self, chip8: &Chip8, window: &mut PistonWindow, event: &Event) {
+        window.draw_2d(event, |c, g| {
+            let view = c.get_view_size();
+            let width = ADDR_WIDTH + BYTE_WIDTH * ROW_BYTES as f64 + MARGIN * 2.0;
+            let height = ROW_HEIGHT * ROWS as f64 + MARGIN * 2.0;
+            let pixel = (view[0] / width).min(view[1] / height).floor().max(1.0);
+            rectangle([0.0, 0.0, 0.0, 0.85], [0.0, 0.0, width * pixel, height * pixel], c.transform, g);
+
+            let pc = chip8.pc() as usize;
+            for row in 0..ROWS {
+                let row_addr = (self.top + row * ROW_BYTES) % 4096;
+                let y = (MARGIN + row as f64 * ROW_HEIGHT) * pixel;
+                draw_number(row_addr, 3, [MARGIN * pixel, y], pixel, [0.5, 0.5, 0.5, 1.0], c, g);
+
+                for column in 0..ROW_BYTES {
+                    let addr = row_addr + column;
+                    let x = (MARGIN + ADDR_WIDTH + column as f64 * BYTE_WIDTH) * pixel;
+                    let cell = [x - pixel, y - pixel, (BYTE_WIDTH - 1.0) * pixel, (ROW_HEIGHT - 1.0) * pixel];
+                    if addr == self.cursor {
+                        rectangle([0.1, 0.3, 0.9, 1.0], cell, c.transform, g);
+                    } else if addr == pc || addr == pc + 1 {
+                        rectangle([0.0, 0.5, 0.1, 1.0], cell, c.transform, g);
+                    }
+
+                    //Recent writes fade from yellow back to white
+                    let age = chip8.last_write(addr as u16).map(|cycle| chip8.cycles().saturating_sub(cycle));
+                    let heat = match age {
+                        Some(age) if age < HIGHLIGHT_CYCLES => 1.0 - age as f32 / HIGHLIGHT_CYCLES as f32,
+                        _ => 0.0,
+                    };
+                    let color = [1.0, 1.0, 1.0 - heat, 1.0];
+                    draw_number(chip8.read_byte(addr as u16) as usize, 2, [x, y], pixel, color, c, g);
+                }
+            }
+        });
+    }
+}
+
+fn hex_digit(key: Key) -> Option<u8> {
+    let code = key as u32;
+    match key {
+        Key::D0 | Key::D1 | Key::D2 | Key::D3 | Key::D4 | Key::D5 | Key::D6 | Key::D7 | Key::D8 | Key::D9 => {
+            Some((code - Key::D0 as u32) as u8)
+        },
+        Key::A | Key::B | Key::C | Key::D | Key::E | Key::F => Some((code - Key::A as u32) as u8 + 10),
+        _ => None,
+    }
+}
+
+//Draws the lowest `digits` hex digits of value
+pub fn draw_number(value: usize, digits: usize, pos: [f64; 2], pixel: f64, color: [f32; 4], c: Context, g: &mut G2d) {
+    for i in 0..digits {
+        let digit = (value >> ((digits - 1 - i) * 4)) & 0xF;
+        draw_digit(digit as u8, [pos[0] + i as f64 * DIGIT * pixel, pos[1]], pixel, color, c, g);
+    }
+}
@@ -0,0 +1,156 @@
+/************
+ROM metadata database
+
+What the emulator knows about particular ROMs, looked up by a hash of the ROM file so renamed
+copies are still recognised. The database is a text file (--rom-db FILE, roms.db in the working
+directory by default), with a section per ROM:
+
+    [0x1A2B3C4D5E6F7081]        - FNV-1a hash of the ROM file, printed at startup for unknown ROMs
+    title Pong
+    controls 1=up 4=down        - CHIP-8 key (hex) and what it does in the game
+    deflicker                   - blend frames to hide sprite flicker for this ROM (see display.rs)
+    score 0x3A0 bcd             - where the game keeps its score, for the best scores in chip8 stats
+                                  (see stats.rs): a byte, or with bcd three BCD digits as FX33 stores them
+
+Blank lines and lines starting with # are ignored. The controls are shown when the ROM starts
+(see controls.rs).
+************/
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+
+use options::parse_number;
+use Chip8;
+
+pub const DEFAULT_DB: &str = "roms.db";
+
+#[derive(Clone, Debug)]
+pub struct Control {
+    pub key: u8,
+    pub action: String,
+}
+
+//Where a game keeps its score
+#[derive(Clone, Copy, Debug)]
+pub struct Score {
+    pub addr: u16,
+    pub bcd: bool,  //Hundreds, tens and ones in three bytes, instead of a single byte
+}
+
+impl Score {
+    fn parse(text: &str) -> Result<Score, String> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (addr, bcd) = match words.as_slice() {
+            [addr] => (*addr, false),
+            [addr, "bcd"] => (*addr, true),
+            _ => return Err(format!("Invalid score {} (expected ADDR or ADDR bcd)", text)),
+        };
+        let addr = parse_number(addr)?;
+        if addr as usize + if bcd { 3 } else { 1 } > 4096 {
+            return Err(format!("Invalid score address {:#06X}", addr));
+        }
+        Ok(Score { addr, bcd })
+    }
+
+    pub fn read(&self, chip8: &Chip8) -> u32 {
+        if self.bcd {
+            (0..3).fold(0, |score, digit| score * 10 + chip8.read_byte(self.addr + digit) as u32)
+        } else {
+            chip8.read_byte(self.addr) as u32
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RomInfo {
+    pub hash: u64,
+    pub title: Option<String>,
+    pub controls: Vec<Control>,
+    pub deflicker: bool,
+    pub score: Option<Score>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RomDb {
+    roms: Vec<RomInfo>,
+}
+
+//FNV-1a, the same hash the core uses for screens
+pub fn rom_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01B3);
+    }
+    hash
+}
+
+//Hashes a ROM file, None if it can't be read
+pub fn rom_file_hash(path: &str) -> Option<u64> {
+    let mut bytes = Vec::new();
+    File::open(path).and_then(|mut file| file.read_to_end(&mut bytes)).ok()?;
+    Some(rom_hash(&bytes))
+}
+
+fn parse_controls(words: &str) -> Result<Vec<Control>, String> {
+    words.split_whitespace().map(|word| {
+        let mut parts = word.splitn(2, '=');
+        let key = parts.next().and_then(|key| u8::from_str_radix(key, 16).ok()).filter(|key| *key <= 0xF);
+        match (key, parts.next()) {
+            (Some(key), Some(action)) if !action.is_empty() => Ok(Control { key, action: action.to_string() }),
+            _ => Err(format!("Invalid control {} (expected KEY=ACTION)", word)),
+        }
+    }).collect()
+}
+
+impl RomDb {
+    pub fn load(path: &str) -> Result<RomDb, String> {
+        let file = File::open(path).map_err(|err| format!("Could not open ROM database {}: {}", path, err))?;
+        let mut roms: Vec<RomInfo> = Vec::new();
+
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|err| format!("Could not read ROM database {}: {}", path, err))?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |err: String| format!("{}:{}: {}", path, number + 1, err);
+
+            if line.starts_with('[') && line.ends_with(']') {
+                let hash = &line[1..line.len() - 1];
+                let hash = hash.strip_prefix("0x").and_then(|hash| u64::from_str_radix(hash, 16).ok())
+                    .ok_or_else(|| invalid(format!("Invalid ROM hash {}", hash)))?;
+                roms.push(RomInfo { hash, ..RomInfo::default() });
+                continue;
+            }
+
+            let rom = roms.last_mut().ok_or_else(|| invalid(String::from("Setting before the first [ROM hash]")))?;
+            let (name, value) = match line.find(char::is_whitespace) {
+                Some(split) => (&line[..split], line[split..].trim()),
+                None => (line, ""),
+            };
+            match name {
+                "title" => rom.title = Some(value.to_string()),
+                "controls" => rom.controls = parse_controls(value).map_err(invalid)?,
+                "deflicker" => rom.deflicker = true,
+                "score" => rom.score = Some(Score::parse(value).map_err(invalid)?),
+                _ => return Err(invalid(format!("Unknown ROM setting {}", name))),
+            }
+        }
+
+        Ok(RomDb { roms })
+    }
+
+    //Loads the default database if there is one, an empty one otherwise
+    pub fn load_default() -> Result<RomDb, String> {
+        if Path::new(DEFAULT_DB).exists() {
+            RomDb::load(DEFAULT_DB)
+        } else {
+            Ok(RomDb::default())
+        }
+    }
+
+    pub fn lookup(&self, hash: u64) -> Option<&RomInfo> {
+        self.roms.iter().find(|rom| rom.hash == hash)
+    }
+}
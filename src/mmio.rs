@@ -0,0 +1,115 @@
+/************
+Memory-mapped pseudo-peripherals
+
+An optional extension for experimenting and for teaching memory-mapped IO. With --mmio ADDR, a
+few bytes of memory starting at ADDR stop being RAM: reads and writes the program makes there
+(FX55/FX65, FX33, DXYN sprites, even opcode fetches) go to host-side devices instead.
+It's off by default, since real ROMs expect plain RAM everywhere.
+
+Standard devices, in order from ADDR:
+    +0     serial console - writing a byte prints it as a character, reads give 0
+    +1 +2  millisecond timer - emulated time since the ROM started, high byte first, wrapping
+           at 65536. It counts emulated cycles, so replays and headless runs stay reproducible
+
+More devices can be attached to the bus with MmioBus::attach, they just implement Peripheral
+(and have to be Send + Sync, the bus is part of the Chip8).
+************/
+use std::io::{self, Write};
+
+use CYCLES_PER_SECOND;
+
+pub trait Peripheral: Send + Sync {
+    //How many bytes of address space the device takes
+    fn size(&self) -> u16;
+    //`offset` is relative to the start of the device, `cycles` is the machine's cycle count
+    fn read(&mut self, offset: u16, cycles: u64) -> u8;
+    fn write(&mut self, offset: u16, value: u8, cycles: u64);
+}
+
+pub struct SerialConsole;
+
+impl Peripheral for SerialConsole {
+    fn size(&self) -> u16 {
+        1
+    }
+
+    fn read(&mut self, _offset: u16, _cycles: u64) -> u8 {
+        0
+    }
+
+    fn write(&mut self, _offset: u16, value: u8, _cycles: u64) {
+        print!("{}", value as char);
+        io::stdout().flush().ok();
+    }
+}
+
+pub struct MillisTimer;
+
+impl Peripheral for MillisTimer {
+    fn size(&self) -> u16 {
+        2
+    }
+
+    fn read(&mut self, offset: u16, cycles: u64) -> u8 {
+        let millis = (cycles as f64 * 1000.0 / CYCLES_PER_SECOND) as u64 as u16;
+        if offset == 0 { (millis >> 8) as u8 } else { millis as u8 }
+    }
+
+    //The timer can't be set
+    fn write(&mut self, _offset: u16, _value: u8, _cycles: u64) {}
+}
+
+pub struct MmioBus {
+    base: u16,
+    end: u16,      //First address past the last device
+    devices: Vec<(u16, Box<dyn Peripheral>)>,   //Start address and device
+}
+
+impl MmioBus {
+    pub fn new(base: u16) -> MmioBus {
+        MmioBus {
+            base,
+            end: base,
+            devices: Vec::new(),
+        }
+    }
+
+    //The serial console and millisecond timer, starting at `base`
+    pub fn standard(base: u16) -> Result<MmioBus, String> {
+        let mut bus = MmioBus::new(base);
+        bus.attach(Box::new(SerialConsole))?;
+        bus.attach(Box::new(MillisTimer))?;
+        Ok(bus)
+    }
+
+    //Maps a device right after the ones already attached
+    pub fn attach(&mut self, device: Box<dyn Peripheral>) -> Result<(), String> {
+        let end = self.end as usize + device.size() as usize;
+        if end > 4096 {
+            return Err(format!("Memory-mapped devices at {:#06X} don't fit in memory", self.base));
+        }
+        self.devices.push((self.end, device));
+        self.end = end as u16;
+        Ok(())
+    }
+
+    pub fn contains(&self, addr: usize) -> bool {
+        addr >= self.base as usize && addr < self.end as usize
+    }
+
+    fn device(&mut self, addr: usize) -> Option<(u16, &mut Box<dyn Peripheral>)> {
+        self.devices.iter_mut()
+            .find(|&&mut (start, ref device)| addr >= start as usize && addr < (start + device.size()) as usize)
+            .map(|&mut (start, ref mut device)| (addr as u16 - start, device))
+    }
+
+    pub fn read(&mut self, addr: usize, cycles: u64) -> u8 {
+        self.device(addr).map(|(offset, device)| device.read(offset, cycles)).unwrap_or(0)
+    }
+
+    pub fn write(&mut self, addr: usize, value: u8, cycles: u64) {
+        if let Some((offset, device)) = self.device(addr) {
+            device.write(offset, value, cycles);
+        }
+    }
+}
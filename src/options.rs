@@ -0,0 +1,515 @@
+/************
+Command line options
+
+Usage: chip8 <romfile> [options]
+       chip8 --library DIR [options]
+
+--poke "ADDR=EXPR,..."  Write values into memory after the ROM is loaded (see poke.rs)
+--audio-device NAME     Audio output to use (see audio.rs), falls back to no audio if unavailable
+--rumble STRENGTH       Experimental: rumble gamepads (0 to 1) while the sound timer runs. Only logged for
+                        now, no backend drives real motors yet (see rumble.rs)
+--rumble-device NAME    Rumble output to use: log (default) or null
+--library DIR           ROM directory for the launcher (see library.rs), can be repeated. The romfile can be
+                        left out when there are library directories, the window then starts on the launcher
+--kiosk                 Fullscreen kiosk mode (see kiosk.rs)
+--playlist FILE         ROMs for kiosk mode to cycle through. The romfile can be left out when given
+--kiosk-interval SECS   Seconds before kiosk mode switches to the next ROM
+--attract               Kiosk mode where games play themselves from their recorded inputs (see replay.rs)
+--record FILE           Save the run's key presses to a replay file when the window closes
+--replay FILE           Play back a replay file instead of reading the keyboard
+--session FILE          Play back a session recording (F8 saves one, see session.rs), as "chip8 session FILE" does
+--lesson NAME           Narrate a tutorial lesson's ROM as it runs, as "chip8 learn NAME" does (see learn.rs)
+--load-addr ADDR        Load the ROM and start running at ADDR instead of 0x200, ie 0x600 for ETI-660 ROMs
+--font-addr ADDR        Put the font sprites (and where FX29 points) at ADDR instead of 0x050, ie 0 for ROMs
+                        that expect them at the start of memory
+--hooks FILE            Run a Rhai script's pre_cycle, post_cycle, on_draw and on_key functions along with the
+                        ROM, for cheats and automated playtesting (see scripting.rs)
+--load FILE@ADDR        Also load FILE into memory at ADDR, after the ROM. Can be repeated, later ones overlay
+                        earlier ones (and the ROM)
+--ips N                 Instructions per second (default 600), rounded to a multiple of the 60Hz timers
+--seed N                Seed for the random number generator (CXNN)
+--game-over-addr ADDR   Treat reaching ADDR as the game being over, instead of guessing (see gameover.rs)
+--protect MODE          Report bad memory accesses: off, warn or fault (see protect.rs)
+--stack MODE            What more than 16 nested calls do: strict (fault, default) or grow (see stack.rs)
+--draw-check MODE       What draws past the end of memory or the sprite limit do: warn (default) or strict (fault)
+--sprite-limit N        Tallest sprite DXYN may draw, 1 to 15 (default 15, see sprite.rs)
+--clip-collision MODE   How pixels clipped off the screen count towards VF: none (default), flag or rows
+--variant NAME          Machine to emulate: chip8 (default) or schip, SUPER-CHIP 1.1 with its hi-res screen (see variant.rs)
+--quirks LIST           Which interpreter the ambiguous instructions follow, ie vip or shift-vy,no-clip (see quirks.rs)
+--address-mode MODE     What data accesses past 0xFFF do: fault (default) or wrap (see memory.rs)
+--keymap FILE           Extra key bindings, ie alternate keys for the same CHIP-8 key (see input.rs)
+--latency               Print and show how long key presses take to reach the program (see latency.rs)
+--rom-db FILE           ROM database with titles and controls (see metadata.rs), default roms.db if it exists
+--profiles FILE         Named color, speed and quirk settings F5 switches between (see profile.rs),
+                        default profiles.cfg if it exists
+--auto-map              Also bind the ROM's left/right/up/down/shoot controls to the arrow keys and space
+--mmio ADDR             Map pseudo-peripherals (serial console, millisecond timer) over memory at ADDR (see mmio.rs)
+--extension NAME        Add an instruction set extension, can be repeated (see extension.rs)
+--fg RRGGBB             Color of lit pixels (white by default)
+--bg RRGGBB             Color of unlit pixels (black by default)
+--palette-effect NAME   Add a per-frame color effect, can be repeated (see palette.rs)
+--rotate DEGREES        Turn the output 90, 180 or 270 degrees clockwise (see display.rs)
+--sharp                 Scale by whole physical pixels, with no blending between them (see display.rs)
+--deflicker             Show pixels lit in this or the last frame, hiding sprite flicker (see display.rs)
+--storage NAME          Where save states and SUPER-CHIP flags are kept: file (default) or memory, for this session
+                        only (see storage.rs)
+--no-focus-pause        Keep running while the window is in the background (it pauses and goes quiet by default)
+--panels LIST           Open debug panels in windows of their own, a comma separated list of hex, disasm and
+                        registers (see panels.rs)
+--inspect               Show the coordinate and value of the pixel under the mouse in the title, right click
+                        pauses, left click flips the pixel while paused (see inspector.rs)
+--no-exit-on-esc        Don't quit when escape is pressed (kiosk mode never does)
+--turbo N               How many times faster than normal the game runs while Tab is held (default 20)
+--rewind SECS           Keep snapshots of the last SECS seconds, hold Backspace to run the game backwards
+                        through them (see snapshot.rs)
+--safe                  Run the ROM as plain CHIP-8, as a baseline when a ROM behaves weirdly: the variant, quirk,
+                        speed, load and font options go back to their defaults, pokes, --load, --hooks, --extension and
+                        --mmio are ignored, and so are the ROM database, the profiles, --keymap and --auto-map
+                        (the default keys only), --palette-effect and --deflicker
+************/
+use audio;
+use display::Rotation;
+use extension;
+use input::Keymap;
+use memory::AddressMode;
+use metadata::RomDb;
+use palette::{self, Palette};
+use panels::PanelKind;
+use kiosk::{self, PlaylistEntry};
+use library;
+use learn::{self, Lesson};
+use protect::{Protection, INTERPRETER_END};
+use quirks::Quirks;
+use rumble;
+use session;
+use sprite::{self, ClipCollision, DrawCheck, MAX_SPRITE_HEIGHT};
+use stack::StackMode;
+use storage;
+use variant::Variant;
+use poke::{self, Poke};
+use profile::Profiles;
+use {CYCLES_PER_SECOND, FONT_ADDR, PROGRAM_START};
+
+pub const DEFAULT_TURBO: u32 = 20;
+
+//An extra file to load into memory alongside the ROM (--load FILE@ADDR)
+#[derive(Clone, Debug)]
+pub struct Segment {
+    pub path: String,
+    pub addr: u16,
+}
+
+impl Segment {
+    pub fn parse(text: &str) -> Result<Segment, String> {
+        match text.rfind('@') {
+            Some(at) if at > 0 => Ok(Segment {
+                path: text[..at].to_string(),
+                addr: parse_number(&text[at + 1..])?,
+            }),
+            _ => Err(format!("Expected FILE@ADDR, got {}", text)),
+        }
+    }
+}
+
+pub struct Options {
+    pub rom: String,            //Empty when the game is to be picked from the library
+    pub library: Vec<String>,
+    pub pokes: Vec<Poke>,
+    pub audio_device: String,
+    pub rumble: Option<f32>,
+    pub rumble_device: String,
+    pub kiosk: bool,
+    pub playlist: Vec<PlaylistEntry>,
+    pub kiosk_interval: f64,
+    pub attract: bool,
+    pub record: Option<String>,
+    pub replay: Option<String>,
+    pub session: Option<String>,
+    pub lesson: Option<&'static Lesson>,
+    pub emulation_args: Vec<String>,   //The options a session recording needs, see session.rs
+    pub seed: Option<u64>,
+    pub load_addr: u16,
+    pub font_addr: u16,
+    pub ips: u32,
+    pub hooks: Option<String>,
+    pub segments: Vec<Segment>,
+    pub game_over_addr: Option<u16>,
+    pub protection: Protection,
+    pub stack_mode: StackMode,
+    pub draw_check: DrawCheck,
+    pub sprite_limit: usize,
+    pub clip_collision: ClipCollision,
+    pub variant: Variant,
+    pub quirks: Quirks,
+    pub address_mode: AddressMode,
+    pub turbo: u32,
+    pub rewind: Option<f64>,
+    pub keymap: Keymap,
+    pub latency: bool,
+    pub rom_db: RomDb,
+    pub profiles: Profiles,
+    pub auto_map: bool,
+    pub mmio: Option<u16>,
+    pub extensions: Vec<String>,
+    pub exit_on_esc: bool,
+    pub focus_pause: bool,
+    pub panels: Vec<PanelKind>,
+    pub inspect: bool,
+    pub palette: Palette,
+    pub palette_effects: Vec<String>,
+    pub rotation: Rotation,
+    pub sharp: bool,
+    pub deflicker: bool,
+    pub storage: String,
+    pub safe: bool,
+}
+
+impl Options {
+    //Parses the program arguments (not including the program name itself)
+    pub fn parse(args: &[String]) -> Result<Options, String> {
+        let mut rom = None;
+        let mut pokes = Vec::new();
+        let mut audio_device = String::from(audio::DEFAULT_DEVICE);
+        let mut rumble = None;
+        let mut rumble_device = String::from(rumble::DEFAULT_DEVICE);
+        let mut library = Vec::new();
+        let mut kiosk = false;
+        let mut playlist = Vec::new();
+        let mut kiosk_interval = kiosk::DEFAULT_INTERVAL;
+        let mut attract = false;
+        let mut record = None;
+        let mut replay = None;
+        let mut session = None;
+        let mut lesson = None;
+        let mut seed = None;
+        let mut load_addr = PROGRAM_START;
+        let mut font_addr = FONT_ADDR;
+        let mut ips = CYCLES_PER_SECOND as u32;
+        let mut hooks = None;
+        let mut segments = Vec::new();
+        let mut game_over_addr = None;
+        let mut protection = Protection::Off;
+        let mut stack_mode = StackMode::Strict;
+        let mut draw_check = DrawCheck::Warn;
+        let mut sprite_limit = MAX_SPRITE_HEIGHT;
+        let mut clip_collision = ClipCollision::None;
+        let mut variant = Variant::Chip8;
+        let mut quirks = Quirks::DEFAULT;
+        let mut address_mode = AddressMode::Fault;
+        let mut turbo = DEFAULT_TURBO;
+        let mut rewind = None;
+        let mut keymap = Keymap::default();
+        let mut latency = false;
+        let mut rom_db = None;
+        let mut profiles = None;
+        let mut auto_map = false;
+        let mut mmio = None;
+        let mut extensions = Vec::new();
+        let mut exit_on_esc = true;
+        let mut focus_pause = true;
+        let mut panels = Vec::new();
+        let mut inspect = false;
+        let mut palette = Palette::default();
+        let mut palette_effects = Vec::new();
+        let mut rotation = Rotation::None;
+        let mut sharp = false;
+        let mut deflicker = false;
+        let mut storage = String::from("file");
+
+        //Safe mode throws away the settings below once they're parsed, so they're still checked
+        let safe = args.iter().any(|arg| arg == "--safe");
+        let emulation_args = if safe { vec![String::from("--safe")] } else { session::emulation_args(args) };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--poke" => {
+                    let value = next_value(&mut args, arg)?;
+                    pokes.extend(poke::parse_pokes(value)?);
+                },
+                "--audio-device" => audio_device = next_value(&mut args, arg)?.to_string(),
+                "--rumble" => {
+                    let value = next_value(&mut args, arg)?;
+                    rumble = match value.parse::<f32>() {
+                        Ok(strength) if (0.0..=1.0).contains(&strength) => Some(strength),
+                        _ => return Err(format!("Invalid rumble strength {} (expected 0 to 1)", value)),
+                    };
+                },
+                "--rumble-device" => {
+                    let name = next_value(&mut args, arg)?;
+                    rumble::open_device(name)?;
+                    rumble_device = name.to_string();
+                },
+                "--library" => library.push(next_value(&mut args, arg)?.to_string()),
+                "--kiosk" => kiosk = true,
+                "--latency" => latency = true,
+                "--sharp" => sharp = true,
+                "--deflicker" => deflicker = true,
+                "--safe" => {},
+                "--no-exit-on-esc" => exit_on_esc = false,
+                "--no-focus-pause" => focus_pause = false,
+                "--inspect" => inspect = true,
+                "--panels" => {
+                    for name in next_value(&mut args, arg)?.split(',') {
+                        panels.push(PanelKind::parse(name.trim())?);
+                    }
+                },
+                "--auto-map" => auto_map = true,
+                "--lesson" => lesson = Some(learn::parse(next_value(&mut args, arg)?)?),
+                "--rom-db" => rom_db = Some(RomDb::load(next_value(&mut args, arg)?)?),
+                "--profiles" => profiles = Some(Profiles::load(next_value(&mut args, arg)?)?),
+                "--attract" => {
+                    attract = true;
+                    kiosk = true;
+                },
+                "--record" => record = Some(next_value(&mut args, arg)?.to_string()),
+                "--replay" => replay = Some(next_value(&mut args, arg)?.to_string()),
+                "--session" => session = Some(next_value(&mut args, arg)?.to_string()),
+                "--game-over-addr" => game_over_addr = Some(parse_number(next_value(&mut args, arg)?)?),
+                "--load" => segments.push(Segment::parse(next_value(&mut args, arg)?)?),
+                "--load-addr" => {
+                    load_addr = parse_number(next_value(&mut args, arg)?)?;
+                    if load_addr < PROGRAM_START || load_addr as usize >= 4096 {
+                        return Err(format!("Invalid load address {:#06X} (must be between 0x200 and 0xFFF)", load_addr));
+                    }
+                },
+                "--hooks" => hooks = Some(next_value(&mut args, arg)?.to_string()),
+                "--ips" => {
+                    let value = next_value(&mut args, arg)?;
+                    ips = match value.parse::<u32>() {
+                        Ok(ips) if ips > 0 => ips,
+                        _ => return Err(format!("Invalid instructions per second {}", value)),
+                    };
+                },
+                "--font-addr" => font_addr = parse_number(next_value(&mut args, arg)?)?,
+                "--mmio" => {
+                    let addr = parse_number(next_value(&mut args, arg)?)?;
+                    if (addr as usize) < INTERPRETER_END || addr as usize >= 4096 {
+                        return Err(format!("Invalid memory-mapped device address {:#06X}", addr));
+                    }
+                    mmio = Some(addr);
+                },
+                "--extension" => {
+                    let name = next_value(&mut args, arg)?;
+                    extension::by_name(name)?;
+                    extensions.push(name.to_string());
+                },
+                "--fg" => palette.foreground = palette::parse_color(next_value(&mut args, arg)?)?,
+                "--bg" => palette.background = palette::parse_color(next_value(&mut args, arg)?)?,
+                "--palette-effect" => {
+                    let name = next_value(&mut args, arg)?;
+                    palette::by_name(name)?;
+                    palette_effects.push(name.to_string());
+                },
+                "--storage" => {
+                    let name = next_value(&mut args, arg)?;
+                    storage::by_name(name)?;
+                    storage = name.to_string();
+                },
+                "--rotate" => rotation = Rotation::parse(next_value(&mut args, arg)?)?,
+                "--protect" => protection = Protection::parse(next_value(&mut args, arg)?)?,
+                "--stack" => stack_mode = StackMode::parse(next_value(&mut args, arg)?)?,
+                "--draw-check" => draw_check = DrawCheck::parse(next_value(&mut args, arg)?)?,
+                "--sprite-limit" => sprite_limit = sprite::parse_limit(next_value(&mut args, arg)?)?,
+                "--clip-collision" => clip_collision = ClipCollision::parse(next_value(&mut args, arg)?)?,
+                "--variant" => variant = Variant::parse(next_value(&mut args, arg)?)?,
+                "--quirks" => quirks = quirks.apply(next_value(&mut args, arg)?)?,
+                "--address-mode" => address_mode = AddressMode::parse(next_value(&mut args, arg)?)?,
+                "--seed" => {
+                    let value = next_value(&mut args, arg)?;
+                    seed = Some(value.parse::<u64>().map_err(|_| format!("Invalid seed {}", value))?);
+                },
+                "--turbo" => {
+                    let value = next_value(&mut args, arg)?;
+                    turbo = match value.parse::<u32>() {
+                        Ok(speed) if speed >= 1 => speed,
+                        _ => return Err(format!("Invalid turbo speed {}", value)),
+                    };
+                },
+                "--rewind" => {
+                    let value = next_value(&mut args, arg)?;
+                    rewind = match value.parse::<f64>() {
+                        Ok(seconds) if seconds > 0.0 && seconds.is_finite() => Some(seconds),
+                        _ => return Err(format!("Invalid rewind length {}", value)),
+                    };
+                },
+                "--keymap" => keymap = Keymap::load(next_value(&mut args, arg)?)?,
+                "--playlist" => playlist = kiosk::load_playlist(next_value(&mut args, arg)?)?,
+                "--kiosk-interval" => {
+                    let value = next_value(&mut args, arg)?;
+                    kiosk_interval = match value.parse::<f64>() {
+                        Ok(seconds) if seconds > 0.0 => seconds,
+                        _ => return Err(format!("Invalid kiosk interval {}", value)),
+                    };
+                },
+                _ if arg.starts_with("--") => return Err(format!("Unknown option {}", arg)),
+                _ => {
+                    if rom.is_some() {
+                        return Err(format!("Unexpected argument {}", arg));
+                    }
+                    rom = Some(arg.clone());
+                }
+            }
+        }
+
+        //Not even the default files are read in safe mode, in case they're what's wrong
+        let rom_db = match rom_db {
+            _ if safe => RomDb::default(),
+            Some(rom_db) => rom_db,
+            None => RomDb::load_default()?,
+        };
+        let library = library::directories(&library)?;
+        let profiles = match profiles {
+            _ if safe => Profiles::default(),
+            Some(profiles) => profiles,
+            None => Profiles::load_default()?,
+        };
+        if safe {
+            pokes.clear();
+            segments.clear();
+            hooks = None;
+            extensions.clear();
+            mmio = None;
+            load_addr = PROGRAM_START;
+            font_addr = FONT_ADDR;
+            ips = CYCLES_PER_SECOND as u32;
+            protection = Protection::Off;
+            stack_mode = StackMode::Strict;
+            draw_check = DrawCheck::Warn;
+            sprite_limit = MAX_SPRITE_HEIGHT;
+            clip_collision = ClipCollision::None;
+            variant = Variant::Chip8;
+            quirks = Quirks::DEFAULT;
+            address_mode = AddressMode::Fault;
+            keymap = Keymap::default();
+            auto_map = false;
+            palette_effects.clear();
+            deflicker = false;
+        }
+
+        //Without a romfile, start with the first ROM of the playlist
+        let rom = match rom {
+            Some(rom) => {
+                //The romfile starts the playlist in kiosk mode, so it gets cycled through too
+                if kiosk {
+                    playlist.insert(0, PlaylistEntry::new(&rom, game_over_addr));
+                }
+                rom
+            },
+            None => match playlist.first() {
+                Some(entry) => {
+                    game_over_addr = game_over_addr.or(entry.game_over);
+                    entry.rom.clone()
+                },
+                None if !library.is_empty() && !kiosk => String::new(),
+                None => return Err(String::from("No Romfile given")),
+            },
+        };
+
+        Ok(Options {
+            rom,
+            library,
+            pokes,
+            audio_device,
+            rumble,
+            rumble_device,
+            kiosk,
+            playlist,
+            kiosk_interval,
+            attract,
+            record,
+            replay,
+            session,
+            lesson,
+            emulation_args,
+            seed,
+            load_addr,
+            font_addr,
+            ips,
+            hooks,
+            segments,
+            game_over_addr,
+            protection,
+            stack_mode,
+            draw_check,
+            sprite_limit,
+            clip_collision,
+            variant,
+            quirks,
+            address_mode,
+            turbo,
+            rewind,
+            keymap,
+            latency,
+            rom_db,
+            profiles,
+            auto_map,
+            mmio,
+            extensions,
+            exit_on_esc,
+            focus_pause,
+            panels,
+            inspect,
+            palette,
+            palette_effects,
+            rotation,
+            sharp,
+            deflicker,
+            storage,
+            safe,
+        })
+    }
+}
+
+//How command line tools print their results
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl OutputFormat {
+    pub fn parse(text: &str) -> Result<OutputFormat, String> {
+        match text {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => Err(format!("Unknown format {} (expected text or json)", text)),
+        }
+    }
+}
+
+//Removes "NAME VALUE" from the arguments and returns the value, so tools can pick out their own
+//options before handing the rest to Options::parse
+pub fn take_value(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    match args.iter().position(|arg| arg == name) {
+        Some(index) => {
+            if index + 1 >= args.len() {
+                return Err(format!("Missing value for {}", name));
+            }
+            let value = args.remove(index + 1);
+            args.remove(index);
+            Ok(Some(value))
+        },
+        None => Ok(None),
+    }
+}
+
+//Grabs the value following an option, ie the "0x200" in "--poke 0x200=1"
+fn next_value<'a, I: Iterator<Item = &'a String>>(args: &mut I, option: &str) -> Result<&'a str, String> {
+    match args.next() {
+        Some(value) => Ok(value.as_str()),
+        None => Err(format!("Missing value for {}", option)),
+    }
+}
+
+//Parses a number written either in hex (0x prefix) or decimal
+pub fn parse_number(text: &str) -> Result<u16, String> {
+    let text = text.trim();
+    let result = if text.starts_with("0x") || text.starts_with("0X") {
+        u16::from_str_radix(&text[2..], 16)
+    } else {
+        text.parse::<u16>()
+    };
+
+    result.map_err(|_| format!("Invalid number {}", text))
+}
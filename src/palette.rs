@@ -0,0 +1,193 @@
+/************
+Screen colors and per-frame color effects
+
+The CHIP-8 screen only knows lit and unlit pixels, the Palette decides what colors those are on
+the host (--fg/--bg, white on black by default). Machines with two bit planes (see framebuffer.rs)
+use two more colors, for pixels lit on the second plane and on both. Palette effects get to change the palette
+before every frame is drawn, based on what the game is doing, without the core's XOR model
+knowing anything about it: an effect only ever sees a copy of the palette and a FrameInfo.
+
+To make an effect available from the command line (--palette-effect NAME), add it to by_name().
+Effects are applied in the order they were given, each one seeing the previous one's result.
+
+Bundled effects:
+    sound-flash   flashes the lit pixels red while the sound timer is running
+    sound-pulse   tints the background with the foreground color while the buzzer sounds, brighter for
+                  longer beeps, fading out after. For demo parties and stream overlays. This core has no
+                  XO-CHIP pitch register, so the tint follows the sound timer only
+    cycle         slowly cycles the lit pixels through the colors of the rainbow
+************/
+pub type Color = [f32; 4];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Palette {
+    pub background: Color,  //Unlit pixels
+    pub foreground: Color,  //Lit pixels
+    pub plane2: Color,      //Lit on the second plane only
+    pub blend: Color,       //Lit on both planes
+}
+
+impl Default for Palette {
+    fn default() -> Palette {
+        Palette {
+            background: [0.0, 0.0, 0.0, 1.0],
+            foreground: [1.0, 1.0, 1.0, 1.0],
+            plane2: [1.0, 0.4, 0.0, 1.0],
+            blend: [0.4, 0.13, 0.0, 1.0],
+        }
+    }
+}
+
+impl Palette {
+    //The color of a framebuffer palette index
+    pub fn color(&self, index: u8) -> Color {
+        match index {
+            0 => self.background,
+            1 => self.foreground,
+            2 => self.plane2,
+            _ => self.blend,
+        }
+    }
+}
+
+//Parses a color written as RRGGBB hex, with or without a leading #
+pub fn parse_color(text: &str) -> Result<Color, String> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    let value = match u32::from_str_radix(hex, 16) {
+        Ok(value) if hex.len() == 6 => value,
+        _ => return Err(format!("Invalid color {} (expected RRGGBB)", text)),
+    };
+    let channel = |shift: u32| ((value >> shift) & 0xFF) as f32 / 255.0;
+    Ok([channel(16), channel(8), channel(0), 1.0])
+}
+
+//What an effect gets to know about the frame being drawn
+pub struct FrameInfo {
+    pub frame: u64,     //Frames drawn so far
+    pub seconds: f64,   //Time since the emulator started
+    pub sound: bool,    //Whether the buzzer is sounding
+    pub sound_timer: u8,
+}
+
+pub trait PaletteEffect {
+    fn name(&self) -> &str;
+    //Changes the palette for the frame about to be drawn
+    fn apply(&mut self, info: &FrameInfo, palette: &mut Palette);
+}
+
+struct SoundFlash;
+
+//Frames the flash stays on, then off
+const FLASH_FRAMES: u64 = 4;
+
+impl PaletteEffect for SoundFlash {
+    fn name(&self) -> &str {
+        "sound-flash"
+    }
+
+    fn apply(&mut self, info: &FrameInfo, palette: &mut Palette) {
+        if info.sound && (info.frame / FLASH_FRAMES).is_multiple_of(2) {
+            palette.foreground = [1.0, palette.foreground[1] * 0.25, palette.foreground[2] * 0.25, palette.foreground[3]];
+        }
+    }
+}
+
+struct SoundPulse {
+    level: f32,     //How strong the tint is, 0 to 1
+}
+
+//How much of the tint is left after each frame of silence
+const PULSE_DECAY: f32 = 0.85;
+//Sound timer value (in 60Hz ticks) at which a beep gets the full tint
+const PULSE_FULL_TICKS: f32 = 30.0;
+//Strongest mix of the foreground into the background
+const PULSE_MIX: f32 = 0.4;
+
+impl PaletteEffect for SoundPulse {
+    fn name(&self) -> &str {
+        "sound-pulse"
+    }
+
+    fn apply(&mut self, info: &FrameInfo, palette: &mut Palette) {
+        if info.sound {
+            //A short blip still shows, a long tone lights up fully
+            let target = 0.5 + 0.5 * (info.sound_timer as f32 / PULSE_FULL_TICKS).min(1.0);
+            self.level = self.level.max(target);
+        } else {
+            self.level *= PULSE_DECAY;
+        }
+        let mix = self.level * PULSE_MIX;
+        for channel in 0..3 {
+            palette.background[channel] += (palette.foreground[channel] - palette.background[channel]) * mix;
+        }
+    }
+}
+
+struct Cycle;
+
+//Seconds for one trip around the color wheel
+const CYCLE_PERIOD: f64 = 6.0;
+
+impl PaletteEffect for Cycle {
+    fn name(&self) -> &str {
+        "cycle"
+    }
+
+    fn apply(&mut self, info: &FrameInfo, palette: &mut Palette) {
+        //Fully saturated hue, via the 6 sectors of the HSV hexagon
+        let hue = (info.seconds / CYCLE_PERIOD).fract() * 6.0;
+        let rising = hue.fract() as f32;
+        let [r, g, b] = match hue as u32 {
+            0 => [1.0, rising, 0.0],
+            1 => [1.0 - rising, 1.0, 0.0],
+            2 => [0.0, 1.0, rising],
+            3 => [0.0, 1.0 - rising, 1.0],
+            4 => [rising, 0.0, 1.0],
+            _ => [1.0, 0.0, 1.0 - rising],
+        };
+        palette.foreground = [r, g, b, palette.foreground[3]];
+    }
+}
+
+pub fn by_name(name: &str) -> Result<Box<dyn PaletteEffect>, String> {
+    match name {
+        "sound-flash" => Ok(Box::new(SoundFlash)),
+        "sound-pulse" => Ok(Box::new(SoundPulse { level: 0.0 })),
+        "cycle" => Ok(Box::new(Cycle)),
+        _ => Err(format!("Unknown palette effect {} (expected sound-flash, sound-pulse or cycle)", name)),
+    }
+}
+
+//The configured palette plus the effects run on it every frame
+pub struct PaletteEffects {
+    base: Palette,
+    effects: Vec<Box<dyn PaletteEffect>>,
+}
+
+impl PaletteEffects {
+    pub fn new(base: Palette) -> PaletteEffects {
+        PaletteEffects {
+            base,
+            effects: Vec::new(),
+        }
+    }
+
+    pub fn set_base(&mut self, base: Palette) {
+        self.base = base;
+    }
+
+    pub fn add(&mut self, effect: Box<dyn PaletteEffect>) {
+        println!("Using palette effect {}", effect.name());
+        self.effects.push(effect);
+    }
+
+    //The palette to draw the next frame with. Every frame starts over from the base palette, so
+    //effects don't pile up
+    pub fn frame(&mut self, info: &FrameInfo) -> Palette {
+        let mut palette = self.base;
+        for effect in self.effects.iter_mut() {
+            effect.apply(info, &mut palette);
+        }
+        palette
+    }
+}
@@ -0,0 +1,318 @@
+/************
+Debug panels
+
+--panels hex,disasm,registers opens debugging panels in OS windows of their own next to the game
+window, to arrange freely, ie over a second monitor while reverse engineering a ROM. They follow
+the running game, and are redrawn along with every frame of the game window:
+
+    hex         The memory viewer (see memview.rs), with its keys when its window has the focus
+    disasm      The code around the PC, an address and opcode per row with the PC's row
+                highlighted. The window title has the instruction at the PC disassembled.
+                Clicking a row runs to it: the game runs on until the PC reaches the row's
+                address (a temporary breakpoint, marked red), then holds there. Space lets it go on
+    registers   V0-V7 and V8-VF on the first two rows, then I, PC, SP, DT and ST, then the call
+                stack, oldest call first. Values that changed since the last frame are yellow
+
+Only hex is drawn, the font sprites are the only glyphs there are, so the titles say what's what.
+Closing a panel's window closes just that panel, closing the game window closes them all.
+************/
+use piston_window::*;
+
+use controls::draw_digit;
+use disasm::disassemble;
+use emulation::Emulation;
+use memview::{draw_number, MemoryViewer};
+use Chip8;
+
+//Layout in font pixels, as in the memory viewer
+const DIGIT: f64 = 5.0;
+const ROW_HEIGHT: f64 = 7.0;
+const MARGIN: f64 = 2.0;
+const DISASM_ROWS: usize = 24;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PanelKind {
+    Hex,
+    Disassembly,
+    Registers,
+}
+
+impl PanelKind {
+    pub fn parse(name: &str) -> Result<PanelKind, String> {
+        match name {
+            "hex" => Ok(PanelKind::Hex),
+            "disasm" => Ok(PanelKind::Disassembly),
+            "registers" => Ok(PanelKind::Registers),
+            _ => Err(format!("Unknown panel {} (expected hex, disasm or registers)", name)),
+        }
+    }
+
+    fn title(self) -> &'static str {
+        match self {
+            PanelKind::Hex => "Memory",
+            PanelKind::Disassembly => "Disassembly",
+            PanelKind::Registers => "Registers - V0-V7, V8-VF, I PC SP DT ST, stack",
+        }
+    }
+
+    fn size(self) -> [u32; 2] {
+        match self {
+            PanelKind::Hex => [480, 480],
+            PanelKind::Disassembly => [240, 600],
+            PanelKind::Registers => [480, 240],
+        }
+    }
+}
+
+//What the registers panel showed last frame, to highlight what changed
+#[derive(Clone, Copy, Default, PartialEq)]
+struct Registers {
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    sp: u16,
+    dt: u8,
+    st: u8,
+}
+
+impl Registers {
+    fn read(chip8: &Chip8) -> Registers {
+        let mut v = [0; 16];
+        for (x, value) in v.iter_mut().enumerate() {
+            *value = chip8.register(x);
+        }
+        Registers { v, i: chip8.index(), pc: chip8.pc(), sp: chip8.sp(), dt: chip8.delay_timer(), st: chip8.sound_timer() }
+    }
+}
+
+struct Panel {
+    kind: PanelKind,
+    window: PistonWindow,
+    viewer: Option<MemoryViewer>,   //The hex panel's
+    title: String,
+    previous: Registers,
+    current: Registers,
+    focused: bool,
+    cursor: [f64; 2],
+    disasm_first: isize,    //Address of the disassembly's top row as last drawn, what a click picks from
+}
+
+//Size of a font pixel for the disassembly in a view
+fn disassembly_pixel(view: [f64; 2]) -> f64 {
+    let width = DIGIT * 8.0 + MARGIN * 2.0;
+    (view[0] / width).min(view[1] / (ROW_HEIGHT * DISASM_ROWS as f64)).floor().max(1.0)
+}
+
+//Address of the disassembly's top row. The PC a third of the way down, so more of what comes next is shown
+fn disassembly_first(pc: u16) -> isize {
+    pc as isize - (DISASM_ROWS / 3 * 2) as isize
+}
+
+//The address of the disassembly row at height y, the highlight of the row included
+fn disassembly_row_at(view: [f64; 2], first: isize, y: f64) -> Option<u16> {
+    let pixel = disassembly_pixel(view);
+    let row = ((y / pixel - MARGIN + 1.0) / ROW_HEIGHT).floor();
+    if row < 0.0 || row >= DISASM_ROWS as f64 {
+        return None;
+    }
+    let addr = first + row as isize * 2;
+    if (0..4095).contains(&addr) { Some(addr as u16) } else { None }
+}
+
+fn draw_disassembly(chip8: &Chip8, run_to: Option<u16>, c: Context, g: &mut G2d) {
+    let view = c.get_view_size();
+    let (pixel, first) = (disassembly_pixel(view), disassembly_first(chip8.pc()));
+    let pc = chip8.pc() as usize;
+    for row in 0..DISASM_ROWS {
+        let addr = first + row as isize * 2;
+        if !(0..4095).contains(&addr) {
+            continue;
+        }
+        let addr = addr as usize;
+        let y = (MARGIN + row as f64 * ROW_HEIGHT) * pixel;
+        if addr == pc {
+            rectangle([0.0, 0.5, 0.1, 1.0], [0.0, y - pixel, view[0], ROW_HEIGHT * pixel], c.transform, g);
+        } else if run_to == Some(addr as u16) {
+            rectangle([0.6, 0.1, 0.1, 1.0], [0.0, y - pixel, view[0], ROW_HEIGHT * pixel], c.transform, g);
+        }
+        draw_number(addr, 3, [MARGIN * pixel, y], pixel, [0.5, 0.5, 0.5, 1.0], c, g);
+        let opcode = chip8.opcode_at(addr as u16) as usize;
+        draw_number(opcode, 4, [(MARGIN + DIGIT * 4.0) * pixel, y], pixel, [1.0, 1.0, 1.0, 1.0], c, g);
+    }
+}
+
+fn draw_registers(now: Registers, before: Registers, chip8: &Chip8, c: Context, g: &mut G2d) {
+    let view = c.get_view_size();
+    let width = DIGIT * 3.0 * 8.0 + MARGIN * 2.0;
+    let pixel = (view[0] / width).min(view[1] / (ROW_HEIGHT * 6.0)).floor().max(1.0);
+    let color = |changed: bool| if changed { [1.0, 0.8, 0.0, 1.0] } else { [1.0, 1.0, 1.0, 1.0] };
+    let cell = |column: usize, row: usize| [(MARGIN + column as f64 * DIGIT * 3.0) * pixel, (MARGIN + row as f64 * ROW_HEIGHT) * pixel];
+
+    for x in 0..16 {
+        draw_number(now.v[x] as usize, 2, cell(x % 8, x / 8), pixel, color(now.v[x] != before.v[x]), c, g);
+    }
+    //Three digit values get a column and a half
+    let others = [
+        (now.i as usize, 3, now.i != before.i),
+        (now.pc as usize, 3, now.pc != before.pc),
+        (now.sp as usize, 2, now.sp != before.sp),
+        (now.dt as usize, 2, now.dt != before.dt),
+        (now.st as usize, 2, now.st != before.st),
+    ];
+    let mut x = MARGIN;
+    for (value, digits, changed) in others.iter() {
+        draw_number(*value, *digits, [x * pixel, cell(0, 2)[1]], pixel, color(*changed), c, g);
+        x += DIGIT * (*digits as f64 + 1.0);
+    }
+    for (index, call) in chip8.call_stack().iter().enumerate() {
+        let [left, top] = cell(index % 8, 3 + index / 8);
+        draw_number(*call as usize, 3, [left, top], pixel, [0.5, 0.5, 0.5, 1.0], c, g);
+    }
+    if chip8.call_stack().is_empty() {
+        draw_digit(0, cell(0, 3), pixel, [0.3, 0.3, 0.3, 1.0], c, g);
+    }
+}
+
+impl Panel {
+    //Takes the window's events and draws it. Returns false once the window was closed
+    fn update(&mut self, emulation: &mut Emulation) -> bool {
+        let chip8 = &mut emulation.game.chip8;
+        while let Some(input) = self.window.poll_event() {
+            let event = Event::Input(input);
+            self.window.event(&event);
+            if let Some(focus) = event.focus_args() {
+                self.focused = focus;
+            }
+            if let (Some(Button::Keyboard(key)), Some(ref mut viewer)) = (event.press_args(), self.viewer.as_mut()) {
+                viewer.key(key, chip8);
+            }
+            if let Some(cursor) = event.mouse_cursor_args() {
+                self.cursor = cursor;
+            }
+            if self.kind == PanelKind::Disassembly {
+                match event.press_args() {
+                    Some(Button::Keyboard(Key::Space)) => {
+                        emulation.run_to = None;
+                        emulation.held = false;
+                    },
+                    Some(Button::Mouse(MouseButton::Left)) => {
+                        let size = self.window.size();
+                        if let Some(addr) = disassembly_row_at([size.width as f64, size.height as f64], self.disasm_first, self.cursor[1]) {
+                            println!("Running to {:#06X}", addr);
+                            emulation.run_to = Some(addr);
+                            emulation.held = false;
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+        let chip8 = &mut emulation.game.chip8;
+        if self.window.should_close() {
+            return false;
+        }
+
+        let title = match self.kind {
+            PanelKind::Disassembly => format!("{}{} - {:#05X}: {}", self.kind.title(), if emulation.held { " (held, Space goes on)" } else { "" },
+                                              chip8.pc(), disassemble(chip8.opcode_at(chip8.pc()), chip8.variant())),
+            PanelKind::Hex if self.viewer.as_ref().is_some_and(|viewer| viewer.paused) => format!("{} (paused)", self.kind.title()),
+            _ => self.kind.title().to_string(),
+        };
+        if title != self.title {
+            self.window.set_title(title.clone());
+            self.title = title;
+        }
+        self.previous = self.current;
+        self.current = Registers::read(chip8);
+
+        //The panels aren't driven by an event loop of their own, the game window's frames are theirs
+        let (size, draw_size) = (self.window.size(), self.window.draw_size());
+        let render = Event::Loop(Loop::Render(RenderArgs {
+            ext_dt: 0.0,
+            width: size.width,
+            height: size.height,
+            draw_width: draw_size.width,
+            draw_height: draw_size.height,
+        }));
+        self.window.event(&render);
+        self.window.draw_2d(&render, |_, g| clear([0.0, 0.0, 0.0, 1.0], g));
+        match self.kind {
+            PanelKind::Hex => {
+                if let Some(ref viewer) = self.viewer {
+                    viewer.draw(chip8, &mut self.window, &render);
+                }
+            },
+            PanelKind::Disassembly => {
+                let run_to = emulation.run_to;
+                self.disasm_first = disassembly_first(chip8.pc());
+                self.window.draw_2d(&render, |c, g| draw_disassembly(chip8, run_to, c, g));
+            },
+            PanelKind::Registers => {
+                let (now, before) = (self.current, self.previous);
+                self.window.draw_2d(&render, |c, g| draw_registers(now, before, chip8, c, g));
+            },
+        }
+        Window::swap_buffers(&mut self.window);
+        self.window.event(&Event::Loop(Loop::AfterRender(AfterRenderArgs)));
+        true
+    }
+}
+
+//The open panels
+pub struct Panels {
+    panels: Vec<Panel>,
+}
+
+impl Panels {
+    //Opens a window for each panel. Panels whose window can't be made are left out
+    pub fn open(kinds: &[PanelKind], chip8: &Chip8) -> Panels {
+        let mut panels = Panels { panels: Vec::new() };
+        for &kind in kinds {
+            panels.add(kind, chip8);
+        }
+        panels
+    }
+
+    fn add(&mut self, kind: PanelKind, chip8: &Chip8) {
+        let window: Result<PistonWindow, _> = WindowSettings::new(kind.title(), kind.size()).exit_on_esc(false).build();
+        match window {
+            Ok(window) => self.panels.push(Panel {
+                kind,
+                window,
+                viewer: if kind == PanelKind::Hex { Some(MemoryViewer::open(chip8)) } else { None },
+                title: kind.title().to_string(),
+                previous: Registers::read(chip8),
+                current: Registers::read(chip8),
+                focused: false,
+                cursor: [0.0, 0.0],
+                disasm_first: 0,
+            }),
+            Err(err) => println!("Could not open the {} panel: {}", kind.title(), err),
+        }
+    }
+
+    //Opens the panel, or closes it if it's already open (the command palette's panel commands)
+    pub fn toggle(&mut self, kind: PanelKind, chip8: &Chip8) {
+        match self.panels.iter().position(|panel| panel.kind == kind) {
+            Some(index) => {
+                self.panels.remove(index);
+            },
+            None => self.add(kind, chip8),
+        }
+    }
+
+    //Whether the hex panel paused the program
+    pub fn paused(&self) -> bool {
+        self.panels.iter().any(|panel| panel.viewer.as_ref().is_some_and(|viewer| viewer.paused))
+    }
+
+    //Whether one of the panels' windows has the focus, so the game doesn't pause for being in the background
+    pub fn focused(&self) -> bool {
+        self.panels.iter().any(|panel| panel.focused)
+    }
+
+    //Handles the panels' input and redraws them, once per frame of the game window
+    pub fn update(&mut self, emulation: &mut Emulation) {
+        self.panels.retain_mut(|panel| panel.update(emulation));
+    }
+}
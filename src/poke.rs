@@ -0,0 +1,161 @@
+/************
+Memory pokes
+
+A poke writes a single byte into memory after the ROM has been loaded, which makes it easy to
+reproduce bug reports or set up a specific game state without a debugger session.
+
+Pokes are given as a comma separated list of ADDR=EXPR pairs, ie "0x3A0=0xFF,0x3A1=v(0x02)"
+The value expression can be:
+    a number (hex with the 0x prefix, or decimal)  - 0xFF, 255
+    v(N)                                           - value of register VN
+    m(ADDR)                                        - value currently in memory at ADDR
+    i                                              - index register (only the low byte is written)
+    pc, sp, dt, st                                 - program counter, stack pointer, delay and sound timers
+    pixel(X,Y)                                     - 1 if the screen pixel at X,Y is lit, else 0
+    frame_hash                                     - Chip8::screen_hash of the screen (only useful to compare with)
+    collision                                      - 1 if the last sprite drawn collided with lit pixels, else 0
+v[N] and m[ADDR] work as well as v(N) and m(ADDR).
+************/
+use display::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use options::parse_number;
+use Chip8;
+
+#[derive(Clone, Debug)]
+pub enum Expr {
+    Literal(u64),
+    Register(usize),
+    Memory(u16),
+    Index,
+    Pc,
+    Sp,
+    DelayTimer,
+    SoundTimer,
+    Pixel(usize, usize),
+    FrameHash,
+    Collision,
+}
+
+impl Expr {
+    pub fn parse(text: &str) -> Result<Expr, String> {
+        let text = text.trim();
+
+        match text {
+            "i" => return Ok(Expr::Index),
+            "pc" => return Ok(Expr::Pc),
+            "sp" => return Ok(Expr::Sp),
+            "dt" => return Ok(Expr::DelayTimer),
+            "st" => return Ok(Expr::SoundTimer),
+            "frame_hash" => return Ok(Expr::FrameHash),
+            "collision" => return Ok(Expr::Collision),
+            _ => {}
+        }
+        if let Some(inner) = call_argument(text, "v") {
+            let reg = parse_number(inner)?;
+            if reg > 0xF {
+                return Err(format!("Invalid register {}", text));
+            }
+            return Ok(Expr::Register(reg as usize));
+        }
+        if let Some(inner) = call_argument(text, "m") {
+            let addr = parse_number(inner)?;
+            if addr as usize >= 4096 {
+                return Err(format!("Address out of range {}", text));
+            }
+            return Ok(Expr::Memory(addr));
+        }
+        if let Some(inner) = call_argument(text, "pixel") {
+            let mut coords = inner.splitn(2, ',');
+            let x = parse_number(coords.next().unwrap_or(""))? as usize;
+            let y = parse_number(coords.next().ok_or_else(|| format!("Expected pixel(X,Y), got {}", text))?)? as usize;
+            if x >= SCREEN_WIDTH || y >= SCREEN_HEIGHT {
+                return Err(format!("Pixel out of range {}", text));
+            }
+            return Ok(Expr::Pixel(x, y));
+        }
+
+        Ok(Expr::Literal(parse_literal(text)?))
+    }
+
+    pub fn eval(&self, chip8: &Chip8) -> u64 {
+        match *self {
+            Expr::Literal(value) => value,
+            Expr::Register(reg) => chip8.register(reg) as u64,
+            Expr::Memory(addr) => chip8.read_byte(addr) as u64,
+            Expr::Index => chip8.index() as u64,
+            Expr::Pc => chip8.pc() as u64,
+            Expr::Sp => chip8.sp() as u64,
+            Expr::DelayTimer => chip8.delay_timer() as u64,
+            Expr::SoundTimer => chip8.sound_timer() as u64,
+            Expr::Pixel(x, y) => chip8.pixel(x, y) as u64,
+            Expr::FrameHash => chip8.screen_hash(),
+            Expr::Collision => chip8.last_collision() as u64,
+        }
+    }
+}
+
+//Returns the text between the parentheses (or brackets) of name(...), if the text is in that form
+fn call_argument<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let rest = text.strip_prefix(name)?;
+    if (rest.starts_with('(') && rest.ends_with(')')) || (rest.starts_with('[') && rest.ends_with(']')) {
+        Some(&rest[1..rest.len() - 1])
+    } else {
+        None
+    }
+}
+
+//Like parse_number, but wide enough for a frame hash
+fn parse_literal(text: &str) -> Result<u64, String> {
+    let result = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => text.parse::<u64>(),
+    };
+    result.map_err(|_| format!("Invalid number {}", text))
+}
+
+#[derive(Clone, Debug)]
+pub struct Poke {
+    pub addr: u16,
+    pub value: Expr,
+}
+
+impl Poke {
+    pub fn apply(&self, chip8: &mut Chip8) {
+        let value = self.value.eval(chip8) as u8;
+        chip8.write_byte(self.addr, value);
+    }
+}
+
+//Parses a comma separated list of pokes, ie "0x3A0=0xFF,0x3A1=v(0x02)"
+pub fn parse_pokes(text: &str) -> Result<Vec<Poke>, String> {
+    let mut pokes = Vec::new();
+
+    for item in text.split(',') {
+        pokes.push(parse_poke(item)?);
+    }
+
+    Ok(pokes)
+}
+
+//Parses a single ADDR=EXPR poke
+pub fn parse_poke(item: &str) -> Result<Poke, String> {
+    let mut parts = item.splitn(2, '=');
+    let addr = parts.next().unwrap_or("");
+    let value = match parts.next() {
+        Some(value) => value,
+        None => return Err(format!("Poke is missing a value: {}", item)),
+    };
+
+    let addr = parse_number(addr)?;
+    if addr as usize >= 4096 {
+        return Err(format!("Poke address out of range: {}", item));
+    }
+
+    let value = Expr::parse(value)?;
+    if let Expr::Literal(literal) = value {
+        if literal > 0xFF {
+            return Err(format!("Poke value does not fit in a byte: {}", item));
+        }
+    }
+
+    Ok(Poke { addr, value })
+}
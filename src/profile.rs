@@ -0,0 +1,171 @@
+/************
+Configuration profiles
+
+Named sets of settings to switch between while a game runs, for trying out how a ROM behaves with
+other colors, speeds and quirks without editing files and restarting. They're defined in a
+profiles file (--profiles FILE, profiles.cfg in the working directory by default), with a section
+per profile and any of these settings in it:
+
+    [slow amber]
+    fg FFB000                   - color of lit pixels
+    bg 200F00                   - color of unlit pixels
+    speed 0.5                   - how fast the game runs, 1 is normal
+    stack grow                  - the quirks, same values as the command line options
+    draw-check strict
+    sprite-limit 10
+    clip-collision rows
+    quirks vip,no-clip
+    address-mode wrap
+    protect warn
+
+F5 switches to the next profile, and back to the command line settings after the last one. Settings
+a profile leaves out are the command line ones. The quirks belong to the machine, so a game kiosk
+mode moves on to starts with the command line ones again until the next switch. Blank lines and
+lines starting with # are ignored.
+************/
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use memory::AddressMode;
+use options::Options;
+use palette::{self, Palette};
+use protect::Protection;
+use quirks::Quirks;
+use sprite::{self, ClipCollision, DrawCheck};
+use stack::StackMode;
+use Chip8;
+
+pub const DEFAULT_FILE: &str = "profiles.cfg";
+
+#[derive(Clone, Debug, Default)]
+pub struct Profile {
+    pub name: String,
+    fg: Option<palette::Color>,
+    bg: Option<palette::Color>,
+    speed: Option<f64>,
+    stack_mode: Option<StackMode>,
+    draw_check: Option<DrawCheck>,
+    sprite_limit: Option<usize>,
+    clip_collision: Option<ClipCollision>,
+    quirks: Option<Quirks>,
+    address_mode: Option<AddressMode>,
+    protection: Option<Protection>,
+}
+
+//Everything a profile can change, as it currently is
+pub struct Settings {
+    pub palette: Palette,
+    pub speed: f64,
+}
+
+impl Profile {
+    fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "fg" => self.fg = Some(palette::parse_color(value)?),
+            "bg" => self.bg = Some(palette::parse_color(value)?),
+            "speed" => self.speed = match value.parse::<f64>() {
+                Ok(speed) if speed > 0.0 && speed.is_finite() => Some(speed),
+                _ => return Err(format!("Invalid speed {}", value)),
+            },
+            "stack" => self.stack_mode = Some(StackMode::parse(value)?),
+            "draw-check" => self.draw_check = Some(DrawCheck::parse(value)?),
+            "sprite-limit" => self.sprite_limit = Some(sprite::parse_limit(value)?),
+            "clip-collision" => self.clip_collision = Some(ClipCollision::parse(value)?),
+            "quirks" => self.quirks = Some(Quirks::parse(value)?),
+            "address-mode" => self.address_mode = Some(AddressMode::parse(value)?),
+            "protect" => self.protection = Some(Protection::parse(value)?),
+            _ => return Err(format!("Unknown profile setting {}", name)),
+        }
+        Ok(())
+    }
+
+    //The command line settings, what there is before the first switch and after the last profile
+    pub fn base() -> Profile {
+        Profile { name: String::from("command line settings"), ..Profile::default() }
+    }
+
+    //Applies the profile on top of the command line options
+    pub fn apply(&self, options: &Options, chip8: &mut Chip8) -> Settings {
+        chip8.set_stack_mode(self.stack_mode.unwrap_or(options.stack_mode));
+        chip8.set_draw_check(self.draw_check.unwrap_or(options.draw_check), self.sprite_limit.unwrap_or(options.sprite_limit));
+        chip8.set_clip_collision(self.clip_collision.unwrap_or(options.clip_collision));
+        chip8.set_quirks(self.quirks.unwrap_or(options.quirks));
+        chip8.set_address_mode(self.address_mode.unwrap_or(options.address_mode));
+        chip8.set_protection(self.protection.unwrap_or(options.protection));
+        Settings {
+            palette: Palette {
+                foreground: self.fg.unwrap_or(options.palette.foreground),
+                background: self.bg.unwrap_or(options.palette.background),
+                ..options.palette
+            },
+            speed: self.speed.unwrap_or(1.0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Profiles {
+    profiles: Vec<Profile>,
+    current: Option<usize>,     //None for the command line settings
+}
+
+impl Profiles {
+    pub fn load(path: &str) -> Result<Profiles, String> {
+        let file = File::open(path).map_err(|err| format!("Could not open profiles {}: {}", path, err))?;
+        let mut profiles: Vec<Profile> = Vec::new();
+
+        for (number, line) in BufReader::new(file).lines().enumerate() {
+            let line = line.map_err(|err| format!("Could not read profiles {}: {}", path, err))?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |err: String| format!("{}:{}: {}", path, number + 1, err);
+
+            if line.starts_with('[') && line.ends_with(']') {
+                profiles.push(Profile { name: line[1..line.len() - 1].trim().to_string(), ..Profile::default() });
+                continue;
+            }
+
+            let profile = profiles.last_mut().ok_or_else(|| invalid(String::from("Setting before the first [profile]")))?;
+            let (name, value) = match line.find(char::is_whitespace) {
+                Some(split) => (&line[..split], line[split..].trim()),
+                None => (line, ""),
+            };
+            profile.set(name, value).map_err(invalid)?;
+        }
+
+        Ok(Profiles { profiles, current: None })
+    }
+
+    //Loads the default file if there is one, no profiles otherwise
+    pub fn load_default() -> Result<Profiles, String> {
+        if Path::new(DEFAULT_FILE).exists() {
+            Profiles::load(DEFAULT_FILE)
+        } else {
+            Ok(Profiles::default())
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.profiles.is_empty()
+    }
+
+    //Moves on to the next profile and returns it
+    pub fn next(&mut self) -> Profile {
+        self.current = match self.current {
+            None => Some(0),
+            Some(current) if current + 1 < self.profiles.len() => Some(current + 1),
+            Some(_) => None,
+        };
+        self.current_profile()
+    }
+
+    pub fn current_profile(&self) -> Profile {
+        match self.current {
+            Some(current) => self.profiles[current].clone(),
+            None => Profile::base(),
+        }
+    }
+}
@@ -0,0 +1,88 @@
+/************
+Memory protection
+
+An optional correctness tool for homebrew developers. When turned on (--protect warn|fault), the
+emulator watches every memory access made by the running program and reports:
+    writes into the interpreter area (0x000-0x1FF, where the font lives)
+    reads of memory that was never loaded or written (uninitialized RAM)
+together with the PC of the offending instruction.
+
+warn  - print the problem and carry on
+fault - stop the program at the offending instruction, without doing the access
+
+Independently of the protection mode, the core also notices self-modifying code: writes to memory
+that has already been executed as an instruction. Classic ROMs use this trick a lot, and it
+confuses anyone reading the code, so the debugger reports these writes (and can break on them).
+************/
+use core::fmt;
+
+use stack::STACK_SIZE;
+
+pub const INTERPRETER_END: usize = 0x200; //First address past the interpreter area
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Protection {
+    Off,
+    Warn,
+    Fault,
+}
+
+impl Protection {
+    pub const NAMES: &'static [&'static str] = &["off", "warn", "fault"];
+
+    #[cfg(feature = "std")]
+    pub fn parse(text: &str) -> Result<Protection, String> {
+        match text {
+            "off" => Ok(Protection::Off),
+            "warn" => Ok(Protection::Warn),
+            "fault" => Ok(Protection::Fault),
+            _ => Err(format!("Unknown protection mode {} (expected off, warn or fault)", text)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FaultKind {
+    InterpreterWrite,
+    UninitializedRead,
+    StackOverflow,      //A call with the stack full, addr is the call target (see stack.rs)
+    StackUnderflow,     //A return with the stack empty
+    SpriteOutOfMemory,  //A draw reading past the end of memory, addr is I (see sprite.rs)
+    SpriteTooTall,      //A draw taller than the sprite limit, addr is I
+    OutOfBounds,        //A read or write past the end of memory (ie FX65 with I near 0xFFF), always faults
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemoryFault {
+    pub kind: FaultKind,
+    pub addr: u16,
+    pub pc: u16,    //Address of the instruction that made the access
+}
+
+impl fmt::Display for MemoryFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            FaultKind::InterpreterWrite => write!(f, "Write to interpreter memory at {:#06X}", self.addr)?,
+            FaultKind::UninitializedRead => write!(f, "Read of uninitialized memory at {:#06X}", self.addr)?,
+            FaultKind::StackOverflow => write!(f, "Stack overflow calling {:#06X} (more than {} nested calls)", self.addr, STACK_SIZE)?,
+            FaultKind::StackUnderflow => write!(f, "Return with an empty stack")?,
+            FaultKind::SpriteOutOfMemory => write!(f, "Sprite at {:#06X} runs past the end of memory", self.addr)?,
+            FaultKind::SpriteTooTall => write!(f, "Sprite at {:#06X} is taller than the sprite limit", self.addr)?,
+            FaultKind::OutOfBounds => write!(f, "Access past the end of memory at {:#06X}", self.addr)?,
+        }
+        write!(f, " by instruction at {:#06X}", self.pc)
+    }
+}
+
+//A write into memory that had already been executed as code
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CodeWrite {
+    pub addr: u16,
+    pub pc: u16,    //Address of the instruction that made the write
+}
+
+impl fmt::Display for CodeWrite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Self-modifying write to code at {:#06X} by instruction at {:#06X}", self.addr, self.pc)
+    }
+}
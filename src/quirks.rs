@@ -0,0 +1,89 @@
+/************
+Behaviour quirks
+
+A handful of instructions do different things on different interpreters, and ROMs are written
+against one of them. Which behaviour the core follows is picked with --quirks, a comma separated
+list of quirk names (turning a quirk on), quirk names starting with no- (turning it off) and
+presets, applied left to right:
+
+vf-reset - 8XY1, 8XY2 and 8XY3 clear VF after the logic operation, like the COSMAC VIP
+shift-vy - 8XY6 and 8XYE shift VY and put the result in VX, like the COSMAC VIP. Otherwise VX is
+           shifted in place and Y is ignored, like SCHIP. VF gets the bit shifted out either way
+jump-vx  - BNNN jumps to NNN + VX, where X is the top nibble of NNN, like SCHIP. Otherwise it
+           jumps to NNN + V0
+clip     - sprite pixels past the right or bottom edge of the screen aren't drawn (counting
+           towards VF as --clip-collision says). no-clip wraps them around to the other side
+           instead. The sprite's starting position always wraps (see dispatch.rs)
+
+Presets:
+
+default - clip only, what Cowgod's reference describes and what most ROMs expect
+vip     - vf-reset, shift-vy and clip, the original COSMAC VIP interpreter
+schip   - jump-vx and clip, SUPER-CHIP 1.1
+
+    --quirks vip,no-clip
+************/
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quirks {
+    pub vf_reset: bool,
+    pub shift_uses_vy: bool,
+    pub jump_with_vx: bool,
+    pub clip: bool,
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks::DEFAULT
+    }
+}
+
+impl Quirks {
+    pub const DEFAULT: Quirks = Quirks { vf_reset: false, shift_uses_vy: false, jump_with_vx: false, clip: true };
+    pub const VIP: Quirks = Quirks { vf_reset: true, shift_uses_vy: true, jump_with_vx: false, clip: true };
+    pub const SCHIP: Quirks = Quirks { vf_reset: false, shift_uses_vy: false, jump_with_vx: true, clip: true };
+
+    //The quirks parse() can turn on and off
+    pub const NAMES: &'static [&'static str] = &["vf-reset", "shift-vy", "jump-vx", "clip"];
+    pub const PRESETS: &'static [&'static str] = &["default", "vip", "schip"];
+
+    #[cfg(feature = "std")]
+    fn flag(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "vf-reset" => Some(&mut self.vf_reset),
+            "shift-vy" => Some(&mut self.shift_uses_vy),
+            "jump-vx" => Some(&mut self.jump_with_vx),
+            "clip" => Some(&mut self.clip),
+            _ => None,
+        }
+    }
+
+    //Applies a --quirks list on top of these quirks
+    #[cfg(feature = "std")]
+    pub fn apply(mut self, text: &str) -> Result<Quirks, String> {
+        for item in text.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            match item {
+                "default" => self = Quirks::DEFAULT,
+                "vip" => self = Quirks::VIP,
+                "schip" => self = Quirks::SCHIP,
+                _ => {
+                    let (name, on) = match item.strip_prefix("no-") {
+                        Some(name) => (name, false),
+                        None => (item, true),
+                    };
+                    match self.flag(name) {
+                        Some(flag) => *flag = on,
+                        None => return Err(format!("Unknown quirk {} (expected vf-reset, shift-vy, jump-vx or clip, \
+                            with or without no-, or a preset: default, vip or schip)", item)),
+                    }
+                },
+            }
+        }
+        Ok(self)
+    }
+
+    #[cfg(feature = "std")]
+    pub fn parse(text: &str) -> Result<Quirks, String> {
+        Quirks::DEFAULT.apply(text)
+    }
+}
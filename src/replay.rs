@@ -0,0 +1,213 @@
+/************
+Input recording and replay
+
+A replay is the random seed a game was started with plus every key event, stamped with the
+cycle it happened on. Since the emulator is deterministic otherwise, feeding the same key events
+in at the same cycles reproduces the original run exactly.
+
+The buzzer starting and stopping is logged too. Playback doesn't need it to reproduce the run,
+but the sound is made host-side, so with the log anything rendering audio from a replay (ie a
+video export) gets it exactly without emulating, and playback uses it to notice when a run has
+drifted from the recording. CHIP-8 only has the one beep; variants with programmable sound
+would log their pattern and pitch changes the same way.
+
+File format (text, one entry per line):
+    seed SEED
+    variant NAME         - machine variant the run was recorded on (see variant.rs), chip8 if missing
+    CYCLE KEY STATE      - STATE is 1 for pressed, 0 for released
+    CYCLE sound on|off   - the buzzer started or stopped, after the cycle ending at CYCLE
+
+--record FILE saves the inputs of a run when the window is closed, --replay FILE plays them back.
+In attract mode, each playlist ROM plays back <rom path>.replay if it exists.
+************/
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use variant::Variant;
+use Chip8;
+
+#[derive(Clone, Copy, Debug)]
+pub struct InputEvent {
+    pub cycle: u64,
+    pub key: u8,
+    pub state: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SoundEvent {
+    pub cycle: u64,
+    pub on: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Replay {
+    pub seed: u64,
+    pub variant: Variant,
+    pub events: Vec<InputEvent>,
+    pub sound: Vec<SoundEvent>,
+}
+
+impl Replay {
+    pub fn new(seed: u64, variant: Variant) -> Replay {
+        Replay {
+            seed,
+            variant,
+            events: Vec::new(),
+            sound: Vec::new(),
+        }
+    }
+
+    pub fn record(&mut self, cycle: u64, key: u8, state: u8) {
+        self.events.push(InputEvent { cycle, key, state });
+    }
+
+    pub fn record_sound(&mut self, cycle: u64, on: bool) {
+        self.sound.push(SoundEvent { cycle, on });
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        let mut file = File::create(path).map_err(|err| format!("Could not create replay {}: {}", path, err))?;
+        file.write_all(self.to_text().as_bytes()).map_err(|err| format!("Could not write replay {}: {}", path, err))
+    }
+
+    //The replay in its file format
+    pub fn to_text(&self) -> String {
+        let mut text = format!("seed {}\nvariant {}\n", self.seed, self.variant);
+        let mut sound = self.sound.iter().peekable();
+        for event in &self.events {
+            while let Some(change) = sound.next_if(|change| change.cycle <= event.cycle) {
+                text.push_str(&sound_line(change));
+            }
+            text.push_str(&format!("{} {} {}\n", event.cycle, event.key, event.state));
+        }
+        for change in sound {
+            text.push_str(&sound_line(change));
+        }
+        text
+    }
+
+    pub fn load(path: &str) -> Result<Replay, String> {
+        let mut file = File::open(path).map_err(|err| format!("Could not open replay {}: {}", path, err))?;
+        let mut text = String::new();
+        file.read_to_string(&mut text).map_err(|err| format!("Could not read replay {}: {}", path, err))?;
+        let lines: Vec<&str> = text.lines().collect();
+        Replay::parse_lines(&lines, path, 1)
+    }
+
+    //Parses replay lines, which start at line first_line of the file at path (for error messages)
+    pub fn parse_lines(lines: &[&str], path: &str, first_line: usize) -> Result<Replay, String> {
+        let mut seed = None;
+        let mut variant = Variant::Chip8;
+        let mut events = Vec::new();
+        let mut sound = Vec::new();
+
+        for (number, line) in lines.iter().enumerate() {
+            let invalid = || format!("{}:{}: Invalid replay line", path, number + first_line);
+            let words: Vec<&str> = line.split_whitespace().collect();
+
+            match words.as_slice() {
+                [] => {},
+                ["seed", value] => seed = Some(value.parse::<u64>().map_err(|_| invalid())?),
+                ["variant", name] => variant = Variant::parse(name).map_err(|err| format!("{}: {}", invalid(), err))?,
+                [cycle, "sound", state] => sound.push(SoundEvent {
+                    cycle: cycle.parse().map_err(|_| invalid())?,
+                    on: match *state {
+                        "on" => true,
+                        "off" => false,
+                        _ => return Err(invalid()),
+                    },
+                }),
+                [cycle, key, state] => {
+                    let event = InputEvent {
+                        cycle: cycle.parse().map_err(|_| invalid())?,
+                        key: key.parse().map_err(|_| invalid())?,
+                        state: state.parse().map_err(|_| invalid())?,
+                    };
+                    if event.key > 0xF || event.state > 1 {
+                        return Err(invalid());
+                    }
+                    events.push(event);
+                },
+                _ => return Err(invalid()),
+            }
+        }
+
+        match seed {
+            Some(seed) => Ok(Replay { seed, variant, events, sound }),
+            None => Err(format!("Replay {} has no seed", path)),
+        }
+    }
+
+    //The replay attract mode uses for a ROM, if one has been recorded
+    pub fn for_rom(rom: &str) -> Option<Replay> {
+        let path = format!("{}.replay", rom);
+        if !Path::new(&path).exists() {
+            return None;
+        }
+        match Replay::load(&path) {
+            Ok(replay) => Some(replay),
+            Err(err) => {
+                println!("{}", err);
+                None
+            }
+        }
+    }
+}
+
+fn sound_line(event: &SoundEvent) -> String {
+    format!("{} sound {}\n", event.cycle, if event.on { "on" } else { "off" })
+}
+
+//Feeds a replay's key events into a Chip8 as it reaches the cycles they were recorded on
+pub struct ReplayPlayer {
+    replay: Replay,
+    next: usize,        //Index of the next event to play
+    next_sound: usize,  //Same for the sound log
+    sound: bool,        //The buzzer as recorded, up to the last sound() call
+    drifted: bool,      //The machine's buzzer has disagreed with the recording
+}
+
+impl ReplayPlayer {
+    //Starts playing a replay. The Chip8 is reseeded so random numbers come out the same as in the recording.
+    //Fails if the replay was recorded on another machine variant, its inputs wouldn't mean the same thing
+    pub fn new(replay: Replay, chip8: &mut Chip8) -> Result<ReplayPlayer, String> {
+        chip8.variant().check(replay.variant, "Replay")?;
+        chip8.set_seed(replay.seed);
+        let sound = chip8.sound_active();
+        Ok(ReplayPlayer { replay, next: 0, next_sound: 0, sound, drifted: false })
+    }
+
+    //Applies every event that is due at the Chip8's current cycle
+    pub fn apply(&mut self, chip8: &mut Chip8) {
+        while let Some(event) = self.replay.events.get(self.next) {
+            if event.cycle > chip8.cycles() {
+                break;
+            }
+            chip8.handle_key(event.key, event.state);
+            self.next += 1;
+        }
+    }
+
+    //The buzzer as recorded for the Chip8's current cycle, call it after every step. The first time the
+    //machine's own buzzer disagrees, the run has drifted from the recording and that gets reported once.
+    //Recordings from before the sound log have no sound events, they just use the machine's buzzer
+    pub fn sound(&mut self, chip8: &Chip8) -> bool {
+        if self.replay.sound.is_empty() {
+            return chip8.sound_active();
+        }
+        while let Some(event) = self.replay.sound.get(self.next_sound) {
+            if event.cycle > chip8.cycles() {
+                break;
+            }
+            self.sound = event.on;
+            self.next_sound += 1;
+        }
+        if self.sound != chip8.sound_active() && !self.drifted {
+            self.drifted = true;
+            println!("Playback has drifted from the recording: the buzzer is {} at cycle {}, it was recorded {}",
+                     if chip8.sound_active() { "on" } else { "off" }, chip8.cycles(), if self.sound { "on" } else { "off" });
+        }
+        self.sound
+    }
+}
@@ -0,0 +1,85 @@
+/************
+Self-describing ROMs
+
+A ROM can carry a small metadata trailer after its last instruction, where the program never
+runs, telling the emulator its title, author and which machine profile (variant, see variant.rs)
+it needs. The emulator prints it when the ROM starts and warns if the profile isn't one it runs.
+Generated ROMs (chip8 bench-rom) carry one, "chip8 rom tag" adds one to any ROM.
+
+Trailer layout, at the very end of the file:
+    "key=value" lines, one per field (UTF-8): title, author, profile
+    length of the lines in bytes (2 bytes, big endian)
+    "C8MD"
+************/
+use std::str;
+
+const MAGIC: &[u8] = b"C8MD";
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RomTag {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub profile: Option<String>,
+}
+
+impl RomTag {
+    //Reads the trailer off the end of a ROM, None if it hasn't got one. Also returns the ROM's length without it
+    pub fn read(rom: &[u8]) -> Option<(RomTag, usize)> {
+        if rom.len() < MAGIC.len() + 2 || !rom.ends_with(MAGIC) {
+            return None;
+        }
+        let len_pos = rom.len() - MAGIC.len() - 2;
+        let len = (rom[len_pos] as usize) << 8 | rom[len_pos + 1] as usize;
+        let start = len_pos.checked_sub(len)?;
+        let text = str::from_utf8(&rom[start..len_pos]).ok()?;
+
+        let mut tag = RomTag::default();
+        for line in text.lines() {
+            let mut parts = line.splitn(2, '=');
+            match (parts.next(), parts.next()) {
+                (Some("title"), Some(value)) => tag.title = Some(value.to_string()),
+                (Some("author"), Some(value)) => tag.author = Some(value.to_string()),
+                (Some("profile"), Some(value)) => tag.profile = Some(value.to_string()),
+                //Fields from newer versions are skipped
+                _ => {},
+            }
+        }
+        Some((tag, start))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none() && self.author.is_none() && self.profile.is_none()
+    }
+
+    //Appends the trailer to a ROM
+    pub fn append_to(&self, rom: &mut Vec<u8>) -> Result<(), String> {
+        let mut text = String::new();
+        for (key, value) in [("title", &self.title), ("author", &self.author), ("profile", &self.profile)].iter() {
+            if let Some(ref value) = **value {
+                if value.contains('\n') {
+                    return Err(format!("The {} can't span lines", key));
+                }
+                text.push_str(&format!("{}={}\n", key, value));
+            }
+        }
+        if text.len() > 0xFFFF {
+            return Err(String::from("ROM metadata is too long"));
+        }
+        rom.extend_from_slice(text.as_bytes());
+        rom.extend_from_slice(&(text.len() as u16).to_be_bytes());
+        rom.extend_from_slice(MAGIC);
+        Ok(())
+    }
+
+    //ie "Pong by Paul Vervalin (chip8)"
+    pub fn describe(&self) -> String {
+        let mut text = self.title.clone().unwrap_or_else(|| String::from("Untitled"));
+        if let Some(ref author) = self.author {
+            text.push_str(&format!(" by {}", author));
+        }
+        if let Some(ref profile) = self.profile {
+            text.push_str(&format!(" ({})", profile));
+        }
+        text
+    }
+}
@@ -0,0 +1,178 @@
+/************
+ROM utilities
+
+Usage: chip8 rom trim <romfile> <outfile> [--force]
+       chip8 rom pad <romfile> <size> <outfile>
+       chip8 rom export <romfile> [--lang rust|c] [--name NAME] [--out FILE]
+       chip8 rom tag <romfile> <outfile> [--title TITLE] [--author AUTHOR] [--profile VARIANT]
+       chip8 rom sprite <pngfile> [--rom OUTFILE] [--out FILE] [--at ADDR] [--x X] [--y Y] [--invert] [--subroutine]
+
+trim - strips the trailing zero bytes off a ROM. Memory past the ROM starts out zero anyway, but
+       the trimmed bytes are checked against the static analysis (see analyze.rs) first: if the
+       code runs or points I into them, the ROM is left alone unless --force is given, since
+       the program may rely on them being part of the ROM (ie with --protect).
+pad  - pads a ROM with zero bytes up to SIZE bytes (hex with 0x, or decimal), for tools and
+       carts that expect a fixed size.
+export - prints the ROM as source code to embed in a program, a `const NAME: &[u8]` for Rust or
+         a `const unsigned char NAME[]` array for C (NAME defaults to ROM). --out writes it to a
+         file instead of stdout.
+tag  - writes the ROM with a metadata trailer (see romtag.rs). Fields the ROM was tagged with
+       before are kept unless they're given again.
+sprite - converts a PNG picture into sprite tables and the code to draw them (see imagesprite.rs).
+************/
+use std::fs;
+use std::path::Path;
+use std::process;
+
+use analyze::analyze;
+use exitcode;
+use imagesprite;
+use options::{parse_number, take_value};
+use romtag::RomTag;
+
+//The most a ROM can be, from 0x200 to the end of memory
+const MAX_ROM_SIZE: usize = 4096 - 0x200;
+
+fn read_rom(path: &str) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|err| format!("Could not read ROM {}: {}", path, err))
+}
+
+fn write_rom(path: &str, rom: &[u8]) -> Result<(), String> {
+    fs::write(path, rom).map_err(|err| format!("Could not write {}: {}", path, err))
+}
+
+fn trim(args: &[String]) -> Result<(), String> {
+    let force = args.iter().any(|arg| arg == "--force");
+    let paths: Vec<&String> = args.iter().filter(|arg| *arg != "--force").collect();
+    if paths.len() != 2 {
+        return Err(String::from("Usage: chip8 rom trim <romfile> <outfile> [--force]"));
+    }
+
+    let rom = read_rom(paths[0])?;
+    let trimmed_len = rom.iter().rposition(|byte| *byte != 0).map(|last| last + 1).unwrap_or(0);
+
+    let analysis = analyze(&rom);
+    let used_len = analysis.used_end() - 0x200;
+    if used_len > trimmed_len && !force {
+        return Err(format!("The code uses the ROM up to {:#05X} but trimming would drop everything from {:#05X} on, use --force to trim anyway",
+                           0x200 + used_len - 1, 0x200 + trimmed_len));
+    }
+    if analysis.computed_jumps {
+        println!("Warning: the ROM has computed jumps (BNNN), code they lead to wasn't checked");
+    }
+
+    write_rom(paths[1], &rom[..trimmed_len])?;
+    println!("Trimmed {} from {} to {} bytes", paths[0], rom.len(), trimmed_len);
+    Ok(())
+}
+
+fn pad(args: &[String]) -> Result<(), String> {
+    if args.len() != 3 {
+        return Err(String::from("Usage: chip8 rom pad <romfile> <size> <outfile>"));
+    }
+
+    let mut rom = read_rom(&args[0])?;
+    let size = parse_number(&args[1])? as usize;
+    if size > MAX_ROM_SIZE {
+        return Err(format!("ROMs can be at most {} bytes", MAX_ROM_SIZE));
+    }
+    if size < rom.len() {
+        return Err(format!("{} is already {} bytes, more than {}", args[0], rom.len(), size));
+    }
+
+    let len = rom.len();
+    rom.resize(size, 0);
+    write_rom(&args[2], &rom)?;
+    println!("Padded {} from {} to {} bytes", args[0], len, size);
+    Ok(())
+}
+
+//The ROM bytes as source lines, 12 bytes to a line
+fn byte_lines(rom: &[u8], indent: &str) -> String {
+    rom.chunks(12)
+        .map(|line| {
+            let bytes: Vec<String> = line.iter().map(|byte| format!("0x{:02X},", byte)).collect();
+            format!("{}{}\n", indent, bytes.join(" "))
+        })
+        .collect()
+}
+
+fn export(args: &[String]) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let lang = take_value(&mut args, "--lang")?.unwrap_or_else(|| String::from("rust"));
+    let name = take_value(&mut args, "--name")?.unwrap_or_else(|| String::from("ROM"));
+    let out = take_value(&mut args, "--out")?;
+    if args.len() != 1 {
+        return Err(String::from("Usage: chip8 rom export <romfile> [--lang rust|c] [--name NAME] [--out FILE]"));
+    }
+    if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') || name.starts_with(|c: char| c.is_ascii_digit()) {
+        return Err(format!("Invalid name {}", name));
+    }
+
+    let rom = read_rom(&args[0])?;
+    let file = Path::new(&args[0]).file_name().map(|file| file.to_string_lossy().into_owned()).unwrap_or_default();
+    let source = match lang.as_str() {
+        "rust" => format!("//{}, {} bytes\npub const {}: &[u8] = &[\n{}];\n",
+                          file, rom.len(), name, byte_lines(&rom, "    ")),
+        "c" => format!("/* {}, {} bytes */\nconst unsigned char {}[{}] = {{\n{}}};\n",
+                       file, rom.len(), name, rom.len(), byte_lines(&rom, "    ")),
+        _ => return Err(format!("Unknown language {} (expected rust or c)", lang)),
+    };
+
+    match out {
+        Some(path) => write_rom(&path, source.as_bytes()),
+        None => {
+            print!("{}", source);
+            Ok(())
+        },
+    }
+}
+
+fn tag(args: &[String]) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let title = take_value(&mut args, "--title")?;
+    let author = take_value(&mut args, "--author")?;
+    let profile = take_value(&mut args, "--profile")?;
+    if args.len() != 2 {
+        return Err(String::from("Usage: chip8 rom tag <romfile> <outfile> [--title TITLE] [--author AUTHOR] [--profile VARIANT]"));
+    }
+
+    let mut rom = read_rom(&args[0])?;
+    let mut tag = match RomTag::read(&rom) {
+        Some((tag, len)) => {
+            rom.truncate(len);
+            tag
+        },
+        None => RomTag::default(),
+    };
+    tag.title = title.or(tag.title);
+    tag.author = author.or(tag.author);
+    tag.profile = profile.or(tag.profile);
+    if tag.is_empty() {
+        return Err(String::from("Nothing to tag the ROM with, give --title, --author or --profile"));
+    }
+
+    tag.append_to(&mut rom)?;
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(format!("The tagged ROM would be {} bytes, more than the {} that fit in memory", rom.len(), MAX_ROM_SIZE));
+    }
+    write_rom(&args[1], &rom)?;
+    println!("Tagged {}: {}", args[1], tag.describe());
+    Ok(())
+}
+
+//Entry point for "chip8 rom ...", with the arguments following "rom"
+pub fn run(args: &[String]) {
+    let result = match args.first().map(|arg| arg.as_str()) {
+        Some("trim") => trim(&args[1..]),
+        Some("pad") => pad(&args[1..]),
+        Some("export") => export(&args[1..]),
+        Some("tag") => tag(&args[1..]),
+        Some("sprite") => imagesprite::convert(&args[1..]),
+        _ => Err(String::from("Usage: chip8 rom trim|pad|export|tag|sprite ...")),
+    };
+    if let Err(err) = result {
+        println!("{}. Aborting", err);
+        process::exit(exitcode::FAILURE);
+    }
+}
@@ -0,0 +1,42 @@
+/************
+SUPER-CHIP flags
+
+FX75 stores V0 to VX in the HP48's RPL user flags and FX85 reads them back (see dispatch.rs).
+On the calculator the flags outlive the program, which is how SUPER-CHIP games keep high scores
+and progress, so the window keeps them per ROM: in <rom path>.rpl, through the storage backend
+(see storage.rs), read when the game starts and written each time the program stores them.
+
+The file is just the RPL_FLAGS bytes, V0's first. With --storage memory they only last for the
+session.
+************/
+use storage::Storage;
+use {Chip8, RPL_FLAGS};
+
+pub fn flags_path(rom: &str) -> String {
+    format!("{}.rpl", rom)
+}
+
+//Puts the ROM's saved flags into the machine, if it has any
+pub fn load(storage: &dyn Storage, chip8: &mut Chip8, rom: &str) {
+    let path = flags_path(rom);
+    match storage.read(&path) {
+        Ok(Some(data)) if data.len() == RPL_FLAGS => {
+            let mut flags = [0; RPL_FLAGS];
+            flags.copy_from_slice(&data);
+            chip8.set_rpl_flags(flags);
+        },
+        Ok(Some(data)) => println!("Ignoring {}: {} bytes instead of {}", path, data.len(), RPL_FLAGS),
+        Ok(None) => {},
+        Err(err) => println!("Could not load the SUPER-CHIP flags: {}", err),
+    }
+}
+
+//Writes the flags if the program stored them since the last time
+pub fn save_written(storage: &mut dyn Storage, chip8: &mut Chip8, rom: &str) {
+    if !chip8.take_rpl_written() {
+        return;
+    }
+    if let Err(err) = storage.write(&flags_path(rom), &chip8.rpl_flags()) {
+        println!("Could not save the SUPER-CHIP flags: {}", err);
+    }
+}
@@ -0,0 +1,95 @@
+/************
+Gamepad rumble (experimental)
+
+With --rumble STRENGTH (0 to 1), gamepads rumble while the sound timer runs, so the buzzer can
+be felt as well as heard. Only gamepads that have been used (pressed a button or moved a stick)
+are known to be connected, so a pad starts rumbling after its first input.
+
+Backends are picked by name with --rumble-device, like audio devices:
+    log  - prints when each pad would start and stop rumbling (default)
+    null - nothing
+
+That's all there is so far: the window library has no force feedback support, so no backend drives
+real motors yet and --rumble stays experimental until one does.
+************/
+use std::collections::BTreeSet;
+
+pub const DEFAULT_DEVICE: &str = "log";
+
+pub trait RumbleBackend {
+    fn name(&self) -> &str;
+
+    //Sets how strongly a pad rumbles, 0 stops it
+    fn set_strength(&mut self, pad: i32, strength: f32);
+}
+
+pub struct NullRumble;
+
+impl RumbleBackend for NullRumble {
+    fn name(&self) -> &str {
+        "null"
+    }
+
+    fn set_strength(&mut self, _pad: i32, _strength: f32) {}
+}
+
+pub struct LogRumble;
+
+impl RumbleBackend for LogRumble {
+    fn name(&self) -> &str {
+        "log"
+    }
+
+    fn set_strength(&mut self, pad: i32, strength: f32) {
+        if strength > 0.0 {
+            println!("Rumble pad{} at {:.0}%", pad, strength * 100.0);
+        } else {
+            println!("Rumble pad{} off", pad);
+        }
+    }
+}
+
+pub fn device_names() -> Vec<&'static str> {
+    vec!["log", "null"]
+}
+
+pub fn open_device(name: &str) -> Result<Box<dyn RumbleBackend>, String> {
+    match name {
+        "log" => Ok(Box::new(LogRumble)),
+        "null" => Ok(Box::new(NullRumble)),
+        _ => Err(format!("Unknown rumble device {} (available: {})", name, device_names().join(", "))),
+    }
+}
+
+//Follows the sound timer on every connected pad
+pub struct Rumble {
+    backend: Box<dyn RumbleBackend>,
+    strength: f32,
+    pads: BTreeSet<i32>,
+    on: bool,
+}
+
+impl Rumble {
+    pub fn new(backend: Box<dyn RumbleBackend>, strength: f32) -> Rumble {
+        Rumble { backend, strength, pads: BTreeSet::new(), on: false }
+    }
+
+    //A pad sent input, so it's connected
+    pub fn pad_seen(&mut self, pad: i32) {
+        if self.pads.insert(pad) && self.on {
+            self.backend.set_strength(pad, self.strength);
+        }
+    }
+
+    //Called every frame with whether the buzzer is sounding
+    pub fn update(&mut self, sound: bool) {
+        if sound == self.on {
+            return;
+        }
+        self.on = sound;
+        let strength = if sound { self.strength } else { 0.0 };
+        for pad in &self.pads {
+            self.backend.set_strength(*pad, strength);
+        }
+    }
+}
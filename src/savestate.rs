@@ -0,0 +1,198 @@
+/************
+Save states
+
+A save state is a snapshot of the whole machine, its MachineState (see state.rs) as bytes. Each state also stores a thumbnail: the screen at the time of saving,
+downscaled to 32 x 16 (whatever the resolution), so states can be told apart without loading them (the window's load menu, see slots.rs).
+
+File format (all numbers little endian):
+    "C8ST", version (1 byte), machine variant id (1 byte, see variant.rs)
+    thumbnail (32 * 16 bytes, one byte per pixel)
+    memory (4096), V registers (16), I (2), PC (2), SP (2), stack (16 * 2)
+    delay timer, sound timer, halt flag, halt register (1 byte each)
+    keys (16), flags (1 byte: 1 hi-res mode, 2 exited, see variant.rs)
+    screen (64 * 32, or 128 * 64 in hi-res mode), cycles (8), seed (8)
+    virtual stack entry count (2), entries (2 each), see stack.rs
+
+Version 1 states have no variant byte, they are loaded as CHIP-8 states. Before version 3 the
+first stack entry went unused and SP pointed at the top entry instead of past it, and there was no
+virtual stack. Before version 4 there were no flags, the screen was always 64 * 32.
+************/
+use display::{HIRES_HEIGHT, HIRES_WIDTH};
+use stack::STACK_SIZE;
+use state::MachineState;
+use variant::Variant;
+use Chip8;
+
+pub const THUMB_WIDTH: usize = 32;
+pub const THUMB_HEIGHT: usize = 16;
+
+const MAGIC: &[u8] = b"C8ST";
+pub const VERSION: u8 = 4;
+const THUMB_SIZE: usize = THUMB_WIDTH * THUMB_HEIGHT;
+
+//Shrinks the 64 x 32 (or 128 x 64) screen to a thumbnail, a thumbnail pixel is lit if any of its
+//2 x 2 (or 4 x 4) screen pixels are
+pub fn thumbnail(screen: &[u8]) -> Vec<u8> {
+    let block = if screen.len() == HIRES_WIDTH * HIRES_HEIGHT { 4 } else { 2 };
+    let width = THUMB_WIDTH * block;
+    let mut thumb = vec![0; THUMB_SIZE];
+    for y in 0..THUMB_HEIGHT {
+        for x in 0..THUMB_WIDTH {
+            let lit = (0..block).any(|dy| (0..block).any(|dx| screen[(x * block + dx) + (y * block + dy) * width] != 0));
+            thumb[x + y * THUMB_WIDTH] = lit as u8;
+        }
+    }
+    thumb
+}
+
+//Reads little endian numbers back out of a state, in the order they were written
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.pos + len > self.data.len() {
+            return Err(String::from("Save state is truncated"));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.bytes(2)?;
+        Ok(bytes[0] as u16 | (bytes[1] as u16) << 8)
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        let bytes = self.bytes(8)?;
+        Ok(bytes.iter().rev().fold(0, |value, byte| value << 8 | *byte as u64))
+    }
+}
+
+//Checks the header and returns the version, the variant the state was made with and where the thumbnail starts
+fn read_header(data: &[u8]) -> Result<(u8, Variant, usize), String> {
+    if data.len() < 5 || &data[0..4] != MAGIC {
+        return Err(String::from("Not a save state"));
+    }
+    match data[4] {
+        1 => Ok((1, Variant::Chip8, 5)),
+        version @ 2..=VERSION if data.len() > 5 => Ok((version, Variant::from_id(data[5])?, 6)),
+        2..=VERSION => Err(String::from("Save state is truncated")),
+        version => Err(format!("Unsupported save state version {}", version)),
+    }
+}
+
+impl Chip8 {
+    //Serializes the machine into a save state
+    pub fn save_state(&self) -> Vec<u8> {
+        let state = self.machine_state();
+        let mut data = Vec::new();
+        data.extend_from_slice(MAGIC);
+        data.push(VERSION);
+        data.push(state.variant.id());
+        data.extend(thumbnail(&state.screen));
+
+        data.extend_from_slice(&state.memory);
+        data.extend_from_slice(&state.v);
+        for value in [state.i, state.pc, state.sp].iter().chain(state.stack.iter()) {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+        data.push(state.delay_timer);
+        data.push(state.sound_timer);
+        data.push(state.halted as u8);
+        data.push(state.halt_reg);
+        data.extend_from_slice(&state.keys);
+        data.push(state.hires as u8 | (state.exited as u8) << 1);
+        data.extend_from_slice(&state.screen);
+        data.extend_from_slice(&state.cycles.to_le_bytes());
+        data.extend_from_slice(&state.seed.to_le_bytes());
+        data.extend_from_slice(&(state.deep_stack.len() as u16).to_le_bytes());
+        for entry in &state.deep_stack {
+            data.extend_from_slice(&entry.to_le_bytes());
+        }
+        data
+    }
+
+    //Restores the machine from a save state. Nothing changes if the state can't be read
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), String> {
+        let (version, variant, header_size) = read_header(data)?;
+        self.variant.check(variant, "Save state")?;
+        let mut reader = Reader { data, pos: header_size + THUMB_SIZE };
+
+        //Read everything first, so a truncated state can't leave the machine half loaded
+        let mut state = MachineState {
+            variant,
+            memory: reader.bytes(4096)?.to_vec(),
+            v: [0; 16],
+            i: 0,
+            pc: 0,
+            sp: 0,
+            stack: [0; STACK_SIZE],
+            deep_stack: Vec::new(),
+            delay_timer: 0,
+            sound_timer: 0,
+            screen: Vec::new(),
+            hires: false,
+            exited: false,
+            keys: [0; 16],
+            halted: false,
+            halt_reg: 0,
+            cycles: 0,
+            seed: 0,
+        };
+        state.v.copy_from_slice(reader.bytes(16)?);
+        state.i = reader.u16()?;
+        state.pc = reader.u16()?;
+        state.sp = reader.u16()?;
+        for entry in state.stack.iter_mut() {
+            *entry = reader.u16()?;
+        }
+        state.delay_timer = reader.u8()?;
+        state.sound_timer = reader.u8()?;
+        state.halted = reader.u8()? != 0;
+        state.halt_reg = reader.u8()?;
+        state.keys.copy_from_slice(reader.bytes(16)?);
+        if version >= 4 {
+            let flags = reader.u8()?;
+            state.hires = flags & 1 != 0;
+            state.exited = flags & 2 != 0;
+        }
+        let (width, height) = state.screen_size();
+        state.screen = reader.bytes(width * height)?.to_vec();
+        state.cycles = reader.u64()?;
+        state.seed = reader.u64()?;
+
+        if version < 3 {
+            //Move the entries down to where they go now, SP already counts them
+            if state.sp as usize >= STACK_SIZE {
+                return Err(String::from("Save state is corrupt"));
+            }
+            state.stack.rotate_left(1);
+        } else {
+            for _ in 0..reader.u16()? {
+                state.deep_stack.push(reader.u16()?);
+            }
+        }
+
+        state.check().map_err(|_| String::from("Save state is corrupt"))?;
+        self.set_machine_state(state)
+    }
+}
+
+//The variant a state was made with, None if it isn't a readable state
+pub fn state_variant(data: &[u8]) -> Option<Variant> {
+    read_header(data).ok().map(|(_, variant, _)| variant)
+}
+
+//Reads just the thumbnail of a state, None if it isn't a readable state
+pub fn state_thumbnail(data: &[u8]) -> Option<Vec<u8>> {
+    let (_, _, header_size) = read_header(data).ok()?;
+    data.get(header_size..header_size + THUMB_SIZE).map(|thumb| thumb.to_vec())
+}
@@ -0,0 +1,186 @@
+/************
+Script hooks
+
+--hooks FILE runs a Rhai script (https://rhai.rs) alongside the ROM, for cheats, automated
+playtesting and ROM analysis without recompiling. The script defines any of these functions, the
+machine is `this` in all of them (see hooks.rs for when they're called):
+
+    fn pre_cycle() { ... }              before every instruction
+    fn post_cycle() { ... }             after every instruction
+    fn on_draw() { ... }                after every DXYN
+    fn on_key(key, pressed) { ... }     when a key is pressed or released
+
+What the machine offers:
+    this.pc, this.i                 read and write
+    this.sp, this.dt, this.st, this.cycles      read only
+    this.v(x), this.set_v(x, value) the V registers
+    this.peek(addr), this.poke(addr, value)     memory
+    this.pixel(x, y)                whether a screen pixel is lit
+    this.key(k)                     whether a key is held
+    this.data                       a map the script can keep its own state in between calls, since
+                                    Rhai functions can't see the script's variables
+
+ie infinite lives and a log of every time the player dies:
+
+    fn post_cycle() { this.poke(0x3A0, 3); }
+    fn on_draw() { if this.pc == 0x2F4 { this.data.deaths = (this.data.deaths ?? 0) + 1; print(`died ${this.data.deaths}`); } }
+
+The top level of the script runs once when it's loaded. A hook that fails is reported with its
+error and not called again. Hooks only cost anything when the script defines them, but
+pre_cycle and post_cycle run hundreds of times a second and copy the machine each time, so they
+slow a game down more than on_draw and on_key.
+************/
+use std::fs;
+
+use rhai::{CallFnOptions, Dynamic, Engine, Map, Scope, AST, INT};
+
+use hooks::Hooks;
+use memory::MEMORY_SIZE;
+use Chip8;
+
+//The machine as the script sees it. A copy, whatever the script changed is written back afterwards
+#[derive(Clone)]
+struct Machine {
+    v: [u8; 16],
+    i: u16,
+    pc: u16,
+    sp: u16,
+    dt: u8,
+    st: u8,
+    cycles: u64,
+    keys: [bool; 16],
+    memory: Vec<u8>,
+    screen: Vec<u8>,
+    data: Map,
+}
+
+impl Machine {
+    fn new(chip8: &Chip8, data: Map) -> Machine {
+        let state = chip8.machine_state();
+        Machine {
+            v: state.v,
+            i: state.i,
+            pc: state.pc,
+            sp: state.sp,
+            dt: state.delay_timer,
+            st: state.sound_timer,
+            cycles: state.cycles,
+            keys: state.keys.map(|key| key != 0),
+            memory: state.memory,
+            screen: state.screen,
+            data,
+        }
+    }
+
+    //Writes what the script changed into the machine, and hands back its data
+    fn apply(self, original: &Machine, chip8: &mut Chip8) -> Map {
+        for x in 0..16 {
+            if self.v[x] != original.v[x] {
+                chip8.set_register(x, self.v[x]);
+            }
+        }
+        if self.i != original.i {
+            chip8.set_index(self.i);
+        }
+        if self.pc != original.pc {
+            chip8.set_pc(self.pc);
+        }
+        for (addr, (new, old)) in self.memory.iter().zip(&original.memory).enumerate() {
+            if new != old {
+                chip8.write_byte(addr as u16, *new);
+            }
+        }
+        self.data
+    }
+}
+
+fn engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.register_type_with_name::<Machine>("Machine")
+        .register_get_set("pc", |m: &mut Machine| m.pc as INT, |m: &mut Machine, value: INT| m.pc = value as u16 & 0xFFF)
+        .register_get_set("i", |m: &mut Machine| m.i as INT, |m: &mut Machine, value: INT| m.i = value as u16)
+        .register_get("sp", |m: &mut Machine| m.sp as INT)
+        .register_get("dt", |m: &mut Machine| m.dt as INT)
+        .register_get("st", |m: &mut Machine| m.st as INT)
+        .register_get("cycles", |m: &mut Machine| m.cycles as INT)
+        .register_get_set("data", |m: &mut Machine| m.data.clone(), |m: &mut Machine, data: Map| m.data = data)
+        .register_fn("v", |m: &mut Machine, x: INT| m.v[x as usize & 0xF] as INT)
+        .register_fn("set_v", |m: &mut Machine, x: INT, value: INT| m.v[x as usize & 0xF] = value as u8)
+        .register_fn("peek", |m: &mut Machine, addr: INT| m.memory[addr as usize % MEMORY_SIZE] as INT)
+        .register_fn("poke", |m: &mut Machine, addr: INT, value: INT| m.memory[addr as usize % MEMORY_SIZE] = value as u8)
+        .register_fn("key", |m: &mut Machine, key: INT| m.keys[key as usize & 0xF])
+        .register_fn("pixel", |m: &mut Machine, x: INT, y: INT| {
+            (0..64).contains(&x) && (0..32).contains(&y) && m.screen[(x + y * 64) as usize] != 0
+        });
+    engine
+}
+
+const HOOKS: [&str; 4] = ["pre_cycle", "post_cycle", "on_draw", "on_key"];
+
+pub struct ScriptHooks {
+    path: String,
+    engine: Engine,
+    ast: AST,
+    defined: [bool; 4],     //Which of HOOKS the script defines, and hasn't failed in
+    data: Map,
+}
+
+impl ScriptHooks {
+    pub fn load(path: &str) -> Result<ScriptHooks, String> {
+        let source = fs::read_to_string(path).map_err(|err| format!("Could not read script {}: {}", path, err))?;
+        let engine = engine();
+        let ast = engine.compile(&source).map_err(|err| format!("{}: {}", path, err))?;
+        engine.run_ast(&ast).map_err(|err| format!("{}: {}", path, err))?;
+
+        let mut defined = [false; 4];
+        for function in ast.iter_functions() {
+            if let Some(index) = HOOKS.iter().position(|name| *name == function.name) {
+                let params = if HOOKS[index] == "on_key" { 2 } else { 0 };
+                if function.params.len() != params {
+                    return Err(format!("{}: {} takes {} arguments", path, function.name, params));
+                }
+                defined[index] = true;
+            }
+        }
+        if !defined.contains(&true) {
+            println!("Warning: {} defines none of the hooks ({})", path, HOOKS.join(", "));
+        }
+        Ok(ScriptHooks { path: path.to_string(), engine, ast, defined, data: Map::new() })
+    }
+
+    fn call(&mut self, hook: usize, chip8: &mut Chip8, args: Vec<Dynamic>) {
+        if !self.defined[hook] {
+            return;
+        }
+        let original = Machine::new(chip8, std::mem::take(&mut self.data));
+        let mut this = Dynamic::from(original.clone());
+        let options = CallFnOptions::new().eval_ast(false).bind_this_ptr(&mut this);
+        let result = self.engine.call_fn_with_options::<Dynamic>(options, &mut Scope::new(), &self.ast, HOOKS[hook], args);
+        if let Err(err) = result {
+            println!("{}: {} failed, not calling it again: {}", self.path, HOOKS[hook], err);
+            self.defined[hook] = false;
+        }
+        self.data = match this.try_cast::<Machine>() {
+            Some(machine) => machine.apply(&original, chip8),
+            None => original.data,
+        };
+    }
+}
+
+impl Hooks for ScriptHooks {
+    fn pre_cycle(&mut self, chip8: &mut Chip8) {
+        self.call(0, chip8, Vec::new());
+    }
+
+    fn post_cycle(&mut self, chip8: &mut Chip8) {
+        self.call(1, chip8, Vec::new());
+    }
+
+    fn on_draw(&mut self, chip8: &mut Chip8) {
+        self.call(2, chip8, Vec::new());
+    }
+
+    fn on_key(&mut self, chip8: &mut Chip8, key: u8, pressed: bool) {
+        self.call(3, chip8, vec![Dynamic::from(key as INT), Dynamic::from(pressed)]);
+    }
+}
@@ -0,0 +1,281 @@
+/************
+Implementation self-test
+
+Usage: chip8 selftest
+
+Runs a set of tiny built-in programs, each exercising one opcode (or one branch of it), headless
+and checks the machine state they leave behind against what Cowgod's technical reference says
+should happen. Prints a pass/fail matrix, one row per test and one column per configuration, and
+exits with 1 if anything failed, so users can check their build before filing compatibility bugs.
+
+The columns are the ways DXYN can count pixels clipped off the bottom of the screen towards VF
+(--clip-collision, see sprite.rs): every test runs under each of them, and the tests of clipping
+itself only in the columns they're about (- in the others). The tests of --quirks (see quirks.rs)
+turn on the quirk they're about before running, the others run with the default quirks. The tests
+of SUPER-CHIP's opcodes run with --variant schip (see variant.rs), the others as CHIP-8.
+************/
+use std::process;
+
+use benchrom::assemble;
+use exitcode;
+use headless::run_cycles;
+use quirks::Quirks;
+use sprite::ClipCollision;
+use variant::Variant;
+use {Chip8, BIG_FONT};
+
+//Plenty for every test program, which all end in a jump to themselves
+const TEST_CYCLES: u64 = 100;
+
+struct Case {
+    opcode: &'static str,
+    name: &'static str,
+    program: Vec<u16>,
+    setup: fn(&mut Chip8),
+    quirks: Quirks,
+    variant: Variant,
+    check: fn(&Chip8) -> bool,
+    modes: &'static [ClipCollision],    //The configurations it runs under
+}
+
+const ALL_MODES: &[ClipCollision] = &[ClipCollision::None, ClipCollision::Flag, ClipCollision::Rows];
+
+fn case(opcode: &'static str, name: &'static str, program: &[u16], check: fn(&Chip8) -> bool) -> Case {
+    Case { opcode, name, program: program.to_vec(), setup: |_| {}, quirks: Quirks::DEFAULT, variant: Variant::Chip8, check, modes: ALL_MODES }
+}
+
+fn v(chip8: &Chip8, reg: usize) -> u8 {
+    chip8.register(reg)
+}
+
+fn cleared(chip8: &Chip8) -> bool {
+    chip8.screen().iter().all(|pixel| *pixel == 0)
+}
+
+fn cases() -> Vec<Case> {
+    let mut cases = vec![
+        case("00E0", "CLS clears the screen", &[0xA050, 0xD005, 0x00E0, 0x1206],
+             |c| c.screen().iter().all(|pixel| *pixel == 0)),
+        case("2NNN", "CALL and RET come back after the call", &[0x2206, 0x6001, 0x1204, 0x6105, 0x00EE],
+             |c| v(c, 0) == 1 && v(c, 1) == 5 && c.sp() == 0),
+        case("1NNN", "JP jumps", &[0x1204, 0x6001, 0x6102, 0x1206],
+             |c| v(c, 0) == 0 && v(c, 1) == 2),
+        case("3XKK", "SE skips when equal", &[0x6005, 0x3005, 0x6101, 0x1206],
+             |c| v(c, 1) == 0),
+        case("3XKK", "SE doesn't skip when different", &[0x6005, 0x3006, 0x6101, 0x1206],
+             |c| v(c, 1) == 1),
+        case("4XKK", "SNE skips when different", &[0x6005, 0x4006, 0x6101, 0x1206],
+             |c| v(c, 1) == 0),
+        case("5XY0", "SE VX, VY skips when equal", &[0x6005, 0x6105, 0x5010, 0x6201, 0x1208],
+             |c| v(c, 2) == 0),
+        case("9XY0", "SNE VX, VY skips when different", &[0x6005, 0x6106, 0x9010, 0x6201, 0x1208],
+             |c| v(c, 2) == 0),
+        case("6XKK", "LD VX, KK", &[0x6A42, 0x1202],
+             |c| v(c, 0xA) == 0x42),
+        case("7XKK", "ADD VX, KK wraps without touching VF", &[0x60FF, 0x7002, 0x1204],
+             |c| v(c, 0) == 1 && v(c, 0xF) == 0),
+        case("8XY0", "LD VX, VY", &[0x6107, 0x8010, 0x1204],
+             |c| v(c, 0) == 7),
+        case("8XY1", "OR", &[0x600C, 0x610A, 0x8011, 0x1206],
+             |c| v(c, 0) == 0x0E),
+        case("8XY2", "AND", &[0x600C, 0x610A, 0x8012, 0x1206],
+             |c| v(c, 0) == 0x08),
+        case("8XY3", "XOR", &[0x600C, 0x610A, 0x8013, 0x1206],
+             |c| v(c, 0) == 0x06),
+        case("8XY4", "ADD sets VF on carry", &[0x60F0, 0x6120, 0x8014, 0x1206],
+             |c| v(c, 0) == 0x10 && v(c, 0xF) == 1),
+        case("8XY4", "ADD clears VF without carry", &[0x6F01, 0x6010, 0x6120, 0x8014, 0x1208],
+             |c| v(c, 0) == 0x30 && v(c, 0xF) == 0),
+        case("8XY5", "SUB sets VF without borrow", &[0x6030, 0x6110, 0x8015, 0x1206],
+             |c| v(c, 0) == 0x20 && v(c, 0xF) == 1),
+        case("8XY5", "SUB clears VF on borrow", &[0x6010, 0x6130, 0x8015, 0x1206],
+             |c| v(c, 0) == 0xE0 && v(c, 0xF) == 0),
+        case("8XY6", "SHR puts the lowest bit in VF", &[0x6005, 0x8006, 0x1204],
+             |c| v(c, 0) == 0x02 && v(c, 0xF) == 1),
+        case("8XY6", "SHR clears VF for an even value", &[0x6F01, 0x6004, 0x8006, 0x1206],
+             |c| v(c, 0) == 0x02 && v(c, 0xF) == 0),
+        case("8XY7", "SUBN sets VF without borrow", &[0x6010, 0x6130, 0x8017, 0x1206],
+             |c| v(c, 0) == 0x20 && v(c, 0xF) == 1),
+        case("8XYE", "SHL puts the highest bit in VF", &[0x6081, 0x800E, 0x1204],
+             |c| v(c, 0) == 0x02 && v(c, 0xF) == 1),
+        case("8XYE", "SHL clears VF for a small value", &[0x6F01, 0x6001, 0x800E, 0x1206],
+             |c| v(c, 0) == 0x02 && v(c, 0xF) == 0),
+        case("ANNN", "LD I, NNN", &[0xA123, 0x1202],
+             |c| c.index() == 0x123),
+        case("BNNN", "JP V0, NNN jumps to NNN + V0", &[0x6002, 0xB204, 0x6101, 0x1206],
+             |c| v(c, 1) == 0 && c.pc() == 0x206),
+        case("CXKK", "RND is masked with KK", &[0xC00F, 0xC100, 0x1204],
+             |c| v(c, 0) <= 0x0F && v(c, 1) == 0),
+        case("DXYN", "DRW draws a sprite without collision", &[0xA050, 0x6000, 0xD005, 0x1206],
+             |c| c.screen()[0] == 1 && c.screen()[3] == 1 && c.screen()[4] == 0 && v(c, 0xF) == 0),
+        case("EX9E", "SKP doesn't skip for a released key", &[0x6005, 0xE09E, 0x6101, 0x1206],
+             |c| v(c, 1) == 1),
+        case("EXA1", "SKNP skips for a released key", &[0x6005, 0xE0A1, 0x6101, 0x1206],
+             |c| v(c, 1) == 0),
+        case("FX07", "LD VX, DT reads the delay timer", &[0x6030, 0xF015, 0xF107, 0x1206],
+             |c| v(c, 1) >= 0x2E && v(c, 1) <= 0x30),
+        case("FX0A", "LD VX, K waits for a key", &[0xF00A, 0x6101, 0x1204],
+             |c| c.is_halted() && v(c, 1) == 0),
+        case("FX18", "LD ST, VX sets the sound timer", &[0x6030, 0xF018, 0x1204],
+             |c| c.sound_active()),
+        case("FX1E", "ADD I, VX", &[0xA100, 0x6023, 0xF01E, 0x1206],
+             |c| c.index() == 0x123),
+        case("FX29", "LD F, VX points I at the font sprite", &[0x6007, 0xF029, 0x1204],
+             |c| c.index() == c.font_addr() + 7 * 5),
+        case("FX33", "LD B, VX stores BCD", &[0xA300, 0x607B, 0xF033, 0x1206],
+             |c| c.read_byte(0x300) == 1 && c.read_byte(0x301) == 2 && c.read_byte(0x302) == 3),
+        case("FX55", "LD [I], VX stores V0 through VX", &[0xA300, 0x6001, 0x6102, 0x6203, 0xF255, 0x120A],
+             |c| c.read_byte(0x300) == 1 && c.read_byte(0x301) == 2 && c.read_byte(0x302) == 3),
+        case("FX65", "LD VX, [I] loads V0 through VX", &[0xA20C, 0xF265, 0x1204, 0x0000, 0x0000, 0x0000, 0x0102, 0x0300],
+             |c| v(c, 0) == 1 && v(c, 1) == 2 && v(c, 2) == 3),
+    ];
+
+    //A held key, which needs a key press before running
+    let mut pressed = case("EX9E", "SKP skips for a held key", &[0x6005, 0xE09E, 0x6101, 0x1206], |c| v(c, 1) == 0);
+    pressed.setup = |c| c.set_key(5, 1);
+    cases.push(pressed);
+
+    //Quirks, each test with the quirk it names turned on. The sprite is the font's 0 drawn at x=62, half of it
+    //past the right edge
+    let past_edge = [0xA050, 0x603E, 0x6100, 0xD015, 0x1208];
+    let quirk_cases: [(Case, Quirks); 10] = [
+        (case("8XY1", "OR leaves VF alone", &[0x6F05, 0x600C, 0x610A, 0x8011, 0x1208],
+              |c| v(c, 0) == 0x0E && v(c, 0xF) == 5), Quirks::DEFAULT),
+        (case("8XY1", "OR clears VF (vf-reset)", &[0x6F05, 0x600C, 0x610A, 0x8011, 0x1208],
+              |c| v(c, 0) == 0x0E && v(c, 0xF) == 0), Quirks { vf_reset: true, ..Quirks::DEFAULT }),
+        (case("8XY6", "SHR sets VF after the result when X is F", &[0x6F05, 0x8F06, 0x1204],
+              |c| v(c, 0xF) == 1), Quirks::DEFAULT),
+        (case("8XY6", "SHR shifts VY into VX (shift-vy)", &[0x6005, 0x6107, 0x8016, 0x1206],
+              |c| v(c, 0) == 0x03 && v(c, 1) == 0x07 && v(c, 0xF) == 1), Quirks::VIP),
+        (case("8XYE", "SHL shifts VY into VX (shift-vy)", &[0x6181, 0x801E, 0x1204],
+              |c| v(c, 0) == 0x02 && v(c, 1) == 0x81 && v(c, 0xF) == 1), Quirks::VIP),
+        (case("BNNN", "JP V0, NNN ignores VX", &[0x6202, 0xB204, 0x6101, 0x1206],
+              |c| v(c, 1) == 1), Quirks::DEFAULT),
+        (case("BNNN", "JP VX, XNN jumps to XNN + VX (jump-vx)", &[0x6202, 0xB204, 0x6101, 0x1206],
+              |c| v(c, 1) == 0 && c.pc() == 0x206), Quirks::SCHIP),
+        (case("DXYN", "DRW wraps the starting position", &[0xA050, 0x6042, 0x6100, 0xD015, 0x1208],
+              |c| c.screen()[2] == 1 && c.screen()[5] == 1), Quirks::DEFAULT),
+        (case("DXYN", "DRW clips at the right edge (clip)", &past_edge,
+              |c| c.screen()[62] == 1 && c.screen()[0] == 0 && c.screen()[64] == 0 && c.screen()[65] == 0), Quirks::DEFAULT),
+        (case("DXYN", "DRW wraps past the right edge (no-clip)", &past_edge,
+              |c| c.screen()[62] == 1 && c.screen()[0] == 1 && c.screen()[1] == 1 && c.screen()[64] == 0),
+         Quirks { clip: false, ..Quirks::DEFAULT }),
+    ];
+    for (mut quirk_case, quirks) in quirk_cases {
+        quirk_case.quirks = quirks;
+        cases.push(quirk_case);
+    }
+
+    //SUPER-CHIP. The sprite is the font's 0 (4 x 5, top row lit) at 0,0 unless the test moves it, the 16 x 16
+    //sprites are all lit
+    let schip_cases = [
+        case("00FF", "HIGH switches to 128 x 64", &[0x00FF, 0x1202],
+             |c| c.is_hires() && c.screen().len() == 128 * 64),
+        case("00FF", "HIGH clears the screen", &[0xA050, 0x6000, 0xD005, 0x00FF, 0x1208],
+             |c| c.is_hires() && cleared(c)),
+        case("00FE", "LOW switches back to 64 x 32", &[0x00FF, 0x00FE, 0x1204],
+             |c| !c.is_hires() && c.screen().len() == 64 * 32),
+        case("00CN", "SCD scrolls down N pixels", &[0xA050, 0x6000, 0xD005, 0x00C2, 0x1208],
+             |c| !c.pixel(0, 0) && !c.pixel(0, 1) && c.pixel(0, 2) && c.pixel(3, 2)),
+        case("00FB", "SCR scrolls right 4 pixels", &[0xA050, 0x6000, 0xD005, 0x00FB, 0x1208],
+             |c| !c.pixel(0, 0) && c.pixel(4, 0) && c.pixel(7, 0) && !c.pixel(8, 0)),
+        case("00FC", "SCL scrolls left 4 pixels", &[0xA050, 0x6004, 0x6100, 0xD015, 0x00FC, 0x120A],
+             |c| c.pixel(0, 0) && c.pixel(3, 0) && !c.pixel(4, 0)),
+        case("00FD", "EXIT stops the program", &[0x6001, 0x00FD, 0x6002, 0x1206],
+             |c| c.has_exited() && v(c, 0) == 1 && c.pc() == 0x202),
+        case("DXY0", "DRW draws a 16 x 16 sprite", &[0xA300, 0x6000, 0xD000, 0x1206],
+             |c| (0..16).all(|y| (0..16).all(|x| c.pixel(x, y))) && !c.pixel(16, 0) && !c.pixel(0, 16) && v(c, 0xF) == 0),
+        case("DXY0", "DRW draws 16 x 16 in hi-res", &[0x00FF, 0xA300, 0x6070, 0x6100, 0xD010, 0x120A],
+             |c| c.pixel(112, 0) && c.pixel(127, 15) && !c.pixel(111, 0) && !c.pixel(112, 16)),
+        case("FX30", "LD HF, VX points I at the big font sprite", &[0x6007, 0xF030, 0x1204],
+             |c| c.index() == c.big_font_addr() + 7 * 10 && c.read_byte(c.index()) == BIG_FONT[70]),
+        case("FX75", "LD R, VX stores V0 to VX in the flags", &[0x6011, 0x6122, 0x6233, 0xF175, 0x1208],
+             |c| c.rpl_flags() == [0x11, 0x22, 0, 0, 0, 0, 0, 0]),
+        case("FX85", "LD VX, R reads V0 to VX from the flags", &[0x6011, 0x6122, 0xF175, 0x6000, 0x6100, 0x6233, 0xF185, 0x120E],
+             |c| v(c, 0) == 0x11 && v(c, 1) == 0x22 && v(c, 2) == 0x33),
+    ];
+    for mut schip_case in schip_cases {
+        schip_case.variant = Variant::Schip;
+        schip_case.setup = |c| c.load_segment(0x300, &[0xFF; 32]).unwrap();
+        cases.push(schip_case);
+    }
+
+    //Collisions, and the font's 0 drawn at y=30: 2 rows on screen, the 3 below clipped
+    let redrawn = [0xA050, 0x6000, 0xD005, 0xD005, 0x1208];
+    let clipped = [0xA050, 0x6000, 0x611E, 0xD015, 0x1208];
+    let clipped_redrawn = [0xA050, 0x6000, 0x611E, 0xD015, 0xD015, 0x120A];
+    let clip_cases: [(Case, &'static [ClipCollision]); 6] = [
+        (case("DXYN", "DRW erases and sets VF on collision", &redrawn, |c| cleared(c) && v(c, 0xF) == 1),
+         &[ClipCollision::None, ClipCollision::Flag]),
+        (case("DXYN", "DRW counts collided rows in VF", &redrawn, |c| cleared(c) && v(c, 0xF) == 5),
+         &[ClipCollision::Rows]),
+        (case("DXYN", "DRW ignores clipped pixels for VF", &clipped, |c| v(c, 0xF) == 0), &[ClipCollision::None]),
+        (case("DXYN", "DRW sets VF for clipped pixels", &clipped, |c| v(c, 0xF) == 1), &[ClipCollision::Flag]),
+        (case("DXYN", "DRW counts clipped rows in VF", &clipped, |c| v(c, 0xF) == 3), &[ClipCollision::Rows]),
+        (case("DXYN", "DRW counts collided and clipped rows in VF", &clipped_redrawn, |c| cleared(c) && v(c, 0xF) == 5),
+         &[ClipCollision::Rows]),
+    ];
+    for (mut clip_case, modes) in clip_cases {
+        clip_case.modes = modes;
+        cases.push(clip_case);
+    }
+    cases
+}
+
+fn run_case(case: &Case, clip_collision: ClipCollision) -> bool {
+    let mut chip8 = Chip8::new();
+    chip8.initialize();
+    chip8.set_variant(case.variant);
+    chip8.trace = false;
+    chip8.set_seed(0);
+    chip8.set_clip_collision(clip_collision);
+    chip8.set_quirks(case.quirks);
+    chip8.load_rom_bytes(&assemble(&case.program));
+    (case.setup)(&mut chip8);
+    let stop = run_cycles(&mut chip8, TEST_CYCLES);
+    stop != "invalid_pc" && stop != "memory_fault" && (case.check)(&chip8)
+}
+
+//Entry point for "chip8 selftest ...", with the arguments following "selftest"
+pub fn run(args: &[String]) {
+    if let Some(arg) = args.first() {
+        println!("Unexpected argument {}. Aborting", arg);
+        process::exit(exitcode::FAILURE);
+    }
+
+    let configurations = [
+        ("standard", ClipCollision::None),
+        ("clip-flag", ClipCollision::Flag),
+        ("clip-rows", ClipCollision::Rows),
+    ];
+    let mut cases = cases();
+    cases.sort_by_key(|case| case.opcode);
+
+    let names: Vec<&str> = configurations.iter().map(|(name, _)| *name).collect();
+    println!("{:6} {:44} {}", "Opcode", "Test", names.join(" "));
+    let mut failed = 0;
+    let mut total = 0;
+    for case in &cases {
+        let mut row = format!("{:6} {:44}", case.opcode, case.name);
+        for (name, mode) in configurations.iter() {
+            let result = if !case.modes.contains(mode) {
+                "-"
+            } else {
+                total += 1;
+                if run_case(case, *mode) {
+                    "pass"
+                } else {
+                    failed += 1;
+                    "FAIL"
+                }
+            };
+            row.push_str(&format!(" {:width$}", result, width = name.len()));
+        }
+        println!("{}", row.trim_end());
+    }
+    println!("{} of {} passed", total - failed, total);
+
+    if failed > 0 {
+        process::exit(exitcode::FAILURE);
+    }
+}
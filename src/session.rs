@@ -0,0 +1,191 @@
+/************
+Session recordings
+
+A session file (<rom path>.c8session) holds everything needed to reproduce a run exactly: which
+ROM it was (path and hash), the options that change how the machine behaves, the state the
+machine was in when recording started, and every key and sound event since, as in a replay (see
+replay.rs).
+Meant as the one attachment a bug report needs.
+
+F8 writes the session of the running game. Recording starts with the game, and starts over
+whenever a save state is loaded, since the run after it no longer follows from the inputs before.
+"chip8 session <file> [options]" plays a session back, any options are added to the recorded ones.
+
+File format (text, one entry per line):
+    c8session 2
+    rom PATH
+    rom-hash HASH        - metadata::rom_hash of the ROM, in hex
+    arg ARG              - one per recorded command line argument, in order
+    packed-state HEX     - save state (see savestate.rs) of the machine when recording started,
+                           compressed on its own (see snapshot.rs)
+    inputs               - everything after this line is a replay
+
+Version 1 sessions have an uncompressed "state HEX" instead, they are still read.
+************/
+use std::fs;
+
+use metadata;
+use replay::{Replay, ReplayPlayer};
+use snapshot;
+use Chip8;
+
+pub const HEADER: &str = "c8session 2";
+const OLD_HEADER: &str = "c8session 1";
+
+//Options that change what the machine does, and so are needed to reproduce a run. Every one of them takes a value
+const EMULATION_OPTIONS: &[&str] = &["--poke", "--load", "--load-addr", "--font-addr", "--protect", "--stack", "--draw-check", "--sprite-limit", "--clip-collision", "--variant", "--quirks", "--address-mode", "--mmio", "--extension", "--hooks", "--ips"];
+
+pub fn session_path(rom: &str) -> String {
+    format!("{}.c8session", rom)
+}
+
+//Picks the emulation options (and their values) out of the command line
+pub fn emulation_args(args: &[String]) -> Vec<String> {
+    let mut kept = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if EMULATION_OPTIONS.contains(&arg.as_str()) {
+            kept.push(arg.clone());
+            kept.extend(args.next().cloned());
+        }
+    }
+    kept
+}
+
+struct Session {
+    rom: String,
+    rom_hash: u64,
+    args: Vec<String>,
+    state: Vec<u8>,
+    inputs: Replay,
+}
+
+impl Session {
+    fn load(path: &str) -> Result<Session, String> {
+        let text = fs::read_to_string(path).map_err(|err| format!("Could not read session {}: {}", path, err))?;
+        let lines: Vec<&str> = text.lines().collect();
+        if lines.first() != Some(&HEADER) && lines.first() != Some(&OLD_HEADER) {
+            return Err(format!("{} is not a session file", path));
+        }
+
+        let mut rom = None;
+        let mut rom_hash = None;
+        let mut args = Vec::new();
+        let mut state = None;
+        for (number, line) in lines.iter().enumerate().skip(1) {
+            let invalid = || format!("{}:{}: Invalid session line", path, number + 1);
+            let (word, value) = match line.find(' ') {
+                Some(space) => (&line[..space], &line[space + 1..]),
+                None => (*line, ""),
+            };
+
+            match word {
+                "rom" => rom = Some(value.to_string()),
+                "rom-hash" => rom_hash = Some(u64::from_str_radix(value, 16).map_err(|_| invalid())?),
+                "arg" => args.push(value.to_string()),
+                "state" => state = Some(parse_hex(value).ok_or_else(invalid)?),
+                "packed-state" => {
+                    let packed = parse_hex(value).ok_or_else(invalid)?;
+                    state = Some(snapshot::decode(&[], &packed).map_err(|err| format!("{}:{}: {}", path, number + 1, err))?);
+                },
+                "inputs" => {
+                    let inputs = Replay::parse_lines(&lines[number + 1..], path, number + 2)?;
+                    return match (rom, rom_hash, state) {
+                        (Some(rom), Some(rom_hash), Some(state)) => Ok(Session { rom, rom_hash, args, state, inputs }),
+                        _ => Err(format!("Session {} is incomplete", path)),
+                    };
+                },
+                "" => {},
+                _ => return Err(invalid()),
+            }
+        }
+        Err(format!("Session {} has no inputs", path))
+    }
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|pos| u8::from_str_radix(text.get(pos..pos + 2)?, 16).ok()).collect()
+}
+
+//Records the session of a running game
+pub struct SessionRecorder {
+    args: Vec<String>,
+    start: Vec<u8>,
+    inputs: Replay,
+}
+
+impl SessionRecorder {
+    pub fn new(chip8: &Chip8, args: Vec<String>) -> SessionRecorder {
+        SessionRecorder {
+            args,
+            start: chip8.save_state(),
+            inputs: Replay::new(chip8.seed(), chip8.variant()),
+        }
+    }
+
+    //Forgets everything recorded so far and starts again from the machine's current state
+    pub fn restart(&mut self, chip8: &Chip8) {
+        self.start = chip8.save_state();
+        self.inputs = Replay::new(chip8.seed(), chip8.variant());
+    }
+
+    pub fn record(&mut self, cycle: u64, key: u8, state: u8) {
+        self.inputs.record(cycle, key, state);
+    }
+
+    pub fn record_sound(&mut self, cycle: u64, on: bool) {
+        self.inputs.record_sound(cycle, on);
+    }
+
+    //Writes the session next to the ROM and returns where it went
+    pub fn save(&self, rom: &str) -> Result<String, String> {
+        let bytes = fs::read(rom).map_err(|err| format!("Could not read {}: {}", rom, err))?;
+
+        let mut text = format!("{}\nrom {}\nrom-hash {:016x}\n", HEADER, rom, metadata::rom_hash(&bytes));
+        for arg in &self.args {
+            text.push_str(&format!("arg {}\n", arg));
+        }
+        text.push_str("packed-state ");
+        for byte in &snapshot::encode(&[], &self.start) {
+            text.push_str(&format!("{:02x}", byte));
+        }
+        text.push_str("\ninputs\n");
+        text.push_str(&self.inputs.to_text());
+
+        let path = session_path(rom);
+        fs::write(&path, text).map_err(|err| format!("Could not write session {}: {}", path, err))?;
+        Ok(path)
+    }
+}
+
+//Turns "chip8 session <file> [options]" into the command line that reproduces the session
+pub fn command_line(args: &[String]) -> Result<Vec<String>, String> {
+    let path = match args.first() {
+        Some(path) => path,
+        None => return Err(String::from("Usage: chip8 session <file> [options]")),
+    };
+    let session = Session::load(path)?;
+
+    let mut command_line = vec![session.rom];
+    command_line.extend(session.args);
+    command_line.extend(args[1..].iter().cloned());
+    command_line.push(String::from("--session"));
+    command_line.push(path.clone());
+    Ok(command_line)
+}
+
+//Puts the machine in the session's starting state and returns the player feeding it the recorded inputs.
+//Refuses if the ROM isn't the one the session was recorded with
+pub fn start_playback(path: &str, chip8: &mut Chip8) -> Result<ReplayPlayer, String> {
+    let session = Session::load(path)?;
+    let hash = metadata::rom_file_hash(&session.rom).ok_or_else(|| format!("Could not read {}", session.rom))?;
+    if hash != session.rom_hash {
+        return Err(format!("{} has changed since the session was recorded (hash {:016x}, expected {:016x})",
+                           session.rom, hash, session.rom_hash));
+    }
+    chip8.load_state(&session.state)?;
+    ReplayPlayer::new(session.inputs, chip8)
+}
@@ -0,0 +1,122 @@
+/************
+Shared debugger sessions
+
+"chip8 debug <romfile> --share ADDR" opens the debugger as usual and also listens on ADDR (ie
+127.0.0.1:6502) for other people to attach to the same session, so two people can look at a tricky
+bug together. Clients speak plain text over TCP, one line at a time (nc or telnet will do):
+
+    the first line picks the mode: "readonly" to only watch, "copilot" to also type commands
+    every command run in the session, whoever typed it, is sent to every client as "> COMMAND",
+    followed by the machine state after it ("pc=... i=... sp=... v=...") or "error: ..."
+    in copilot mode, every further line is a debugger command, run as if typed at the debugger
+
+Commands run one at a time, in the order they arrive. Clients can't quit the session or run the
+commands that read or write files (save, load, export, import, hook), and their commands run at
+most 100000 cycles (REMOTE_RUN_LIMIT in debugger.rs). The full output (register dumps, memory
+dumps, history) stays on the terminal running the debugger, clients get the summary. Anyone on
+the network can attach, so only share on addresses you trust.
+************/
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+//Where a command came from
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Local,
+    Remote(String),     //Address of the client
+}
+
+type Clients = Arc<Mutex<Vec<TcpStream>>>;
+
+pub struct Share {
+    commands: Receiver<(Source, String)>,
+    clients: Clients,
+}
+
+impl Share {
+    //Starts listening for clients and reading the local terminal
+    pub fn listen(addr: &str) -> Result<Share, String> {
+        let listener = TcpListener::bind(addr).map_err(|err| format!("Could not listen on {}: {}", addr, err))?;
+        let (sender, commands) = mpsc::channel();
+        let clients: Clients = Arc::new(Mutex::new(Vec::new()));
+        println!("Sharing the session on {}", addr);
+
+        let accepting = Arc::clone(&clients);
+        let remote = sender.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let clients = Arc::clone(&accepting);
+                let sender = remote.clone();
+                thread::spawn(move || serve(stream, clients, sender));
+            }
+        });
+
+        thread::spawn(move || {
+            let stdin = io::stdin();
+            for line in stdin.lock().lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if sender.send((Source::Local, line)).is_err() {
+                    return;
+                }
+            }
+            //End of input quits, as it does without sharing
+            let _ = sender.send((Source::Local, String::from("quit")));
+        });
+
+        Ok(Share { commands, clients })
+    }
+
+    //Waits for the next command from anyone in the session
+    pub fn next_command(&self) -> Option<(Source, String)> {
+        self.commands.recv().ok()
+    }
+
+    //Sends a line to every client, dropping the ones that went away
+    pub fn broadcast(&self, line: &str) {
+        let mut clients = self.clients.lock().unwrap();
+        clients.retain(|mut client| writeln!(client, "{}", line).is_ok());
+    }
+}
+
+//Talks to one client until it disconnects
+fn serve(stream: TcpStream, clients: Clients, sender: Sender<(Source, String)>) {
+    let peer = stream.peer_addr().map(|addr| addr.to_string()).unwrap_or_else(|_| String::from("unknown"));
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return,
+    };
+    let mut lines = BufReader::new(stream).lines();
+
+    let copilot = match lines.next() {
+        Some(Ok(ref mode)) if mode.trim() == "copilot" => true,
+        Some(Ok(ref mode)) if mode.trim() == "readonly" => false,
+        _ => {
+            let _ = writeln!(writer, "error: expected readonly or copilot");
+            return;
+        },
+    };
+    println!("{} joined the session ({})", peer, if copilot { "copilot" } else { "read-only" });
+    let _ = writeln!(writer, "joined as {}", if copilot { "copilot" } else { "read-only" });
+    if let Ok(client) = writer.try_clone() {
+        clients.lock().unwrap().push(client);
+    }
+
+    for line in lines {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if !copilot {
+            let _ = writeln!(writer, "error: read-only, commands are ignored");
+        } else if sender.send((Source::Remote(peer.clone()), line)).is_err() {
+            break;
+        }
+    }
+    println!("{} left the session", peer);
+}
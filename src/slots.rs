@@ -0,0 +1,112 @@
+/************
+Save state slots
+
+Save states (see savestate.rs) of the game in the window are kept in SLOTS slots next to the ROM
+(<rom path>.state1 to .state4, through the storage backend, see storage.rs), with a load menu
+showing the thumbnail of each.
+
+Hotkeys:
+    F1-F4     Save to slot 1-4
+    F9        Open/close the load menu. Left/Right pick a slot, Enter loads it
+    F10       Undo the last load, going back to the state from right before it (press again to redo)
+************/
+use piston_window::*;
+
+use savestate::{state_thumbnail, THUMB_HEIGHT, THUMB_WIDTH};
+use storage::Storage;
+use Chip8;
+
+pub const SLOTS: usize = 4;
+
+pub fn slot_path(rom: &str, slot: usize) -> String {
+    format!("{}.state{}", rom, slot + 1)
+}
+
+pub fn save_slot(storage: &mut dyn Storage, chip8: &Chip8, rom: &str, slot: usize) -> Result<(), String> {
+    storage.write(&slot_path(rom, slot), &chip8.save_state())
+        .map_err(|err| format!("Could not save state: {}", err))
+}
+
+fn read_slot(storage: &dyn Storage, rom: &str, slot: usize) -> Result<Vec<u8>, String> {
+    let path = slot_path(rom, slot);
+    match storage.read(&path) {
+        Ok(Some(data)) => Ok(data),
+        Ok(None) => Err(format!("No save state in slot {}", slot + 1)),
+        Err(err) => Err(format!("Could not read save state: {}", err)),
+    }
+}
+
+pub fn load_slot(storage: &dyn Storage, chip8: &mut Chip8, rom: &str, slot: usize) -> Result<(), String> {
+    chip8.load_state(&read_slot(storage, rom, slot)?)
+}
+
+//Reads just the thumbnail of a slot, None if the slot is empty or unreadable
+pub fn slot_thumbnail(storage: &dyn Storage, rom: &str, slot: usize) -> Option<Vec<u8>> {
+    state_thumbnail(&read_slot(storage, rom, slot).ok()?)
+}
+
+//The load-state menu: a row of slot thumbnails drawn over the game, which is paused while it's open
+pub struct LoadMenu {
+    pub selected: usize,
+    thumbnails: Vec<Option<Vec<u8>>>,
+}
+
+impl LoadMenu {
+    pub fn open(storage: &dyn Storage, rom: &str) -> LoadMenu {
+        LoadMenu {
+            selected: 0,
+            thumbnails: (0..SLOTS).map(|slot| slot_thumbnail(storage, rom, slot)).collect(),
+        }
+    }
+
+    pub fn left(&mut self) {
+        self.selected = (self.selected + SLOTS - 1) % SLOTS;
+    }
+
+    pub fn right(&mut self) {
+        self.selected = (self.selected + 1) % SLOTS;
+    }
+
+    pub fn draw(&self, window: &mut PistonWindow, event: &Event) {
+        window.draw_2d(event, |c, g| {
+            let view = c.get_view_size();
+            //Each slot gets an equal share of the window width, with a margin around the thumbnail
+            let slot_width = view[0] / SLOTS as f64;
+            let pixel = ((slot_width - 16.0) / THUMB_WIDTH as f64).floor().max(1.0);
+            let thumb_width = pixel * THUMB_WIDTH as f64;
+            let thumb_height = pixel * THUMB_HEIGHT as f64;
+            let top = ((view[1] - thumb_height) / 2.0).floor();
+
+            //Dim the game behind the menu
+            rectangle([0.0, 0.0, 0.0, 0.8], [0.0, 0.0, view[0], view[1]], c.transform, g);
+
+            for (slot, thumb) in self.thumbnails.iter().enumerate() {
+                let left = (slot as f64 * slot_width + (slot_width - thumb_width) / 2.0).floor();
+
+                //Frame, highlighted for the selected slot
+                let frame = if slot == self.selected { [1.0, 0.8, 0.0, 1.0] } else { [0.4, 0.4, 0.4, 1.0] };
+                rectangle(frame, [left - 2.0, top - 2.0, thumb_width + 4.0, thumb_height + 4.0], c.transform, g);
+                rectangle([0.0, 0.0, 0.0, 1.0], [left, top, thumb_width, thumb_height], c.transform, g);
+
+                match *thumb {
+                    Some(ref thumb) => {
+                        for y in 0..THUMB_HEIGHT {
+                            for x in 0..THUMB_WIDTH {
+                                if thumb[x + y * THUMB_WIDTH] != 0 {
+                                    let square = [left + x as f64 * pixel, top + y as f64 * pixel, pixel, pixel];
+                                    rectangle([1.0, 1.0, 1.0, 1.0], square, c.transform, g);
+                                }
+                            }
+                        }
+                    },
+                    //An empty slot is crossed out
+                    None => {
+                        let color = [0.4, 0.4, 0.4, 1.0];
+                        line(color, 1.0, [left, top, left + thumb_width, top + thumb_height], c.transform, g);
+                        line(color, 1.0, [left, top + thumb_height, left + thumb_width, top], c.transform, g);
+                    },
+                }
+            }
+        });
+    }
+}
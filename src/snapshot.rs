@@ -0,0 +1,145 @@
+/************
+Snapshot compression
+
+Save states are 6.8KB (see savestate.rs), nearly all of it memory and screen that hardly change
+from one frame to the next. encode() stores a state as the XOR against a base state, run-length
+encoded: unchanged bytes XOR to zeros and collapse into runs, so a frame's snapshot against the
+frame before usually takes tens of bytes. Against an empty base (ie &[]) it compresses a single
+state on its own, mostly zeroed memory, for writing it to disk.
+
+Encoded format (little endian):
+    length of the state (4 bytes)
+    chunks until the state is complete: zero run (2 bytes), literal count (2 bytes), literal bytes
+
+The Rewind buffer keeps the last N snapshots of a running game this way. Each entry is the XOR of
+a state with the one after it, and XOR works both ways, so stepping back from the current state
+only ever decodes one entry and there's no need for full keyframes. The oldest entries are dropped
+once the buffer is full.
+************/
+use std::collections::VecDeque;
+
+//Longest run or literal a chunk can hold
+const CHUNK_MAX: usize = 0xFFFF;
+
+fn base_byte(base: &[u8], index: usize) -> u8 {
+    base.get(index).cloned().unwrap_or(0)
+}
+
+//Encodes `state` as its difference to `base`, which may be of another length (missing bytes are zeros)
+pub fn encode(base: &[u8], state: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(64);
+    out.extend_from_slice(&(state.len() as u32).to_le_bytes());
+
+    let mut pos = 0;
+    while pos < state.len() {
+        let zeros_start = pos;
+        while pos < state.len() && pos - zeros_start < CHUNK_MAX && state[pos] == base_byte(base, pos) {
+            pos += 1;
+        }
+        let zeros = pos - zeros_start;
+
+        //Literals run until the next stretch of at least 4 unchanged bytes, shorter ones are cheaper kept in
+        let literal_start = pos;
+        while pos < state.len() && pos - literal_start < CHUNK_MAX {
+            let unchanged = (pos..state.len().min(pos + 4)).take_while(|&at| state[at] == base_byte(base, at)).count();
+            if unchanged == 4 || (unchanged > 0 && pos + unchanged == state.len()) {
+                break;
+            }
+            pos += 1;
+        }
+
+        out.extend_from_slice(&(zeros as u16).to_le_bytes());
+        out.extend_from_slice(&((pos - literal_start) as u16).to_le_bytes());
+        for (at, byte) in state.iter().enumerate().take(pos).skip(literal_start) {
+            out.push(byte ^ base_byte(base, at));
+        }
+    }
+    out
+}
+
+//The state encode() was given, from the same base
+pub fn decode(base: &[u8], data: &[u8]) -> Result<Vec<u8>, String> {
+    let invalid = || String::from("Invalid compressed snapshot");
+    if data.len() < 4 {
+        return Err(invalid());
+    }
+    let length = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    let mut state = Vec::with_capacity(length);
+    let mut pos = 4;
+
+    while state.len() < length {
+        let header = data.get(pos..pos + 4).ok_or_else(invalid)?;
+        let zeros = u16::from_le_bytes([header[0], header[1]]) as usize;
+        let literals = u16::from_le_bytes([header[2], header[3]]) as usize;
+        pos += 4;
+        if state.len() + zeros + literals > length {
+            return Err(invalid());
+        }
+        for _ in 0..zeros {
+            let index = state.len();
+            state.push(base_byte(base, index));
+        }
+        let bytes = data.get(pos..pos + literals).ok_or_else(invalid)?;
+        for byte in bytes {
+            let index = state.len();
+            state.push(byte ^ base_byte(base, index));
+        }
+        pos += literals;
+    }
+    if pos != data.len() {
+        return Err(invalid());
+    }
+    Ok(state)
+}
+
+//The states of the last `limit` snapshots, to step back through
+pub struct Rewind {
+    limit: usize,
+    deltas: VecDeque<Vec<u8>>,  //deltas[i] turns the state after it back into the one before
+    current: Vec<u8>,
+    bytes: usize,               //Held by the deltas
+}
+
+impl Rewind {
+    pub fn new(limit: usize) -> Rewind {
+        Rewind { limit, deltas: VecDeque::new(), current: Vec::new(), bytes: 0 }
+    }
+
+    //Adds the latest state
+    pub fn push(&mut self, state: Vec<u8>) {
+        if !self.current.is_empty() && self.limit > 0 {
+            let delta = encode(&state, &self.current);
+            self.bytes += delta.len();
+            self.deltas.push_back(delta);
+            while self.deltas.len() > self.limit {
+                let dropped = self.deltas.pop_front().unwrap();
+                self.bytes -= dropped.len();
+            }
+        }
+        self.current = state;
+    }
+
+    //Steps back one snapshot and returns that state, None once there's nothing older
+    pub fn back(&mut self) -> Option<Vec<u8>> {
+        let delta = self.deltas.pop_back()?;
+        self.bytes -= delta.len();
+        //The deltas are made by this buffer, so they always decode
+        let state = decode(&self.current, &delta).unwrap();
+        self.current = state.clone();
+        Some(state)
+    }
+
+    //How many times back() can step
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    //Memory taken by the compressed snapshots, not counting the current state
+    pub fn bytes(&self) -> usize {
+        self.bytes
+    }
+}
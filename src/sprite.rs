@@ -0,0 +1,71 @@
+/************
+Sprite draw checks
+
+DXYN draws N rows of sprite data, read from memory starting at I. A draw whose rows would run past
+the end of memory (I + N beyond 0xFFF), or that is taller than the sprite height limit
+(--sprite-limit, MAX_SPRITE_HEIGHT by default, the most DXYN can ask for), is a bug in the
+program. What happens then is picked with --draw-check:
+
+warn   - print a warning and draw only the rows that are in memory and within the limit (the default)
+strict - stop the program at the draw with a fault, so the debugger breaks on it and headless runs
+         end with the emulation error exit code
+
+Pixels of a sprite that fall off the right or bottom of the screen aren't drawn (unless the clip
+quirk is off and they wrap around, see quirks.rs). Whether they count towards VF differs between
+interpreters, picked with --clip-collision:
+
+none - clipped pixels never collide, VF is 1 only if a drawn pixel turned one off (the default)
+flag - VF is also set to 1 if any lit pixel of the sprite was clipped
+rows - VF is the number of sprite rows that either collided or were clipped, like SCHIP does in
+       high resolution mode
+************/
+
+pub const MAX_SPRITE_HEIGHT: usize = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DrawCheck {
+    Warn,
+    Strict,
+}
+
+impl DrawCheck {
+    pub const NAMES: &'static [&'static str] = &["warn", "strict"];
+
+    #[cfg(feature = "std")]
+    pub fn parse(text: &str) -> Result<DrawCheck, String> {
+        match text {
+            "warn" => Ok(DrawCheck::Warn),
+            "strict" => Ok(DrawCheck::Strict),
+            _ => Err(format!("Unknown draw check {} (expected warn or strict)", text)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ClipCollision {
+    None,
+    Flag,
+    Rows,
+}
+
+impl ClipCollision {
+    pub const NAMES: &'static [&'static str] = &["none", "flag", "rows"];
+
+    #[cfg(feature = "std")]
+    pub fn parse(text: &str) -> Result<ClipCollision, String> {
+        match text {
+            "none" => Ok(ClipCollision::None),
+            "flag" => Ok(ClipCollision::Flag),
+            "rows" => Ok(ClipCollision::Rows),
+            _ => Err(format!("Unknown clip collision mode {} (expected none, flag or rows)", text)),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+pub fn parse_limit(text: &str) -> Result<usize, String> {
+    match text.parse::<usize>() {
+        Ok(limit) if (1..=MAX_SPRITE_HEIGHT).contains(&limit) => Ok(limit),
+        _ => Err(format!("Invalid sprite limit {} (expected 1 to {})", text, MAX_SPRITE_HEIGHT)),
+    }
+}
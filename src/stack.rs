@@ -0,0 +1,34 @@
+/************
+Call stack limits
+
+The CHIP-8 stack holds STACK_SIZE return addresses. A program that nests more calls than that
+(usually runaway recursion) is broken, what happens then is picked with --stack:
+
+strict - stop the program at the offending call with a stack overflow fault (the default)
+grow   - keep going on a virtual stack that grows as deep as needed, printing a warning. For ROMs
+         written against interpreters with deeper stacks
+
+A return (00EE) with nothing on the stack always faults, there's nowhere to return to.
+************/
+
+pub const STACK_SIZE: usize = 16;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StackMode {
+    Strict,
+    Grow,
+}
+
+impl StackMode {
+    //What parse() takes
+    pub const NAMES: &'static [&'static str] = &["strict", "grow"];
+
+    #[cfg(feature = "std")]
+    pub fn parse(text: &str) -> Result<StackMode, String> {
+        match text {
+            "strict" => Ok(StackMode::Strict),
+            "grow" => Ok(StackMode::Grow),
+            _ => Err(format!("Unknown stack mode {} (expected strict or grow)", text)),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+/************
+Machine state
+
+Everything a running program can see or change, as a plain struct: memory, the V registers, I,
+PC, SP and the stack (the grow mode's entries past the end included), the timers, the screen (and
+SUPER-CHIP's resolution and exit), the keys and whether an FX0A is waiting for one. machine_state() takes it out of a Chip8 and
+set_machine_state() puts it back, so two machines can be compared field by field (it's
+PartialEq) and save states (see savestate.rs) are just this struct written out as bytes.
+
+Settings aren't part of it (quirks, protection, the address mode, tracing), they stay with the
+machine a state is loaded into. Neither is the random number generator's position: like save
+states, restoring one reseeds CXNN from the seed and cycle count.
+
+With the serde feature (it needs std) MachineState derives Serialize and Deserialize, and Chip8
+implements them through it, so any serde format can persist a machine or send it to a debugger:
+
+    let text = serde_json::to_string(&chip8)?;
+    let chip8: Chip8 = serde_json::from_str(&text)?;
+************/
+#[cfg(feature = "serde")]
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use display::{HIRES_HEIGHT, HIRES_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
+use memory::MEMORY_SIZE;
+use stack::STACK_SIZE;
+use variant::Variant;
+use Chip8;
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MachineState {
+    pub variant: Variant,
+    pub memory: Vec<u8>,        //MEMORY_SIZE bytes
+    pub v: [u8; 16],
+    pub i: u16,
+    pub pc: u16,
+    pub sp: u16,
+    pub stack: [u16; STACK_SIZE],
+    pub deep_stack: Vec<u16>,   //Entries past the end of the stack, in grow mode
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub screen: Vec<u8>,        //SCREEN_WIDTH * SCREEN_HEIGHT pixels (HIRES_WIDTH * HIRES_HEIGHT when hires), 0 or 1
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hires: bool,            //SUPER-CHIP's 128 x 64 mode
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub exited: bool,           //SUPER-CHIP's 00FD ran
+    pub keys: [u8; 16],
+    pub halted: bool,           //Waiting in an FX0A
+    pub halt_reg: u8,           //Where that FX0A stores the key
+    pub cycles: u64,
+    pub seed: u64,
+}
+
+impl MachineState {
+    //Width and height of the screen
+    pub fn screen_size(&self) -> (usize, usize) {
+        if self.hires { (HIRES_WIDTH, HIRES_HEIGHT) } else { (SCREEN_WIDTH, SCREEN_HEIGHT) }
+    }
+
+    //Errors if the state couldn't have come from a machine, ie a stack pointer past the stack
+    pub fn check(&self) -> Result<(), String> {
+        let (width, height) = self.screen_size();
+        if self.memory.len() != MEMORY_SIZE || self.screen.len() != width * height {
+            return Err(String::from("Machine state has the wrong memory or screen size"));
+        }
+        let stack_ok = if self.deep_stack.is_empty() {
+            self.sp as usize <= STACK_SIZE
+        } else {
+            self.sp as usize == STACK_SIZE + self.deep_stack.len()
+        };
+        if !stack_ok || self.halt_reg > 0xF || (self.hires && self.variant != Variant::Schip) {
+            return Err(String::from("Machine state is corrupt"));
+        }
+        Ok(())
+    }
+}
+
+impl Chip8 {
+    pub fn machine_state(&self) -> MachineState {
+        MachineState {
+            variant: self.variant,
+            memory: self.memory.as_slice().to_vec(),
+            v: self.v,
+            i: self.i,
+            pc: self.pc,
+            sp: self.sp,
+            stack: self.stack,
+            deep_stack: self.deep_stack.clone(),
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            screen: self.screen().to_vec(),
+            hires: self.hires,
+            exited: self.exited,
+            keys: self.key,
+            halted: self.halt_flag,
+            halt_reg: self.halt_reg,
+            cycles: self.cycles,
+            seed: self.seed,
+        }
+    }
+
+    //Restores a state taken with machine_state(), possibly on another machine. Nothing changes if it's refused
+    pub fn set_machine_state(&mut self, state: MachineState) -> Result<(), String> {
+        self.variant.check(state.variant, "Machine state")?;
+        state.check()?;
+
+        //Copied in rather than replaced, the address mode is a setting and not part of the state
+        self.memory.as_mut_slice().copy_from_slice(&state.memory);
+        self.v = state.v;
+        self.i = state.i;
+        self.pc = state.pc;
+        self.sp = state.sp;
+        self.stack = state.stack;
+        self.deep_stack = state.deep_stack;
+        self.delay_timer = state.delay_timer;
+        self.sound_timer = state.sound_timer;
+        self.halt_flag = state.halted;
+        self.halt_reg = state.halt_reg;
+        self.key = state.keys;
+        self.hires = state.hires;
+        self.exited = state.exited;
+        self.clear_screenbuf();
+        self.screen[..state.screen.len()].copy_from_slice(&state.screen);
+        self.cycles = state.cycles;
+        //The generator's exact position can't be saved, so restart it from the seed and cycle count
+        self.set_seed(state.seed ^ state.cycles);
+        self.seed = state.seed;
+        //Everything in memory now came from the state
+        self.initialized = [true; MEMORY_SIZE];
+        self.fault = None;
+        //Not saved, the screen didn't come from a draw
+        self.last_draw = None;
+        self.last_collision = false;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl Serialize for Chip8 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.machine_state().serialize(serializer)
+    }
+}
+
+//A fresh machine (default settings) holding the state
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Chip8 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Chip8, D::Error> {
+        let state = MachineState::deserialize(deserializer)?;
+        let mut chip8 = Chip8::new();
+        chip8.set_machine_state(state).map_err(de::Error::custom)?;
+        Ok(chip8)
+    }
+}
@@ -0,0 +1,136 @@
+/************
+Save state diff
+
+Usage: chip8 state-diff <a.state> <b.state>
+
+Answers "what changed between these two points?": prints every register that differs (as
+a -> b), the memory that differs as ranges of consecutive bytes, and the pixels that differ
+between the two screens (the XOR of them, # for a changed pixel, rows without any left out).
+Works on any save state (see savestate.rs), ie two slots of the same game, or a state from
+before and after a bug.
+************/
+use std::fs;
+use std::process;
+
+use exitcode;
+use savestate::state_variant;
+use Chip8;
+
+//Longest range whose bytes are printed, longer ones only get their size
+const MAX_SHOWN_BYTES: usize = 16;
+
+fn load(path: &str) -> Result<Chip8, String> {
+    let data = fs::read(path).map_err(|err| format!("Could not read {}: {}", path, err))?;
+    let mut chip8 = Chip8::new();
+    //Whatever machine made it, so SUPER-CHIP states can be compared too
+    if let Some(variant) = state_variant(&data) {
+        chip8.set_variant(variant);
+    }
+    chip8.load_state(&data).map_err(|err| format!("{}: {}", path, err))?;
+    Ok(chip8)
+}
+
+//Prints "name: a -> b" if the values differ, returns whether they did
+fn field<T: PartialEq>(name: &str, a: T, b: T, show: fn(T) -> String) -> bool {
+    if a == b {
+        return false;
+    }
+    println!("{:8} {} -> {}", format!("{}:", name), show(a), show(b));
+    true
+}
+
+fn hex_byte(value: u8) -> String {
+    format!("{:#04X}", value)
+}
+
+fn hex_addr(value: u16) -> String {
+    format!("{:#06X}", value)
+}
+
+fn stack(calls: Vec<u16>) -> String {
+    let calls: Vec<String> = calls.iter().map(|call| format!("{:#06X}", call)).collect();
+    format!("[{}]", calls.join(" "))
+}
+
+//Ranges of consecutive addresses where the memories differ
+fn memory_ranges(a: &Chip8, b: &Chip8) -> Vec<(usize, usize)> {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for addr in (0..4096).filter(|addr| a.read_byte(*addr as u16) != b.read_byte(*addr as u16)) {
+        match ranges.last_mut() {
+            Some(range) if range.1 + 1 == addr => range.1 = addr,
+            _ => ranges.push((addr, addr)),
+        }
+    }
+    ranges
+}
+
+fn bytes(chip8: &Chip8, start: usize, end: usize) -> String {
+    (start..=end).map(|addr| format!("{:02X}", chip8.read_byte(addr as u16))).collect::<Vec<String>>().join(" ")
+}
+
+//Returns whether anything differed
+fn diff(a: &Chip8, b: &Chip8) -> bool {
+    let mut differs = false;
+    differs |= field("cycles", a.cycles(), b.cycles(), |value| value.to_string());
+    differs |= field("pc", a.pc(), b.pc(), hex_addr);
+    differs |= field("i", a.index(), b.index(), hex_addr);
+    for reg in 0..16 {
+        differs |= field(&format!("V{:X}", reg), a.register(reg), b.register(reg), hex_byte);
+    }
+    differs |= field("sp", a.sp(), b.sp(), |value| value.to_string());
+    differs |= field("stack", a.call_stack(), b.call_stack(), stack);
+    differs |= field("dt", a.delay_timer(), b.delay_timer(), |value| value.to_string());
+    differs |= field("st", a.sound_timer(), b.sound_timer(), |value| value.to_string());
+    differs |= field("halted", a.is_halted(), b.is_halted(), |value| value.to_string());
+    differs |= field("seed", a.seed(), b.seed(), |value| format!("{:#X}", value));
+
+    let ranges = memory_ranges(a, b);
+    if !ranges.is_empty() {
+        differs = true;
+        let total: usize = ranges.iter().map(|(start, end)| end - start + 1).sum();
+        println!("memory:  {} bytes differ in {} ranges", total, ranges.len());
+        for (start, end) in ranges {
+            if end - start < MAX_SHOWN_BYTES {
+                println!("    {:#06X}-{:#06X}: {} -> {}", start, end, bytes(a, start, end), bytes(b, start, end));
+            } else {
+                println!("    {:#06X}-{:#06X}: {} bytes", start, end, end - start + 1);
+            }
+        }
+    }
+
+    let changed = a.screen().iter().zip(b.screen()).filter(|(a, b)| a != b).count();
+    if changed > 0 {
+        differs = true;
+        println!("screen:  {} pixels differ", changed);
+        //Only the rows from the first to the last one with a changed pixel
+        let (width, height) = (a.screen_width().max(b.screen_width()), a.screen_height().max(b.screen_height()));
+        let rows: Vec<String> = (0..height).map(|y| {
+            (0..width).map(|x| if a.pixel(x, y) != b.pixel(x, y) { '#' } else { '.' }).collect()
+        }).collect();
+        let first = rows.iter().position(|row| row.contains('#')).unwrap_or(0);
+        let last = rows.iter().rposition(|row| row.contains('#')).unwrap_or(0);
+        for (y, row) in rows.iter().enumerate().take(last + 1).skip(first) {
+            println!("    {:2} {}", y, row);
+        }
+    }
+    differs
+}
+
+//Entry point for "chip8 state-diff ...", with the arguments following "state-diff"
+pub fn run(args: &[String]) {
+    if args.len() != 2 {
+        println!("Usage: chip8 state-diff <a.state> <b.state>");
+        process::exit(exitcode::FAILURE);
+    }
+    match load(&args[0]).and_then(|a| load(&args[1]).map(|b| (a, b))) {
+        Ok((a, b)) => {
+            if !diff(&a, &b) {
+                println!("The states are identical");
+            }
+        },
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::FAILURE);
+        }
+    }
+}
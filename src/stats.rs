@@ -0,0 +1,290 @@
+/************
+Play statistics
+
+Usage: chip8 stats [--sort playtime|launches|last|best|name] [--rom-db FILE] [--format text|json]
+
+Every ROM played in the window is counted: how often it was started, how long it ran in total and
+when it was last played, plus the best score for ROMs the ROM database knows the score of (see
+score in metadata.rs). chip8 stats lists them, the ones played longest first, with the titles
+from the ROM database where it has them. --format json prints them as an array instead, one object
+per ROM in the same order, with the playtime and last time played in seconds and best left out
+when there is none.
+
+The numbers are kept in stats.db in the data directory: $CHIP8_DATA_DIR if it's set, otherwise
+chip8 in $XDG_DATA_HOME or ~/.local/share. ROMs are told apart by hash like in the ROM database,
+so a ROM keeps its numbers when it's moved or renamed:
+
+    [0x1A2B3C4D5E6F7081]        - FNV-1a hash of the ROM file
+    rom games/pong.ch8          - where it was last played from
+    launches 12
+    playtime 3605               - seconds
+    last 1760000000             - when it was last played, in seconds since 1970
+    best 42
+
+A run is added when it ends, so a ROM that's still running (or the emulator crashing) doesn't
+show up until next time. The window reads and writes the file through the storage backend (see
+storage.rs), so with --storage memory runs are only counted for the session and chip8 stats
+doesn't see them.
+************/
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use exitcode;
+use json::Json;
+use metadata::{self, RomDb, Score};
+use options::{take_value, OutputFormat};
+use storage::{FileStorage, Storage};
+use Chip8;
+
+const STATS_FILE: &str = "stats.db";
+
+#[derive(Clone, Debug, Default)]
+pub struct RomStats {
+    pub hash: u64,
+    pub rom: String,
+    pub launches: u64,
+    pub playtime: u64,
+    pub last: u64,
+    pub best: Option<u32>,
+}
+
+#[derive(Default)]
+pub struct Stats {
+    roms: Vec<RomStats>,
+}
+
+//Where the emulator keeps what it remembers between runs
+pub fn data_dir() -> PathBuf {
+    if let Some(dir) = env::var_os("CHIP8_DATA_DIR") {
+        return PathBuf::from(dir);
+    }
+    let base = match (env::var_os("XDG_DATA_HOME"), env::var_os("HOME")) {
+        (Some(data), _) => PathBuf::from(data),
+        (None, Some(home)) => PathBuf::from(home).join(".local").join("share"),
+        (None, None) => PathBuf::from("."),
+    };
+    base.join("chip8")
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or(0)
+}
+
+impl Stats {
+    pub fn path() -> PathBuf {
+        data_dir().join(STATS_FILE)
+    }
+
+    //Under which key the statistics are stored (see storage.rs)
+    fn key() -> String {
+        Stats::path().to_string_lossy().into_owned()
+    }
+
+    //Reads the statistics, none yet if there aren't any
+    pub fn load(storage: &dyn Storage) -> Result<Stats, String> {
+        let key = Stats::key();
+        let text = match storage.read(&key)? {
+            Some(data) => String::from_utf8(data).map_err(|_| format!("{} is not text", key))?,
+            None => return Ok(Stats::default()),
+        };
+        let mut roms: Vec<RomStats> = Vec::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let invalid = |err: String| format!("{}:{}: {}", key, number + 1, err);
+
+            if line.starts_with('[') && line.ends_with(']') {
+                let hash = &line[1..line.len() - 1];
+                let hash = hash.strip_prefix("0x").and_then(|hash| u64::from_str_radix(hash, 16).ok())
+                    .ok_or_else(|| invalid(format!("Invalid ROM hash {}", hash)))?;
+                roms.push(RomStats { hash, ..RomStats::default() });
+                continue;
+            }
+
+            let rom = roms.last_mut().ok_or_else(|| invalid(String::from("Entry before the first [ROM hash]")))?;
+            let (name, value) = match line.find(char::is_whitespace) {
+                Some(split) => (&line[..split], line[split..].trim()),
+                None => (line, ""),
+            };
+            let number = || value.parse::<u64>().map_err(|_| invalid(format!("Invalid {} {}", name, value)));
+            match name {
+                "rom" => rom.rom = value.to_string(),
+                "launches" => rom.launches = number()?,
+                "playtime" => rom.playtime = number()?,
+                "last" => rom.last = number()?,
+                "best" => rom.best = Some(number()? as u32),
+                _ => return Err(invalid(format!("Unknown entry {}", name))),
+            }
+        }
+        Ok(Stats { roms })
+    }
+
+    pub fn save(&self, storage: &mut dyn Storage) -> Result<(), String> {
+        let mut text = String::new();
+        for rom in &self.roms {
+            text.push_str(&format!("[{:#018X}]\nrom {}\nlaunches {}\nplaytime {}\nlast {}\n", rom.hash, rom.rom, rom.launches, rom.playtime, rom.last));
+            if let Some(best) = rom.best {
+                text.push_str(&format!("best {}\n", best));
+            }
+            text.push('\n');
+        }
+        storage.write(&Stats::key(), text.as_bytes())
+    }
+
+    pub fn get(&self, hash: u64) -> Option<&RomStats> {
+        self.roms.iter().find(|rom| rom.hash == hash)
+    }
+
+    fn entry(&mut self, hash: u64) -> &mut RomStats {
+        match self.roms.iter().position(|rom| rom.hash == hash) {
+            Some(index) => &mut self.roms[index],
+            None => {
+                self.roms.push(RomStats { hash, ..RomStats::default() });
+                self.roms.last_mut().unwrap()
+            },
+        }
+    }
+}
+
+//Follows a ROM while it runs, and adds the run to the statistics once it's over (finish(), called
+//when the window moves on to another game or closes)
+pub struct PlayTracker {
+    rom: String,
+    hash: Option<u64>,
+    started: Instant,
+    score: Option<Score>,
+    best: Option<u32>,
+}
+
+impl PlayTracker {
+    pub fn new(rom: &str, hash: Option<u64>, score: Option<Score>) -> PlayTracker {
+        PlayTracker { rom: rom.to_string(), hash, started: Instant::now(), score, best: None }
+    }
+
+    //Looks at the score, once per frame
+    pub fn update(&mut self, chip8: &Chip8) {
+        if let Some(score) = self.score {
+            let value = score.read(chip8);
+            self.best = Some(self.best.map_or(value, |best| best.max(value)));
+        }
+    }
+
+    fn record(&self, hash: u64, storage: &mut dyn Storage) -> Result<(), String> {
+        let mut stats = Stats::load(storage)?;
+        let entry = stats.entry(hash);
+        entry.rom = self.rom.clone();
+        entry.launches += 1;
+        entry.playtime += self.started.elapsed().as_secs();
+        entry.last = now();
+        if let Some(best) = self.best {
+            entry.best = Some(entry.best.map_or(best, |old| old.max(best)));
+        }
+        stats.save(storage)
+    }
+
+    //Adds the run to the statistics, only the first time it's called
+    pub fn finish(&mut self, storage: &mut dyn Storage) {
+        //A ROM that couldn't be hashed couldn't have run either
+        if let Some(hash) = self.hash.take() {
+            if let Err(err) = self.record(hash, storage) {
+                println!("Could not update the play statistics: {}", err);
+            }
+        }
+    }
+}
+
+pub fn format_playtime(seconds: u64) -> String {
+    format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+fn format_last(last: u64) -> String {
+    match now().saturating_sub(last) / 86400 {
+        0 => String::from("today"),
+        1 => String::from("yesterday"),
+        days => format!("{} days ago", days),
+    }
+}
+
+fn list(args: &[String]) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let sort = take_value(&mut args, "--sort")?.unwrap_or_else(|| String::from("playtime"));
+    let rom_db = match take_value(&mut args, "--rom-db")? {
+        Some(path) => RomDb::load(&path)?,
+        None => RomDb::load_default()?,
+    };
+    let format = match take_value(&mut args, "--format")? {
+        Some(format) => OutputFormat::parse(&format)?,
+        None => OutputFormat::Text,
+    };
+    if !args.is_empty() {
+        return Err(String::from("Usage: chip8 stats [--sort playtime|launches|last|best|name] [--rom-db FILE] [--format text|json]"));
+    }
+
+    //The ones on disk, --storage only lasts for the window's session
+    let stats = Stats::load(&FileStorage)?;
+    let name = |rom: &RomStats| match rom_db.lookup(rom.hash).and_then(|info| info.title.clone()) {
+        Some(title) => title,
+        None => rom.rom.clone(),
+    };
+    let mut roms: Vec<(String, &RomStats)> = stats.roms.iter().map(|rom| (name(rom), rom)).collect();
+    match sort.as_str() {
+        "playtime" => roms.sort_by_key(|(_, rom)| std::cmp::Reverse(rom.playtime)),
+        "launches" => roms.sort_by_key(|(_, rom)| std::cmp::Reverse(rom.launches)),
+        "last" => roms.sort_by_key(|(_, rom)| std::cmp::Reverse(rom.last)),
+        "best" => roms.sort_by_key(|(_, rom)| std::cmp::Reverse(rom.best)),
+        "name" => roms.sort_by_key(|(name, _)| name.to_lowercase()),
+        other => return Err(format!("Unknown sort order {} (expected playtime, launches, last, best or name)", other)),
+    }
+
+    if format == OutputFormat::Json {
+        let records: Vec<Json> = roms.iter().map(|(name, rom)| rom_json(name, rom)).collect();
+        println!("{}", Json::from(records));
+        return Ok(());
+    }
+    if roms.is_empty() {
+        println!("No ROMs played yet ({})", Stats::path().display());
+        return Ok(());
+    }
+    println!("{:<32} {:>8} {:>10} {:>6}  Last played", "ROM", "Launches", "Playtime", "Best");
+    for (name, rom) in &roms {
+        let best = rom.best.map(|best| best.to_string()).unwrap_or_else(|| String::from("-"));
+        println!("{:<32} {:>8} {:>10} {:>6}  {}", name, rom.launches, format_playtime(rom.playtime), best, format_last(rom.last));
+    }
+    let total: u64 = roms.iter().map(|(_, rom)| rom.playtime).sum();
+    println!("{} ROMs, {} played in total", roms.len(), format_playtime(total));
+    Ok(())
+}
+
+fn rom_json(name: &str, rom: &RomStats) -> Json {
+    let record = Json::object()
+        .field("name", name)
+        .field("rom", rom.rom.as_str())
+        .field("hash", format!("{:#018X}", rom.hash))
+        .field("launches", rom.launches)
+        .field("playtime", rom.playtime)
+        .field("last", rom.last);
+    match rom.best {
+        Some(best) => record.field("best", best as u64),
+        None => record,
+    }
+}
+
+//Entry point for "chip8 stats ...", with the arguments following "stats"
+pub fn run(args: &[String]) {
+    if let Err(err) = list(args) {
+        println!("{}", err);
+        process::exit(exitcode::FAILURE);
+    }
+}
+
+//The tracker for a ROM the window is starting
+pub fn track(rom: &str, rom_db: &RomDb) -> PlayTracker {
+    let hash = metadata::rom_file_hash(rom);
+    let score = hash.and_then(|hash| rom_db.lookup(hash)).and_then(|info| info.score);
+    PlayTracker::new(rom, hash, score)
+}
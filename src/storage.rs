@@ -0,0 +1,79 @@
+/************
+Persistence backends
+
+Everything the emulator keeps between runs (save states, SUPER-CHIP flags, see rpl.rs, the play
+statistics, see stats.rs, and the library's thumbnail cache) goes through the Storage trait
+instead of the file system directly, so the same code works wherever the data actually ends up.
+Data is stored under a key, which for files is simply the path.
+
+Backends:
+    file     files on disk, next to the ROM or in the data directory (the default). Missing
+             directories are made on the first write
+    memory   kept in memory for the session only, nothing is written to disk. Handy for kiosk
+             installations where visitors shouldn't leave save files behind, and for tests
+
+Other targets (ie a browser's localStorage for a WASM build) only need another implementation.
+************/
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+//Send, the game and its storage live on the emulation thread (see emulation.rs)
+pub trait Storage: Send {
+    fn name(&self) -> &str;
+    //The data stored under key, None if nothing is
+    fn read(&self, key: &str) -> Result<Option<Vec<u8>>, String>;
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), String>;
+}
+
+pub struct FileStorage;
+
+impl Storage for FileStorage {
+    fn name(&self) -> &str {
+        "file"
+    }
+
+    fn read(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        match fs::read(key) {
+            Ok(data) => Ok(Some(data)),
+            Err(ref err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(format!("Could not read {}: {}", key, err)),
+        }
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), String> {
+        if let Some(dir) = Path::new(key).parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(|err| format!("Could not create {}: {}", dir.display(), err))?;
+        }
+        fs::write(key, data).map_err(|err| format!("Could not write {}: {}", key, err))
+    }
+}
+
+#[derive(Default)]
+pub struct MemoryStorage {
+    entries: HashMap<String, Vec<u8>>,
+}
+
+impl Storage for MemoryStorage {
+    fn name(&self) -> &str {
+        "memory"
+    }
+
+    fn read(&self, key: &str) -> Result<Option<Vec<u8>>, String> {
+        Ok(self.entries.get(key).cloned())
+    }
+
+    fn write(&mut self, key: &str, data: &[u8]) -> Result<(), String> {
+        self.entries.insert(key.to_string(), data.to_vec());
+        Ok(())
+    }
+}
+
+pub fn by_name(name: &str) -> Result<Box<dyn Storage>, String> {
+    match name {
+        "file" => Ok(Box::new(FileStorage)),
+        "memory" => Ok(Box::new(MemoryStorage::default())),
+        _ => Err(format!("Unknown storage {} (expected file or memory)", name)),
+    }
+}
@@ -0,0 +1,137 @@
+/************
+Terminal frontend
+
+Usage: chip8 terminal <romfile> [--frames N] [--fast] [options]
+
+Shows the ROM in the terminal instead of a window, two rows of pixels per line of text drawn with
+half block characters, redrawn in place whenever the screen changes. It's a Frontend (see
+frontend.rs) like any other: a TerminalDisplay, the --audio-device backend for the buzzer, and no
+input, since reading single key presses and releases needs the terminal in raw mode. So it's for
+watching demos and ROMs that play by themselves, over ssh or on machines without a display.
+
+Runs at 60 frames per second until the program stops, for N frames with --frames, or as fast as
+it can with --fast. When stdout isn't a terminal, every changed screen is printed one after the
+other instead of drawing over the last one.
+************/
+use std::io::{self, IsTerminal, Write};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use audio;
+use exitcode;
+use framebuffer::Framebuffer;
+use frontend::{DisplaySink, Frontend, NoInput};
+use options::{take_value, Options};
+use {create_chip8, TIMER_HZ};
+
+pub struct TerminalDisplay {
+    in_place: bool,     //Draw over the last screen with cursor movements
+    last: Option<u64>,  //Hash of the screen on display
+}
+
+impl TerminalDisplay {
+    pub fn new() -> TerminalDisplay {
+        TerminalDisplay { in_place: io::stdout().is_terminal(), last: None }
+    }
+}
+
+//One line of text for rows y and y + 1
+fn text_row(screen: &Framebuffer, y: usize) -> String {
+    (0..screen.width()).map(|x| {
+        let top = screen.index(x, y) != 0;
+        let bottom = y + 1 < screen.height() && screen.index(x, y + 1) != 0;
+        match (top, bottom) {
+            (false, false) => ' ',
+            (true, false) => '\u{2580}',
+            (false, true) => '\u{2584}',
+            (true, true) => '\u{2588}',
+        }
+    }).collect()
+}
+
+impl DisplaySink for TerminalDisplay {
+    fn present(&mut self, screen: &Framebuffer) {
+        let hash = screen.hash();
+        if self.last == Some(hash) {
+            return;
+        }
+        let mut text = String::new();
+        if self.in_place {
+            //Clear once, after that just go back to the top left corner
+            text.push_str(if self.last.is_none() { "\x1b[2J\x1b[H" } else { "\x1b[H" });
+        } else if self.last.is_some() {
+            text.push('\n');
+        }
+        self.last = Some(hash);
+        for y in (0..screen.height()).step_by(2) {
+            text.push_str(&text_row(screen, y));
+            text.push('\n');
+        }
+        let mut stdout = io::stdout();
+        stdout.write_all(text.as_bytes()).and_then(|_| stdout.flush()).unwrap_or(());
+    }
+}
+
+//Entry point for "chip8 terminal ...", with the arguments following "terminal"
+pub fn run(args: &[String]) {
+    let mut args = args.to_vec();
+    let parsed = take_value(&mut args, "--frames").and_then(|frames| {
+        let frames = match frames {
+            Some(value) => match value.parse::<u64>() {
+                Ok(frames) if frames > 0 => Some(frames),
+                _ => return Err(format!("Invalid frame count {}", value)),
+            },
+            None => None,
+        };
+        let fast = match args.iter().position(|arg| arg == "--fast") {
+            Some(index) => {
+                args.remove(index);
+                true
+            },
+            None => false,
+        };
+        let options = Options::parse(&args)?;
+        Ok((frames, fast, options))
+    });
+    let (frames, fast, options) = match parsed {
+        Ok(result) => result,
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::FAILURE);
+        }
+    };
+
+    let mut chip8 = match create_chip8(&options, &options.rom) {
+        Ok(chip8) => chip8,
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::ROM_LOAD);
+        }
+    };
+    chip8.trace = false;
+
+    let audio = audio::open_device(&options.audio_device);
+    let mut frontend = Frontend::new(Box::new(TerminalDisplay::new()), Box::new(NoInput), audio);
+    let frame_time = Duration::from_secs_f64(1.0 / TIMER_HZ);
+    let started = Instant::now();
+    let mut frame: u64 = 0;
+
+    while frames.is_none_or(|frames| frame < frames) {
+        let end = frontend.run_frame(&mut chip8);
+        frame += 1;
+        if let Some(event) = end {
+            frontend.stop();
+            println!("Stopped after {} frames: {}", frame, event);
+            process::exit(exitcode::EMULATION_ERROR);
+        }
+        if !fast {
+            //Sleep off whatever is left of the frame, measured from the start so errors don't add up
+            let due = frame_time * frame as u32;
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+    }
+    frontend.stop();
+}
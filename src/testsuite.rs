@@ -0,0 +1,253 @@
+/************
+Multi-ROM regression runner
+
+Usage: chip8 test-suite <directory> [--jobs N] [--cycles N] [--seed N] [--update] [--format text|json]
+
+Runs every ROM in the directory (.ch8 and .c8 files) headless, N at a time on worker threads,
+and checks how each run ended against <rom>.expected, next to the ROM. The expected file holds
+the screen hash the run should end with. A ROM passes if its run ends on that hash without
+faulting or leaving memory, and is reported as new if it has no expected file yet.
+--update writes the current results as the new expectations.
+
+Every ROM starts from the same seed (0 unless --seed is given), so results are reproducible.
+Exits with 1 if any ROM failed.
+************/
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use exitcode;
+use headless::run_cycles;
+use json::Json;
+use options::{take_value, OutputFormat};
+use {Chip8, CYCLES_PER_SECOND};
+
+const DEFAULT_CYCLES: u64 = (CYCLES_PER_SECOND * 10.0) as u64;
+const DEFAULT_JOBS: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Status {
+    Pass,
+    Fail,
+    New,
+}
+
+impl Status {
+    fn name(self) -> &'static str {
+        match self {
+            Status::Pass => "pass",
+            Status::Fail => "fail",
+            Status::New => "new",
+        }
+    }
+}
+
+struct TestResult {
+    rom: PathBuf,
+    status: Status,
+    stop: &'static str,
+    cycles: u64,
+    screen_hash: u64,
+    expected: Option<u64>,
+    error: Option<String>,
+}
+
+fn expected_path(rom: &Path) -> PathBuf {
+    let mut path = rom.as_os_str().to_owned();
+    path.push(".expected");
+    PathBuf::from(path)
+}
+
+fn read_expected(rom: &Path) -> Option<u64> {
+    let text = fs::read_to_string(expected_path(rom)).ok()?;
+    let text = text.trim();
+    u64::from_str_radix(text.strip_prefix("0x").unwrap_or(text), 16).ok()
+}
+
+//Runs one ROM on the current thread
+fn run_rom(rom: &Path, cycles: u64, seed: u64) -> TestResult {
+    let expected = read_expected(rom);
+    let mut result = TestResult {
+        rom: rom.to_path_buf(),
+        status: Status::Fail,
+        stop: "not_run",
+        cycles: 0,
+        screen_hash: 0,
+        expected,
+        error: None,
+    };
+
+    let bytes = match fs::read(rom) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            result.error = Some(format!("Could not read ROM: {}", err));
+            return result;
+        }
+    };
+    if bytes.len() > 4096 - 512 {
+        result.error = Some(String::from("ROM doesn't fit in memory"));
+        return result;
+    }
+
+    let mut chip8 = Chip8::new();
+    chip8.initialize();
+    chip8.load_rom_bytes(&bytes);
+    chip8.set_seed(seed);
+    chip8.trace = false;
+
+    result.stop = run_cycles(&mut chip8, cycles);
+    result.cycles = chip8.cycles();
+    result.screen_hash = chip8.screen_hash();
+    result.error = chip8.fault().map(|fault| fault.to_string());
+
+    let crashed = result.stop == "invalid_pc" || result.stop == "memory_fault";
+    result.status = match expected {
+        _ if crashed => Status::Fail,
+        Some(hash) if hash == result.screen_hash => Status::Pass,
+        Some(_) => Status::Fail,
+        None => Status::New,
+    };
+    result
+}
+
+//The ROMs of a directory, sorted so the report is always in the same order
+pub fn find_roms(dir: &str) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir).map_err(|err| format!("Could not read directory {}: {}", dir, err))?;
+    let mut roms: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) => ext.eq_ignore_ascii_case("ch8") || ext.eq_ignore_ascii_case("c8"),
+            None => false,
+        })
+        .collect();
+    roms.sort();
+    Ok(roms)
+}
+
+//Runs all the ROMs on `jobs` worker threads, each taking the next ROM off a shared queue
+fn run_all(roms: Vec<PathBuf>, jobs: usize, cycles: u64, seed: u64) -> Vec<TestResult> {
+    let count = roms.len();
+    let queue = Arc::new(Mutex::new(roms));
+    let (sender, receiver) = mpsc::channel();
+
+    let workers: Vec<_> = (0..jobs.min(count)).map(|_| {
+        let queue = Arc::clone(&queue);
+        let sender = sender.clone();
+        thread::spawn(move || loop {
+            let rom = match queue.lock().unwrap().pop() {
+                Some(rom) => rom,
+                None => break,
+            };
+            if sender.send(run_rom(&rom, cycles, seed)).is_err() {
+                break;
+            }
+        })
+    }).collect();
+    drop(sender);
+
+    let mut results: Vec<TestResult> = receiver.iter().collect();
+    for worker in workers {
+        worker.join().unwrap();
+    }
+    results.sort_by(|a, b| a.rom.cmp(&b.rom));
+    results
+}
+
+fn result_json(result: &TestResult) -> Json {
+    Json::object()
+        .field("rom", result.rom.display().to_string())
+        .field("status", result.status.name())
+        .field("stop", result.stop)
+        .field("cycles", result.cycles)
+        .field("screen_hash", format!("{:#018X}", result.screen_hash))
+        .field("expected", result.expected.map(|hash| format!("{:#018X}", hash)).unwrap_or_default())
+        .field("error", result.error.clone().unwrap_or_default())
+}
+
+fn parse_count(value: Option<String>, default: u64, what: &str) -> Result<u64, String> {
+    match value {
+        Some(value) => value.parse::<u64>().ok().filter(|count| *count > 0)
+            .ok_or_else(|| format!("Invalid {} {}", what, value)),
+        None => Ok(default),
+    }
+}
+
+//Entry point for "chip8 test-suite ...", with the arguments following "test-suite"
+pub fn run(args: &[String]) {
+    let mut args = args.to_vec();
+    let parsed = (|| {
+        let jobs = parse_count(take_value(&mut args, "--jobs")?, DEFAULT_JOBS as u64, "job count")? as usize;
+        let cycles = parse_count(take_value(&mut args, "--cycles")?, DEFAULT_CYCLES, "cycle count")?;
+        let seed = match take_value(&mut args, "--seed")? {
+            Some(seed) => seed.parse::<u64>().map_err(|_| format!("Invalid seed {}", seed))?,
+            None => 0,
+        };
+        let format = match take_value(&mut args, "--format")? {
+            Some(format) => OutputFormat::parse(&format)?,
+            None => OutputFormat::Text,
+        };
+        let update = match args.iter().position(|arg| arg == "--update") {
+            Some(index) => {
+                args.remove(index);
+                true
+            },
+            None => false,
+        };
+        if args.len() != 1 {
+            return Err(String::from("Usage: chip8 test-suite <directory> [--jobs N] [--cycles N] [--seed N] [--update] [--format text|json]"));
+        }
+        let roms = find_roms(&args[0])?;
+        Ok((roms, jobs, cycles, seed, format, update))
+    })();
+    let (roms, jobs, cycles, seed, format, update) = match parsed {
+        Ok(parsed) => parsed,
+        Err(err) => {
+            println!("{}. Aborting", err);
+            process::exit(exitcode::FAILURE);
+        }
+    };
+
+    let results = run_all(roms, jobs, cycles, seed);
+
+    if update {
+        for result in results.iter().filter(|result| result.error.is_none()) {
+            let path = expected_path(&result.rom);
+            if let Err(err) = fs::write(&path, format!("{:#018X}\n", result.screen_hash)) {
+                println!("Could not write {}: {}", path.display(), err);
+            }
+        }
+    }
+
+    let count = |status| results.iter().filter(|result| result.status == status).count();
+    let (passed, failed, new) = (count(Status::Pass), count(Status::Fail), count(Status::New));
+
+    match format {
+        OutputFormat::Json => {
+            let results: Vec<Json> = results.iter().map(result_json).collect();
+            println!("{}", Json::object()
+                .field("passed", passed)
+                .field("failed", failed)
+                .field("new", new)
+                .field("results", results));
+        },
+        OutputFormat::Text => {
+            for result in &results {
+                print!("{:4}  {}  {:#018X}  ({}, {} cycles)", result.status.name().to_uppercase(),
+                       result.rom.display(), result.screen_hash, result.stop, result.cycles);
+                match (&result.error, result.expected) {
+                    (Some(error), _) => println!(" - {}", error),
+                    (None, Some(hash)) if result.status == Status::Fail => println!(" - expected {:#018X}", hash),
+                    _ => println!(),
+                }
+            }
+            println!("{} passed, {} failed, {} new", passed, failed, new);
+        },
+    }
+
+    if failed > 0 {
+        process::exit(exitcode::FAILURE);
+    }
+}
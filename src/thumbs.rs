@@ -0,0 +1,155 @@
+/************
+ROM thumbnails
+
+Usage: chip8 thumbs <directory> [--out DIR] [--seconds N] [--scale N]
+
+Runs every ROM in the directory (.ch8 and .c8 files) headless for a couple of seconds (2 unless
+--seconds is given) and saves a picture of it as <rom name>.png, next to the ROMs or in --out.
+The picture is the frame with the most pixels lit, which skips the blank screens and half drawn
+frames most games start with. It's the 64 x 32 screen white on black, each pixel --scale (4)
+pixels wide, so any frontend can show it as it is.
+
+The window's ROM library (see library.rs) shows these pictures when a ROM has one next to it, and
+only runs the ROMs that don't. Every ROM starts from seed 0, so the pictures are the same on every
+run and regenerating them only changes the ones of ROMs that changed.
+************/
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use png::{self, BitDepth, ColorType, HasParameters};
+
+use display::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use exitcode;
+use framebuffer::{ColorDepth, Framebuffer};
+use options::take_value;
+use palette::Palette;
+use testsuite::find_roms;
+use {Chip8, TIMER_HZ};
+
+pub const DEFAULT_SECONDS: f64 = 2.0;
+const DEFAULT_SCALE: usize = 4;
+
+//Where the picture of a ROM goes: pong.ch8 has pong.png
+pub fn png_path(rom: &Path, out: Option<&Path>) -> PathBuf {
+    let name = rom.with_extension("png");
+    match (out, name.file_name()) {
+        (Some(dir), Some(file)) => dir.join(file),
+        _ => name,
+    }
+}
+
+//Runs the ROM for the given time and returns the screen with the most pixels lit (the first
+//such frame), None if the ROM doesn't load
+pub fn representative_frame(rom: &[u8], seconds: f64) -> Option<Vec<u8>> {
+    let mut chip8 = Chip8::builder().trace(false).quiet(true).seed(0).rom_bytes(rom).build().ok()?;
+    let frames = (seconds * TIMER_HZ).ceil() as usize;
+    let mut best = chip8.screen().to_vec();
+    let mut best_lit = 0;
+    for frame in chip8.frames().take(frames) {
+        let lit = frame.screen.iter().filter(|pixel| **pixel != 0).count();
+        if lit > best_lit {
+            best_lit = lit;
+            best = frame.screen;
+        }
+    }
+    Some(best)
+}
+
+fn save_png(screen: &[u8], scale: usize, path: &Path) -> Result<(), String> {
+    let rgba = Framebuffer::new(screen, SCREEN_WIDTH, SCREEN_HEIGHT, ColorDepth::OnePlane).to_rgba(&Palette::default());
+    let (width, height) = (SCREEN_WIDTH * scale, SCREEN_HEIGHT * scale);
+    let mut scaled = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let pixel = ((y / scale) * SCREEN_WIDTH + x / scale) * 4;
+            scaled.extend_from_slice(&rgba[pixel..pixel + 4]);
+        }
+    }
+
+    let failed = |err: String| format!("Could not write {}: {}", path.display(), err);
+    let file = File::create(path).map_err(|err| failed(err.to_string()))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set(ColorType::RGBA).set(BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|err| failed(err.to_string()))?;
+    writer.write_image_data(&scaled).map_err(|err| failed(err.to_string()))
+}
+
+//Reads a picture written by chip8 thumbs back into a 64 x 32 screen, from the middle of each
+//scaled pixel. Errors for pictures in any other shape
+pub fn load_png(path: &Path) -> Result<Vec<u8>, String> {
+    let failed = |err: String| format!("Could not read {}: {}", path.display(), err);
+    let file = File::open(path).map_err(|err| failed(err.to_string()))?;
+    let (info, mut reader) = png::Decoder::new(file).read_info().map_err(|err| failed(err.to_string()))?;
+    let (width, height) = (info.width as usize, info.height as usize);
+    let scale = width / SCREEN_WIDTH;
+    if info.color_type != ColorType::RGBA || scale == 0 || width != SCREEN_WIDTH * scale || height != SCREEN_HEIGHT * scale {
+        return Err(failed(String::from("not a thumbnail (expected a 64 x 32 screen scaled up, in RGBA)")));
+    }
+    let mut data = vec![0; info.buffer_size()];
+    reader.next_frame(&mut data).map_err(|err| failed(err.to_string()))?;
+
+    let mut screen = vec![0; SCREEN_WIDTH * SCREEN_HEIGHT];
+    for y in 0..SCREEN_HEIGHT {
+        for x in 0..SCREEN_WIDTH {
+            let offset = (y * scale + scale / 2) * info.line_size + (x * scale + scale / 2) * 4;
+            let light = (data[offset] as u32 * 299 + data[offset + 1] as u32 * 587 + data[offset + 2] as u32 * 114) / 1000;
+            screen[x + y * SCREEN_WIDTH] = (light >= 128) as u8;
+        }
+    }
+    Ok(screen)
+}
+
+fn generate(args: &[String]) -> Result<(), String> {
+    let mut args = args.to_vec();
+    let out = take_value(&mut args, "--out")?.map(PathBuf::from);
+    let seconds = match take_value(&mut args, "--seconds")? {
+        Some(value) => value.parse::<f64>().ok().filter(|seconds| *seconds > 0.0)
+            .ok_or_else(|| format!("Invalid number of seconds {}", value))?,
+        None => DEFAULT_SECONDS,
+    };
+    let scale = match take_value(&mut args, "--scale")? {
+        Some(value) => value.parse::<usize>().ok().filter(|scale| (1..=32).contains(scale))
+            .ok_or_else(|| format!("Invalid scale {} (1 to 32)", value))?,
+        None => DEFAULT_SCALE,
+    };
+    let dir = match args.as_slice() {
+        [dir] => dir.clone(),
+        _ => return Err(String::from("Usage: chip8 thumbs <directory> [--out DIR] [--seconds N] [--scale N]")),
+    };
+    if let Some(ref out) = out {
+        std::fs::create_dir_all(out).map_err(|err| format!("Could not create {}: {}", out.display(), err))?;
+    }
+
+    let roms = find_roms(&dir)?;
+    let mut failed = 0;
+    for rom in &roms {
+        let path = png_path(rom, out.as_deref());
+        let screen = std::fs::read(rom).ok().and_then(|bytes| representative_frame(&bytes, seconds));
+        let result = match screen {
+            Some(screen) => save_png(&screen, scale, &path),
+            None => Err(format!("Could not load {}", rom.display())),
+        };
+        match result {
+            Ok(()) => println!("{} -> {}", rom.display(), path.display()),
+            Err(err) => {
+                println!("{}", err);
+                failed += 1;
+            },
+        }
+    }
+    println!("{} thumbnails written, {} failed", roms.len() - failed, failed);
+    if failed > 0 {
+        return Err(String::from("Some ROMs had no thumbnail written"));
+    }
+    Ok(())
+}
+
+//Entry point for "chip8 thumbs ...", with the arguments following "thumbs"
+pub fn run(args: &[String]) {
+    if let Err(err) = generate(args) {
+        println!("{}", err);
+        process::exit(exitcode::FAILURE);
+    }
+}
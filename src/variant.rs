@@ -0,0 +1,84 @@
+/************
+Machine variants
+
+Which flavour of CHIP-8 the core is emulating, picked with --variant:
+
+chip8 - the original CHIP-8, a 64 x 32 screen and the 35 standard instructions
+schip - SUPER-CHIP 1.1, which adds a 128 x 64 hi-res mode (00FF, 00FE goes back to 64 x 32),
+        scrolling (00CN down N pixels, 00FB right and 00FC left by 4), 16 x 16 sprites (DXY0),
+        a big 8 x 10 font for the digits (FX30), exit (00FD) and the HP48's 8 flags (FX75 stores
+        V0 to VX in them, FX85 reads them back, X up to 7). Scrolls are in pixels of the
+        current resolution, and switching resolution clears the screen. SCHIP ROMs usually
+        also want --quirks schip
+
+XO-CHIP (bigger memory, more colors) is meant to be added here.
+
+Save states and replays record the variant they were made with. A state or replay from another
+variant is refused instead of loaded, since its memory, screen and timing can't be interpreted by
+this one and would silently corrupt the run. Files from before variants were recorded are
+treated as CHIP-8, which was the only thing that could have made them.
+************/
+use core::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(rename_all = "lowercase"))]
+pub enum Variant {
+    Chip8,
+    Schip,
+}
+
+impl Variant {
+    //Every variant the core can run
+    pub const ALL: &'static [Variant] = &[Variant::Chip8, Variant::Schip];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Variant::Chip8 => "chip8",
+            Variant::Schip => "schip",
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn parse(text: &str) -> Result<Variant, String> {
+        match text {
+            "chip8" => Ok(Variant::Chip8),
+            "schip" => Ok(Variant::Schip),
+            _ => Err(format!("Unknown machine variant {} (expected chip8 or schip)", text)),
+        }
+    }
+
+    //Single byte id, as stored in save states
+    pub fn id(self) -> u8 {
+        match self {
+            Variant::Chip8 => 0,
+            Variant::Schip => 1,
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub fn from_id(id: u8) -> Result<Variant, String> {
+        match id {
+            0 => Ok(Variant::Chip8),
+            1 => Ok(Variant::Schip),
+            _ => Err(format!("Unknown machine variant id {}", id)),
+        }
+    }
+
+    //Errors unless something made with `made_with` can run on this variant
+    #[cfg(feature = "std")]
+    pub fn check(self, made_with: Variant, what: &str) -> Result<(), String> {
+        if made_with != self {
+            return Err(format!("{} was made in {} mode, but the machine is running in {} mode", what, made_with, self));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,124 @@
+/************
+WebAssembly frontend
+
+The core for JavaScript, through wasm-bindgen. Build it with wasm-pack (wasm-pack build wasm
+--target web) or cargo build -p chip8-wasm --target wasm32-unknown-unknown and the wasm-bindgen
+CLI, then index.html next to this crate runs a ROM in the page:
+
+    const chip8 = new Emulator(seed);
+    chip8.load_rom(new Uint8Array(await (await fetch(url)).arrayBuffer()));
+    every animation frame: chip8.run_frame(), draw chip8.framebuffer(), beep while chip8.sound_active()
+    keydown/keyup: chip8.key_event(event.code, pressed)
+
+It uses the no_std core (see the top of src/lib.rs): there are no files, clock or window to
+depend on in a browser, and the page brings its own. The random numbers for CXNN come from the
+seed given to the constructor, ie Math.random() * 2 ** 32, or a fixed one to reproduce a run.
+************/
+extern crate chip8;
+extern crate wasm_bindgen;
+
+use wasm_bindgen::prelude::*;
+
+use chip8::display::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use chip8::Chip8;
+
+//What run_frame() left the machine doing
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameStatus {
+    Running = 0,
+    WaitingForKey = 1,  //Stopped at an FX0A until a key is pressed
+    Fault = 2,          //Stopped by a fault, ie an access past the end of memory (see the core's error.rs)
+    InvalidPc = 3,      //The program counter left memory
+}
+
+//The usual 1234 / QWER / ASDF / ZXCV layout, by KeyboardEvent.code so it doesn't move with the keyboard language
+const KEYMAP: &[(&str, u8)] = &[
+    ("Digit1", 0x1), ("Digit2", 0x2), ("Digit3", 0x3), ("Digit4", 0xC),
+    ("KeyQ", 0x4), ("KeyW", 0x5), ("KeyE", 0x6), ("KeyR", 0xD),
+    ("KeyA", 0x7), ("KeyS", 0x8), ("KeyD", 0x9), ("KeyF", 0xE),
+    ("KeyZ", 0xA), ("KeyX", 0x0), ("KeyC", 0xB), ("KeyV", 0xF),
+];
+
+#[wasm_bindgen]
+pub struct Emulator {
+    chip8: Chip8,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    //A fresh machine with the font loaded, CXNN seeded with `seed`
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u32) -> Emulator {
+        let mut chip8 = Chip8::new();
+        chip8.initialize();
+        chip8.trace = false;
+        chip8.set_seed(seed as u64);
+        Emulator { chip8 }
+    }
+
+    //Copies a ROM (a Uint8Array) into memory at 0x200
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), String> {
+        let space = 4096 - self.chip8.load_addr() as usize;
+        if rom.len() > space {
+            return Err(format!("ROM is {} bytes, only {} fit", rom.len(), space));
+        }
+        self.chip8.load_rom_bytes(rom);
+        Ok(())
+    }
+
+    //Runs one 60Hz frame (cycles_per_tick() cycles, so the timers count down once), stopping early if the
+    //program can't go on
+    pub fn run_frame(&mut self) -> FrameStatus {
+        for _ in 0..self.chip8.cycles_per_tick() {
+            if self.chip8.pc() as usize + 1 >= 4096 {
+                return FrameStatus::InvalidPc;
+            }
+            if self.chip8.step().is_err_and(|err| err.is_fatal()) {
+                return FrameStatus::Fault;
+            }
+        }
+        if self.chip8.is_halted() {
+            FrameStatus::WaitingForKey
+        } else {
+            FrameStatus::Running
+        }
+    }
+
+    //A copy of the screen, width() x height() bytes (0 or 1 per pixel) row by row
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.chip8.screen().to_vec()
+    }
+
+    pub fn width() -> usize {
+        SCREEN_WIDTH
+    }
+
+    pub fn height() -> usize {
+        SCREEN_HEIGHT
+    }
+
+    //Presses or releases key 0-F, other keys are ignored
+    pub fn set_key(&mut self, key: u8, pressed: bool) {
+        if key < 16 {
+            self.chip8.press_key(key, pressed);
+        }
+    }
+
+    //Forwards a keydown (pressed) or keyup event by its code. Returns whether it's a keypad key, so the page
+    //knows to call preventDefault() on it
+    pub fn key_event(&mut self, code: &str, pressed: bool) -> bool {
+        match KEYMAP.iter().find(|&&(name, _)| name == code) {
+            Some(&(_, key)) => {
+                self.chip8.press_key(key, pressed);
+                true
+            },
+            None => false,
+        }
+    }
+
+    //Whether the buzzer should sound
+    pub fn sound_active(&self) -> bool {
+        self.chip8.sound_active()
+    }
+}